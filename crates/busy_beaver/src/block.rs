@@ -0,0 +1,98 @@
+//! Block size selection for macro machines
+//!
+//! A macro machine treats `k` adjacent cells as one symbol. Deciders for bouncers and similar machines work much better when `k` matches the period of the patterns the machine writes, and choosing `k` by hand does not scale to millions of machines. This module picks `k` by simulating the machine briefly and finding the block size under which the tape compresses best.
+//!
+//! The tape is compressed by run length encoding its blocks. The cost of a block size is the number of cells the encoding needs, which is the number of runs times the block size. A tape without structure costs about its length for every block size. A tape made of a repeated pattern costs about one pattern for block sizes that are multiples of the period. The smallest block size with the lowest cost wins so that unstructured tapes get block size 1.
+
+use crate::{
+    run::{DenseTape, Runner, StepResult},
+    states::States,
+};
+
+/// Number of snapshots of the tape taken during the simulation. Costs are summed over all snapshots so that a pattern that only shows at one moment does not decide the block size.
+const SNAPSHOTS: u64 = 4;
+
+/// Longest tape that is allocated, in cells. A machine that leaves it is not simulated further, and the tape it wrote so far is already long enough to show its structure.
+const MAX_TAPE_LEN: usize = 1 << 20;
+
+/// Number of runs of equal blocks when `cells` is split into blocks of `block` cells. A partial block at the end is padded with 0.
+pub fn runs(cells: &[u8], block: usize) -> usize {
+    assert!(block > 0);
+    let mut runs = 0;
+    let mut previous: Option<&[u8]> = None;
+    for chunk in cells.chunks(block) {
+        // Only the last chunk can be partial. It equals the previous block if the previous block continues with 0s.
+        let same = previous.is_some_and(|previous| {
+            previous[..chunk.len()] == *chunk && previous[chunk.len()..].iter().all(|s| *s == 0)
+        });
+        if !same {
+            runs += 1;
+        }
+        previous = Some(chunk);
+    }
+    runs
+}
+
+/// Simulate `states` for up to `steps` steps and return the best block size up to `max_block`. Returns 1 if the machine halts or leaves the tape before showing any structure. The tape has at most `MAX_TAPE_LEN` cells however large `steps` is.
+pub fn select_block_size<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+    steps: u64,
+    max_block: usize,
+) -> usize {
+    assert!(max_block > 0);
+    // The head cannot leave a tape this long within `steps` steps unless it is capped.
+    let tape_len = usize::try_from(steps)
+        .unwrap_or(usize::MAX)
+        .saturating_mul(2)
+        .saturating_add(1)
+        .min(MAX_TAPE_LEN);
+    let mut runner = Runner::<STATES, SYMBOLS, _>::new(DenseTape::new(vec![0u8; tape_len]));
+    runner.set_states(states);
+    let mut costs = vec![0usize; max_block];
+    let mut snapshot = |tape: &DenseTape<Vec<u8>>| {
+        for (block, cost) in (1..=max_block).zip(costs.iter_mut()) {
+            *cost += runs(tape.visited(), block) * block;
+        }
+    };
+    for step in 1..=steps {
+        if !matches!(runner.step(), StepResult::Ok) {
+            break;
+        }
+        if step % (steps / SNAPSHOTS).max(1) == 0 {
+            snapshot(runner.tape());
+        }
+    }
+    snapshot(runner.tape());
+    // `min_by_key` returns the first minimum, which is the smallest block size.
+    (1..=max_block)
+        .min_by_key(|block| costs[block - 1])
+        .unwrap()
+}
+
+#[test]
+fn block_runs() {
+    assert_eq!(runs(&[1, 0, 1, 0, 1, 0], 1), 6);
+    assert_eq!(runs(&[1, 0, 1, 0, 1, 0], 2), 1);
+    assert_eq!(runs(&[1, 0, 1, 0, 1], 2), 1);
+    assert_eq!(runs(&[1, 0, 1, 0, 0, 1], 2), 2);
+    assert_eq!(runs(&[], 3), 0);
+}
+
+#[test]
+fn select_block_sizes() {
+    use crate::format::read_compact;
+    // Writes 10 forever while moving right.
+    let alternating = read_compact::<2, 2>(b"1RB---_0RA---").unwrap();
+    assert_eq!(select_block_size(&alternating, 1000, 8), 2);
+    // Writes 110 forever while moving right.
+    let period_3 = read_compact::<3, 2>(b"1RB---_1RC---_0RA---").unwrap();
+    assert_eq!(select_block_size(&period_3, 1000, 8), 3);
+    // Fills the tape with 1s, which compresses equally well for every block size.
+    let ones = read_compact::<1, 2>(b"1RA---").unwrap();
+    assert_eq!(select_block_size(&ones, 1000, 8), 1);
+    // Halts immediately.
+    let halt = read_compact::<1, 2>(b"------").unwrap();
+    assert_eq!(select_block_size(&halt, 1000, 8), 1);
+    // The tape is capped, so the machine leaves it instead of the step count sizing the allocation.
+    assert_eq!(select_block_size(&alternating, u64::MAX, 8), 2);
+}
@@ -0,0 +1,358 @@
+//! Patterns of configurations
+//!
+//! Deciders and proofs reason about sets of configurations like "state A reads a 0, to its left are one or more 1 and to its right alternating 1 and 0". A `ConfigurationPattern` describes such a set and tests whether a concrete configuration belongs to it.
+//!
+//! Patterns are written like `1^+ [A0] (1 0)^*`. The bracket is the head with the state and the symbol under it, either of which can be `.` to match anything. The words before and after it describe the cells to the left and the right of the head from left to right. Words consist of symbols, `.` for any symbol and groups in parentheses. Symbols, `.` and groups can have an exponent: `^3` for exactly 3 repetitions, `^3+` for at least 3, `^*` for any number and `^+` for at least one. All cells beyond the words are 0 and the words can cover some of those 0. `1 [A0]` matches the head in state A on a 0 with a single 1 to its left and all other cells 0, `.^* [A0]` ignores the cells to the left.
+
+use std::collections::BTreeSet;
+
+use crate::error::{Context, Error, Result};
+
+use crate::{
+    run::{Observer, Runner, Tape},
+    states::{State, Symbol},
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Item {
+    Symbol(u8),
+    Any,
+    /// `body` repeated at least `min` times and at most `max` times. `None` is unbounded.
+    Repeat {
+        body: Word,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+/// Sequence of items matching a sequence of cells.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Word(pub Vec<Item>);
+
+impl Word {
+    /// Whether the word matches `cells` followed by infinitely many 0.
+    pub fn matches(&self, cells: &[u8]) -> bool {
+        let used = cells.iter().rposition(|c| *c != 0).map_or(0, |i| i + 1);
+        let mut padded = cells[..used].to_vec();
+        padded.resize(used + self.padding(), 0);
+        self.ends(&padded, BTreeSet::from([0]))
+            .into_iter()
+            .any(|end| end >= used)
+    }
+
+    /// The word read from right to left.
+    pub fn reversed(&self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .rev()
+                .map(|item| match item {
+                    Item::Repeat { body, min, max } => Item::Repeat {
+                        body: body.reversed(),
+                        min: *min,
+                        max: *max,
+                    },
+                    item => item.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.0
+            .iter()
+            .map(|item| match item {
+                Item::Symbol(_) | Item::Any => 1,
+                Item::Repeat { body, min, .. } => *min as usize * body.min_len(),
+            })
+            .sum()
+    }
+
+    /// Number of 0 after the last non blank cell that is enough for any match. A shortest match repeats every group that lies entirely in the 0 as few times as possible, so at most one repetition of each group is in the 0 beyond the minimum.
+    fn padding(&self) -> usize {
+        self.0
+            .iter()
+            .map(|item| match item {
+                Item::Symbol(_) | Item::Any => 1,
+                Item::Repeat { body, min, .. } => *min as usize * body.min_len() + body.padding(),
+            })
+            .sum()
+    }
+
+    /// Positions after matches of the word in `cells` that start at one of `starts`.
+    fn ends(&self, cells: &[u8], starts: BTreeSet<usize>) -> BTreeSet<usize> {
+        self.0
+            .iter()
+            .fold(starts, |positions, item| item.ends(cells, positions))
+    }
+}
+
+impl Item {
+    fn ends(&self, cells: &[u8], starts: BTreeSet<usize>) -> BTreeSet<usize> {
+        match self {
+            Item::Symbol(symbol) => starts
+                .into_iter()
+                .filter(|p| cells.get(*p) == Some(symbol))
+                .map(|p| p + 1)
+                .collect(),
+            Item::Any => starts
+                .into_iter()
+                .filter(|p| *p < cells.len())
+                .map(|p| p + 1)
+                .collect(),
+            Item::Repeat { body, min, max } => {
+                let mut result = BTreeSet::new();
+                let mut current = starts;
+                for count in 0u32.. {
+                    if count >= *min {
+                        // A position that was already reached is already being repeated from.
+                        current.retain(|p| !result.contains(p));
+                        result.extend(current.iter().copied());
+                    }
+                    if current.is_empty() || max.is_some_and(|max| count >= max) {
+                        break;
+                    }
+                    current = body.ends(cells, current);
+                }
+                result
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConfigurationPattern<const STATES: usize, const SYMBOLS: usize> {
+    /// The cells to the left of the head from left to right.
+    pub left: Word,
+    /// `None` matches any state.
+    pub state: Option<State<STATES>>,
+    /// The symbol under the head. `None` matches any symbol.
+    pub symbol: Option<Symbol<SYMBOLS>>,
+    /// The cells to the right of the head from left to right.
+    pub right: Word,
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> ConfigurationPattern<STATES, SYMBOLS> {
+    /// Parse the format described in the module documentation.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            s: s.as_bytes(),
+            i: 0,
+            symbols: SYMBOLS,
+        };
+        let left = parser.word(false).context("left word")?;
+        if parser.next() != Some(b'[') {
+            return Err(Error::Parse("expected the head like `[A0]`".into()));
+        }
+        let state = match parser.next().context("missing state")? {
+            b'.' => None,
+            c => Some(State::new(c.wrapping_sub(b'A')).context("invalid state")?),
+        };
+        let symbol = match parser.next().context("missing symbol")? {
+            b'.' => None,
+            c => Some(Symbol::new(c.wrapping_sub(b'0')).context("invalid symbol")?),
+        };
+        if parser.next() != Some(b']') {
+            return Err(Error::Parse("expected `]`".into()));
+        }
+        let right = parser.word(false).context("right word")?;
+        if parser.peek().is_some() {
+            return Err(Error::Parse(format!(
+                "unexpected character at {}",
+                parser.i
+            )));
+        }
+        Ok(Self {
+            left,
+            state,
+            symbol,
+            right,
+        })
+    }
+
+    /// Whether the configuration of `runner` matches.
+    pub fn matches<T: Tape, O: Observer<STATES, SYMBOLS>>(
+        &self,
+        runner: &Runner<STATES, SYMBOLS, T, O>,
+    ) -> bool {
+        let (left, right) = runner.extent();
+        let position = runner.position();
+        let tape = runner.tape();
+        let left: Vec<u8> = (left..position).rev().map(|p| tape.cell(p)).collect();
+        let right: Vec<u8> = (position + 1..=right).map(|p| tape.cell(p)).collect();
+        self.matches_cells(runner.state(), runner.symbol(), &left, &right)
+    }
+
+    /// Whether a configuration matches. `left` and `right` are the cells to the left and the right of the head in the order in which they are reached from the head. All cells beyond them are 0.
+    pub fn matches_cells(
+        &self,
+        state: State<STATES>,
+        symbol: Symbol<SYMBOLS>,
+        left: &[u8],
+        right: &[u8],
+    ) -> bool {
+        self.state.is_none_or(|s| s == state)
+            && self.symbol.is_none_or(|s| s == symbol)
+            && self.left.reversed().matches(left)
+            && self.right.matches(right)
+    }
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+    symbols: usize,
+}
+
+impl Parser<'_> {
+    /// The next character that is not whitespace.
+    fn peek(&mut self) -> Option<u8> {
+        while self.s.get(self.i).is_some_and(|c| c.is_ascii_whitespace()) {
+            self.i += 1;
+        }
+        self.s.get(self.i).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.i += 1;
+        Some(c)
+    }
+
+    fn word(&mut self, in_group: bool) -> Result<Word> {
+        let mut items = Vec::new();
+        loop {
+            let item = match self.peek() {
+                None | Some(b'[') if !in_group => break,
+                Some(b')') if in_group => break,
+                Some(b'.') => {
+                    self.i += 1;
+                    Item::Any
+                }
+                Some(b'(') => {
+                    self.i += 1;
+                    let body = self.word(true)?;
+                    if self.next() != Some(b')') {
+                        return Err(Error::Parse("expected `)`".into()));
+                    }
+                    Item::Repeat {
+                        body,
+                        min: 1,
+                        max: Some(1),
+                    }
+                }
+                Some(c @ b'0'..=b'9') if ((c - b'0') as usize) < self.symbols => {
+                    self.i += 1;
+                    Item::Symbol(c - b'0')
+                }
+                _ => return Err(Error::Parse(format!("unexpected character at {}", self.i))),
+            };
+            items.push(self.exponent(item)?);
+        }
+        Ok(Word(items))
+    }
+
+    fn exponent(&mut self, item: Item) -> Result<Item> {
+        // The exponent has to follow without whitespace because `1^2 0` and `1^20` are different.
+        if self.s.get(self.i) != Some(&b'^') {
+            return Ok(item);
+        }
+        self.i += 1;
+        let digits = self.s[self.i..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let count: Option<u32> = match digits {
+            0 => None,
+            _ => Some(
+                std::str::from_utf8(&self.s[self.i..self.i + digits])
+                    .unwrap()
+                    .parse()
+                    .context("invalid exponent")?,
+            ),
+        };
+        self.i += digits;
+        let (min, max) = match (count, self.s.get(self.i)) {
+            (None, Some(b'*')) => (0, None),
+            (None, Some(b'+')) => (1, None),
+            (Some(count), Some(b'+')) => (count, None),
+            (Some(count), _) => (count, Some(count)),
+            (None, _) => return Err(Error::Parse(format!("expected exponent at {}", self.i))),
+        };
+        if max.is_none() {
+            self.i += 1;
+        }
+        let body = match item {
+            Item::Repeat {
+                body,
+                min: 1,
+                max: Some(1),
+            } => body,
+            item => Word(vec![item]),
+        };
+        Ok(Item::Repeat { body, min, max })
+    }
+}
+
+#[test]
+fn configuration_pattern() {
+    use crate::{format::read_compact, run::StepResult};
+    let states = read_compact::<2, 2>(b"1RB1LB_1LA---").unwrap();
+    let mut dense = Runner::vector_backed(20);
+    let mut sparse = Runner::sparse();
+    fn matches<T: Tape>(runner: &Runner<2, 2, T>, pattern: &str) -> bool {
+        ConfigurationPattern::parse(pattern)
+            .unwrap()
+            .matches(runner)
+    }
+    dense.set_states(&states);
+    sparse.set_states(&states);
+    assert!(matches(&dense, "[A0]"));
+    assert!(matches(&dense, "0^* [A0] 0^5"));
+    assert!(!matches(&dense, "[A0] 1"));
+    for _ in 0..5 {
+        assert!(matches!(dense.step(), StepResult::Ok));
+        assert!(matches!(sparse.step(), StepResult::Ok));
+    }
+    // After 5 steps the cells -2 to 1 are 1 and the head is at -1 in state B.
+    for pattern in [
+        "1 [B1] 1 1",
+        "1^+ [B1] 1^2",
+        "0^* 1 [B1] 1^* 0^*",
+        "1 [B.] . .",
+        "1 [B1] 1 1 0 0",
+        "0 0 0 1 [B1] 1 1",
+        "1 [B1] (1 1)^+",
+        "((1))^1+ [.1] (1^2)^*",
+        ".^* [B1] 1 1",
+    ] {
+        assert!(matches(&dense, pattern), "{pattern}");
+        assert!(matches(&sparse, pattern), "{pattern}");
+    }
+    for pattern in [
+        "[B1] 1 1",
+        "1 [A.] 1 1",
+        "1 [B1] 1",
+        "1 [B1] (1 1 1)^*",
+        "1 [B1] 1 1 1",
+        "(1 1)^+ [B1] 1 1",
+    ] {
+        assert!(!matches(&dense, pattern), "{pattern}");
+        assert!(!matches(&sparse, pattern), "{pattern}");
+    }
+
+    for invalid in [
+        "",
+        "1 [C0]",
+        "[A2]",
+        "[A0] 1^",
+        "[A0] (1",
+        "[A0] 1)",
+        "[A0] [A0]",
+    ] {
+        assert!(
+            ConfigurationPattern::<2, 2>::parse(invalid).is_err(),
+            "{invalid}"
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Configuration graphs on a bounded tape window
+//!
+//! A configuration of a machine restricted to a window of the tape is the state, the position of the head and the contents of the window. The cells outside of the window are not tracked, so the head can also be somewhere outside to the left or to the right, where it reads any symbol and its writes are forgotten. A head outside of the window that moves towards it can either enter the window or stay outside. With these rules a configuration has one successor for every symbol it could read, and there are finitely many configurations.
+//!
+//! `explore` runs a breadth first search from the blank window and returns the reachable configurations and the steps between them as a graph. Unlike the joins of `segment`, configurations are kept exact, so the graph can be drawn and inspected. If the search completes and no configuration reads a halting transition, the graph is closed: it over-approximates every configuration of the real machine, which therefore runs forever.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Write,
+};
+
+use crate::states::{Direction, States, Transition};
+
+/// Parameters of the search.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Config {
+    /// Number of cells that are tracked. Must not be 0.
+    pub window: usize,
+    /// Cell of the window the head starts on.
+    pub start: usize,
+    /// The search stops after this many configurations.
+    pub max_nodes: usize,
+}
+
+/// Position of the head relative to the window.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Head {
+    Left,
+    Inside(usize),
+    Right,
+}
+
+/// Configuration on the window.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Node {
+    pub state: u8,
+    pub head: Head,
+    pub cells: Vec<u8>,
+}
+
+impl Node {
+    /// Like `B 01[1]0`: the state and the window with the cell under the head in brackets. A head outside of the window is shown as `<` or `>` on that side.
+    pub fn label(&self) -> String {
+        let mut result = format!("{} ", char::from(b'A' + self.state));
+        if self.head == Head::Left {
+            result.push('<');
+        }
+        for (i, cell) in self.cells.iter().enumerate() {
+            let cell = char::from(b'0' + cell);
+            if self.head == Head::Inside(i) {
+                result.extend(['[', cell, ']']);
+            } else {
+                result.push(cell);
+            }
+        }
+        if self.head == Head::Right {
+            result.push('>');
+        }
+        result
+    }
+}
+
+/// Reachable configurations and the steps between them. Node 0 is the start.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConfigurationGraph {
+    pub nodes: Vec<Node>,
+    /// Steps as indexes into `nodes`.
+    pub edges: Vec<(usize, usize)>,
+    /// Nodes that can read a halting transition.
+    pub halting: Vec<usize>,
+    /// Whether every reachable configuration was explored within `Config::max_nodes`.
+    pub complete: bool,
+}
+
+impl ConfigurationGraph {
+    /// Whether the graph proves that the machine runs forever.
+    pub fn is_closed(&self) -> bool {
+        self.complete && self.halting.is_empty()
+    }
+
+    /// Write the graph in the DOT language of Graphviz. The start is drawn bold and configurations that can halt are red.
+    pub fn write_dot(&self, output: &mut impl Write) -> std::io::Result<()> {
+        writeln!(output, "digraph configurations {{")?;
+        writeln!(output, "  node [shape=box, fontname=monospace];")?;
+        for (i, node) in self.nodes.iter().enumerate() {
+            let mut style = String::new();
+            if i == 0 {
+                style.push_str(", style=bold");
+            }
+            if self.halting.contains(&i) {
+                style.push_str(", color=red");
+            }
+            writeln!(output, "  n{i} [label=\"{}\"{style}];", node.label())?;
+        }
+        for (from, to) in &self.edges {
+            writeln!(output, "  n{from} -> n{to};")?;
+        }
+        writeln!(output, "}}")
+    }
+}
+
+/// Search the configurations reachable from the blank window breadth first.
+pub fn explore<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+    config: Config,
+) -> ConfigurationGraph {
+    assert!(config.start < config.window);
+    let mut graph = ConfigurationGraph {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+        halting: Vec::new(),
+        complete: true,
+    };
+    let mut indexes = HashMap::<Node, usize>::new();
+    let mut queue = VecDeque::<usize>::new();
+    let start = Node {
+        state: 0,
+        head: Head::Inside(config.start),
+        cells: vec![0; config.window],
+    };
+    indexes.insert(start.clone(), 0);
+    graph.nodes.push(start);
+    queue.push_back(0);
+    while let Some(index) = queue.pop_front() {
+        let node = graph.nodes[index].clone();
+        let mut halts = false;
+        let first_edge = graph.edges.len();
+        for successor in successors(states, &node, &mut halts) {
+            let next = match indexes.get(&successor) {
+                Some(&next) => next,
+                None => {
+                    if graph.nodes.len() >= config.max_nodes {
+                        graph.complete = false;
+                        continue;
+                    }
+                    let next = graph.nodes.len();
+                    indexes.insert(successor.clone(), next);
+                    graph.nodes.push(successor);
+                    queue.push_back(next);
+                    next
+                }
+            };
+            if !graph.edges[first_edge..].contains(&(index, next)) {
+                graph.edges.push((index, next));
+            }
+        }
+        if halts {
+            graph.halting.push(index);
+        }
+    }
+    graph
+}
+
+/// The configurations after one step from `node`. Sets `halts` if `node` can read a halting transition.
+fn successors<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+    node: &Node,
+    halts: &mut bool,
+) -> Vec<Node> {
+    let symbols = match node.head {
+        Head::Inside(i) => node.cells[i]..node.cells[i] + 1,
+        Head::Left | Head::Right => 0..SYMBOLS as u8,
+    };
+    let mut result = Vec::new();
+    for symbol in symbols {
+        let Transition::Continue(t) = states.0[node.state as usize][symbol as usize] else {
+            *halts = true;
+            continue;
+        };
+        let next = |head: Head, cells: &Vec<u8>| Node {
+            state: t.state.get(),
+            head,
+            cells: cells.clone(),
+        };
+        match node.head {
+            Head::Inside(i) => {
+                let mut cells = node.cells.clone();
+                cells[i] = t.write.get();
+                let head = match t.move_ {
+                    Direction::Left if i == 0 => Head::Left,
+                    Direction::Left => Head::Inside(i - 1),
+                    Direction::Right if i + 1 == cells.len() => Head::Right,
+                    Direction::Right => Head::Inside(i + 1),
+                };
+                result.push(next(head, &cells));
+            }
+            // The distance to the window is unknown, so moving towards it can enter it or not.
+            Head::Left => {
+                result.push(next(Head::Left, &node.cells));
+                if t.move_ == Direction::Right {
+                    result.push(next(Head::Inside(0), &node.cells));
+                }
+            }
+            Head::Right => {
+                result.push(next(Head::Right, &node.cells));
+                if t.move_ == Direction::Left {
+                    result.push(next(Head::Inside(node.cells.len() - 1), &node.cells));
+                }
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn configuration_graph() {
+    use crate::format::read_compact;
+
+    // Moves between two blank cells forever.
+    let oscillating = read_compact::<2, 2>(b"0RB---_0LA---").unwrap();
+    let config = Config {
+        window: 2,
+        start: 0,
+        max_nodes: 100,
+    };
+    let graph = explore(&oscillating, config);
+    assert!(graph.is_closed());
+    assert_eq!(graph.nodes.len(), 2);
+    assert_eq!(graph.edges, [(0, 1), (1, 0)]);
+    assert_eq!(graph.nodes[1].label(), "B 0[0]");
+    let mut dot = Vec::new();
+    graph.write_dot(&mut dot).unwrap();
+    assert!(String::from_utf8(dot)
+        .unwrap()
+        .contains("n0 [label=\"A [0]0\", style=bold];"));
+
+    // Starting on the right cell the head leaves the window, where it could read a 1.
+    let graph = explore(&oscillating, Config { start: 1, ..config });
+    assert!(graph.complete);
+    assert!(!graph.is_closed());
+    assert!(graph.nodes[graph.halting[0]].head == Head::Right);
+
+    let graph = explore(
+        &crate::fixtures::BB5,
+        Config {
+            window: 8,
+            start: 4,
+            max_nodes: 50,
+        },
+    );
+    assert!(!graph.complete);
+    assert_eq!(graph.nodes.len(), 50);
+}
+
+#[test]
+fn closed_graphs_are_sound() {
+    use crate::{
+        run::{Runner, SparseTape, StepResult},
+        stress::{machine, Kind, Rng},
+    };
+    let mut runner = Runner::new(SparseTape::unbounded());
+    let mut rng = Rng::new(1);
+    let mut closed = 0;
+    for _ in 0..1000 {
+        for kind in Kind::ALL {
+            let states = machine::<3, 2>(&mut rng, kind);
+            let graph = explore(
+                &states,
+                Config {
+                    window: 4,
+                    start: 1,
+                    max_nodes: 1000,
+                },
+            );
+            if !graph.is_closed() {
+                continue;
+            }
+            closed += 1;
+            runner.set_states(&states);
+            runner.reset();
+            for _ in 0..1000 {
+                assert!(!matches!(runner.step(), StepResult::Halt), "{states}");
+            }
+        }
+    }
+    assert!(closed > 0);
+}
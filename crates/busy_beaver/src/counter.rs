@@ -0,0 +1,639 @@
+//! Decider for counters
+//!
+//! A counter keeps a number on the tape, usually in binary or ternary, and increments it forever. Every increment walks over the digits that carry and stops at the first one that does not, so the used tape grows with the logarithm of the steps. Counters never repeat a configuration and their tape never settles into a growing repeated pattern, which is why cyclers, translated cyclers and bouncers deciders do not decide them.
+//!
+//! Deciding happens in two parts. The machine is first recognized as a counter by running it and checking that the used tape keeps growing but stays within a logarithm of the steps. Then non-halting is proven with a closed set of local contexts on a macro machine. The macro machine reads `block` adjacent cells as one symbol and runs the base machine inside a block until the head leaves it. A digit of a counter is often a block of a few cells, so on the right block size the tape becomes a plain word of digits. The abstract configuration is the state, the side of the block the head entered from, the block under the head and the `radius` blocks on either side of it. The sets of all words of `radius` blocks that can appear on the left and on the right of the head are grown until every abstract configuration that can be reached from the blank tape only needs words that are already in the sets. Every real tape is then made of words of the sets, so if no abstract configuration reaches a halting transition, the machine runs forever.
+//!
+//! The sets are the certificate. They describe the digits of the counter and how they follow each other, and `Certificate::check` verifies them without growing them.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    decider::{Decider, Decision},
+    format::write_markdown_table,
+    run::{DenseTape, Runner, StepResult},
+    states::{Direction, States, Transition},
+};
+
+/// Parameters of the decider.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Config {
+    /// Steps the machine runs for to be recognized as a counter.
+    pub steps: u64,
+    /// Largest number of cells per macro symbol that is tried.
+    pub max_block: usize,
+    /// Largest number of macro symbols on either side of the head that is tried.
+    pub max_radius: usize,
+    /// Proofs that reach more abstract configurations are given up.
+    pub max_configurations: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            steps: 100_000,
+            max_block: 4,
+            max_radius: 4,
+            max_configurations: 100_000,
+        }
+    }
+}
+
+/// Result of the decider.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    /// The machine halts after this many steps.
+    Halts { steps: u64 },
+    /// The machine does not behave like a counter within the configured steps.
+    NotACounter,
+    /// The machine behaves like a counter but no proof was found within the configured limits.
+    MayHalt,
+    /// The machine is a counter and runs forever.
+    NeverHalts(Certificate),
+}
+
+/// Proof that a machine runs forever: the words of `radius` macro symbols of `block` cells that can appear on either side of the head. Words are written in the order of the cells on the tape, so the cell next to the head is the last of a left word and the first of a right word.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Certificate {
+    pub block: usize,
+    pub radius: usize,
+    pub left: Vec<Vec<u8>>,
+    pub right: Vec<Vec<u8>>,
+}
+
+impl Certificate {
+    /// Whether the certificate proves that `states` runs forever. Fails if a reachable abstract configuration halts or needs a word that is not in the certificate.
+    pub fn check<const STATES: usize, const SYMBOLS: usize>(
+        &self,
+        states: &States<STATES, SYMBOLS>,
+    ) -> bool {
+        self.closure(states).is_some()
+    }
+
+    /// Short proof sketch in Markdown for posting the result: the machine, the words, the macro rules and why the words are closed. Returns `None` if the certificate does not check.
+    pub fn to_markdown<const STATES: usize, const SYMBOLS: usize>(
+        &self,
+        states: &States<STATES, SYMBOLS>,
+    ) -> Option<String> {
+        let closure = self.closure(states)?;
+        let words = |words: &[Vec<u8>]| {
+            words
+                .iter()
+                .map(|word| format!("`{}`", self.format_word(word)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut result = format!(
+            "## `{states}` runs forever\n\n{}\n",
+            write_markdown_table(states)
+        );
+        result.push_str(&format!(
+            "The proof looks at the machine as a macro machine whose symbols are blocks of {} cells, separated by `.` below. A configuration is abstracted to the state, the end of the block the head is on, the block under the head and the {} blocks on either side of it. These words of blocks are all that can appear next to the head:\n\n- left of the head: {}\n- right of the head: {}\n\n",
+            self.block,
+            self.radius,
+            words(&self.left),
+            words(&self.right)
+        ));
+        let mut rules: Vec<_> = closure.macro_steps.iter().collect();
+        rules.sort_by_key(|((state, entered, block), _)| (*state, *entered as u8, *block));
+        result.push_str("The macro rules that are used:\n\n| State | Head at | Block | Result |\n|---|---|---|---|\n");
+        let mut cells = vec![0; self.block];
+        let mut block = |code: u64| {
+            decode::<SYMBOLS>(code, &mut cells);
+            cells
+                .iter()
+                .map(|cell| cell.to_string())
+                .collect::<String>()
+        };
+        for ((state, entered, code), step) in rules {
+            let head = match entered {
+                Side::Left => "left end",
+                Side::Right => "right end",
+            };
+            let outcome = match *step {
+                MacroStep::Leave(side, next, written) => format!(
+                    "writes `{}`, leaves to the {} in state {}",
+                    block(written),
+                    match side {
+                        Side::Left => "left",
+                        Side::Right => "right",
+                    },
+                    char::from(b'A' + next)
+                ),
+                MacroStep::Stuck => "stays in the block forever".into(),
+                MacroStep::Halt => "halts".into(),
+            };
+            result.push_str(&format!(
+                "| {} | {head} | `{}` | {outcome} |\n",
+                char::from(b'A' + state),
+                block(*code)
+            ));
+        }
+        result.push_str(&format!(
+            "\nStarting from the blank tape, whose words are all blank, {} abstract configurations are reachable. Each of them applies one of these rules. The block a rule leaves behind forms a word of the set with the blocks next to it, and the blocks the head moves onto continue a word of the set, so the sets are closed: every tape the machine reaches is made of these words. No rule halts, so the machine runs forever.\n",
+            closure.seen.len()
+        ));
+        Some(result)
+    }
+
+    fn format_word(&self, word: &[u8]) -> String {
+        word.chunks(self.block)
+            .map(|block| block.iter().map(|cell| cell.to_string()).collect())
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    /// The closure of the abstract configurations with the words of the certificate, or `None` if it is not closed or halts.
+    fn closure<'a, const STATES: usize, const SYMBOLS: usize>(
+        &self,
+        states: &'a States<STATES, SYMBOLS>,
+    ) -> Option<Closure<'a, STATES, SYMBOLS>> {
+        let len = self.block * self.radius;
+        if self.block == 0 || self.radius == 0 || !fits::<SYMBOLS>(self.block) {
+            return None;
+        }
+        let valid_word = |word: &Vec<u8>| {
+            word.len() == len && word.iter().all(|&cell| (cell as usize) < SYMBOLS)
+        };
+        if !self.left.iter().chain(&self.right).all(valid_word) {
+            return None;
+        }
+        let mut closure = Closure::new(states, self.block, self.radius, usize::MAX);
+        for word in &self.left {
+            let mut word = encode_word::<SYMBOLS>(word, self.block);
+            word.reverse();
+            closure.insert(Side::Left, word);
+        }
+        for word in &self.right {
+            closure.insert(Side::Right, encode_word::<SYMBOLS>(word, self.block));
+        }
+        // The blank tape is made of blank words.
+        let blank = vec![0; self.radius];
+        if !closure.contains(Side::Left, &blank) || !closure.contains(Side::Right, &blank) {
+            return None;
+        }
+        closure.frozen = true;
+        closure.run().then_some(closure)
+    }
+}
+
+impl std::fmt::Display for Certificate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let words = |words: &[Vec<u8>]| {
+            words
+                .iter()
+                .map(|word| self.format_word(word))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        write!(
+            f,
+            "block {} radius {}, left {}, right {}",
+            self.block,
+            self.radius,
+            words(&self.left),
+            words(&self.right)
+        )
+    }
+}
+
+/// Recognizes counters and proves that they run forever.
+#[derive(Debug, Clone)]
+pub struct CounterDecider<const STATES: usize, const SYMBOLS: usize> {
+    config: Config,
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> CounterDecider<STATES, SYMBOLS> {
+    pub fn new(config: Config) -> Self {
+        assert!(config.max_block > 0 && config.max_radius > 0);
+        Self { config }
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    pub fn run(&self, states: &States<STATES, SYMBOLS>) -> Outcome {
+        match self.recognize(states) {
+            Some(Outcome::NotACounter) => Outcome::NotACounter,
+            Some(halts) => halts,
+            None => match self.prove(states) {
+                Some(certificate) => Outcome::NeverHalts(certificate),
+                None => Outcome::MayHalt,
+            },
+        }
+    }
+
+    /// Run the machine for the configured steps. Returns `None` if it behaves like a counter: the used tape still grows in the last half of the steps but is shorter than a few times the logarithm of the steps.
+    fn recognize(&self, states: &States<STATES, SYMBOLS>) -> Option<Outcome> {
+        let steps = self.config.steps;
+        let mut runner =
+            Runner::<STATES, SYMBOLS, _>::new(DenseTape::new(vec![0u8; 2 * steps as usize + 1]));
+        runner.set_states(states);
+        let mut half = 0;
+        for step in 1..=steps {
+            if runner.step() == StepResult::Halt {
+                return Some(Outcome::Halts { steps: step });
+            }
+            if step == steps / 2 {
+                half = runner.tape().visited().len();
+            }
+        }
+        let used = runner.tape().visited().len();
+        let logarithmic = used as u32 <= 4 * steps.max(2).ilog2();
+        (used <= half || !logarithmic).then_some(Outcome::NotACounter)
+    }
+
+    /// Look for a closed set of contexts with every block size and radius up to the configured ones, smallest first. Does not check whether the machine is a counter, so it also proves other machines whose tapes have a simple local structure.
+    pub fn prove(&self, states: &States<STATES, SYMBOLS>) -> Option<Certificate> {
+        for block in (1..=self.config.max_block).filter(|&block| fits::<SYMBOLS>(block)) {
+            for radius in 1..=self.config.max_radius {
+                let mut closure =
+                    Closure::new(states, block, radius, self.config.max_configurations);
+                closure.insert(Side::Left, vec![0; radius]);
+                closure.insert(Side::Right, vec![0; radius]);
+                if closure.run() {
+                    return Some(closure.certificate());
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Decider for CounterDecider<5, 2> {
+    fn decide(&mut self, states: &States<5, 2>) -> Decision {
+        match self.run(states) {
+            Outcome::Halts { .. } => Decision::Halt,
+            Outcome::NeverHalts(_) => Decision::RunForever,
+            Outcome::NotACounter | Outcome::MayHalt => Decision::Undecided,
+        }
+    }
+}
+
+/// Blocks are encoded in a `u64` with a digit of base `SYMBOLS` per cell. Limiting them to 16 bits also keeps the macro steps cheap.
+fn fits<const SYMBOLS: usize>(block: usize) -> bool {
+    (SYMBOLS as u64)
+        .checked_pow(block as u32)
+        .is_some_and(|blocks| blocks <= 1 << 16)
+}
+
+/// The first cell is the least significant digit.
+fn encode<const SYMBOLS: usize>(cells: &[u8]) -> u64 {
+    cells
+        .iter()
+        .rev()
+        .fold(0, |code, &cell| code * SYMBOLS as u64 + cell as u64)
+}
+
+fn decode<const SYMBOLS: usize>(mut code: u64, cells: &mut [u8]) {
+    for cell in cells {
+        *cell = (code % SYMBOLS as u64) as u8;
+        code /= SYMBOLS as u64;
+    }
+}
+
+fn encode_word<const SYMBOLS: usize>(word: &[u8], block: usize) -> Vec<u64> {
+    word.chunks(block).map(encode::<SYMBOLS>).collect()
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn opposite(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// What the base machine does from one end of a block until the head leaves it.
+#[derive(Debug, Clone, Copy)]
+enum MacroStep {
+    Halt,
+    /// The head stays in the block forever.
+    Stuck,
+    /// The head leaves the block to `Side` in the state after writing the block.
+    Leave(Side, u8, u64),
+}
+
+/// Abstract configuration. The words on either side have the macro symbol next to the head first.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct Configuration {
+    state: u8,
+    /// The end of the block the head is on.
+    entered: Side,
+    head: u64,
+    words: [Vec<u64>; 2],
+}
+
+/// An abstract configuration that is waiting for the words that continue a prefix on side `Side`.
+#[derive(Debug, Clone)]
+struct Waiter {
+    state: u8,
+    head: u64,
+    /// The word on the other side.
+    behind: Vec<u64>,
+}
+
+struct Closure<'a, const STATES: usize, const SYMBOLS: usize> {
+    states: &'a States<STATES, SYMBOLS>,
+    block: usize,
+    radius: usize,
+    max_configurations: usize,
+    /// Whether words must not be added.
+    frozen: bool,
+    macro_steps: HashMap<(u8, Side, u64), MacroStep>,
+    /// The last macro symbols of the words on each side by the other macro symbols of the word.
+    words: [HashMap<Vec<u64>, Vec<u64>>; 2],
+    waiters: [HashMap<Vec<u64>, Vec<Waiter>>; 2],
+    seen: HashSet<Configuration>,
+    pending: Vec<Configuration>,
+    /// Set when a configuration halts, needs a word while frozen or the configurations exceed the maximum.
+    failed: bool,
+}
+
+impl<'a, const STATES: usize, const SYMBOLS: usize> Closure<'a, STATES, SYMBOLS> {
+    fn new(
+        states: &'a States<STATES, SYMBOLS>,
+        block: usize,
+        radius: usize,
+        max_configurations: usize,
+    ) -> Self {
+        Self {
+            states,
+            block,
+            radius,
+            max_configurations,
+            frozen: false,
+            macro_steps: HashMap::new(),
+            words: [HashMap::new(), HashMap::new()],
+            waiters: [HashMap::new(), HashMap::new()],
+            seen: HashSet::new(),
+            pending: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Explore every abstract configuration reachable from the blank tape. Returns whether none of them halts.
+    fn run(&mut self) -> bool {
+        let blank = vec![0; self.radius];
+        self.push(Configuration {
+            state: 0,
+            entered: Side::Left,
+            head: 0,
+            words: [blank.clone(), blank],
+        });
+        while let Some(configuration) = self.pending.pop() {
+            if self.failed {
+                break;
+            }
+            self.step(configuration);
+        }
+        !self.failed
+    }
+
+    fn step(&mut self, configuration: Configuration) {
+        let (to, state, written) = match self.macro_step(
+            configuration.state,
+            configuration.entered,
+            configuration.head,
+        ) {
+            MacroStep::Halt => {
+                self.failed = true;
+                return;
+            }
+            MacroStep::Stuck => return,
+            MacroStep::Leave(to, state, written) => (to, state, written),
+        };
+        let behind_side = to.opposite();
+        // The written block becomes the macro symbol next to the head on the side it leaves.
+        let mut behind = vec![written];
+        behind.extend_from_slice(&configuration.words[behind_side as usize][..self.radius - 1]);
+        self.add(behind_side, behind.clone());
+        // The head moves onto the next macro symbol. The word after it is any word that continues the rest of the old word.
+        let ahead = &configuration.words[to as usize];
+        let waiter = Waiter {
+            state,
+            head: ahead[0],
+            behind,
+        };
+        let prefix = ahead[1..].to_vec();
+        for last in self.words[to as usize]
+            .get(&prefix)
+            .cloned()
+            .unwrap_or_default()
+        {
+            self.push_continued(to, &prefix, last, &waiter);
+        }
+        self.waiters[to as usize]
+            .entry(prefix)
+            .or_default()
+            .push(waiter);
+    }
+
+    fn push_continued(&mut self, to: Side, prefix: &[u64], last: u64, waiter: &Waiter) {
+        let mut ahead = prefix.to_vec();
+        ahead.push(last);
+        let mut words = [waiter.behind.clone(), ahead];
+        if to == Side::Left {
+            words.swap(0, 1);
+        }
+        self.push(Configuration {
+            state: waiter.state,
+            entered: to.opposite(),
+            head: waiter.head,
+            words,
+        });
+    }
+
+    fn push(&mut self, configuration: Configuration) {
+        if self.seen.len() >= self.max_configurations {
+            self.failed = true;
+        } else if self.seen.insert(configuration.clone()) {
+            self.pending.push(configuration);
+        }
+    }
+
+    /// Add a word that can appear on `side` and continue the configurations waiting for it.
+    fn add(&mut self, side: Side, word: Vec<u64>) {
+        let (last, prefix) = word.split_last().unwrap();
+        let lasts = self.words[side as usize]
+            .entry(prefix.to_vec())
+            .or_default();
+        if lasts.contains(last) {
+            return;
+        }
+        if self.frozen {
+            self.failed = true;
+            return;
+        }
+        lasts.push(*last);
+        let waiters = self.waiters[side as usize]
+            .get(prefix)
+            .cloned()
+            .unwrap_or_default();
+        for waiter in &waiters {
+            self.push_continued(side, prefix, *last, waiter);
+        }
+    }
+
+    fn contains(&self, side: Side, word: &[u64]) -> bool {
+        let (last, prefix) = word.split_last().unwrap();
+        self.words[side as usize]
+            .get(prefix)
+            .is_some_and(|lasts| lasts.contains(last))
+    }
+
+    /// Add a word before the exploration.
+    fn insert(&mut self, side: Side, word: Vec<u64>) {
+        let (last, prefix) = word.split_last().unwrap();
+        let lasts = self.words[side as usize]
+            .entry(prefix.to_vec())
+            .or_default();
+        if !lasts.contains(last) {
+            lasts.push(*last);
+        }
+    }
+
+    fn macro_step(&mut self, state: u8, entered: Side, block: u64) -> MacroStep {
+        if let Some(step) = self.macro_steps.get(&(state, entered, block)) {
+            return *step;
+        }
+        let mut cells = vec![0u8; self.block];
+        decode::<SYMBOLS>(block, &mut cells);
+        let mut position = match entered {
+            Side::Left => 0,
+            Side::Right => self.block as isize - 1,
+        };
+        let mut current = state;
+        // A run inside the block longer than the number of its configurations repeats one.
+        let limit = STATES * self.block * SYMBOLS.pow(self.block as u32);
+        let mut step = MacroStep::Stuck;
+        for _ in 0..=limit {
+            let cell = &mut cells[position as usize];
+            let Transition::Continue(t) = self.states.0[current as usize][*cell as usize] else {
+                step = MacroStep::Halt;
+                break;
+            };
+            *cell = t.write.get();
+            current = t.state.get();
+            position += match t.move_ {
+                Direction::Left => -1,
+                Direction::Right => 1,
+            };
+            if position < 0 {
+                step = MacroStep::Leave(Side::Left, current, encode::<SYMBOLS>(&cells));
+                break;
+            }
+            if position == self.block as isize {
+                step = MacroStep::Leave(Side::Right, current, encode::<SYMBOLS>(&cells));
+                break;
+            }
+        }
+        self.macro_steps.insert((state, entered, block), step);
+        step
+    }
+
+    fn certificate(&self) -> Certificate {
+        let mut cells = vec![0u8; self.block];
+        let mut words = |side: Side| {
+            let mut words: Vec<Vec<u8>> = self.words[side as usize]
+                .iter()
+                .flat_map(|(prefix, lasts)| {
+                    lasts.iter().map(move |last| {
+                        let mut word = prefix.clone();
+                        word.push(*last);
+                        word
+                    })
+                })
+                .map(|mut word| {
+                    if side == Side::Left {
+                        word.reverse();
+                    }
+                    word.iter()
+                        .flat_map(|&block| {
+                            decode::<SYMBOLS>(block, &mut cells);
+                            cells.clone()
+                        })
+                        .collect()
+                })
+                .collect();
+            words.sort();
+            words
+        };
+        Certificate {
+            block: self.block,
+            radius: self.radius,
+            left: words(Side::Left),
+            right: words(Side::Right),
+        }
+    }
+}
+
+#[test]
+fn binary_counter() {
+    let counter = crate::fixtures::get("binary counter")
+        .unwrap()
+        .machine::<2, 2>();
+    let decider = CounterDecider::new(Config::default());
+    let Outcome::NeverHalts(certificate) = decider.run(&counter) else {
+        panic!("binary counter is not decided");
+    };
+    assert!(certificate.check(&counter));
+    // Every word is needed.
+    let mut incomplete = certificate.clone();
+    incomplete.right.pop();
+    assert!(!incomplete.check(&counter));
+    assert!(!certificate.check(&crate::fixtures::BB2));
+    let markdown = certificate.to_markdown(&counter).unwrap();
+    assert!(markdown.starts_with("## `1RB1LA_0LA0RB` runs forever"));
+    assert!(incomplete.to_markdown(&counter).is_none());
+
+    let bouncer = crate::fixtures::get("bouncer").unwrap().machine::<2, 2>();
+    assert_eq!(decider.run(&bouncer), Outcome::NotACounter);
+    assert_eq!(
+        decider.run(&crate::fixtures::BB2),
+        Outcome::Halts { steps: 6 }
+    );
+    let mut decider = CounterDecider::<5, 2>::new(Config::default());
+    assert_eq!(decider.decide(&crate::fixtures::BB5), Decision::Undecided);
+}
+
+#[test]
+fn counter_decider_is_sound() {
+    use crate::{
+        run::SparseTape,
+        stress::{machine, Kind, Rng},
+    };
+    let decider = CounterDecider::<3, 2>::new(Config {
+        max_block: 2,
+        max_radius: 2,
+        max_configurations: 10_000,
+        ..Config::default()
+    });
+    let mut runner = Runner::new(SparseTape::unbounded());
+    let mut rng = Rng::new(0);
+    let mut never_halts = 0;
+    for _ in 0..1000 {
+        for kind in Kind::ALL {
+            let states = machine::<3, 2>(&mut rng, kind);
+            let Some(certificate) = decider.prove(&states) else {
+                continue;
+            };
+            assert!(certificate.check(&states));
+            never_halts += 1;
+            runner.set_states(&states);
+            runner.reset();
+            for _ in 0..1000 {
+                assert!(!matches!(runner.step(), StepResult::Halt), "{states}");
+            }
+        }
+    }
+    assert!(never_halts > 0);
+}
@@ -1,6 +1,16 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::states::States;
 
-#[derive(Debug)]
+#[cfg(feature = "std")]
+pub mod cyclers;
+
+/// Result of a `Decider`.
+///
+/// `name` and `code` are stable tags for storing results outside of the program. They agree with `format::LogDecision`, where running forever is called loop.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Decision {
     Halt,
     RunForever,
@@ -8,6 +18,53 @@ pub enum Decision {
     Undecided,
 }
 
+impl Decision {
+    pub const ALL: [Self; 4] = [
+        Self::Halt,
+        Self::RunForever,
+        Self::Irrelevant,
+        Self::Undecided,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Halt => "halt",
+            Self::RunForever => "loop",
+            Self::Irrelevant => "irrelevant",
+            Self::Undecided => "undecided",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|decision| decision.name() == name)
+    }
+
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Halt => b'h',
+            Self::RunForever => b'l',
+            Self::Irrelevant => b'i',
+            Self::Undecided => b'u',
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|decision| decision.code() == code)
+    }
+}
+
 pub trait Decider {
     fn decide(&mut self, states: &States<5, 2>) -> Decision;
 }
+
+#[test]
+fn decision_tags() {
+    for decision in Decision::ALL {
+        assert_eq!(Decision::from_name(decision.name()), Some(decision));
+        assert_eq!(Decision::from_code(decision.code()), Some(decision));
+    }
+}
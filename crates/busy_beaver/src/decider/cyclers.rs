@@ -0,0 +1,141 @@
+//! Decider for cyclers
+//!
+//! A cycler returns to a configuration it was in before: the same state, the same head position and the same cells. From there it repeats the same steps forever, so it never halts. The decider runs the machine on a bounded tape and remembers every configuration it passes through. Configurations are looked up by the checksum of a `ChecksumTape` and only compared cell by cell when the checksums match.
+//!
+//! Machines that repeat a configuration shifted along the tape, like translated cyclers, are not decided because the position is part of the configuration.
+
+use std::collections::HashMap;
+
+use crate::{
+    decider::{Decider, Decision},
+    run::{ChecksumTape, DenseTape, Runner, Snapshot, StepResult},
+    states::States,
+};
+
+/// Parameters of the decider. The defaults are those of bbchallenge's cyclers decider.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Config {
+    /// Machines that do not repeat a configuration within this many steps are not decided.
+    pub steps: u64,
+    /// Cells in either direction of the start cell. Machines that use more are not decided.
+    pub space: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            steps: 1000,
+            space: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Outcome {
+    /// The machine halts. `steps` counts the halting transition.
+    Halts { steps: u64 },
+    /// The configuration after `start` steps is repeated every `period` steps.
+    Cycles { start: u64, period: u64 },
+    /// No configuration was repeated within the limits.
+    MayHalt,
+}
+
+/// Detects machines that return to a previous configuration. The tape and the seen configurations are kept between machines to save allocations.
+pub struct CyclersDecider<const STATES: usize, const SYMBOLS: usize> {
+    config: Config,
+    runner: Runner<STATES, SYMBOLS, ChecksumTape<DenseTape<Vec<u8>>>>,
+    /// The configurations by checksum and the step they were seen at.
+    seen: HashMap<u64, Vec<(u64, Snapshot)>>,
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> CyclersDecider<STATES, SYMBOLS> {
+    pub fn new(config: Config) -> Self {
+        let tape = DenseTape::new(vec![0u8; 2 * config.space + 1]);
+        Self {
+            config,
+            runner: Runner::new(ChecksumTape::new(tape)),
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    pub fn run(&mut self, states: &States<STATES, SYMBOLS>) -> Outcome {
+        self.seen.clear();
+        self.runner.set_states(states);
+        self.runner.reset();
+        for step in 0..self.config.steps {
+            let snapshot = self.runner.snapshot();
+            let earlier = self.seen.entry(self.runner.checksum()).or_default();
+            if let Some((start, _)) = earlier
+                .iter()
+                .find(|(_, earlier)| earlier.same_configuration(&snapshot))
+            {
+                return Outcome::Cycles {
+                    start: *start,
+                    period: step - start,
+                };
+            }
+            earlier.push((step, snapshot));
+            match self.runner.step() {
+                StepResult::Ok => (),
+                StepResult::Halt => return Outcome::Halts { steps: step + 1 },
+                StepResult::TapeFullLeft | StepResult::TapeFullRight => return Outcome::MayHalt,
+            }
+        }
+        Outcome::MayHalt
+    }
+}
+
+impl Decider for CyclersDecider<5, 2> {
+    fn decide(&mut self, states: &States<5, 2>) -> Decision {
+        match self.run(states) {
+            Outcome::Halts { .. } => Decision::Halt,
+            Outcome::Cycles { .. } => Decision::RunForever,
+            Outcome::MayHalt => Decision::Undecided,
+        }
+    }
+}
+
+#[test]
+fn cyclers() {
+    use crate::{fixtures, format::read_compact};
+
+    let cycler = fixtures::get("cycler").unwrap().machine::<3, 2>();
+    let mut decider = CyclersDecider::<3, 2>::new(Config::default());
+    let Outcome::Cycles { start, period } = decider.run(&cycler) else {
+        panic!("the cycler is not decided");
+    };
+    // The configuration really repeats.
+    let mut runner = Runner::<3, 2, _>::sparse();
+    runner.set_states(&cycler);
+    (0..start).for_each(|_| assert_eq!(runner.step(), StepResult::Ok));
+    let first = runner.snapshot();
+    (0..period).for_each(|_| assert_eq!(runner.step(), StepResult::Ok));
+    assert!(first.same_configuration(&runner.snapshot()));
+    for name in ["translated cycler", "skelet 1"] {
+        let states = fixtures::get(name).unwrap();
+        let outcome = match states.states {
+            3 => decider.run(&states.machine()),
+            _ => CyclersDecider::<5, 2>::new(Config::default()).run(&states.machine()),
+        };
+        assert_eq!(outcome, Outcome::MayHalt, "{name}");
+    }
+    let bb4 = fixtures::get("bb4").unwrap().machine::<4, 2>();
+    assert_eq!(
+        CyclersDecider::<4, 2>::new(Config::default()).run(&bb4),
+        Outcome::Halts { steps: 107 }
+    );
+
+    let mut decider = CyclersDecider::<5, 2>::new(Config {
+        steps: 1000,
+        space: 2,
+    });
+    let padded = read_compact(b"1RB0LC_0LB1LC_0RA0LB_------_------").unwrap();
+    assert_eq!(decider.decide(&padded), Decision::RunForever);
+    // Leaves the small tape.
+    let bouncer = read_compact(b"1RB1LA_1LA1RB_------_------_------").unwrap();
+    assert_eq!(decider.decide(&bouncer), Decision::Undecided);
+}
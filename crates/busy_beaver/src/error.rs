@@ -0,0 +1,158 @@
+//! Errors of the library
+//!
+//! The binaries use anyhow, which only carries messages. The library returns this type instead so that callers can tell an invalid machine from a failed read or a bad limit without matching on text. Like anyhow, errors can be wrapped in context that says what was being done. `{:#}` prints the whole chain and `Error::root` gives the error below the context.
+
+use std::fmt::{self, Display, Formatter};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// New kinds of errors can be added without a breaking change, so matches need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The input does not have the expected format.
+    Parse(String),
+    /// The input is well formed but violates an invariant, like a proof that does not match its machine or a machine that cannot be represented.
+    Invariant(String),
+    /// A limit or another parameter cannot be used, like an interval of 0 or a step limit that is too small for the machine.
+    Limit(String),
+    /// Reading or writing failed.
+    Io(std::io::Error),
+    /// `source` happened while doing `context`.
+    Context { context: String, source: Box<Error> },
+}
+
+impl Error {
+    /// The error without the context around it.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(message) | Error::Invariant(message) | Error::Limit(message) => {
+                f.write_str(message)
+            }
+            // The I/O error is the source, so like the context it is only printed with the chain.
+            Error::Io(err) => {
+                f.write_str("reading or writing failed")?;
+                if f.alternate() {
+                    write!(f, ": {err}")?;
+                }
+                Ok(())
+            }
+            Error::Context { context, source } => {
+                f.write_str(context)?;
+                if f.alternate() {
+                    write!(f, ": {source:#}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Context { source, .. } => Some(source.as_ref()),
+            Error::Io(err) => Some(err),
+            Error::Parse(_) | Error::Invariant(_) | Error::Limit(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(err: std::num::ParseIntError) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+/// Adds context to errors like `anyhow::Context`. A missing `Option` value becomes a `Error::Parse` with the context as its message because in this crate that is almost always missing input.
+pub(crate) trait Context<T> {
+    fn context(self, context: impl Display) -> Result<T>;
+
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Display) -> Result<T> {
+        self.map_err(|err| Error::Context {
+            context: context.to_string(),
+            source: Box::new(err.into()),
+        })
+    }
+
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|err| Error::Context {
+            context: context().to_string(),
+            source: Box::new(err.into()),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, context: impl Display) -> Result<T> {
+        self.ok_or_else(|| Error::Parse(context.to_string()))
+    }
+
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.ok_or_else(|| Error::Parse(context().to_string()))
+    }
+}
+
+#[test]
+fn context_chain() {
+    let err = "x"
+        .parse::<u8>()
+        .context("invalid step")
+        .context("checkpoint 3")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "checkpoint 3");
+    assert_eq!(
+        format!("{err:#}"),
+        "checkpoint 3: invalid step: invalid digit found in string"
+    );
+    assert!(matches!(err.root(), Error::Parse(_)));
+    // anyhow prints the same chain through `source`.
+    let err = anyhow::Error::from(err);
+    assert_eq!(
+        format!("{err:#}"),
+        "checkpoint 3: invalid step: invalid digit found in string"
+    );
+}
+
+#[test]
+fn io_source() {
+    use std::error::Error as _;
+
+    let err = Err::<(), _>(std::io::Error::new(std::io::ErrorKind::NotFound, "gone"))
+        .context("open log")
+        .unwrap_err();
+    let io = err.source().unwrap().source().unwrap();
+    assert_eq!(
+        io.downcast_ref::<std::io::Error>().unwrap().kind(),
+        std::io::ErrorKind::NotFound
+    );
+    // The message of the I/O error is printed once, by this type and by anyhow.
+    assert_eq!(
+        format!("{err:#}"),
+        "open log: reading or writing failed: gone"
+    );
+    let err = anyhow::Error::from(err);
+    assert_eq!(
+        format!("{err:#}"),
+        "open log: reading or writing failed: gone"
+    );
+}
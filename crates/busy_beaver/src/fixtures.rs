@@ -0,0 +1,441 @@
+//! Notable turing machines with known behavior
+//!
+//! Tests in different modules use this corpus so that they agree on which machines are interesting and how they behave.
+
+use crate::{
+    format::{read_compact, read_compact_const},
+    score::Score,
+    states::States,
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Behavior {
+    /// The machine halts. `steps` counts the halting transition. `ones` is the number of non blank symbols after halting when the halting transition writes a 1, which is the convention used in the literature.
+    Halt { steps: u64, ones: u64 },
+    /// The machine returns to a previous configuration including the head position.
+    Cycler,
+    /// The machine returns to a previous configuration shifted along the tape.
+    TranslatedCycler,
+    /// The head sweeps back and forth over a region that grows with every sweep. The used tape grows with the square root of the steps.
+    Bouncer,
+    /// The tape holds a number that is incremented forever. The used tape grows with the logarithm of the steps.
+    Counter,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub states: usize,
+    pub symbols: usize,
+    /// The machine in the format of `format::read_compact`.
+    pub compact: &'static str,
+    pub behavior: Behavior,
+}
+
+impl Fixture {
+    /// Panics if `STATES` and `SYMBOLS` do not match the size of the fixture.
+    pub fn machine<const STATES: usize, const SYMBOLS: usize>(&self) -> States<STATES, SYMBOLS> {
+        assert_eq!(
+            (STATES, SYMBOLS),
+            (self.states, self.symbols),
+            "fixture {} has a different size",
+            self.name
+        );
+        read_compact(self.compact.as_bytes()).unwrap()
+    }
+}
+
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "bb2",
+        states: 2,
+        symbols: 2,
+        compact: BB2_COMPACT,
+        behavior: Behavior::Halt { steps: 6, ones: 4 },
+    },
+    Fixture {
+        name: "bb3",
+        states: 3,
+        symbols: 2,
+        compact: BB3_COMPACT,
+        behavior: Behavior::Halt { steps: 21, ones: 5 },
+    },
+    Fixture {
+        name: "sigma3",
+        states: 3,
+        symbols: 2,
+        compact: "1RB---_0RC1RB_1LC1LA",
+        behavior: Behavior::Halt { steps: 14, ones: 6 },
+    },
+    Fixture {
+        name: "bb4",
+        states: 4,
+        symbols: 2,
+        compact: BB4_COMPACT,
+        behavior: Behavior::Halt {
+            steps: 107,
+            ones: 13,
+        },
+    },
+    Fixture {
+        name: "bb5",
+        states: 5,
+        symbols: 2,
+        compact: BB5_COMPACT,
+        behavior: Behavior::Halt {
+            steps: 47176870,
+            ones: 4098,
+        },
+    },
+    Fixture {
+        name: "bb2x3",
+        states: 2,
+        symbols: 3,
+        compact: BB2X3_COMPACT,
+        behavior: Behavior::Halt { steps: 38, ones: 9 },
+    },
+    Fixture {
+        name: "bb2x4",
+        states: 2,
+        symbols: 4,
+        compact: BB2X4_COMPACT,
+        behavior: Behavior::Halt {
+            steps: 3932964,
+            ones: 2050,
+        },
+    },
+    Fixture {
+        name: "cycler",
+        states: 3,
+        symbols: 2,
+        compact: "1RB0LC_0LB1LC_0RA0LB",
+        behavior: Behavior::Cycler,
+    },
+    Fixture {
+        name: "translated cycler",
+        states: 3,
+        symbols: 2,
+        compact: "1RB0LA_1LC0RC_0RA0LC",
+        behavior: Behavior::TranslatedCycler,
+    },
+    Fixture {
+        name: "bouncer",
+        states: 2,
+        symbols: 2,
+        compact: "1RB1LA_1LA1RB",
+        behavior: Behavior::Bouncer,
+    },
+    Fixture {
+        name: "binary counter",
+        states: 2,
+        symbols: 2,
+        compact: "1RB1LA_0LA0RB",
+        behavior: Behavior::Counter,
+    },
+    // Skelet's list of 43 machines that his own deciders could not resolve. They were long the hardest holdouts of BB(5). Only the machines whose description could be checked against the literature are included. More can be added here or passed to the tools as a list file.
+    Fixture {
+        name: "skelet 1",
+        states: 5,
+        symbols: 2,
+        compact: "1RB1RD_1LC0RC_1RA1LD_0RE0LB_---1RC",
+        behavior: Behavior::TranslatedCycler,
+    },
+    Fixture {
+        name: "skelet 17",
+        states: 5,
+        symbols: 2,
+        compact: "1RB---_0LC1RE_0LD1LC_1RA1LB_0RB0RA",
+        behavior: Behavior::Counter,
+    },
+];
+
+/// Find a fixture by name.
+pub fn get(name: &str) -> Option<&'static Fixture> {
+    FIXTURES.iter().find(|f| f.name == name)
+}
+
+/// Number of machines in Skelet's list. `skelet` only has some of them.
+pub const SKELET_MACHINES: u8 = 43;
+
+/// Skelet's machines and their number in his list.
+pub fn skelet() -> impl Iterator<Item = (u8, &'static Fixture)> {
+    FIXTURES.iter().filter_map(|f| {
+        let number = f.name.strip_prefix("skelet ")?.parse().ok()?;
+        Some((number, f))
+    })
+}
+
+/// Fixtures with the given number of states and symbols.
+pub fn with_size(states: usize, symbols: usize) -> impl Iterator<Item = &'static Fixture> {
+    FIXTURES
+        .iter()
+        .filter(move |f| (f.states, f.symbols) == (states, symbols))
+}
+
+/// The best known machine by steps for a number of states and symbols.
+#[derive(Debug, Clone, Copy)]
+pub struct Champion {
+    pub states: usize,
+    pub symbols: usize,
+    /// The machine in the format of `format::read_compact`.
+    pub compact: &'static str,
+    /// `None` if the numbers do not fit in a `u64`.
+    pub score: Option<Score>,
+}
+
+impl Champion {
+    /// Panics if `STATES` and `SYMBOLS` do not match the size of the champion.
+    pub fn machine<const STATES: usize, const SYMBOLS: usize>(&self) -> States<STATES, SYMBOLS> {
+        assert_eq!(
+            (STATES, SYMBOLS),
+            (self.states, self.symbols),
+            "champion has a different size"
+        );
+        read_compact(self.compact.as_bytes()).unwrap()
+    }
+}
+
+// The champions in the format of `format::read_compact`. `FIXTURES`, `CHAMPIONS` and the typed constants below all refer to these.
+pub const BB2_COMPACT: &str = "1RB1LB_1LA---";
+pub const BB3_COMPACT: &str = "1RB---_1LB0RC_1LC1LA";
+pub const BB4_COMPACT: &str = "1RB1LB_1LA0LC_---1LD_1RD0RA";
+pub const BB5_COMPACT: &str = "1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA";
+pub const BB6_COMPACT: &str = "1RB1RA_1RC---_1LD0RF_1RA0LE_0LD1RC_1RA0RE";
+pub const BB2X3_COMPACT: &str = "1RB2LB---_2LA2RB1LB";
+pub const BB2X4_COMPACT: &str = "1RB2LA1RA1RA_1LB1LA3RB---";
+
+pub const BB2: States<2, 2> = read_compact_const(BB2_COMPACT.as_bytes());
+pub const BB3: States<3, 2> = read_compact_const(BB3_COMPACT.as_bytes());
+pub const BB4: States<4, 2> = read_compact_const(BB4_COMPACT.as_bytes());
+pub const BB5: States<5, 2> = read_compact_const(BB5_COMPACT.as_bytes());
+pub const BB6: States<6, 2> = read_compact_const(BB6_COMPACT.as_bytes());
+pub const BB2X3: States<2, 3> = read_compact_const(BB2X3_COMPACT.as_bytes());
+pub const BB2X4: States<2, 4> = read_compact_const(BB2X4_COMPACT.as_bytes());
+
+/// The champions in the order of the typed constants above. BB(2) to BB(5) and BB(2,3) and BB(2,4) are proven. The BB(6) machine halts after more than 10↑↑15 steps and is only the best known.
+pub const CHAMPIONS: &[Champion] = &[
+    Champion {
+        states: 2,
+        symbols: 2,
+        compact: BB2_COMPACT,
+        score: Some(Score {
+            steps: 6,
+            ones: 4,
+            space: 4,
+        }),
+    },
+    Champion {
+        states: 3,
+        symbols: 2,
+        compact: BB3_COMPACT,
+        score: Some(Score {
+            steps: 21,
+            ones: 5,
+            space: 5,
+        }),
+    },
+    Champion {
+        states: 4,
+        symbols: 2,
+        compact: BB4_COMPACT,
+        score: Some(Score {
+            steps: 107,
+            ones: 13,
+            space: 14,
+        }),
+    },
+    Champion {
+        states: 5,
+        symbols: 2,
+        compact: BB5_COMPACT,
+        score: Some(Score {
+            steps: 47176870,
+            ones: 4098,
+            space: 12289,
+        }),
+    },
+    Champion {
+        states: 6,
+        symbols: 2,
+        compact: BB6_COMPACT,
+        score: None,
+    },
+    Champion {
+        states: 2,
+        symbols: 3,
+        compact: BB2X3_COMPACT,
+        score: Some(Score {
+            steps: 38,
+            ones: 9,
+            space: 9,
+        }),
+    },
+    Champion {
+        states: 2,
+        symbols: 4,
+        compact: BB2X4_COMPACT,
+        score: Some(Score {
+            steps: 3932964,
+            ones: 2050,
+            space: 2050,
+        }),
+    },
+];
+
+/// The champion with the given number of states and symbols.
+pub fn champion(states: usize, symbols: usize) -> Option<&'static Champion> {
+    CHAMPIONS
+        .iter()
+        .find(|c| (c.states, c.symbols) == (states, symbols))
+}
+
+#[cfg(test)]
+fn halting_steps<const STATES: usize, const SYMBOLS: usize>(fixture: &Fixture) -> Option<u64> {
+    use crate::run::{Runner, StepResult};
+    let limit = match fixture.behavior {
+        Behavior::Halt { steps, .. } => steps,
+        _ => 1_000_000,
+    };
+    let mut runner = Runner::<STATES, SYMBOLS, _>::vector_backed(100_000);
+    runner.set_states(&fixture.machine());
+    for step in 1..=limit {
+        match runner.step() {
+            StepResult::Ok => (),
+            StepResult::Halt => return Some(step),
+            StepResult::TapeFullLeft | StepResult::TapeFullRight => return None,
+        }
+    }
+    None
+}
+
+#[test]
+fn fixtures() {
+    for fixture in FIXTURES {
+        let (compact, steps) = match (fixture.states, fixture.symbols) {
+            (2, 2) => (
+                fixture.machine::<2, 2>().to_string(),
+                halting_steps::<2, 2>(fixture),
+            ),
+            (3, 2) => (
+                fixture.machine::<3, 2>().to_string(),
+                halting_steps::<3, 2>(fixture),
+            ),
+            (4, 2) => (
+                fixture.machine::<4, 2>().to_string(),
+                halting_steps::<4, 2>(fixture),
+            ),
+            (5, 2) => (
+                fixture.machine::<5, 2>().to_string(),
+                halting_steps::<5, 2>(fixture),
+            ),
+            (2, 3) => (
+                fixture.machine::<2, 3>().to_string(),
+                halting_steps::<2, 3>(fixture),
+            ),
+            (2, 4) => (
+                fixture.machine::<2, 4>().to_string(),
+                halting_steps::<2, 4>(fixture),
+            ),
+            size => panic!("fixture {} has untested size {size:?}", fixture.name),
+        };
+        assert_eq!(compact, fixture.compact);
+        let expected = match fixture.behavior {
+            Behavior::Halt { steps, .. } => Some(steps),
+            _ => None,
+        };
+        assert_eq!(steps, expected, "{}", fixture.name);
+    }
+}
+
+#[test]
+fn non_halting_fixtures() {
+    for fixture in FIXTURES {
+        match (fixture.states, fixture.symbols) {
+            _ if matches!(fixture.behavior, Behavior::Halt { .. }) => (),
+            (2, 2) => check_non_halting::<2, 2>(fixture),
+            (3, 2) => check_non_halting::<3, 2>(fixture),
+            (5, 2) => check_non_halting::<5, 2>(fixture),
+            size => panic!("fixture {} has untested size {size:?}", fixture.name),
+        }
+    }
+}
+
+/// Checks that the fixture does not halt and that its used tape grows like its behavior says.
+#[cfg(test)]
+fn check_non_halting<const STATES: usize, const SYMBOLS: usize>(fixture: &Fixture) {
+    use crate::run::{Runner, StepResult};
+    let name = fixture.name;
+    let mut runner = Runner::<STATES, SYMBOLS, _>::sparse();
+    runner.set_states(&fixture.machine());
+    let mut snapshots = vec![runner.snapshot()];
+    // Width of the used tape at every checkpoint.
+    let checkpoints = [1_000, 4_000, 16_000, 64_000, 256_000];
+    let mut widths = Vec::new();
+    for step in 1..=256_000u64 {
+        assert_eq!(runner.step(), StepResult::Ok, "{name} halts");
+        if step <= 1000 {
+            snapshots.push(runner.snapshot());
+        }
+        if checkpoints.contains(&step) {
+            let (left, right) = runner.extent();
+            widths.push((right - left + 1) as f64);
+        }
+    }
+    let repeats = snapshots
+        .iter()
+        .enumerate()
+        .any(|(i, a)| snapshots[i + 1..].iter().any(|b| a.same_configuration(b)));
+    // Growth of the used tape when the steps are multiplied by 4.
+    let [.., before, last] = widths[..] else {
+        unreachable!()
+    };
+    // Skelet's machines only show their behavior after far more steps than a test can take.
+    if name.starts_with("skelet ") {
+        assert!(!repeats, "{name}");
+        return;
+    }
+    match fixture.behavior {
+        Behavior::Halt { .. } => unreachable!(),
+        Behavior::Cycler => assert!(repeats && last == before, "{name}"),
+        Behavior::TranslatedCycler => assert!(!repeats && last / before > 3.5, "{name}"),
+        Behavior::Bouncer => assert!(!repeats && (1.8..2.2).contains(&(last / before)), "{name}"),
+        Behavior::Counter => assert!(!repeats && last - before <= 4., "{name}"),
+    }
+}
+
+#[test]
+fn skelet_numbers() {
+    let mut numbers: Vec<u8> = skelet().map(|(number, _)| number).collect();
+    assert!(!numbers.is_empty());
+    assert!(numbers.iter().all(|n| (1..=SKELET_MACHINES).contains(n)));
+    let count = numbers.len();
+    numbers.dedup();
+    assert_eq!(numbers.len(), count);
+}
+
+#[test]
+fn champions() {
+    use crate::{run::Runner, score::score};
+    fn check<const STATES: usize, const SYMBOLS: usize>(typed: States<STATES, SYMBOLS>) {
+        let champion = champion(STATES, SYMBOLS).unwrap();
+        assert_eq!(typed, champion.machine());
+        assert_eq!(typed.to_string(), champion.compact);
+        if let Some(expected) = champion.score {
+            let actual = score(&mut Runner::sparse(), &typed, expected.steps);
+            assert_eq!(actual, Some(expected), "{}", champion.compact);
+        }
+    }
+    check(BB2);
+    check(BB3);
+    check(BB4);
+    check(BB5);
+    check(BB6);
+    check(BB2X3);
+    check(BB2X4);
+    assert!(champion(3, 3).is_none());
+    // The padded BB(4) champion of `format` is the same machine.
+    assert_eq!(
+        crate::format::BB4_CHAMPION_COMPACT,
+        format!("{BB4_COMPACT}_------").as_bytes()
+    );
+}
@@ -1,133 +1,1454 @@
 //! Turing machine formatting
 
-use anyhow::{anyhow, Context, Result};
-use arrayvec::ArrayVec;
+use crate::error::{Context, Error, Result};
+use crate::states::{
+    DefinedTransition, Direction, Quadruple, QuadrupleAction, State, States, Transition,
+};
 
-use crate::states::{DefinedTransition, Direction, State, States, Symbol, Transition};
-
-pub const BB5_CHAMPION_COMPACT: &[u8] = b"1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA";
+pub const BB5_CHAMPION_COMPACT: &[u8] = crate::fixtures::BB5_COMPACT.as_bytes();
+/// `fixtures::BB4_COMPACT` as a 5 state machine whose last state is unused.
 pub const BB4_CHAMPION_COMPACT: &[u8] = b"1RB1LB_1LA0LC_---1LD_1RD0RA_------";
 
-/// Parse a compact human readable turing machine representation.
-pub fn read_compact(s: &[u8]) -> Result<States<5, 2>> {
-    if s.len() != 34 {
-        return Err(anyhow!("invalid length"));
-    }
-    let states = s
-        .chunks(7)
-        .map(|s| {
-            s.chunks_exact(3)
-                .map(read_transition_compact)
-                .collect::<Result<ArrayVec<_, 2>>>()
-                .map(|a| a.into_inner().unwrap())
-        })
-        .collect::<Result<ArrayVec<_, 5>>>()?
-        .into_inner()
-        .unwrap();
-    Ok(States(states))
+/// Parse a compact human readable turing machine representation like `1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA`.
+///
+/// States are separated by `_`. Halting transitions are written as `---`.
+pub fn read_compact<const STATES: usize, const SYMBOLS: usize>(
+    s: &[u8],
+) -> Result<States<STATES, SYMBOLS>> {
+    let state_len = 3 * SYMBOLS + 1;
+    if s.len() + 1 != STATES * state_len {
+        return Err(Error::Parse("invalid length".into()));
+    }
+    let mut states = States::default();
+    for (chunk, state) in s.chunks(state_len).zip(states.0.iter_mut()) {
+        if chunk.len() == state_len && chunk[state_len - 1] != b'_' {
+            return Err(Error::Parse("expected `_` between states".into()));
+        }
+        for (s, transition) in chunk.chunks_exact(3).zip(state.iter_mut()) {
+            *transition = read_transition_compact(s)?;
+        }
+    }
+    Ok(states)
 }
 
-fn read_transition_compact(s: &[u8]) -> Result<Transition<5, 2>> {
+fn read_transition_compact<const STATES: usize, const SYMBOLS: usize>(
+    s: &[u8],
+) -> Result<Transition<STATES, SYMBOLS>> {
     assert_eq!(s.len(), 3);
     if s == b"---" {
         return Ok(Transition::Halt);
     }
-    let write = Symbol::new(s[0] - b'0').context("invalid symbol")?;
-    let move_ = match s[1] {
-        b'L' => Direction::Left,
-        b'R' => Direction::Right,
-        _ => return Err(anyhow!("invalid move direction")),
+    let direction = match s[1] {
+        b'L' => 0,
+        b'R' => 1,
+        _ => return Err(Error::Parse("invalid move direction".into())),
+    };
+    DefinedTransition::from_parts(s[0].wrapping_sub(b'0'), direction, s[2].wrapping_sub(b'A'))
+        .map(Transition::Continue)
+        .context("invalid symbol or state")
+}
+
+/// `read_compact` for constants. Invalid input panics, which is a compile error when the machine is a constant.
+///
+/// ```
+/// use busy_beaver::{format::read_compact_const, states::States};
+/// const BB2: States<2, 2> = read_compact_const(b"1RB1LB_1LA---");
+/// ```
+pub const fn read_compact_const<const STATES: usize, const SYMBOLS: usize>(
+    s: &[u8],
+) -> States<STATES, SYMBOLS> {
+    let state_len = 3 * SYMBOLS + 1;
+    assert!(s.len() + 1 == STATES * state_len, "invalid length");
+    let mut states = States([[Transition::Halt; SYMBOLS]; STATES]);
+    let mut state = 0;
+    while state < STATES {
+        let start = state * state_len;
+        assert!(
+            state + 1 == STATES || s[start + state_len - 1] == b'_',
+            "expected `_` between states"
+        );
+        let mut symbol = 0;
+        while symbol < SYMBOLS {
+            let i = start + 3 * symbol;
+            if !(s[i] == b'-' && s[i + 1] == b'-' && s[i + 2] == b'-') {
+                let direction = match s[i + 1] {
+                    b'L' => 0,
+                    b'R' => 1,
+                    _ => panic!("invalid move direction"),
+                };
+                let Some(transition) = DefinedTransition::from_parts(
+                    s[i].wrapping_sub(b'0'),
+                    direction,
+                    s[i + 2].wrapping_sub(b'A'),
+                ) else {
+                    panic!("invalid symbol or state");
+                };
+                states.0[state][symbol] = Transition::Continue(transition);
+            }
+            symbol += 1;
+        }
+        state += 1;
+    }
+    states
+}
+
+/// Length of the header at the start of a Bbchallenge seed database.
+pub const SEED_DATABASE_HEADER_LEN: usize = 30;
+/// Length of one machine in a Bbchallenge seed database.
+pub const SEED_DATABASE_ENTRY_LEN: usize = 30;
+
+/// Header of a Bbchallenge seed database.
+///
+/// The header consists of big endian `u32` counts of time limit machines, space limit machines and all machines, followed by a byte that is 1 if the machines are sorted. The remaining bytes are zero. Time limit machines come before space limit machines.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct SeedDatabaseHeader {
+    /// Machines that were undecided because they reached the step limit.
+    pub time_limit: u32,
+    /// Machines that were undecided because they reached the space limit.
+    pub space_limit: u32,
+    pub sorted: bool,
+}
+
+impl SeedDatabaseHeader {
+    pub fn total(&self) -> u32 {
+        self.time_limit + self.space_limit
+    }
+}
+
+pub fn read_seed_database_header(s: &[u8]) -> Result<SeedDatabaseHeader> {
+    if s.len() != SEED_DATABASE_HEADER_LEN {
+        return Err(Error::Parse("invalid length".into()));
+    }
+    let u32_at = |i: usize| u32::from_be_bytes(s[i..i + 4].try_into().unwrap());
+    let header = SeedDatabaseHeader {
+        time_limit: u32_at(0),
+        space_limit: u32_at(4),
+        sorted: match s[12] {
+            0 => false,
+            1 => true,
+            _ => return Err(Error::Parse("invalid sorted flag".into())),
+        },
     };
-    let state = State::new(s[2] - b'A').context("invalid state")?;
-    Ok(Transition::Continue(DefinedTransition {
-        write,
-        move_,
-        state,
-    }))
+    if header.time_limit.checked_add(header.space_limit) != Some(u32_at(8)) {
+        return Err(Error::Parse(
+            "total does not match the sum of the counts".into(),
+        ));
+    }
+    Ok(header)
+}
+
+pub fn write_seed_database_header(header: &SeedDatabaseHeader) -> [u8; SEED_DATABASE_HEADER_LEN] {
+    let mut result = [0u8; SEED_DATABASE_HEADER_LEN];
+    result[0..4].copy_from_slice(&header.time_limit.to_be_bytes());
+    result[4..8].copy_from_slice(&header.space_limit.to_be_bytes());
+    result[8..12].copy_from_slice(&header.total().to_be_bytes());
+    result[12] = header.sorted as u8;
+    result
+}
+
+/// How halting transitions are written in the seed database format.
+///
+/// The format has no halting state. The official database writes halting transitions, which are the transitions the enumeration left undefined, as three 0 bytes. Some tools write the halting transition of a machine like `1RZ` instead: write 1, move right and go to state 0, which the format does not use for a real state.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum SeedDatabaseHalt {
+    /// All three bytes are 0, like in the official database.
+    #[default]
+    Undefined,
+    /// Write 1, move right and go to state 0. The official database does not contain this.
+    Explicit,
+}
+
+impl SeedDatabaseHalt {
+    fn bytes(self) -> [u8; 3] {
+        match self {
+            Self::Undefined => [0, 0, 0],
+            Self::Explicit => [1, 0, 0],
+        }
+    }
 }
 
-/// Parse a Bbchallenge seed database turing machine representation.
+/// Parse a Bbchallenge seed database turing machine representation. Only halting transitions like in the official database are accepted.
 pub fn read_seed_database(s: &[u8]) -> Result<States<5, 2>> {
+    read_seed_database_with(s, &[SeedDatabaseHalt::Undefined])
+}
+
+/// Like `read_seed_database` but halting transitions can be in any of the given encodings.
+pub fn read_seed_database_with(s: &[u8], halts: &[SeedDatabaseHalt]) -> Result<States<5, 2>> {
     if s.len() != 30 {
-        return Err(anyhow!("invalid length"));
+        return Err(Error::Parse("invalid length".into()));
     }
     let mut states = States::default();
     for (chunk, transition) in s.chunks_exact(3).zip(states.0.iter_mut().flatten()) {
-        *transition = read_transition_seed_database(chunk)?;
+        *transition = read_transition_seed_database(chunk, halts)?;
     }
     Ok(states)
 }
 
-fn read_transition_seed_database(s: &[u8]) -> Result<Transition<5, 2>> {
+fn read_transition_seed_database(s: &[u8], halts: &[SeedDatabaseHalt]) -> Result<Transition<5, 2>> {
     assert_eq!(s.len(), 3);
-    if s == [0, 0, 0] {
+    if halts.iter().any(|halt| halt.bytes() == s) {
         return Ok(Transition::Halt);
     }
-    let write = Symbol::new(s[0]).context("invalid symbol")?;
-    let move_ = match s[1] {
-        0 => Direction::Right,
-        1 => Direction::Left,
-        _ => return Err(anyhow!("invalid move direction")),
-    };
-    let state = State::new(s[2] - 1).context("invalid state")?;
-    Ok(Transition::Continue(DefinedTransition {
-        write,
-        move_,
-        state,
-    }))
+    // The database uses 0 for right and states start at 1.
+    DefinedTransition::from_parts(s[0], s[1] ^ 1, s[2].wrapping_sub(1))
+        .map(Transition::Continue)
+        .context("invalid transition")
 }
 
-impl std::fmt::Display for States<5, 2> {
+/// The compact format of `read_compact`. The alternate format `{:#}` is a multi-line table with a row for each state and a column for each symbol, which is easier to read when debugging.
+impl<const STATES: usize, const SYMBOLS: usize> std::fmt::Display for States<STATES, SYMBOLS> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            let header: String = (0..SYMBOLS).map(|s| format!(" {s:^3}")).collect();
+            write!(f, " {}", header.trim_end())?;
+            for (i, state) in self.0.iter().enumerate() {
+                write!(f, "\n{}", char::from(b'A' + i as u8))?;
+                for transition in state {
+                    write!(f, " {transition}")?;
+                }
+            }
+            return Ok(());
+        }
         for (i, state) in self.0.iter().enumerate() {
             if i != 0 {
                 write!(f, "_")?;
             }
             for transition in state {
-                let Transition::Continue(DefinedTransition {
-                    write,
-                    move_,
-                    state,
-                }) = transition
-                else {
-                    write!(f, "---")?;
-                    continue;
-                };
-                let write = char::from_u32(b'0' as u32 + write.get() as u32).unwrap();
-                let direction = match move_ {
-                    Direction::Left => 'L',
-                    Direction::Right => 'R',
-                };
-                let state = char::from_u32(b'A' as u32 + state.get() as u32).unwrap();
-                write!(f, "{write}{direction}{state}")?;
+                write!(f, "{transition}")?;
             }
         }
         Ok(())
     }
 }
 
-/// Write a turing machine in Bbchallenge seed database representation.
+impl<const STATES: usize, const SYMBOLS: usize> std::fmt::Display for Transition<STATES, SYMBOLS> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Transition::Continue(DefinedTransition {
+            write,
+            move_,
+            state,
+        }) = self
+        else {
+            return write!(f, "---");
+        };
+        let write = char::from_u32(b'0' as u32 + write.get() as u32).unwrap();
+        let direction = match move_ {
+            Direction::Left => 'L',
+            Direction::Right => 'R',
+        };
+        let state = char::from_u32(b'A' as u32 + state.get() as u32).unwrap();
+        write!(f, "{write}{direction}{state}")
+    }
+}
+
+/// Write a turing machine in Bbchallenge seed database representation, byte for byte like the official database.
 pub fn write_seed_database(states: &States<5, 2>) -> [u8; 30] {
+    write_seed_database_with(states, SeedDatabaseHalt::Undefined)
+}
+
+/// Like `write_seed_database` but halting transitions are written in the given encoding.
+pub fn write_seed_database_with(states: &States<5, 2>, halt: SeedDatabaseHalt) -> [u8; 30] {
     let mut result = [0u8; 30];
     for (transition, chunk) in states.0.iter().flatten().zip(result.chunks_exact_mut(3)) {
         match transition {
-            Transition::Halt => chunk.copy_from_slice(&[0; 3]),
+            Transition::Halt => chunk.copy_from_slice(&halt.bytes()),
             Transition::Continue(t) => {
-                chunk[0] = t.write.get();
-                chunk[1] = match t.move_ {
-                    Direction::Left => 1,
-                    Direction::Right => 0,
-                };
-                chunk[2] = t.state.get() + 1;
+                let (write, direction, state) = t.to_parts();
+                chunk.copy_from_slice(&[write, direction ^ 1, state + 1]);
+            }
+        }
+    }
+    result
+}
+
+/// Parse a machine as listed by Marxen and Buntrock, who found the BB(5) champion. Their tables have one row per state with the state letter followed by the transitions for each read symbol, optionally with a header row of the symbols:
+///
+/// ```text
+///     0   1
+/// A 1RB 1LC
+/// B 1RC 1RB
+/// C 1RD 0LE
+/// D 1LA 1LD
+/// E 1RH 0LA
+/// ```
+///
+/// Rows can also be written on one line. Halting transitions go to the state `H` or `Z`, which is only possible if that letter is not one of the machine's states. `---` is accepted for halting too.
+pub fn read_marxen_buntrock<const STATES: usize, const SYMBOLS: usize>(
+    s: &str,
+) -> Result<States<STATES, SYMBOLS>> {
+    // State labels and symbol headers are shorter than transitions.
+    let transitions: Vec<&str> = s
+        .split_whitespace()
+        .filter(|token| token.len() == 3)
+        .collect();
+    if transitions.len() != STATES * SYMBOLS {
+        return Err(Error::Parse(format!(
+            "expected {} transitions, found {}",
+            STATES * SYMBOLS,
+            transitions.len()
+        )));
+    }
+    let mut states = States::default();
+    for (token, transition) in transitions.iter().zip(states.0.iter_mut().flatten()) {
+        *transition = read_transition_lenient(token)?;
+    }
+    Ok(states)
+}
+
+/// Parse a transition like `read_transition_compact` but also accept the ways halting transitions are written in the literature: `---` or a transition to the state `H` or `Z` if that letter is not one of the machine's states.
+fn read_transition_lenient<const STATES: usize, const SYMBOLS: usize>(
+    s: &str,
+) -> Result<Transition<STATES, SYMBOLS>> {
+    let bytes = s.as_bytes();
+    if bytes.len() != 3 {
+        return Err(Error::Parse(format!("invalid transition {s:?}")));
+    }
+    let is_halt_state =
+        matches!(bytes[2], b'H' | b'Z') && State::<STATES>::new(bytes[2] - b'A').is_none();
+    if is_halt_state {
+        return Ok(Transition::Halt);
+    }
+    read_transition_compact(bytes).with_context(|| format!("invalid transition {s:?}"))
+}
+
+/// Parse a machine from a spreadsheet style table in CSV or TSV. Rows are states and columns are read symbols:
+///
+/// ```text
+/// ,0,1
+/// A,1RB,1LC
+/// B,1RC,1RB
+/// ```
+///
+/// The header row and the column of state labels are optional. Cells are separated by tabs if there are any tabs and by commas otherwise. Halting transitions are written like in `read_marxen_buntrock` or as empty cells.
+pub fn read_table<const STATES: usize, const SYMBOLS: usize>(
+    s: &str,
+) -> Result<States<STATES, SYMBOLS>> {
+    let delimiter = if s.contains('\t') { '\t' } else { ',' };
+    let is_header = |cells: &[&str]| {
+        cells.len() == SYMBOLS
+            && cells
+                .iter()
+                .enumerate()
+                .all(|(i, cell)| *cell == i.to_string())
+    };
+    let mut rows = Vec::<Vec<&str>>::new();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cells: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+        let cells = match cells.len() {
+            n if n == SYMBOLS => cells,
+            n if n == SYMBOLS + 1 => cells[1..].to_vec(),
+            n => {
+                return Err(Error::Parse(format!(
+                    "line {}: expected {SYMBOLS} transitions, found {n} cells",
+                    i + 1
+                )))
+            }
+        };
+        if rows.is_empty() && is_header(&cells) {
+            continue;
+        }
+        rows.push(cells);
+    }
+    if rows.len() != STATES {
+        return Err(Error::Parse(format!(
+            "expected {STATES} states, found {}",
+            rows.len()
+        )));
+    }
+    let mut states = States::default();
+    for (i, (row, state)) in rows.iter().zip(states.0.iter_mut()).enumerate() {
+        for (cell, transition) in row.iter().zip(state.iter_mut()) {
+            *transition = match *cell {
+                "" => Transition::Halt,
+                cell => read_transition_lenient(cell).with_context(|| format!("state {i}"))?,
+            };
+        }
+    }
+    Ok(states)
+}
+
+/// Write a machine as a table in the format of `read_table` with a header row and state labels. `delimiter` is usually `,` or `\t`.
+pub fn write_table<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+    delimiter: char,
+) -> String {
+    let mut result = String::new();
+    for symbol in 0..SYMBOLS {
+        result.push(delimiter);
+        result.push_str(&symbol.to_string());
+    }
+    for (i, state) in states.0.iter().enumerate() {
+        result.push('\n');
+        result.push(char::from(b'A' + i as u8));
+        for transition in state {
+            result.push(delimiter);
+            result.push_str(&transition.to_string());
+        }
+    }
+    result.push('\n');
+    result
+}
+
+/// Write a machine as a Markdown table with a row per state and a column per read symbol, for proof sketches and reports that are posted as Markdown. Halting transitions are written as `---`.
+pub fn write_markdown_table<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+) -> String {
+    let mut result = String::from("|   |");
+    for symbol in 0..SYMBOLS {
+        result.push_str(&format!(" {symbol} |"));
+    }
+    result.push_str("\n|---|");
+    result.push_str(&"---|".repeat(SYMBOLS));
+    for (i, state) in states.0.iter().enumerate() {
+        result.push_str(&format!("\n| {} |", char::from(b'A' + i as u8)));
+        for transition in state {
+            result.push_str(&format!(" {transition} |"));
+        }
+    }
+    result.push('\n');
+    result
+}
+
+/// Write a machine as a table in the format of `read_marxen_buntrock`. Halting transitions are written as `1RH`, which is the convention of the literature. Panics if the machine has more than 7 states because `H` would be one of them.
+pub fn write_marxen_buntrock<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+) -> String {
+    assert!(STATES <= 7, "`H` is a state of the machine");
+    let mut result = String::from(" ");
+    for symbol in 0..SYMBOLS {
+        result.push_str(&format!("   {symbol}"));
+    }
+    for (i, state) in states.0.iter().enumerate() {
+        result.push('\n');
+        result.push(char::from(b'A' + i as u8));
+        for transition in state {
+            match transition {
+                Transition::Halt => result.push_str(" 1RH"),
+                transition => result.push_str(&format!(" {transition}")),
             }
         }
     }
+    result.push('\n');
     result
 }
 
+/// Convert a rule number of Wolfram's Turing machine numbering (as in Mathematica's `TuringMachine[{rule, states, symbols}]`) to a machine.
+///
+/// The rule number is written in base `2 * STATES * SYMBOLS`. There is one digit per case. The most significant digit is for the first state reading the largest symbol. Then the symbols of that state follow in descending order and then the next state. A digit encodes `2 * SYMBOLS * next_state + 2 * write + right`. Wolfram's machines do not halt so every transition is defined.
+pub fn from_wolfram_rule<const STATES: usize, const SYMBOLS: usize>(
+    rule: u128,
+) -> Result<States<STATES, SYMBOLS>> {
+    let base = (2 * STATES * SYMBOLS) as u128;
+    let mut remaining = rule;
+    let mut states = States::default();
+    // The least significant digit is for the last state reading 0.
+    for state in states.0.iter_mut().rev() {
+        for transition in state.iter_mut() {
+            let digit = (remaining % base) as usize;
+            remaining /= base;
+            let write = (digit / 2) % SYMBOLS;
+            let state = digit / (2 * SYMBOLS);
+            *transition = Transition::Continue(
+                DefinedTransition::from_parts(write as u8, (digit % 2) as u8, state as u8).unwrap(),
+            );
+        }
+    }
+    if remaining != 0 {
+        return Err(Error::Parse("rule number is too large".into()));
+    }
+    Ok(states)
+}
+
+/// Inverse of `from_wolfram_rule`. Returns an error if the machine has a halting transition or if the rule number does not fit.
+pub fn to_wolfram_rule<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+) -> Result<u128> {
+    let base = (2 * STATES * SYMBOLS) as u128;
+    let mut rule: u128 = 0;
+    for state in states.0.iter() {
+        for transition in state.iter().rev() {
+            let Transition::Continue(t) = transition else {
+                return Err(Error::Invariant("machine has a halting transition".into()));
+            };
+            let (write, direction, state) = t.to_parts();
+            let digit = 2 * SYMBOLS * state as usize + 2 * write as usize + direction as usize;
+            rule = rule
+                .checked_mul(base)
+                .and_then(|rule| rule.checked_add(digit as u128))
+                .ok_or_else(|| Error::Invariant("rule number is too large".into()))?;
+        }
+    }
+    Ok(rule)
+}
+
+/// Bits of a halting transition in `write_packed`. No defined transition of a machine that fits has these bits.
+const PACKED_HALT: u64 = 0x3f;
+
+/// Pack a machine into a `u64` with 6 bits per transition: the bits of `DefinedTransition::to_bits` or all bits set for a halting transition. The first transition is in the least significant bits. Returns an error if the machine has more than 10 transitions, more than 8 states or more than 4 symbols.
+pub fn write_packed<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+) -> Result<u64> {
+    check_packed_size::<STATES, SYMBOLS>()?;
+    Ok(states
+        .0
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(i, transition)| {
+            let bits = match transition {
+                Transition::Halt => PACKED_HALT,
+                Transition::Continue(t) => t.to_bits() as u64,
+            };
+            bits << (6 * i)
+        })
+        .fold(0, |packed, bits| packed | bits))
+}
+
+/// Inverse of `write_packed`. Bits after the last transition must be 0.
+pub fn read_packed<const STATES: usize, const SYMBOLS: usize>(
+    packed: u64,
+) -> Result<States<STATES, SYMBOLS>> {
+    check_packed_size::<STATES, SYMBOLS>()?;
+    let transitions = STATES * SYMBOLS;
+    if transitions < 10 && packed >> (6 * transitions) != 0 {
+        return Err(Error::Parse("bits after the last transition".into()));
+    }
+    let mut states = States::default();
+    for (i, transition) in states.0.iter_mut().flatten().enumerate() {
+        let bits = (packed >> (6 * i)) & 0x3f;
+        *transition = if bits == PACKED_HALT {
+            Transition::Halt
+        } else {
+            Transition::Continue(
+                DefinedTransition::from_bits(bits as u8)
+                    .ok_or_else(|| Error::Parse(format!("invalid transition {i}")))?,
+            )
+        };
+    }
+    Ok(states)
+}
+
+fn check_packed_size<const STATES: usize, const SYMBOLS: usize>() -> Result<()> {
+    if STATES * SYMBOLS > 10 || STATES > 8 || SYMBOLS > 4 {
+        return Err(Error::Invariant("machine is too large to pack".into()));
+    }
+    Ok(())
+}
+
+/// Parse a quadruple machine with one rule per line like `A 0 1 B` or `B 1 L -`.
+///
+/// The fields are the state, the read symbol, the action and the next state. States are letters starting at `A`. The action is a symbol to write or `L` or `R` to move. `-` as the next state halts. Empty lines and lines starting with `#` are ignored.
+pub fn read_quadruples(s: &str) -> Result<Vec<Quadruple>> {
+    let state = |s: &str| -> Result<u8> {
+        match s.as_bytes() {
+            [c @ b'A'..=b'Z'] => Ok(c - b'A'),
+            _ => Err(Error::Parse(format!("invalid state {s:?}"))),
+        }
+    };
+    let mut result = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parse = || -> Result<Quadruple> {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [state_, read, action, next] = fields[..] else {
+                return Err(Error::Parse("expected 4 fields".into()));
+            };
+            Ok(Quadruple {
+                state: state(state_)?,
+                read: read.parse().context("invalid symbol")?,
+                action: match action {
+                    "L" => QuadrupleAction::Move(Direction::Left),
+                    "R" => QuadrupleAction::Move(Direction::Right),
+                    write => QuadrupleAction::Write(write.parse().context("invalid action")?),
+                },
+                next: match next {
+                    "-" => None,
+                    next => Some(state(next)?),
+                },
+            })
+        };
+        result.push(parse().with_context(|| format!("line {}", i + 1))?);
+    }
+    Ok(result)
+}
+
+/// Write a quadruple machine in the format of `read_quadruples`. Panics if a state is not below 26.
+pub fn write_quadruples(quadruples: &[Quadruple]) -> String {
+    let state = |state: u8| {
+        assert!(state < 26, "state {state} cannot be written");
+        char::from(b'A' + state)
+    };
+    let mut result = String::new();
+    for q in quadruples {
+        let action = match q.action {
+            QuadrupleAction::Write(write) => write.to_string(),
+            QuadrupleAction::Move(Direction::Left) => "L".to_string(),
+            QuadrupleAction::Move(Direction::Right) => "R".to_string(),
+        };
+        let next = match q.next {
+            Some(next) => state(next),
+            None => '-',
+        };
+        result.push_str(&format!("{} {} {action} {next}\n", state(q.state), q.read));
+    }
+    result
+}
+
+/// Length of one entry in the log of the seed run including the newline. An entry is a 5 state machine in the format of `read_compact`, a space, a one character decision code and a newline.
+pub const LOG_ENTRY_LEN: usize = 37;
+
+/// Decision of a log entry.
+///
+/// The codes are those of the seed run. Finer results, like which limit an undecided machine reached, are not encoded: every entry has the same length and many tools, hashes and published logs depend on the four codes. Such results are kept next to the log instead.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum LogDecision {
+    Halt,
+    Loop,
+    Undecided,
+    Irrelevant,
+}
+
+impl LogDecision {
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            b'h' => Some(Self::Halt),
+            b'l' => Some(Self::Loop),
+            b'u' => Some(Self::Undecided),
+            b'i' => Some(Self::Irrelevant),
+            _ => None,
+        }
+    }
+
+    /// The character used in the log.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Halt => b'h',
+            Self::Loop => b'l',
+            Self::Undecided => b'u',
+            Self::Irrelevant => b'i',
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Halt => "halt",
+            Self::Loop => "loop",
+            Self::Undecided => "undecided",
+            Self::Irrelevant => "irrelevant",
+        }
+    }
+}
+
+impl From<crate::decider::Decision> for LogDecision {
+    fn from(decision: crate::decider::Decision) -> Self {
+        match decision {
+            crate::decider::Decision::Halt => Self::Halt,
+            crate::decider::Decision::RunForever => Self::Loop,
+            crate::decider::Decision::Irrelevant => Self::Irrelevant,
+            crate::decider::Decision::Undecided => Self::Undecided,
+        }
+    }
+}
+
+/// Start of the header record of a log.
+pub const LOG_HEADER_PREFIX: &[u8] = b"# seed run ";
+
+/// The header record that starts the log of a run. It identifies the run so that a log is not continued with the resume file of another run. The record is as long as an entry so that entries stay aligned: `LOG_HEADER_PREFIX`, the run id in 16 hex digits, spaces and a newline. Logs of older versions have no header.
+pub fn write_log_header(run_id: u64) -> [u8; LOG_ENTRY_LEN] {
+    let mut header = [b' '; LOG_ENTRY_LEN];
+    let text = format!(
+        "{}{run_id:016x}",
+        std::str::from_utf8(LOG_HEADER_PREFIX).unwrap()
+    );
+    header[..text.len()].copy_from_slice(text.as_bytes());
+    header[LOG_ENTRY_LEN - 1] = b'\n';
+    header
+}
+
+/// The run id of a header record of `write_log_header`. `None` if `record` is not a header.
+pub fn read_log_header(record: &[u8; LOG_ENTRY_LEN]) -> Option<u64> {
+    let rest = record.strip_prefix(LOG_HEADER_PREFIX)?;
+    let (id, padding) = rest.split_at(16);
+    if padding.last() != Some(&b'\n') || padding[..padding.len() - 1].iter().any(|&b| b != b' ') {
+        return None;
+    }
+    u64::from_str_radix(std::str::from_utf8(id).ok()?, 16).ok()
+}
+
+/// Parse one log entry.
+pub fn read_log_entry(entry: &[u8; LOG_ENTRY_LEN]) -> Result<(States<5, 2>, LogDecision)> {
+    if entry[34] != b' ' || entry[36] != b'\n' {
+        return Err(Error::Parse("invalid separator".into()));
+    }
+    let states = read_compact(&entry[..34]).context("invalid machine")?;
+    let decision = LogDecision::from_code(entry[35]).context("invalid decision")?;
+    Ok((states, decision))
+}
+
+/// An entry of a log together with its position in the log.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LogRecord {
+    /// 1 based line number.
+    pub line: u64,
+    pub states: States<5, 2>,
+    pub decision: LogDecision,
+}
+
+impl LogRecord {
+    /// Byte offset of the entry in the log, not counting a header record.
+    pub fn offset(&self) -> u64 {
+        (self.line - 1) * LOG_ENTRY_LEN as u64
+    }
+}
+
+/// Streaming reader for the log of the seed run.
+///
+/// Reading does not buffer. Wrap the reader in a `BufReader` when reading from a file. Entries that are cut off at the end of the log are an error instead of being silently dropped. A header record of `write_log_header` at the start is skipped and is not counted as a line.
+pub struct LogReader<R> {
+    reader: R,
+    entry: [u8; LOG_ENTRY_LEN],
+    line: u64,
+    /// Whether the start of the log was checked for a header.
+    started: bool,
+    run_id: Option<u64>,
+}
+
+impl<R: std::io::Read> LogReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            entry: [0; LOG_ENTRY_LEN],
+            line: 0,
+            started: false,
+            run_id: None,
+        }
+    }
+
+    /// The run id of the header. Only known after the first entry was read.
+    pub fn run_id(&self) -> Option<u64> {
+        self.run_id
+    }
+
+    /// Number of entries read so far.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// Read the next entry without parsing it. Returns `None` at the end of the log.
+    pub fn next_raw(&mut self) -> Result<Option<&[u8; LOG_ENTRY_LEN]>> {
+        if !self.started {
+            self.started = true;
+            if !self.fill()? {
+                return Ok(None);
+            }
+            self.run_id = read_log_header(&self.entry);
+            if self.run_id.is_none() {
+                self.line += 1;
+                return Ok(Some(&self.entry));
+            }
+        }
+        Ok(self.fill()?.then(|| {
+            self.line += 1;
+            &self.entry
+        }))
+    }
+
+    /// Read the next record into `entry`. Returns false at the end of the log.
+    fn fill(&mut self) -> Result<bool> {
+        let mut filled = 0;
+        while filled < LOG_ENTRY_LEN {
+            match self.reader.read(&mut self.entry[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(err) => {
+                    return Err(err).with_context(|| format!("read line {}", self.line + 1))
+                }
+            }
+        }
+        match filled {
+            0 => Ok(false),
+            LOG_ENTRY_LEN => Ok(true),
+            _ => Err(Error::Parse(format!(
+                "line {} is cut off after {filled} bytes",
+                self.line + 1
+            ))),
+        }
+    }
+}
+
+impl<R: std::io::Read> Iterator for LogReader<R> {
+    type Item = Result<LogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = match self.next_raw() {
+            Ok(Some(entry)) => *entry,
+            Ok(None) => return None,
+            Err(err) => return Some(Err(err)),
+        };
+        let line = self.line;
+        Some(
+            read_log_entry(&entry)
+                .map(|(states, decision)| LogRecord {
+                    line,
+                    states,
+                    decision,
+                })
+                .with_context(|| format!("parse line {line}")),
+        )
+    }
+}
+
+/// First line of a `LogManifest`.
+pub const LOG_MANIFEST_HEADER: &str = "# seed log manifest";
+
+/// Description of a log that is split into several files.
+///
+/// Very long runs split their log so that no single file becomes too large to store and transfer. The manifest takes the place of the log file. It is a text file whose first line is `LOG_MANIFEST_HEADER` and whose second line is `entries-per-file N`, the number of entries after which the writer starts a new file. Every further line is the index of the first entry of a file and the name of the file relative to the manifest's directory. The files are listed in order and together hold the whole log. A header record at the start of the log counts as an entry.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct LogManifest {
+    pub entries_per_file: u64,
+    /// Index of the first entry and file name.
+    pub files: Vec<(u64, String)>,
+}
+
+impl LogManifest {
+    /// Whether a file starting with `prefix` is a manifest.
+    pub fn is_manifest(prefix: &[u8]) -> bool {
+        prefix.starts_with(LOG_MANIFEST_HEADER.as_bytes())
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        if lines.next() != Some(LOG_MANIFEST_HEADER) {
+            return Err(Error::Parse("missing header".into()));
+        }
+        let entries_per_file = lines
+            .next()
+            .and_then(|line| line.strip_prefix("entries-per-file "))
+            .context("missing entries per file")?
+            .parse()
+            .context("invalid entries per file")?;
+        let mut files = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let (first, name) = line
+                .split_once(' ')
+                .with_context(|| format!("file {i}: expected first entry and name"))?;
+            let first: u64 = first
+                .parse()
+                .with_context(|| format!("file {i}: invalid first entry"))?;
+            let expected_first = files.last().map(|(previous, _)| previous + 1).unwrap_or(0);
+            let in_order = if i == 0 {
+                first == 0
+            } else {
+                first >= expected_first
+            };
+            if !in_order || name.is_empty() || name.contains('/') {
+                return Err(Error::Parse(format!("file {i}: invalid entry {line:?}")));
+            }
+            files.push((first, name.to_string()));
+        }
+        Ok(Self {
+            entries_per_file,
+            files,
+        })
+    }
+
+    pub fn write(&self) -> String {
+        let mut s = format!(
+            "{LOG_MANIFEST_HEADER}\nentries-per-file {}\n",
+            self.entries_per_file
+        );
+        for (first, name) in &self.files {
+            s.push_str(&format!("{first} {name}\n"));
+        }
+        s
+    }
+
+    pub fn read(path: &std::path::Path) -> Result<Self> {
+        let s =
+            std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        Self::parse(&s).with_context(|| format!("parse manifest {}", path.display()))
+    }
+
+    /// Paths of the files of the manifest at `path`.
+    pub fn paths(&self, path: &std::path::Path) -> Vec<std::path::PathBuf> {
+        let directory = path.parent().unwrap_or(std::path::Path::new(""));
+        self.files
+            .iter()
+            .map(|(_, name)| directory.join(name))
+            .collect()
+    }
+
+    /// Read the files of the manifest at `path` as one log. Every file but the last has to hold exactly the entries up to the first entry of the next file.
+    pub fn open(&self, path: &std::path::Path) -> Result<LogFiles> {
+        let paths = self.paths(path);
+        for (i, path) in paths.iter().enumerate() {
+            let len = std::fs::metadata(path)
+                .with_context(|| format!("read metadata of {}", path.display()))?
+                .len();
+            if let Some((next, _)) = self.files.get(i + 1) {
+                let expected = (next - self.files[i].0) * LOG_ENTRY_LEN as u64;
+                if len != expected {
+                    return Err(Error::Invariant(format!(
+                        "{} has {len} bytes instead of {expected}",
+                        path.display()
+                    )));
+                }
+            }
+        }
+        Ok(LogFiles {
+            paths: paths.into_iter(),
+            current: None,
+        })
+    }
+}
+
+/// The files of a `LogManifest` read one after another. Files are opened when they are reached.
+pub struct LogFiles {
+    paths: std::vec::IntoIter<std::path::PathBuf>,
+    current: Option<std::io::BufReader<std::fs::File>>,
+}
+
+impl std::io::Read for LogFiles {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let current = match &mut self.current {
+                Some(current) => current,
+                None => {
+                    let Some(path) = self.paths.next() else {
+                        return Ok(0);
+                    };
+                    self.current
+                        .insert(std::io::BufReader::new(std::fs::File::open(path)?))
+                }
+            };
+            match current.read(buf)? {
+                0 if !buf.is_empty() => self.current = None,
+                n => return Ok(n),
+            }
+        }
+    }
+}
+
+/// Open a log that is either a single file or a `LogManifest`. The reader is buffered.
+pub fn open_log(path: &std::path::Path) -> Result<Box<dyn std::io::Read>> {
+    let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut file = std::io::BufReader::new(file);
+    let prefix = std::io::BufRead::fill_buf(&mut file).context("read start")?;
+    if !LogManifest::is_manifest(prefix) {
+        return Ok(Box::new(file));
+    }
+    let manifest = LogManifest::read(path)?;
+    Ok(Box::new(manifest.open(path)?))
+}
+
+/// Format of a file of machines. See `MachineList`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MachineFormat {
+    /// One machine per line in the format of `read_compact`. Other whitespace separated fields on the line like a number or a decision are ignored. Empty lines and lines starting with `#` are skipped.
+    Text,
+    /// Bbchallenge seed database.
+    SeedDatabase,
+    /// Log of the seed run. See `LogReader`.
+    Log,
+}
+
+impl MachineFormat {
+    /// Guess the format from the first bytes of a file.
+    ///
+    /// A seed database is recognized by a valid header including its zero padding, which text never contains. A log is recognized by a valid first entry.
+    pub fn detect(prefix: &[u8]) -> Self {
+        if let Some(header) = prefix.get(..SEED_DATABASE_HEADER_LEN) {
+            if read_seed_database_header(header).is_ok() && header[13..].iter().all(|&b| b == 0) {
+                return Self::SeedDatabase;
+            }
+        }
+        if let Some(entry) = prefix.get(..LOG_ENTRY_LEN) {
+            let entry = entry.try_into().unwrap();
+            if read_log_entry(entry).is_ok() || read_log_header(entry).is_some() {
+                return Self::Log;
+            }
+        }
+        Self::Text
+    }
+}
+
+/// A machine read by `MachineList`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ListedMachine {
+    /// 0 based position of the machine in the source. For a seed database this is the index of the machine in the database, also when it is read through an index file.
+    pub index: u64,
+    pub states: States<5, 2>,
+    /// The decision if the source is a log.
+    pub decision: Option<LogDecision>,
+    /// The number in front of the machine if the source is text, like the 17 of `17 1RB...` or `17: 1RB...`. Lists of machines from the literature often number them this way.
+    pub number: Option<u64>,
+}
+
+/// Seekable reader for the database of `MachineList::indexed`.
+trait Database: std::io::Read + std::io::Seek {}
+impl<T: std::io::Read + std::io::Seek> Database for T {}
+
+enum MachineSource {
+    Text {
+        lines: std::io::Lines<Box<dyn std::io::BufRead>>,
+        line: u64,
+        count: u64,
+    },
+    SeedDatabase {
+        reader: Box<dyn std::io::Read>,
+        index: u64,
+        total: u64,
+    },
+    Log(LogReader<Box<dyn std::io::Read>>),
+    Indexed {
+        database: Box<dyn Database>,
+        index: Box<dyn std::io::Read>,
+        total: u32,
+    },
+}
+
+/// Iterator over the machines of a file in any of the formats of `MachineFormat` or of a seed database restricted by an index file.
+///
+/// Tools that take machines as input use this so that they accept all formats without the user having to convert between them.
+pub struct MachineList {
+    source: MachineSource,
+}
+
+impl MachineList {
+    /// Read machines from `reader`, detecting the format from the first bytes.
+    pub fn new(reader: impl std::io::Read + 'static) -> Result<Self> {
+        let mut reader = std::io::BufReader::new(reader);
+        let prefix = std::io::BufRead::fill_buf(&mut reader).context("read start")?;
+        let format = MachineFormat::detect(prefix);
+        Self::with_format(reader, format)
+    }
+
+    /// Read machines from `reader` in the given format.
+    pub fn with_format(
+        reader: impl std::io::BufRead + 'static,
+        format: MachineFormat,
+    ) -> Result<Self> {
+        let source = match format {
+            MachineFormat::Text => MachineSource::Text {
+                lines: std::io::BufRead::lines(Box::new(reader) as Box<dyn std::io::BufRead>),
+                line: 0,
+                count: 0,
+            },
+            MachineFormat::SeedDatabase => {
+                let mut reader: Box<dyn std::io::Read> = Box::new(reader);
+                let mut header = [0u8; SEED_DATABASE_HEADER_LEN];
+                reader.read_exact(&mut header).context("read header")?;
+                let header = read_seed_database_header(&header).context("parse header")?;
+                MachineSource::SeedDatabase {
+                    reader,
+                    index: 0,
+                    total: header.total() as u64,
+                }
+            }
+            MachineFormat::Log => MachineSource::Log(LogReader::new(Box::new(reader))),
+        };
+        Ok(Self { source })
+    }
+
+    /// Read the machines of a seed database whose indices are listed in `index`. The index is a sequence of big endian `u32` like the index files published by Bbchallenge.
+    pub fn indexed(
+        mut database: impl std::io::Read + std::io::Seek + 'static,
+        index: impl std::io::Read + 'static,
+    ) -> Result<Self> {
+        let mut header = [0u8; SEED_DATABASE_HEADER_LEN];
+        database.read_exact(&mut header).context("read header")?;
+        let header = read_seed_database_header(&header).context("parse header")?;
+        Ok(Self {
+            source: MachineSource::Indexed {
+                database: Box::new(database),
+                index: Box::new(std::io::BufReader::new(index)),
+                total: header.total(),
+            },
+        })
+    }
+
+    /// Open a file with `new`. A `LogManifest` is read as the log it describes.
+    pub fn open(path: &std::path::Path) -> Result<Self> {
+        let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let mut file = std::io::BufReader::new(file);
+        let prefix = std::io::BufRead::fill_buf(&mut file).context("read start")?;
+        if LogManifest::is_manifest(prefix) {
+            let log = open_log(path)?;
+            return Self::with_format(std::io::BufReader::new(log), MachineFormat::Log);
+        }
+        Self::new(file).with_context(|| format!("read {}", path.display()))
+    }
+
+    /// Open a seed database and an index file with `indexed`.
+    pub fn open_indexed(database: &std::path::Path, index: &std::path::Path) -> Result<Self> {
+        let open = |path: &std::path::Path| {
+            std::fs::File::open(path).with_context(|| format!("open {}", path.display()))
+        };
+        Self::indexed(open(database)?, open(index)?)
+    }
+
+    fn next_machine(&mut self) -> Result<Option<ListedMachine>> {
+        use std::io::{Read, Seek};
+        match &mut self.source {
+            MachineSource::Text { lines, line, count } => loop {
+                let Some(text) = lines.next() else {
+                    return Ok(None);
+                };
+                *line += 1;
+                let text = text.with_context(|| format!("read line {line}"))?;
+                let text = text.trim();
+                if text.is_empty() || text.starts_with('#') {
+                    continue;
+                }
+                let mut tokens = text.split_whitespace();
+                let mut number = None;
+                let states = tokens
+                    .find_map(|token| {
+                        let states = read_compact(token.as_bytes()).ok();
+                        if states.is_none() && number.is_none() {
+                            number = token
+                                .trim_start_matches('#')
+                                .trim_end_matches([':', '.', ')'])
+                                .parse()
+                                .ok();
+                        }
+                        states
+                    })
+                    .with_context(|| format!("no machine on line {line}"))?;
+                *count += 1;
+                return Ok(Some(ListedMachine {
+                    index: *count - 1,
+                    states,
+                    decision: None,
+                    number,
+                }));
+            },
+            MachineSource::SeedDatabase {
+                reader,
+                index,
+                total,
+            } => {
+                if *index == *total {
+                    return Ok(None);
+                }
+                let mut entry = [0u8; SEED_DATABASE_ENTRY_LEN];
+                reader
+                    .read_exact(&mut entry)
+                    .with_context(|| format!("read machine {index}"))?;
+                let states =
+                    read_seed_database(&entry).with_context(|| format!("parse machine {index}"))?;
+                *index += 1;
+                Ok(Some(ListedMachine {
+                    index: *index - 1,
+                    states,
+                    decision: None,
+                    number: None,
+                }))
+            }
+            MachineSource::Log(log) => Ok(log.next().transpose()?.map(|record| ListedMachine {
+                index: record.line - 1,
+                states: record.states,
+                decision: Some(record.decision),
+                number: None,
+            })),
+            MachineSource::Indexed {
+                database,
+                index,
+                total,
+            } => {
+                let mut bytes = [0u8; 4];
+                match index.read_exact(&mut bytes) {
+                    Ok(()) => (),
+                    Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+                    Err(err) => return Err(err).context("read index"),
+                }
+                let i = u32::from_be_bytes(bytes);
+                if i >= *total {
+                    return Err(Error::Invariant(format!(
+                        "index {i} is out of bounds of {total} machines"
+                    )));
+                }
+                let offset = SEED_DATABASE_HEADER_LEN + i as usize * SEED_DATABASE_ENTRY_LEN;
+                let mut entry = [0u8; SEED_DATABASE_ENTRY_LEN];
+                database
+                    .seek(std::io::SeekFrom::Start(offset as u64))
+                    .and_then(|_| database.read_exact(&mut entry))
+                    .with_context(|| format!("read machine {i}"))?;
+                let states =
+                    read_seed_database(&entry).with_context(|| format!("parse machine {i}"))?;
+                Ok(Some(ListedMachine {
+                    index: i as u64,
+                    states,
+                    decision: None,
+                    number: None,
+                }))
+            }
+        }
+    }
+}
+
+impl Iterator for MachineList {
+    type Item = Result<ListedMachine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_machine().transpose()
+    }
+}
+
+/// Convert `states` to every format that can represent it and back. Returns an error that names the first format that changes the machine or cannot read what it wrote. Formats that cannot represent the machine, like the seed database for machines that do not have 5 states and 2 symbols, are skipped.
+///
+/// New formats belong here so that the round trip test and the fuzz target cover them.
+pub fn round_trip_check<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+) -> Result<()> {
+    let check = |format: &str, result: Result<States<STATES, SYMBOLS>>| match result {
+        Ok(back) if back == *states => Ok(()),
+        Ok(back) => Err(Error::Invariant(format!(
+            "{format} changes {states} to {back}"
+        ))),
+        Err(err) => Err(err).with_context(|| format!("{format} cannot read back {states}")),
+    };
+    let compact = states.to_string();
+    check("compact", read_compact(compact.as_bytes()))?;
+    check("CSV table", read_table(&write_table(states, ',')))?;
+    check("TSV table", read_table(&write_table(states, '\t')))?;
+    if STATES <= 7 {
+        check(
+            "Marxen and Buntrock",
+            read_marxen_buntrock(&write_marxen_buntrock(states)),
+        )?;
+    }
+    if let Ok(rule) = to_wolfram_rule(states) {
+        check("Wolfram rule", from_wolfram_rule(rule))?;
+    }
+    if let Ok(packed) = write_packed(states) {
+        check("packed", read_packed(packed))?;
+    }
+    check(
+        "quadruples",
+        read_quadruples(&write_quadruples(&states.to_quadruples()))
+            .and_then(|quadruples| States::from_quadruples(&quadruples)),
+    )?;
+    let json = serde_json::to_string(states).map_err(|err| Error::Parse(err.to_string()))?;
+    check(
+        "JSON",
+        serde_json::from_str(&json).map_err(|err| Error::Parse(err.to_string())),
+    )?;
+    if STATES == 5 && SYMBOLS == 2 {
+        // The seed database only has machines with 5 states and 2 symbols. The compact format converts between the types.
+        let seed: States<5, 2> = read_compact(compact.as_bytes())?;
+        let back = read_seed_database(&write_seed_database(&seed));
+        check(
+            "seed database",
+            back.and_then(|back| read_compact(back.to_string().as_bytes())),
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn log_reader() {
+    let log = b"1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA h\n1RB---_------_------_------_------ u\n";
+    let records: Vec<LogRecord> = LogReader::new(&log[..]).collect::<Result<_>>().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        records[0].states.to_string().as_bytes(),
+        BB5_CHAMPION_COMPACT
+    );
+    assert_eq!(records[0].decision, LogDecision::Halt);
+    assert_eq!(records[1].line, 2);
+    assert_eq!(records[1].offset(), LOG_ENTRY_LEN as u64);
+    assert_eq!(records[1].decision, LogDecision::Undecided);
+
+    let mut truncated = LogReader::new(&log[..LOG_ENTRY_LEN + 10]);
+    assert!(truncated.next().unwrap().is_ok());
+    assert!(truncated.next().unwrap().is_err());
+
+    let mut invalid = *log;
+    invalid[35] = b'x';
+    assert!(LogReader::new(&invalid[..]).next().unwrap().is_err());
+
+    let header = write_log_header(0x0123_4567_89ab_cdef);
+    assert_eq!(&header, b"# seed run 0123456789abcdef         \n");
+    assert_eq!(read_log_header(&header), Some(0x0123_4567_89ab_cdef));
+    assert_eq!(
+        read_log_header(log[..LOG_ENTRY_LEN].try_into().unwrap()),
+        None
+    );
+    assert_eq!(MachineFormat::detect(&header), MachineFormat::Log);
+    let with_header = [&header[..], &log[..]].concat();
+    let mut reader = LogReader::new(&with_header[..]);
+    let with_header: Vec<LogRecord> = reader.by_ref().collect::<Result<_>>().unwrap();
+    assert_eq!(with_header, records);
+    assert_eq!(reader.run_id(), Some(0x0123_4567_89ab_cdef));
+    let mut empty = LogReader::new(&header[..]);
+    assert!(empty.next().is_none());
+    assert_eq!(empty.run_id(), Some(0x0123_4567_89ab_cdef));
+}
+
+#[test]
+fn machine_list() {
+    let bb5: States<5, 2> = read_compact(BB5_CHAMPION_COMPACT).unwrap();
+    let machines = |list: MachineList| -> Vec<(u64, States<5, 2>, Option<LogDecision>)> {
+        list.map(|m| m.map(|m| (m.index, m.states, m.decision)))
+            .collect::<Result<_>>()
+            .unwrap()
+    };
+
+    let text = format!("# champion\n\n{bb5}\n17 {bb5} extra\n43: {bb5} 2\n");
+    assert_eq!(MachineFormat::detect(text.as_bytes()), MachineFormat::Text);
+    let list = MachineList::new(std::io::Cursor::new(text.clone())).unwrap();
+    assert_eq!(
+        machines(list),
+        [(0, bb5, None), (1, bb5, None), (2, bb5, None)]
+    );
+    let numbers = MachineList::new(std::io::Cursor::new(text))
+        .unwrap()
+        .map(|m| m.unwrap().number)
+        .collect::<Vec<_>>();
+    assert_eq!(numbers, [None, Some(17), Some(43)]);
+    assert!(MachineList::new(&b"1 2\n"[..])
+        .unwrap()
+        .next()
+        .unwrap()
+        .is_err());
+
+    let log = format!("{bb5} h\n{bb5} u\n");
+    assert_eq!(MachineFormat::detect(log.as_bytes()), MachineFormat::Log);
+    let list = MachineList::new(std::io::Cursor::new(log)).unwrap();
+    assert_eq!(
+        machines(list),
+        [
+            (0, bb5, Some(LogDecision::Halt)),
+            (1, bb5, Some(LogDecision::Undecided))
+        ]
+    );
+
+    let mut database = write_seed_database_header(&SeedDatabaseHeader {
+        time_limit: 2,
+        space_limit: 1,
+        sorted: false,
+    })
+    .to_vec();
+    for _ in 0..3 {
+        database.extend_from_slice(&write_seed_database(&bb5));
+    }
+    assert_eq!(
+        MachineFormat::detect(&database),
+        MachineFormat::SeedDatabase
+    );
+    let list = MachineList::new(std::io::Cursor::new(database.clone())).unwrap();
+    assert_eq!(machines(list).len(), 3);
+
+    let index = [2u32, 0]
+        .iter()
+        .flat_map(|i| i.to_be_bytes())
+        .collect::<Vec<u8>>();
+    let list = MachineList::indexed(
+        std::io::Cursor::new(database.clone()),
+        std::io::Cursor::new(index),
+    )
+    .unwrap();
+    assert_eq!(machines(list), [(2, bb5, None), (0, bb5, None)]);
+    let mut list = MachineList::indexed(
+        std::io::Cursor::new(database),
+        std::io::Cursor::new(3u32.to_be_bytes()),
+    )
+    .unwrap();
+    assert!(list.next().unwrap().is_err());
+}
+
+#[test]
+fn display_table() {
+    let bb5: States<5, 2> = read_compact(BB5_CHAMPION_COMPACT).unwrap();
+    let expected = "   0   1
+A 1RB 1LC
+B 1RC 1RB
+C 1RD 0LE
+D 1LA 1LD
+E --- 0LA";
+    assert_eq!(format!("{bb5:#}"), expected);
+}
+
+#[test]
+fn table() {
+    let bb5: States<5, 2> = read_compact(BB5_CHAMPION_COMPACT).unwrap();
+    let csv = write_table(&bb5, ',');
+    assert_eq!(csv.lines().nth(5), Some("E,---,0LA"));
+    assert_eq!(read_table::<5, 2>(&csv).unwrap(), bb5);
+    let tsv = "1RB\t1LC\n1RC\t1RB\n1RD\t0LE\n1LA\t1LD\n\t0LA\n";
+    assert_eq!(read_table::<5, 2>(tsv).unwrap(), bb5);
+    assert_eq!(read_table::<5, 2>(&write_table(&bb5, '\t')).unwrap(), bb5);
+    assert!(read_table::<5, 2>("1RB,1LC\n").is_err());
+}
+
+#[test]
+fn marxen_buntrock() {
+    let table = "    0   1\nA 1RB 1LC\nB 1RC 1RB\nC 1RD 0LE\nD 1LA 1LD\nE 1RH 0LA\n";
+    let bb5: States<5, 2> = read_marxen_buntrock(table).unwrap();
+    assert_eq!(bb5.to_string().as_bytes(), BB5_CHAMPION_COMPACT);
+    assert_eq!(write_marxen_buntrock(&bb5), table);
+    let one_line = "1RB 1LC 1RC 1RB 1RD 0LE 1LA 1LD 1RZ 0LA";
+    assert_eq!(read_marxen_buntrock::<5, 2>(one_line).unwrap(), bb5);
+    assert!(read_marxen_buntrock::<5, 2>("1RB 1LC").is_err());
+}
+
+#[test]
+fn wolfram_rule() {
+    // Wolfram's universal 2 state 3 symbol machine.
+    let states = from_wolfram_rule::<2, 3>(596440).unwrap();
+    assert_eq!(states.to_string(), "1RB2LA1LA_2LA2RB0RA");
+    assert_eq!(to_wolfram_rule(&states).unwrap(), 596440);
+    assert!(from_wolfram_rule::<2, 3>(12u128.pow(6)).is_err());
+    let bb2: States<2, 2> = read_compact(b"1RB1LB_1LA---").unwrap();
+    assert!(to_wolfram_rule(&bb2).is_err());
+}
+
+#[test]
+fn quadruples() {
+    let bb5: States<5, 2> = read_compact(BB5_CHAMPION_COMPACT).unwrap();
+    let quadruples = bb5.to_quadruples();
+    let text = write_quadruples(&quadruples);
+    assert_eq!(read_quadruples(&text).unwrap(), quadruples);
+    assert_eq!(States::<5, 2>::from_quadruples(&quadruples).unwrap(), bb5);
+
+    // Writes are combined with the following move. Halting after a write loses the write.
+    let text = "A 0 1 B\nB 1 R A\nA 1 L C\nC 0 0 -\n";
+    let states = States::<3, 2>::from_quadruples(&read_quadruples(text).unwrap()).unwrap();
+    assert_eq!(states.to_string(), "1RA1LC_---1RA_------");
+    assert!(States::<2, 2>::from_quadruples(&read_quadruples(text).unwrap()).is_err());
+    assert!(
+        States::<2, 2>::from_quadruples(&read_quadruples("A 0 1 A\nA 1 0 A").unwrap()).is_err()
+    );
+}
+
+#[test]
+fn compact_const() {
+    assert_eq!(
+        crate::fixtures::BB5,
+        read_compact(BB5_CHAMPION_COMPACT).unwrap()
+    );
+    for fixture in crate::fixtures::with_size(3, 2) {
+        assert_eq!(
+            read_compact_const::<3, 2>(fixture.compact.as_bytes()),
+            fixture.machine::<3, 2>()
+        );
+    }
+    assert!(std::panic::catch_unwind(|| read_compact_const::<2, 2>(b"1RB1LB_1LA--")).is_err());
+    assert!(std::panic::catch_unwind(|| read_compact_const::<2, 2>(b"1RB1LB_1LC---")).is_err());
+}
+
 #[test]
 fn parse_bb5_champion() {
-    let states = read_compact(BB5_CHAMPION_COMPACT).unwrap();
+    let states: States<5, 2> = read_compact(BB5_CHAMPION_COMPACT).unwrap();
     assert_eq!(BB5_CHAMPION_COMPACT, states.to_string().as_bytes());
 }
 
@@ -142,4 +1463,78 @@ fn database() {
     assert_eq!(a, b);
     let a = write_seed_database(&a);
     assert_eq!(database, &a);
+    // Found by fuzzing: state 0 is only valid in a halting transition.
+    let mut invalid = *database;
+    invalid[2] = 0;
+    assert!(read_seed_database(&invalid).is_err());
+
+    // D1 halts. Written explicitly it is only read back when explicit halts are allowed.
+    let explicit = write_seed_database_with(&b, SeedDatabaseHalt::Explicit);
+    assert_eq!(explicit[21..24], [1, 0, 0]);
+    assert_eq!(explicit[..21], database[..21]);
+    assert_eq!(explicit[24..], database[24..]);
+    assert!(read_seed_database(&explicit).is_err());
+    let both = [SeedDatabaseHalt::Undefined, SeedDatabaseHalt::Explicit];
+    assert_eq!(read_seed_database_with(&explicit, &both).unwrap(), b);
+    assert_eq!(read_seed_database_with(database, &both).unwrap(), b);
+    assert!(read_seed_database_with(database, &[SeedDatabaseHalt::Explicit]).is_err());
+}
+
+#[test]
+fn database_header() {
+    let header = SeedDatabaseHeader {
+        time_limit: 14322029,
+        space_limit: 74342035,
+        sorted: true,
+    };
+    let bytes = write_seed_database_header(&header);
+    assert_eq!(read_seed_database_header(&bytes).unwrap(), header);
+}
+
+#[test]
+fn log_manifest() {
+    let manifest = LogManifest {
+        entries_per_file: 2,
+        files: vec![(0, "log.0000".to_string()), (2, "log.0001".to_string())],
+    };
+    let s = manifest.write();
+    assert!(LogManifest::is_manifest(s.as_bytes()));
+    assert_eq!(LogManifest::parse(&s).unwrap(), manifest);
+    for invalid in [
+        "entries-per-file 2\n0 log.0000\n",
+        "# seed log manifest\n0 log.0000\n",
+        "# seed log manifest\nentries-per-file 2\n1 log.0000\n",
+        "# seed log manifest\nentries-per-file 2\n0 log.0000\n0 log.0001\n",
+        "# seed log manifest\nentries-per-file 2\n0 ../log\n",
+    ] {
+        assert!(LogManifest::parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn round_trip() {
+    use crate::fixtures;
+    use crate::stress::{machine, Kind, Rng};
+
+    let mut rng = Rng::new(0);
+    for kind in Kind::ALL.into_iter().cycle().take(300) {
+        round_trip_check(&machine::<5, 2>(&mut rng, kind)).unwrap();
+        round_trip_check(&machine::<2, 2>(&mut rng, kind)).unwrap();
+        round_trip_check(&machine::<3, 3>(&mut rng, kind)).unwrap();
+        round_trip_check(&machine::<2, 4>(&mut rng, kind)).unwrap();
+    }
+    for fixture in fixtures::with_size(5, 2) {
+        round_trip_check(&fixture.machine::<5, 2>()).unwrap();
+    }
+    round_trip_check(&fixtures::BB2).unwrap();
+    round_trip_check(&fixtures::BB6).unwrap();
+    round_trip_check(&fixtures::BB2X3).unwrap();
+    round_trip_check(&States::<5, 2>::default()).unwrap();
+
+    let two_by_two: States<2, 2> = read_compact(b"1RB1LB_1LA---").unwrap();
+    let packed = write_packed(&two_by_two).unwrap();
+    assert_eq!(packed >> 24, 0);
+    assert_eq!(read_packed::<2, 2>(packed).unwrap(), two_by_two);
+    assert!(read_packed::<2, 2>(packed | 1 << 24).is_err());
+    assert!(write_packed(&States::<3, 4>::default()).is_err());
 }
@@ -0,0 +1,103 @@
+//! Tape storage in huge pages
+//!
+//! The step loop accesses the tape at the head, which moves one cell at a time, so the TLB misses when the head crosses into another page. The seed run's tape of 24 KB spans six 4 KB pages and the runners of all worker threads compete for TLB entries with the rest of the program. On Linux, memory that is aligned to 2 MB can be backed by a single huge page after `madvise(MADV_HUGEPAGE)`, if transparent huge pages are enabled in `madvise` or `always` mode. On other platforms the buffer is only aligned.
+//!
+//! A buffer takes at least one huge page of memory, so this is meant for the one runner of every thread and not for many small tapes.
+
+use std::{alloc::Layout, ptr::NonNull};
+
+/// Size and alignment of a huge page on x86-64 and most aarch64 systems.
+pub const HUGE_PAGE_LEN: usize = 2 << 20;
+
+/// Zeroed bytes in memory that is aligned to and rounded up to huge pages. Use it as the storage of a `DenseTape`.
+pub struct HugePageBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    advised: bool,
+}
+
+// The buffer owns its memory like a `Vec`.
+unsafe impl Send for HugePageBuffer {}
+unsafe impl Sync for HugePageBuffer {}
+
+impl HugePageBuffer {
+    pub fn new(len: usize) -> Self {
+        assert!(len > 0);
+        let layout = Self::layout(len);
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        let Some(ptr) = NonNull::new(ptr) else {
+            std::alloc::handle_alloc_error(layout)
+        };
+        #[cfg(target_os = "linux")]
+        let advised =
+            unsafe { libc::madvise(ptr.as_ptr().cast(), layout.size(), libc::MADV_HUGEPAGE) } == 0;
+        #[cfg(not(target_os = "linux"))]
+        let advised = false;
+        Self { ptr, len, advised }
+    }
+
+    /// Whether the kernel accepted the advice to use huge pages. It can still fall back to normal pages when no huge page is free.
+    pub fn is_advised(&self) -> bool {
+        self.advised
+    }
+
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(len.next_multiple_of(HUGE_PAGE_LEN), HUGE_PAGE_LEN).unwrap()
+    }
+}
+
+impl Drop for HugePageBuffer {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.len)) };
+    }
+}
+
+impl Clone for HugePageBuffer {
+    fn clone(&self) -> Self {
+        let mut clone = Self::new(self.len);
+        clone.as_mut().copy_from_slice(self.as_ref());
+        clone
+    }
+}
+
+impl AsRef<[u8]> for HugePageBuffer {
+    fn as_ref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsMut<[u8]> for HugePageBuffer {
+    fn as_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl std::fmt::Debug for HugePageBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HugePageBuffer")
+            .field("len", &self.len)
+            .field("advised", &self.advised)
+            .finish()
+    }
+}
+
+#[test]
+fn huge_page_tape() {
+    use crate::run::Runner;
+
+    let buffer = HugePageBuffer::new(100);
+    assert_eq!(buffer.as_ref(), [0; 100]);
+    assert_eq!(buffer.as_ref().as_ptr() as usize % HUGE_PAGE_LEN, 0);
+    let mut clone = buffer.clone();
+    clone.as_mut()[0] = 1;
+    assert_eq!(buffer.as_ref()[0], 0);
+
+    let mut huge = Runner::<5, 2, _>::huge_page_backed(30_000);
+    let mut vector = Runner::<5, 2, _>::vector_backed(30_000);
+    huge.set_states(&crate::fixtures::BB5);
+    vector.set_states(&crate::fixtures::BB5);
+    for _ in 0..100_000 {
+        assert_eq!(huge.step(), vector.step());
+    }
+    assert!(huge.snapshot().same_configuration(&vector.snapshot()));
+}
@@ -1,8 +1,48 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod block;
+#[cfg(feature = "std")]
+pub mod configuration;
+#[cfg(feature = "std")]
+pub mod configuration_graph;
+#[cfg(feature = "std")]
+pub mod counter;
 pub mod decider;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod fixtures;
+#[cfg(feature = "std")]
 pub mod format;
+#[cfg(feature = "std")]
+pub mod huge_page;
+#[cfg(feature = "std")]
 pub mod normalize;
+#[cfg(feature = "std")]
+pub mod pattern;
+#[cfg(feature = "std")]
+pub mod proof;
 pub mod run;
+#[cfg(feature = "std")]
+pub mod score;
+#[cfg(feature = "std")]
+pub mod seed;
+#[cfg(feature = "std")]
+pub mod segment;
 pub mod states;
+#[cfg(feature = "std")]
+pub mod stress;
+#[cfg(feature = "std")]
+pub mod trace;
+
+#[cfg(feature = "std")]
+pub use error::{Error, Result};
+#[cfg(feature = "std")]
+pub use seed::seed_decide;
 
 /// Calling this function is a hint to the compiler that this code path is unlikely to be executed.
 #[cold]
@@ -1,28 +1,184 @@
 //! Turing machine normalization
+//!
+//! Machines that differ only in the names of their states and symbols or in the direction of every move behave the same. A normal form picks one of them. Datasets disagree on the details, so `NormalForm` selects the rules. Renaming and mirroring establish the ordering rules. The other rules are properties of the machine that no renaming changes, so `normalize` leaves them alone and only `is_normal` checks them.
 
 use arrayvec::ArrayVec;
 
-use crate::states::{DefinedTransition, Direction, State, States, Transition};
+use crate::states::{DefinedTransition, Direction, State, States, Symbol, Transition};
 
-pub fn is_normal<const STATES: usize, const SYMBOLS: usize>(d: &States<STATES, SYMBOLS>) -> bool {
-    // TODO:
-    // - Enforce first write is 1?
-    // - Enforce halt transitions at end and only 1 halt transition?
-    // - Enforce that non blank symbols first occur in ascending order. This is true for all 2 symbol machines.
+/// Rules of a normal form. The default is the form of this crate: the first transition moves right and non initial states and non blank symbols first occur in ascending order.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct NormalForm {
+    /// Non initial states and non blank symbols first occur in ascending order in the transitions, like in tree normal form. This and that the first transition moves right is established by `normalize`.
+    pub tnf_order: bool,
+    /// The first defined transition writes 1.
+    pub first_write_one: bool,
+    /// There is at most one halting transition.
+    pub single_halting_transition: bool,
+    /// Halting transitions come after every defined transition.
+    pub halting_transition_last: bool,
+}
+
+impl Default for NormalForm {
+    fn default() -> Self {
+        Self {
+            tnf_order: true,
+            first_write_one: false,
+            single_halting_transition: false,
+            halting_transition_last: false,
+        }
+    }
+}
+
+impl NormalForm {
+    /// Every rule.
+    pub fn strict() -> Self {
+        Self {
+            tnf_order: true,
+            first_write_one: true,
+            single_halting_transition: true,
+            halting_transition_last: true,
+        }
+    }
+
+    pub fn is_normal<const STATES: usize, const SYMBOLS: usize>(
+        &self,
+        d: &States<STATES, SYMBOLS>,
+    ) -> bool {
+        self.is_ordered(d)
+            && (!self.first_write_one || first_write_is_one(d))
+            && (!self.single_halting_transition || halting_transitions(d) <= 1)
+            && (!self.halting_transition_last || halting_transitions_are_last(d))
+    }
+
+    /// Establish the rules that renaming and mirroring can. Returns how the machine was changed so that results about the normalized machine can be translated back to the original machine.
+    pub fn normalize<const STATES: usize, const SYMBOLS: usize>(
+        &self,
+        d: &mut States<STATES, SYMBOLS>,
+    ) -> Normalization<STATES, SYMBOLS> {
+        let mut normalization = Normalization::identity();
+        if !first_transition_moves_right(d) {
+            reverse_directions(d);
+            normalization.mirrored = true;
+            debug_assert!(first_transition_moves_right(d));
+        }
+        if self.tnf_order
+            && (!non_initial_states_first_occur_in_ascending_order(d)
+                || !non_blank_symbols_first_occur_in_ascending_order(d))
+        {
+            order_states_and_symbols(d, &mut normalization.states, &mut normalization.symbols);
+            debug_assert!(non_initial_states_first_occur_in_ascending_order(d));
+            debug_assert!(non_blank_symbols_first_occur_in_ascending_order(d));
+        }
+        debug_assert!(self.is_ordered(d));
+        normalization
+    }
+
+    fn is_ordered<const STATES: usize, const SYMBOLS: usize>(
+        &self,
+        d: &States<STATES, SYMBOLS>,
+    ) -> bool {
+        first_transition_moves_right(d)
+            && (!self.tnf_order
+                || (non_initial_states_first_occur_in_ascending_order(d)
+                    && non_blank_symbols_first_occur_in_ascending_order(d)))
+    }
+}
 
-    first_transition_moves_right(d) && non_initial_states_first_occur_in_ascending_order(d)
+/// Whether `d` is in the default normal form.
+pub fn is_normal<const STATES: usize, const SYMBOLS: usize>(d: &States<STATES, SYMBOLS>) -> bool {
+    NormalForm::default().is_normal(d)
 }
 
+/// Bring `d` into the default normal form.
 pub fn normalize<const STATES: usize, const SYMBOLS: usize>(d: &mut States<STATES, SYMBOLS>) {
-    if !first_transition_moves_right(d) {
-        reverse_directions(d);
-        debug_assert!(first_transition_moves_right(d));
+    normalize_recorded(d);
+}
+
+/// Like `normalize` but returns how the machine was changed so that results about the normalized machine can be translated back to the original machine.
+pub fn normalize_recorded<const STATES: usize, const SYMBOLS: usize>(
+    d: &mut States<STATES, SYMBOLS>,
+) -> Normalization<STATES, SYMBOLS> {
+    NormalForm::default().normalize(d)
+}
+
+/// The changes `normalize` made to a machine.
+///
+/// The normalized machine run on the mirrored tape with renamed states and symbols behaves exactly like the original machine. The methods translate between the two.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Normalization<const STATES: usize, const SYMBOLS: usize> {
+    /// Whether left and right were swapped.
+    pub mirrored: bool,
+    /// The state in the normalized machine of each state of the original machine.
+    pub states: [State<STATES>; STATES],
+    /// The symbol in the normalized machine of each symbol of the original machine. The blank symbol is never renamed.
+    pub symbols: [Symbol<SYMBOLS>; SYMBOLS],
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> Normalization<STATES, SYMBOLS> {
+    pub fn identity() -> Self {
+        Self {
+            mirrored: false,
+            states: std::array::from_fn(|i| State::new(i as u8).unwrap()),
+            symbols: std::array::from_fn(|i| Symbol::new(i as u8).unwrap()),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+
+    /// The original machine of a normalized machine.
+    pub fn denormalize(&self, normalized: &States<STATES, SYMBOLS>) -> States<STATES, SYMBOLS> {
+        let mut original = States::default();
+        for (state, transitions) in original.0.iter_mut().enumerate() {
+            let normalized_state = self.states[state].get() as usize;
+            for (symbol, transition) in transitions.iter_mut().enumerate() {
+                let normalized_symbol = self.symbols[symbol].get() as usize;
+                *transition = match normalized.0[normalized_state][normalized_symbol] {
+                    Transition::Halt => Transition::Halt,
+                    Transition::Continue(t) => Transition::Continue(DefinedTransition {
+                        write: self.original_symbol(t.write),
+                        move_: if self.mirrored {
+                            t.move_.reversed()
+                        } else {
+                            t.move_
+                        },
+                        state: self.original_state(t.state),
+                    }),
+                };
+            }
+        }
+        original
     }
-    if !non_initial_states_first_occur_in_ascending_order(d) {
-        order_states(d);
-        debug_assert!(non_initial_states_first_occur_in_ascending_order(d));
+
+    pub fn original_state(&self, normalized: State<STATES>) -> State<STATES> {
+        let i = self.states.iter().position(|s| *s == normalized).unwrap();
+        State::new(i as u8).unwrap()
+    }
+
+    pub fn normalized_state(&self, original: State<STATES>) -> State<STATES> {
+        self.states[original.get() as usize]
+    }
+
+    pub fn original_symbol(&self, normalized: Symbol<SYMBOLS>) -> Symbol<SYMBOLS> {
+        let i = self.symbols.iter().position(|s| *s == normalized).unwrap();
+        Symbol::new(i as u8).unwrap()
+    }
+
+    /// Translate a symbol on the tape or in a transition.
+    pub fn normalized_symbol(&self, original: Symbol<SYMBOLS>) -> Symbol<SYMBOLS> {
+        self.symbols[original.get() as usize]
+    }
+
+    /// Translate a head position or tape index relative to the start position. The translation is its own inverse.
+    pub fn translate_position(&self, position: isize) -> isize {
+        if self.mirrored {
+            -position
+        } else {
+            position
+        }
     }
-    debug_assert!(is_normal(d));
 }
 
 fn first_transition_moves_right<const STATES: usize, const SYMBOLS: usize>(
@@ -54,6 +210,36 @@ fn reverse_directions<const STATES: usize, const SYMBOLS: usize>(d: &mut States<
     }
 }
 
+fn first_write_is_one<const STATES: usize, const SYMBOLS: usize>(
+    d: &States<STATES, SYMBOLS>,
+) -> bool {
+    match d.0.iter().flatten().find_map(|t| match t {
+        Transition::Halt => None,
+        Transition::Continue(DefinedTransition { write, .. }) => Some(*write),
+    }) {
+        Some(write) => write.get() == 1,
+        None => true,
+    }
+}
+
+fn halting_transitions<const STATES: usize, const SYMBOLS: usize>(
+    d: &States<STATES, SYMBOLS>,
+) -> usize {
+    d.0.iter()
+        .flatten()
+        .filter(|t| matches!(t, Transition::Halt))
+        .count()
+}
+
+fn halting_transitions_are_last<const STATES: usize, const SYMBOLS: usize>(
+    d: &States<STATES, SYMBOLS>,
+) -> bool {
+    d.0.iter()
+        .flatten()
+        .skip_while(|t| matches!(t, Transition::Continue(_)))
+        .all(|t| matches!(t, Transition::Halt))
+}
+
 fn non_initial_states_first_occur_in_ascending_order<const STATES: usize, const SYMBOLS: usize>(
     d: &States<STATES, SYMBOLS>,
 ) -> bool {
@@ -63,45 +249,311 @@ fn non_initial_states_first_occur_in_ascending_order<const STATES: usize, const
         .all(|states| states[0] < states[1])
 }
 
-fn order_states<const STATES: usize, const SYMBOLS: usize>(d: &mut States<STATES, SYMBOLS>) {
-    let actual_order = order_in_which_non_initial_states_occur(d);
-    let target_order = {
-        let mut o = actual_order.clone();
-        o.sort();
-        o
-    };
-    for (a, b) in actual_order.iter().zip(target_order.iter()) {
-        swap_states(d, *a, *b);
+/// Renaming the states also reorders the rows of the table, which changes where states first occur, and renaming the symbols reorders the transitions within every row. So the new names are assigned while scanning the renamed table: the rows are scanned in the order of their new names, the transitions of a row in the order of the new names of their symbols, and every state and symbol gets the next name when it first occurs. States and symbols that do not occur get the remaining names in their original order when their row or transition is reached.
+///
+/// `renamed_states` and `renamed_symbols` hold the current name of every original state and symbol and are updated with the renaming.
+fn order_states_and_symbols<const STATES: usize, const SYMBOLS: usize>(
+    d: &mut States<STATES, SYMBOLS>,
+    renamed_states: &mut [State<STATES>; STATES],
+    renamed_symbols: &mut [Symbol<SYMBOLS>; SYMBOLS],
+) {
+    let mut new_states = Names::<STATES>::new();
+    let mut new_symbols = Names::<SYMBOLS>::new();
+    for scanned_state in 0..STATES {
+        let state = new_states.original(scanned_state);
+        for scanned_symbol in 0..SYMBOLS {
+            let symbol = new_symbols.original(scanned_symbol);
+            let Transition::Continue(DefinedTransition {
+                write, state: next, ..
+            }) = d.0[state][symbol]
+            else {
+                continue;
+            };
+            new_symbols.occur(write.get() as usize);
+            new_states.occur(next.get() as usize);
+        }
+    }
+    let states = new_states
+        .names
+        .map(|name| State::new(name.unwrap()).unwrap());
+    let symbols = new_symbols
+        .names
+        .map(|name| Symbol::new(name.unwrap()).unwrap());
+    rename_states(d, &states);
+    rename_symbols(d, &symbols);
+    for state in renamed_states.iter_mut() {
+        *state = states[state.get() as usize];
+    }
+    for symbol in renamed_symbols.iter_mut() {
+        *symbol = symbols[symbol.get() as usize];
+    }
+}
+
+/// New names that are assigned in the order of first occurrence. Name 0 is fixed.
+struct Names<const N: usize> {
+    /// The new name of each original name.
+    names: [Option<u8>; N],
+    /// The original name of each new name.
+    order: [usize; N],
+    named: usize,
+}
+
+impl<const N: usize> Names<N> {
+    fn new() -> Self {
+        let mut names = [None; N];
+        names[0] = Some(0);
+        Self {
+            names,
+            order: [0; N],
+            named: 1,
+        }
+    }
+
+    fn occur(&mut self, original: usize) {
+        if self.names[original].is_none() {
+            self.names[original] = Some(self.named as u8);
+            self.order[self.named] = original;
+            self.named += 1;
+        }
+    }
+
+    /// The original name of new name `name`, which is reached by a scan in the order of the new names. If it has not been assigned yet, the first original name without a new name gets it.
+    fn original(&mut self, name: usize) -> usize {
+        if name == self.named {
+            let unnamed = self.names.iter().position(Option::is_none).unwrap();
+            self.occur(unnamed);
+        }
+        self.order[name]
     }
 }
 
+/// Non initial states in the order of their first occurrence in the transitions.
+///
+/// A state that does not occur before its own row counts as occurring at the start of its row. Otherwise machines with unreachable states that refer to each other would have no normal form.
 fn order_in_which_non_initial_states_occur<const STATES: usize, const SYMBOLS: usize>(
     d: &States<STATES, SYMBOLS>,
 ) -> ArrayVec<State<STATES>, STATES> {
-    d.0.iter()
-        .flatten()
-        .filter_map(|t| match t {
-            Transition::Halt => None,
-            Transition::Continue(DefinedTransition { state, .. }) => Some(*state),
-        })
-        .filter(|s| *s != State::new(0).unwrap())
-        .collect()
+    let mut order = ArrayVec::new();
+    let mut occur = |state: State<STATES>| {
+        if state != State::new(0).unwrap() && !order.contains(&state) {
+            order.push(state);
+        }
+    };
+    for (i, transitions) in d.0.iter().enumerate() {
+        occur(State::new(i as u8).unwrap());
+        for t in transitions {
+            if let Transition::Continue(DefinedTransition { state, .. }) = t {
+                occur(*state);
+            }
+        }
+    }
+    order
 }
 
-fn swap_states<const STATES: usize, const SYMBOLS: usize>(
+/// Rename every state `s` to `permutation[s]`.
+fn rename_states<const STATES: usize, const SYMBOLS: usize>(
     d: &mut States<STATES, SYMBOLS>,
-    a: State<STATES>,
-    b: State<STATES>,
+    permutation: &[State<STATES>; STATES],
 ) {
-    d.0.swap(a.get() as usize, b.get() as usize);
-    for state in d.0.iter_mut().flatten().filter_map(|t| match t {
-        Transition::Halt => None,
-        Transition::Continue(DefinedTransition { state, .. }) => Some(state),
-    }) {
-        if *state == a {
-            *state = b;
-        } else if *state == b {
-            *state = a;
+    let original = *d;
+    for (state, transitions) in original.0.iter().enumerate() {
+        d.0[permutation[state].get() as usize] = transitions.map(|t| match t {
+            Transition::Halt => Transition::Halt,
+            Transition::Continue(t) => Transition::Continue(DefinedTransition {
+                state: permutation[t.state.get() as usize],
+                ..t
+            }),
+        });
+    }
+}
+
+fn non_blank_symbols_first_occur_in_ascending_order<const STATES: usize, const SYMBOLS: usize>(
+    d: &States<STATES, SYMBOLS>,
+) -> bool {
+    order_in_which_non_blank_symbols_occur(d)
+        .as_slice()
+        .windows(2)
+        .all(|symbols| symbols[0] < symbols[1])
+}
+
+/// Non blank symbols in the order in which transitions first write them.
+///
+/// Like states at the start of their row, a symbol that is not written before its own transition counts as occurring at the start of its transition. Otherwise a symbol that is never written could not be placed.
+fn order_in_which_non_blank_symbols_occur<const STATES: usize, const SYMBOLS: usize>(
+    d: &States<STATES, SYMBOLS>,
+) -> ArrayVec<Symbol<SYMBOLS>, SYMBOLS> {
+    let mut order = ArrayVec::new();
+    let mut occur = |symbol: Symbol<SYMBOLS>| {
+        if symbol.get() != 0 && !order.contains(&symbol) {
+            order.push(symbol);
+        }
+    };
+    for transitions in d.0.iter() {
+        for (i, t) in transitions.iter().enumerate() {
+            occur(Symbol::new(i as u8).unwrap());
+            if let Transition::Continue(DefinedTransition { write, .. }) = t {
+                occur(*write);
+            }
+        }
+    }
+    order
+}
+
+/// Rename every symbol `s` to `permutation[s]`, both the symbols that are written and the symbols that select transitions.
+fn rename_symbols<const STATES: usize, const SYMBOLS: usize>(
+    d: &mut States<STATES, SYMBOLS>,
+    permutation: &[Symbol<SYMBOLS>; SYMBOLS],
+) {
+    let original = *d;
+    for (transitions, original) in d.0.iter_mut().zip(original.0) {
+        for (symbol, t) in original.into_iter().enumerate() {
+            transitions[permutation[symbol].get() as usize] = match t {
+                Transition::Halt => Transition::Halt,
+                Transition::Continue(t) => Transition::Continue(DefinedTransition {
+                    write: permutation[t.write.get() as usize],
+                    ..t
+                }),
+            };
         }
     }
 }
+
+#[test]
+fn denormalize() {
+    use crate::run::{Runner, StepResult};
+
+    let bb4 = crate::fixtures::get("bb4").unwrap().machine::<4, 2>();
+    let state = |i| State::<4>::new(i).unwrap();
+    let scramble = Normalization {
+        mirrored: true,
+        states: [state(0), state(3), state(1), state(2)],
+        symbols: Normalization::<4, 2>::identity().symbols,
+    };
+    let original = scramble.denormalize(&bb4);
+    assert_eq!(original.to_string(), "1LC1RC_1LB0LA_1RA0RD_---1RB");
+
+    let mut normalized = original;
+    let normalization = normalize_recorded(&mut normalized);
+    assert_eq!(normalized, bb4);
+    assert_eq!(normalization.denormalize(&normalized), original);
+    assert!(!normalization.is_identity());
+
+    let mut rng = crate::stress::Rng::new(0);
+    for _ in 0..1000 {
+        let original = crate::stress::machine::<5, 2>(&mut rng, crate::stress::Kind::Random);
+        let mut normalized = original;
+        let normalization = normalize_recorded(&mut normalized);
+        assert!(is_normal(&normalized), "{original}");
+        assert_eq!(normalization.denormalize(&normalized), original);
+    }
+
+    // Both machines run in lockstep when positions and states are translated.
+    let mut original_runner = Runner::<4, 2, _>::vector_backed(1000);
+    original_runner.set_states(&original);
+    let mut normalized_runner = Runner::<4, 2, _>::vector_backed(1000);
+    normalized_runner.set_states(&normalized);
+    loop {
+        let result = original_runner.step();
+        assert_eq!(
+            std::mem::discriminant(&result),
+            std::mem::discriminant(&normalized_runner.step())
+        );
+        assert_eq!(
+            normalization.translate_position(original_runner.position()),
+            normalized_runner.position()
+        );
+        assert_eq!(
+            normalization.normalized_state(original_runner.state()),
+            normalized_runner.state()
+        );
+        if let StepResult::Halt = result {
+            break;
+        }
+    }
+}
+
+#[test]
+fn normalize_symbols() {
+    use crate::run::{Runner, StepResult};
+
+    // Writes 2 before 1.
+    let mut states = crate::format::read_compact::<2, 3>(b"2RB1LA---_0LA1RB2RA").unwrap();
+    assert!(!is_normal(&states));
+    let original = states;
+    let normalization = normalize_recorded(&mut states);
+    assert_eq!(states.to_string(), "1RB---2LA_0LA1RA2RB");
+    assert!(is_normal(&states));
+    assert_eq!(normalization.denormalize(&states), original);
+    // Two symbol machines never need their symbols renamed.
+    assert!(is_normal(&crate::fixtures::BB5));
+
+    let mut rng = crate::stress::Rng::new(0);
+    for _ in 0..1000 {
+        let original = crate::stress::machine::<4, 3>(&mut rng, crate::stress::Kind::Random);
+        let mut normalized = original;
+        let normalization = normalize_recorded(&mut normalized);
+        assert!(is_normal(&normalized), "{original}");
+        assert_eq!(normalization.denormalize(&normalized), original);
+        let mut again = normalized;
+        assert!(normalize_recorded(&mut again).is_identity(), "{original}");
+
+        // Both machines run in lockstep when positions, states and symbols are translated.
+        let mut original_runner = Runner::<4, 3, _>::vector_backed(200);
+        original_runner.set_states(&original);
+        let mut normalized_runner = Runner::<4, 3, _>::vector_backed(200);
+        normalized_runner.set_states(&normalized);
+        for _ in 0..50 {
+            assert_eq!(
+                normalization.normalized_symbol(original_runner.symbol()),
+                normalized_runner.symbol()
+            );
+            let result = original_runner.step();
+            assert_eq!(result, normalized_runner.step());
+            assert_eq!(
+                normalization.normalized_state(original_runner.state()),
+                normalized_runner.state()
+            );
+            if result != StepResult::Ok {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn normal_forms() {
+    use crate::format::read_compact;
+
+    let machine = |s: &str| read_compact::<2, 2>(s.as_bytes()).unwrap();
+    let strict = NormalForm::strict();
+    assert!(strict.is_normal(&machine("1RB1LB_1LA---")));
+    // Writes 0 first.
+    assert!(is_normal(&machine("0RB1LB_1LA---")));
+    assert!(!strict.is_normal(&machine("0RB1LB_1LA---")));
+    // Two halting transitions.
+    assert!(!strict.is_normal(&machine("1RB1LB_------")));
+    let form = NormalForm {
+        single_halting_transition: false,
+        ..strict
+    };
+    assert!(form.is_normal(&machine("1RB1LB_------")));
+    // Halts before the last transition.
+    assert!(!strict.is_normal(&machine("1RB---_1LA1RB")));
+
+    // Without the ordering rule only the directions are normalized.
+    let form = NormalForm {
+        tnf_order: false,
+        ..NormalForm::default()
+    };
+    let original = read_compact::<3, 2>(b"1LC1RC_1LB---_1LA0LB").unwrap();
+    let mut normalized = original;
+    let normalization = form.normalize(&mut normalized);
+    assert_eq!(normalized.to_string(), "1RC1LC_1RB---_1RA0RB");
+    assert!(form.is_normal(&normalized));
+    assert!(!is_normal(&normalized));
+    assert_eq!(
+        normalization.states,
+        Normalization::<3, 2>::identity().states
+    );
+    assert_eq!(normalization.denormalize(&normalized), original);
+}
@@ -0,0 +1,203 @@
+//! Patterns that describe families of machines
+//!
+//! A pattern is written like a machine in the compact format of `format::read_compact` except that any character can be `.`, which matches anything. `1L.` matches every transition that writes 1 and moves left, `...` matches every transition including halting ones and `---` only halting transitions. In addition a pattern can require a number of halting transitions, in total or in a single state. The builder methods create the same patterns in code.
+//!
+//! ```
+//! use busy_beaver::{fixtures::BB5, pattern::Pattern, states::State};
+//! // State B writes 1 and moves left on both symbols and state E has exactly one halting transition.
+//! let pattern = Pattern::<5, 2>::parse(b"......_1L.1L._......_......_......")
+//!     .unwrap()
+//!     .halting_in(State::new(4).unwrap(), 1);
+//! assert!(!pattern.matches(&BB5));
+//! ```
+
+use crate::error::{Context, Error, Result};
+
+use crate::states::{Direction, State, States, Symbol, Transition};
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum TransitionPattern<const STATES: usize, const SYMBOLS: usize> {
+    /// Matches every transition.
+    #[default]
+    Any,
+    /// Matches the halting transition.
+    Halt,
+    /// Matches transitions that do not halt. `None` fields match any value.
+    Continue {
+        write: Option<Symbol<SYMBOLS>>,
+        move_: Option<Direction>,
+        state: Option<State<STATES>>,
+    },
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> TransitionPattern<STATES, SYMBOLS> {
+    pub fn matches(&self, transition: &Transition<STATES, SYMBOLS>) -> bool {
+        match (self, transition) {
+            (Self::Any, _) | (Self::Halt, Transition::Halt) => true,
+            (
+                Self::Continue {
+                    write,
+                    move_,
+                    state,
+                },
+                Transition::Continue(t),
+            ) => {
+                write.is_none_or(|w| w == t.write)
+                    && move_.is_none_or(|m| m == t.move_)
+                    && state.is_none_or(|s| s == t.state)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Pattern<const STATES: usize, const SYMBOLS: usize> {
+    transitions: [[TransitionPattern<STATES, SYMBOLS>; SYMBOLS]; STATES],
+    /// Required numbers of halting transitions. `None` counts in all states.
+    halting: Vec<(Option<State<STATES>>, usize)>,
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> Default for Pattern<STATES, SYMBOLS> {
+    fn default() -> Self {
+        Self {
+            transitions: [[TransitionPattern::Any; SYMBOLS]; STATES],
+            halting: Vec::new(),
+        }
+    }
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> Pattern<STATES, SYMBOLS> {
+    /// Parse the format described in the module documentation.
+    pub fn parse(s: &[u8]) -> Result<Self> {
+        let state_len = 3 * SYMBOLS + 1;
+        if s.len() + 1 != STATES * state_len {
+            return Err(Error::Parse("invalid length".into()));
+        }
+        let mut pattern = Self::default();
+        for (i, (chunk, state)) in s
+            .chunks(state_len)
+            .zip(pattern.transitions.iter_mut())
+            .enumerate()
+        {
+            if chunk.len() == state_len && chunk[state_len - 1] != b'_' {
+                return Err(Error::Parse("expected `_` between states".into()));
+            }
+            for (s, transition) in chunk.chunks_exact(3).zip(state.iter_mut()) {
+                *transition = parse_transition(s).with_context(|| format!("state {i}"))?;
+            }
+        }
+        Ok(pattern)
+    }
+
+    pub fn transition(
+        mut self,
+        state: State<STATES>,
+        symbol: Symbol<SYMBOLS>,
+        pattern: TransitionPattern<STATES, SYMBOLS>,
+    ) -> Self {
+        self.transitions[state.get() as usize][symbol.get() as usize] = pattern;
+        self
+    }
+
+    /// Require exactly `count` halting transitions.
+    pub fn halting(mut self, count: usize) -> Self {
+        self.halting.push((None, count));
+        self
+    }
+
+    /// Require exactly `count` halting transitions in `state`.
+    pub fn halting_in(mut self, state: State<STATES>, count: usize) -> Self {
+        self.halting.push((Some(state), count));
+        self
+    }
+
+    pub fn matches(&self, states: &States<STATES, SYMBOLS>) -> bool {
+        let transitions_match = self
+            .transitions
+            .iter()
+            .flatten()
+            .zip(states.0.iter().flatten())
+            .all(|(pattern, transition)| pattern.matches(transition));
+        transitions_match
+            && self.halting.iter().all(|(state, count)| {
+                let halting = |transitions: &[Transition<STATES, SYMBOLS>]| {
+                    transitions
+                        .iter()
+                        .filter(|t| **t == Transition::Halt)
+                        .count()
+                };
+                let actual = match state {
+                    Some(state) => halting(states.get_state(*state)),
+                    None => states.0.iter().map(|t| halting(t)).sum(),
+                };
+                actual == *count
+            })
+    }
+}
+
+fn parse_transition<const STATES: usize, const SYMBOLS: usize>(
+    s: &[u8],
+) -> Result<TransitionPattern<STATES, SYMBOLS>> {
+    match s {
+        b"---" => return Ok(TransitionPattern::Halt),
+        b"..." => return Ok(TransitionPattern::Any),
+        _ => (),
+    }
+    let write = match s[0] {
+        b'.' => None,
+        c => Some(Symbol::new(c.wrapping_sub(b'0')).context("invalid symbol")?),
+    };
+    let move_ = match s[1] {
+        b'.' => None,
+        b'L' => Some(Direction::Left),
+        b'R' => Some(Direction::Right),
+        _ => return Err(Error::Parse("invalid move direction".into())),
+    };
+    let state = match s[2] {
+        b'.' => None,
+        c => Some(State::new(c.wrapping_sub(b'A')).context("invalid state")?),
+    };
+    Ok(TransitionPattern::Continue {
+        write,
+        move_,
+        state,
+    })
+}
+
+#[test]
+fn pattern() {
+    use crate::fixtures::{BB4, BB5};
+    // Every champion matches itself and the pattern that matches everything.
+    let bb5 = Pattern::<5, 2>::parse(BB5.to_string().as_bytes()).unwrap();
+    assert!(bb5.matches(&BB5));
+    assert!(Pattern::default().matches(&BB5));
+
+    // State E halts on 0.
+    let pattern = Pattern::<5, 2>::parse(b"......_......_......_......_---...").unwrap();
+    assert!(pattern.matches(&BB5));
+    assert!(pattern.clone().halting(1).matches(&BB5));
+    assert!(!pattern.clone().halting(2).matches(&BB5));
+    let e = State::new(4).unwrap();
+    assert!(pattern.clone().halting_in(e, 1).matches(&BB5));
+    assert!(!pattern.halting_in(State::new(0).unwrap(), 1).matches(&BB5));
+
+    // Partial transitions. BB(4) state A writes 1 on both symbols, moving right then left.
+    let pattern = Pattern::<4, 2>::parse(b"1R.1L._......_......_......").unwrap();
+    assert!(pattern.matches(&BB4));
+    let left = TransitionPattern::Continue {
+        write: None,
+        move_: Some(Direction::Left),
+        state: None,
+    };
+    let a = State::new(0).unwrap();
+    let symbol = Symbol::new(0).unwrap();
+    assert!(!pattern.transition(a, symbol, left).matches(&BB4));
+    // A continuing pattern does not match a halting transition.
+    let pattern = Pattern::<4, 2>::parse(b"......_......_.L...._......").unwrap();
+    assert!(!pattern.matches(&BB4));
+
+    assert!(Pattern::<2, 2>::parse(b"1X.1.._......").is_err());
+    assert!(Pattern::<2, 2>::parse(b"1R.1..x......").is_err());
+    assert!(Pattern::<2, 2>::parse(b"1R.1..").is_err());
+}
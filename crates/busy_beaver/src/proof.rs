@@ -0,0 +1,356 @@
+//! Spot-checkable records of long halting runs
+//!
+//! Checking that a machine halts after tens of millions of steps requires simulating all of them. A `Proof` records the full configuration of the run every `interval` steps. Anyone can then check a random selection of the segments between two checkpoints without simulating the whole run. A wrong claim has to be wrong in at least one segment, so checking enough random segments finds it with high probability.
+//!
+//! The simulation in this module is deliberately simple and independent of `Runner` so that checking a proof does not trust the optimized code that produced the claim.
+
+use crate::error::{Context, Error, Result};
+
+use crate::{
+    format::{read_compact, write_markdown_table},
+    states::{Direction, States, Transition},
+};
+
+/// Configuration of a run after `step` steps.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Checkpoint {
+    pub step: u64,
+    pub state: u8,
+    /// Head position relative to the start cell.
+    pub position: i64,
+    /// Position of the first cell of `tape` relative to the start cell.
+    pub left: i64,
+    /// The visited cells from left to right. All other cells are 0.
+    pub tape: Vec<u8>,
+}
+
+impl Checkpoint {
+    /// FNV-1a hash of the configuration, for comparing checkpoints at a glance.
+    pub fn checksum(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut add = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+        add(&[self.state]);
+        add(&self.position.to_le_bytes());
+        add(&self.left.to_le_bytes());
+        add(&self.tape);
+        hash
+    }
+}
+
+/// Checkpoints of a halting run. The first checkpoint is the blank tape at step 0. The last checkpoint is the configuration in which the machine halts, at the step count including the halting step.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Proof<const STATES: usize, const SYMBOLS: usize> {
+    pub states: States<STATES, SYMBOLS>,
+    pub interval: u64,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> Proof<STATES, SYMBOLS> {
+    /// Run `states` for up to `limit` steps and record a checkpoint every `interval` steps. Fails if the machine does not halt within the limit.
+    pub fn record(states: &States<STATES, SYMBOLS>, interval: u64, limit: u64) -> Result<Self> {
+        if interval == 0 {
+            return Err(Error::Limit("interval must be positive".into()));
+        }
+        let mut machine = Machine::new(states, &Checkpoint::blank());
+        let mut checkpoints = vec![machine.checkpoint()];
+        loop {
+            let remaining = limit - machine.step;
+            if remaining == 0 {
+                return Err(Error::Limit(format!(
+                    "machine does not halt within {limit} steps"
+                )));
+            }
+            let halted = machine.run(interval.min(remaining)).is_none();
+            checkpoints.push(machine.checkpoint());
+            if halted {
+                break;
+            }
+        }
+        Ok(Self {
+            states: *states,
+            interval,
+            checkpoints,
+        })
+    }
+
+    /// Number of segments between checkpoints. Segments can be checked with `check_segment`.
+    pub fn segments(&self) -> usize {
+        self.checkpoints.len().saturating_sub(1)
+    }
+
+    /// Total steps of the run including the halting step.
+    pub fn steps(&self) -> u64 {
+        self.checkpoints.last().map(|c| c.step).unwrap_or(0)
+    }
+
+    /// Check that simulating from checkpoint `segment` leads to checkpoint `segment + 1`. For the last segment the machine also has to halt there.
+    pub fn check_segment(&self, segment: usize) -> Result<()> {
+        let (Some(from), Some(to)) = (
+            self.checkpoints.get(segment),
+            self.checkpoints.get(segment + 1),
+        ) else {
+            return Err(Error::Limit(format!("segment {segment} does not exist")));
+        };
+        if segment == 0 && *from != Checkpoint::blank() {
+            return Err(Error::Invariant(
+                "the run does not start on a blank tape".into(),
+            ));
+        }
+        let is_last = segment + 2 == self.checkpoints.len();
+        let steps = to
+            .step
+            .checked_sub(from.step)
+            .filter(|steps| *steps == self.interval || (is_last && *steps <= self.interval))
+            .ok_or_else(|| {
+                Error::Invariant(format!("segment {segment} has the wrong number of steps"))
+            })?;
+        let mut machine = Machine::new(&self.states, from);
+        let completed = machine.run(steps).is_some();
+        let expected_end = if is_last {
+            machine.halted && machine.step == to.step
+        } else {
+            completed && !machine.halted
+        };
+        if !expected_end || machine.checkpoint() != *to {
+            return Err(Error::Invariant(format!(
+                "segment {segment}: simulation ends at step {} with checksum {:016x} but the proof claims step {} with checksum {:016x}",
+                machine.step,
+                machine.checkpoint().checksum(),
+                to.step,
+                to.checksum()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Short proof sketch in Markdown for posting the result: the machine, how the proof works and the checkpoints. Long proofs show only their first and last checkpoints.
+    pub fn to_markdown(&self) -> String {
+        const SHOWN: usize = 4;
+        let mut result = format!(
+            "## `{}` halts after {} steps\n\n{}\n",
+            self.states,
+            self.steps(),
+            write_markdown_table(&self.states)
+        );
+        result.push_str(&format!(
+            "The run was recorded with a checkpoint every {} steps. A checkpoint is the full configuration: the state, the head position and the visited cells. Simulating the machine from a checkpoint for the interval reaches the next checkpoint, and from the last but one checkpoint the machine halts at the last one. Every segment between two checkpoints can be checked on its own, so checking random segments finds a wrong claim with high probability.\n\n",
+            self.interval
+        ));
+        result.push_str(
+            "| Step | State | Head | Visited cells | Checksum |\n|---:|---|---:|---:|---|\n",
+        );
+        let row = |c: &Checkpoint| {
+            format!(
+                "| {} | {} | {} | {} | `{:016x}` |\n",
+                c.step,
+                char::from(b'A' + c.state),
+                c.position,
+                c.tape.len(),
+                c.checksum()
+            )
+        };
+        let count = self.checkpoints.len();
+        for (i, checkpoint) in self.checkpoints.iter().enumerate() {
+            if count <= 2 * SHOWN || i < SHOWN || i >= count - SHOWN {
+                result.push_str(&row(checkpoint));
+            } else if i == SHOWN {
+                result.push_str("| ... | | | | |\n");
+            }
+        }
+        if let Some(last) = self.checkpoints.last() {
+            let read = last.tape[(last.position - last.left) as usize];
+            result.push_str(&format!(
+                "\nIn the last configuration the machine reads {read} in state {}, which is a halting transition. {} cells are not blank.\n",
+                char::from(b'A' + last.state),
+                last.tape.iter().filter(|cell| **cell != 0).count()
+            ));
+        }
+        result
+    }
+
+    /// Line based text format. The first line is the machine in the format of `read_compact`, the second line the interval. Every further line is a checkpoint: step, state, position, left, checksum in hexadecimal and the tape as digits.
+    pub fn write(&self) -> String {
+        let mut result = format!("{}\n{}\n", self.states, self.interval);
+        for c in &self.checkpoints {
+            let tape: String = c.tape.iter().map(|s| char::from(b'0' + s)).collect();
+            result.push_str(&format!(
+                "{} {} {} {} {:016x} {tape}\n",
+                c.step,
+                c.state,
+                c.position,
+                c.left,
+                c.checksum()
+            ));
+        }
+        result
+    }
+
+    /// Parse the format of `write`. The checksums are verified against the configurations.
+    pub fn read(s: &str) -> Result<Self> {
+        assert!(SYMBOLS <= 10, "the tape is written as digits");
+        let mut lines = s.lines();
+        let states = read_compact(lines.next().context("missing machine")?.as_bytes())
+            .context("invalid machine")?;
+        let interval = lines
+            .next()
+            .context("missing interval")?
+            .parse()
+            .context("invalid interval")?;
+        let checkpoints = lines
+            .enumerate()
+            .map(|(i, line)| {
+                read_checkpoint::<STATES, SYMBOLS>(line).with_context(|| format!("checkpoint {i}"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            states,
+            interval,
+            checkpoints,
+        })
+    }
+}
+
+fn read_checkpoint<const STATES: usize, const SYMBOLS: usize>(line: &str) -> Result<Checkpoint> {
+    let fields: Vec<&str> = line.split(' ').collect();
+    let [step, state, position, left, checksum, tape] = fields[..] else {
+        return Err(Error::Parse("expected 6 fields".into()));
+    };
+    let tape = tape
+        .bytes()
+        .map(|b| match b.checked_sub(b'0') {
+            Some(symbol) if (symbol as usize) < SYMBOLS => Ok(symbol),
+            _ => Err(Error::Parse("invalid symbol".into())),
+        })
+        .collect::<Result<Vec<u8>>>()?;
+    let checkpoint = Checkpoint {
+        step: step.parse().context("invalid step")?,
+        state: state
+            .parse()
+            .ok()
+            .filter(|s| (*s as usize) < STATES)
+            .context("invalid state")?,
+        position: position.parse().context("invalid position")?,
+        left: left.parse().context("invalid left")?,
+        tape,
+    };
+    let in_tape = checkpoint.position - checkpoint.left;
+    if checkpoint.tape.is_empty() || in_tape < 0 || in_tape >= checkpoint.tape.len() as i64 {
+        return Err(Error::Invariant("head is outside of the tape".into()));
+    }
+    if u64::from_str_radix(checksum, 16).ok() != Some(checkpoint.checksum()) {
+        return Err(Error::Invariant("checksum does not match".into()));
+    }
+    Ok(checkpoint)
+}
+
+impl Checkpoint {
+    fn blank() -> Self {
+        Self {
+            step: 0,
+            state: 0,
+            position: 0,
+            left: 0,
+            tape: vec![0],
+        }
+    }
+}
+
+/// Straightforward simulation on a tape that grows as needed.
+struct Machine<'a, const STATES: usize, const SYMBOLS: usize> {
+    states: &'a States<STATES, SYMBOLS>,
+    step: u64,
+    state: u8,
+    /// `tape[0]` is the cell at position `left`.
+    tape: Vec<u8>,
+    left: i64,
+    position: i64,
+    halted: bool,
+}
+
+impl<'a, const STATES: usize, const SYMBOLS: usize> Machine<'a, STATES, SYMBOLS> {
+    fn new(states: &'a States<STATES, SYMBOLS>, checkpoint: &Checkpoint) -> Self {
+        Self {
+            states,
+            step: checkpoint.step,
+            state: checkpoint.state,
+            tape: checkpoint.tape.clone(),
+            left: checkpoint.left,
+            position: checkpoint.position,
+            halted: false,
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            step: self.step,
+            state: self.state,
+            position: self.position,
+            left: self.left,
+            tape: self.tape.clone(),
+        }
+    }
+
+    /// Run `steps` steps. Returns `None` if the machine halts before. The halting step counts as a step.
+    fn run(&mut self, steps: u64) -> Option<()> {
+        for _ in 0..steps {
+            let index = (self.position - self.left) as usize;
+            self.step += 1;
+            let Transition::Continue(t) =
+                self.states.0[self.state as usize][self.tape[index] as usize]
+            else {
+                self.halted = true;
+                return None;
+            };
+            self.tape[index] = t.write.get();
+            self.state = t.state.get();
+            match t.move_ {
+                Direction::Left => {
+                    self.position -= 1;
+                    if self.position < self.left {
+                        self.tape.insert(0, 0);
+                        self.left -= 1;
+                    }
+                }
+                Direction::Right => {
+                    self.position += 1;
+                    if self.position - self.left == self.tape.len() as i64 {
+                        self.tape.push(0);
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+#[test]
+fn proof() {
+    let bb4 = crate::fixtures::get("bb4").unwrap().machine::<4, 2>();
+    let proof = Proof::record(&bb4, 10, 1000).unwrap();
+    assert_eq!(proof.steps(), 107);
+    assert_eq!(proof.segments(), 11);
+    // The champion visits 14 cells.
+    assert_eq!(proof.checkpoints.last().unwrap().tape.len(), 14);
+    for segment in 0..proof.segments() {
+        proof.check_segment(segment).unwrap();
+    }
+    assert_eq!(Proof::<4, 2>::read(&proof.write()).unwrap(), proof);
+    let markdown = proof.to_markdown();
+    assert!(markdown.starts_with("## `1RB1LB_1LA0LC_---1LD_1RD0RA` halts after 107 steps"));
+    assert!(markdown.contains("| ... |"));
+    // Sigma of 13 counts the 1 that the halting transition writes by convention.
+    assert!(markdown.contains("reads 0 in state C, which is a halting transition. 12 cells"));
+
+    let mut forged = proof.clone();
+    forged.checkpoints[5].tape[0] ^= 1;
+    assert!(forged.check_segment(4).is_err());
+    assert!(forged.check_segment(5).is_err());
+    assert!(Proof::<4, 2>::read(&forged.write().replace(" 0 ", " 1 ")).is_err());
+
+    assert!(Proof::record(&bb4, 10, 100).is_err());
+}
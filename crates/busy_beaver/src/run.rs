@@ -13,46 +13,123 @@
 // - Optionally the tape can be detected as full and reads out of bounds prevented by doing something like `let pos_ = pos; pos = pos.max(0); pos = pos.min(ape.len()); is_full |= pos_ != pos;`.
 // Despite resulting in simpler assembly with less instructions and less branches, the program runs slower for BB(5), which is the best case for this adapted algorithm. Machines that halt earlier have less benefit because the new algorithm doesn't exit early on halting. It even runs slower when removing the tape out of bounds check. Unrolling the loop did not help either.
 
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+#[cfg(feature = "std")]
+use crate::huge_page::HugePageBuffer;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::states::{DefinedTransition, Direction, State, States, Symbol, Transition};
 
 #[derive(Clone)]
-pub struct Runner<const STATES: usize, const SYMBOLS: usize, Storage> {
+pub struct Runner<const STATES: usize, const SYMBOLS: usize, T, Observer = ()> {
     states: [[Transition_; SYMBOLS]; STATES],
     state: u8,
-    tape: Tape<Storage>,
+    tape: T,
+    observer: Observer,
+    boundary: Boundary,
+}
+
+/// What a `Runner` does when a transition would move the head off the tape.
+///
+/// Implementations differ here, which matters when comparing the classification of machines that reach the boundary.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Boundary {
+    /// The symbol is written and the state changes but the head is not moved. Stepping again continues from there.
+    #[default]
+    Saturate,
+    /// The transition is not applied. The tape and the state stay as they were before the step, so stepping again reports the boundary again. Observers still see the transition.
+    Stop,
+    /// The tape grows and the transition is applied normally. Tapes that cannot grow, like `DenseTape`, behave like `Stop`.
+    Grow,
+    /// The head continues on the cell at the other end of the tape, so the tape is circular and the machine never runs out of tape. This is useful for studying how machines behave modulo a translation of the tape. Tapes without ends, like an unbounded `SparseTape`, behave like `Stop`.
+    Wrap,
 }
 
-impl<const STATES: usize, const SYMBOLS: usize> Runner<STATES, SYMBOLS, Vec<u8>> {
+#[cfg(feature = "alloc")]
+impl<const STATES: usize, const SYMBOLS: usize> Runner<STATES, SYMBOLS, DenseTape<Vec<u8>>> {
     pub fn vector_backed(length: usize) -> Self {
-        Self::new(vec![0u8; length])
+        Self::new(DenseTape::new(vec![0u8; length]))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<const STATES: usize, const SYMBOLS: usize> Runner<STATES, SYMBOLS, DenseTape<HugePageBuffer>> {
+    /// Like `vector_backed` but the tape is in huge pages where the platform supports them, which saves TLB misses in the step loop. The tape takes at least 2 MB of memory. See `huge_page`.
+    pub fn huge_page_backed(length: usize) -> Self {
+        Self::new(DenseTape::new(HugePageBuffer::new(length)))
     }
 }
 
 impl<const STATES: usize, const SYMBOLS: usize, const LENGTH: usize>
-    Runner<STATES, SYMBOLS, [u8; LENGTH]>
+    Runner<STATES, SYMBOLS, DenseTape<[u8; LENGTH]>>
 {
     pub fn array_backed() -> Self {
-        Self::new([0u8; LENGTH])
+        Self::new(DenseTape::new([0u8; LENGTH]))
     }
 }
 
-impl<const STATES: usize, const SYMBOLS: usize, Storage> Runner<STATES, SYMBOLS, Storage>
+#[cfg(feature = "std")]
+impl<const STATES: usize, const SYMBOLS: usize> Runner<STATES, SYMBOLS, SparseTape> {
+    /// A runner whose tape has no bounds.
+    pub fn sparse() -> Self {
+        Self::new(SparseTape::unbounded())
+    }
+}
+
+impl<const STATES: usize, const SYMBOLS: usize, T: Tape> Runner<STATES, SYMBOLS, T> {
+    pub fn new(tape: T) -> Self {
+        Self::with_observer(tape, ())
+    }
+}
+
+impl<const STATES: usize, const SYMBOLS: usize, T, O> Runner<STATES, SYMBOLS, T, O>
 where
-    Storage: AsRef<[u8]> + AsMut<[u8]>,
+    T: Tape,
+    O: Observer<STATES, SYMBOLS>,
 {
-    pub fn new(storage: Storage) -> Self {
+    pub fn with_observer(mut tape: T, observer: O) -> Self {
         assert!(STATES > 0);
+        tape.reset();
         Self {
             states: [[Transition_::default(); SYMBOLS]; STATES],
             state: 0,
-            tape: Tape::new(storage),
+            tape,
+            observer,
+            boundary: Boundary::default(),
         }
     }
 
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    pub fn tape(&self) -> &T {
+        &self.tape
+    }
+
     #[inline(always)]
     pub fn reset(&mut self) {
         self.state = 0;
         self.tape.reset();
+        self.observer.reset();
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
     }
 
     #[inline(always)]
@@ -101,7 +178,19 @@ where
         unsafe { Symbol::new_unchecked(s) }
     }
 
-    /// When the head of the tape moves out of bounds the current transition is still applied but the head is not moved.
+    /// Position of the head relative to the cell it started on. Negative values are left of the start.
+    #[inline(always)]
+    pub fn position(&self) -> isize {
+        self.tape.position()
+    }
+
+    /// Leftmost and rightmost position the head has visited since the last reset, relative to the start cell.
+    #[inline(always)]
+    pub fn extent(&self) -> (isize, isize) {
+        self.tape.extent()
+    }
+
+    /// When the head of the tape would move out of bounds the result depends on `Boundary`. By default the current transition is still applied but the head is not moved.
     #[inline(always)]
     pub fn step(&mut self) -> StepResult<STATES, SYMBOLS> {
         let symbol = self.tape.read() as usize;
@@ -120,25 +209,387 @@ where
                 move_,
                 state,
             } => {
+                self.observer.on_step(
+                    unsafe { State::new_unchecked(self.state) },
+                    unsafe { Symbol::new_unchecked(symbol as u8) },
+                    self.tape.position(),
+                );
                 self.tape.write(write);
-                self.state = state;
-                match self.tape.move_(move_) {
+                let previous_state = core::mem::replace(&mut self.state, state);
+                match self.tape.move_by(move_ as isize) {
                     Ok(()) => StepResult::Ok,
-                    Err(OutOfBounds::Left) => {
-                        crate::cold();
-                        StepResult::TapeFullLeft
-                    }
-                    Err(OutOfBounds::Right) => {
+                    Err(side) => {
                         crate::cold();
-                        StepResult::TapeFullRight
+                        self.out_of_bounds(side, symbol as u8, previous_state)
                     }
                 }
             }
         }
     }
+
+    #[inline(never)]
+    fn out_of_bounds(
+        &mut self,
+        side: OutOfBounds,
+        previous_symbol: u8,
+        previous_state: u8,
+    ) -> StepResult<STATES, SYMBOLS> {
+        let offset = match side {
+            OutOfBounds::Left => -1,
+            OutOfBounds::Right => 1,
+        };
+        let moved = match self.boundary {
+            Boundary::Saturate => false,
+            Boundary::Stop => {
+                self.tape.write(previous_symbol);
+                self.state = previous_state;
+                false
+            }
+            Boundary::Grow | Boundary::Wrap => {
+                let moved = match self.boundary {
+                    Boundary::Grow => self.tape.grow(side) && self.tape.move_by(offset).is_ok(),
+                    _ => self.tape.wrap(side),
+                };
+                if !moved {
+                    self.tape.write(previous_symbol);
+                    self.state = previous_state;
+                }
+                moved
+            }
+        };
+        match (moved, side) {
+            (true, _) => StepResult::Ok,
+            (false, OutOfBounds::Left) => StepResult::TapeFullLeft,
+            (false, OutOfBounds::Right) => StepResult::TapeFullRight,
+        }
+    }
+}
+
+/// Configuration of a `Runner`: its state, the position of its head and the visited cells. Restoring it continues the simulation exactly where it was taken, also in a runner with another kind of tape, which saves simulating long running machines again from the start. The transitions are not part of it.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    pub state: u8,
+    /// Relative to the start cell.
+    pub position: isize,
+    /// Leftmost visited position relative to the start cell.
+    pub left: isize,
+    /// The visited cells from `left` to the rightmost visited position.
+    pub cells: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl<const STATES: usize, const SYMBOLS: usize, T, O> Runner<STATES, SYMBOLS, T, O>
+where
+    T: Tape,
+    O: Observer<STATES, SYMBOLS>,
+{
+    pub fn snapshot(&self) -> Snapshot {
+        let (left, right) = self.tape.extent();
+        Snapshot {
+            state: self.state,
+            position: self.tape.position(),
+            left,
+            cells: (left..=right)
+                .map(|position| self.tape.cell(position))
+                .collect(),
+        }
+    }
+
+    /// Reset the runner and replace its configuration with `snapshot`. The observer does not see the steps that led to the snapshot. Fails if the snapshot is inconsistent or the visited cells do not fit on the tape, in which case the runner is left reset.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> crate::Result<()> {
+        use crate::Error;
+        self.reset();
+        let right = snapshot.left + snapshot.cells.len() as isize - 1;
+        if !(snapshot.left <= 0 && right >= 0) {
+            return Err(Error::Invariant(
+                "the visited cells do not include the start cell".into(),
+            ));
+        }
+        if !(snapshot.left..=right).contains(&snapshot.position) {
+            return Err(Error::Invariant(
+                "the head is outside the visited cells".into(),
+            ));
+        }
+        if snapshot.state as usize >= STATES {
+            return Err(Error::Invariant(format!(
+                "invalid state {}",
+                snapshot.state
+            )));
+        }
+        if let Some(symbol) = snapshot.cells.iter().find(|s| **s as usize >= SYMBOLS) {
+            return Err(Error::Invariant(format!("invalid symbol {symbol}")));
+        }
+        // Moving over the cells makes the tape track the extent like it does while running.
+        let restored = (|| {
+            for _ in snapshot.left..0 {
+                self.tape.move_by(-1)?;
+            }
+            for (i, symbol) in snapshot.cells.iter().enumerate() {
+                if i > 0 {
+                    self.tape.move_by(1)?;
+                }
+                self.tape.write(*symbol);
+            }
+            for _ in snapshot.position..right {
+                self.tape.move_by(-1)?;
+            }
+            Ok::<_, OutOfBounds>(())
+        })();
+        if restored.is_err() {
+            self.reset();
+            return Err(Error::Limit(
+                "the visited cells do not fit on the tape".into(),
+            ));
+        }
+        self.state = snapshot.state;
+        Ok(())
+    }
+}
+
+impl<const STATES: usize, const SYMBOLS: usize, T, O> Runner<STATES, SYMBOLS, ChecksumTape<T>, O>
+where
+    T: Tape,
+    O: Observer<STATES, SYMBOLS>,
+{
+    /// Checksum of the whole configuration: the cells, the position of the head and the state. Equal configurations have equal checksums.
+    pub fn checksum(&self) -> u64 {
+        configuration_checksum(self.tape.checksum(), self.tape.position(), self.state)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Snapshot {
+    /// The configuration checksum that a runner with a `ChecksumTape` has in this configuration.
+    pub fn checksum(&self) -> u64 {
+        let cells = self
+            .cells
+            .iter()
+            .zip(self.left..)
+            .fold(0u64, |sum, (symbol, position)| {
+                sum.wrapping_add((*symbol as u64).wrapping_mul(checksum_weight(position)))
+            });
+        configuration_checksum(cells, self.position, self.state)
+    }
+
+    /// Whether the configurations are the same. Unlike `==` this ignores how far the head has gone because cells that were visited but are 0 do not matter.
+    pub fn same_configuration(&self, other: &Snapshot) -> bool {
+        let cell = |snapshot: &Snapshot, position: isize| {
+            usize::try_from(position - snapshot.left)
+                .ok()
+                .and_then(|i| snapshot.cells.get(i).copied())
+                .unwrap_or(0)
+        };
+        let right = |snapshot: &Snapshot| snapshot.left + snapshot.cells.len() as isize;
+        self.state == other.state
+            && self.position == other.position
+            && (self.left.min(other.left)..right(self).max(right(other)))
+                .all(|position| cell(self, position) == cell(other, position))
+    }
+}
+
+/// Receives every transition a `Runner` applies. This gathers information about a run without slowing down runners that do not need it. Those use the no-op observer `()`.
+pub trait Observer<const STATES: usize, const SYMBOLS: usize> {
+    /// Called for every applied transition before the tape is modified. `state` and `symbol` select the transition. `position` is the cell it is applied on relative to the start cell.
+    #[inline(always)]
+    fn on_step(&mut self, state: State<STATES>, symbol: Symbol<SYMBOLS>, position: isize) {
+        let _ = (state, symbol, position);
+    }
+
+    /// Called when the runner is reset.
+    #[inline(always)]
+    fn reset(&mut self) {}
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> Observer<STATES, SYMBOLS> for () {}
+
+/// Observer that counts how many steps were spent at each tape offset.
+///
+/// The histogram has a fixed number of buckets. Each bucket covers `width` adjacent cells. When the head leaves the covered range, neighboring buckets are merged and the width doubles. This bounds the memory use while keeping the resolution as fine as the run allows.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct VisitHistogram {
+    width: isize,
+    // Bucket `i` covers the offsets starting at `(i - buckets.len() / 2) * width`.
+    buckets: Vec<u64>,
+}
+
+#[cfg(feature = "alloc")]
+impl VisitHistogram {
+    /// `buckets` is rounded up to an even number.
+    pub fn new(buckets: usize) -> Self {
+        let buckets = buckets.max(1).next_multiple_of(2);
+        Self {
+            width: 1,
+            buckets: vec![0; buckets],
+        }
+    }
+
+    /// Number of cells covered by each bucket.
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    /// The first offset covered by each bucket and the number of steps spent in the bucket.
+    pub fn buckets(&self) -> impl Iterator<Item = (isize, u64)> + '_ {
+        let half = (self.buckets.len() / 2) as isize;
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(move |(i, count)| ((i as isize - half) * self.width, *count))
+    }
+
+    /// Steps spent left of the start cell.
+    pub fn steps_left(&self) -> u64 {
+        self.buckets[..self.buckets.len() / 2].iter().sum()
+    }
+
+    /// Steps spent on or right of the start cell.
+    pub fn steps_right(&self) -> u64 {
+        self.buckets[self.buckets.len() / 2..].iter().sum()
+    }
+
+    fn coarsen(&mut self) {
+        let half = (self.buckets.len() / 2) as isize;
+        let mut buckets = vec![0; self.buckets.len()];
+        for (i, count) in self.buckets.iter().enumerate() {
+            let offset = i as isize - half;
+            buckets[(offset.div_euclid(2) + half) as usize] += count;
+        }
+        self.buckets = buckets;
+        self.width *= 2;
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const STATES: usize, const SYMBOLS: usize> Observer<STATES, SYMBOLS> for VisitHistogram {
+    #[inline(always)]
+    fn on_step(&mut self, _: State<STATES>, _: Symbol<SYMBOLS>, position: isize) {
+        let half = (self.buckets.len() / 2) as isize;
+        let mut index = position.div_euclid(self.width) + half;
+        while !(0..self.buckets.len() as isize).contains(&index) {
+            crate::cold();
+            self.coarsen();
+            index = position.div_euclid(self.width) + half;
+        }
+        self.buckets[index as usize] += 1;
+    }
+
+    fn reset(&mut self) {
+        self.width = 1;
+        self.buckets.iter_mut().for_each(|count| *count = 0);
+    }
+}
+
+/// Observer that counts how often each transition was applied. The halting transition is not applied and so not counted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TransitionCounts<const STATES: usize, const SYMBOLS: usize>(
+    pub [[u64; SYMBOLS]; STATES],
+);
+
+impl<const STATES: usize, const SYMBOLS: usize> Default for TransitionCounts<STATES, SYMBOLS> {
+    fn default() -> Self {
+        Self([[0; SYMBOLS]; STATES])
+    }
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> TransitionCounts<STATES, SYMBOLS> {
+    pub fn get(&self, state: State<STATES>, symbol: Symbol<SYMBOLS>) -> u64 {
+        self.0[state.get() as usize][symbol.get() as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().flatten().sum()
+    }
+
+    /// All transitions with their count, most frequent first. Ties are ordered by state and symbol.
+    #[cfg(feature = "alloc")]
+    pub fn by_frequency(&self) -> Vec<(State<STATES>, Symbol<SYMBOLS>, u64)> {
+        let mut counts: Vec<_> = (0..STATES)
+            .flat_map(|state| (0..SYMBOLS).map(move |symbol| (state, symbol)))
+            .map(|(state, symbol)| {
+                (
+                    State::new(state as u8).unwrap(),
+                    Symbol::new(symbol as u8).unwrap(),
+                    self.0[state][symbol],
+                )
+            })
+            .collect();
+        counts.sort_by_key(|(_, _, count)| core::cmp::Reverse(*count));
+        counts
+    }
+
+    /// Transitions that `states` defines but that were never applied.
+    pub fn unused<'a>(
+        &'a self,
+        states: &'a States<STATES, SYMBOLS>,
+    ) -> impl Iterator<Item = (State<STATES>, Symbol<SYMBOLS>)> + 'a {
+        (0..STATES)
+            .flat_map(|state| (0..SYMBOLS).map(move |symbol| (state, symbol)))
+            .filter(|&(state, symbol)| {
+                matches!(states.0[state][symbol], Transition::Continue(_))
+                    && self.0[state][symbol] == 0
+            })
+            .map(|(state, symbol)| {
+                (
+                    State::new(state as u8).unwrap(),
+                    Symbol::new(symbol as u8).unwrap(),
+                )
+            })
+    }
+}
+
+/// Defined transitions that a run from the blank tape did not apply.
+///
+/// A machine with dead transitions behaves like the machine in which they are halting transitions. Datasets that define every transition contain such machines as duplicates of the machines with fewer defined transitions. The dead transitions are only known to be dead for good if the run halted, otherwise they might still be applied after the bound.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DeadTransitions<const STATES: usize, const SYMBOLS: usize> {
+    pub transitions: Vec<(State<STATES>, Symbol<SYMBOLS>)>,
+    /// Whether the run halted within the bound.
+    pub halted: bool,
+}
+
+/// Run `states` for up to `steps` steps and report the defined transitions it did not apply.
+#[cfg(feature = "std")]
+pub fn dead_transitions<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+    steps: u64,
+) -> DeadTransitions<STATES, SYMBOLS> {
+    let mut runner = Runner::with_observer(SparseTape::unbounded(), TransitionCounts::default());
+    runner.set_states(states);
+    let mut halted = false;
+    for _ in 0..steps {
+        if runner.step() == StepResult::Halt {
+            halted = true;
+            break;
+        }
+    }
+    DeadTransitions {
+        transitions: runner.observer().unused(states).collect(),
+        halted,
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+impl<const STATES: usize, const SYMBOLS: usize> Observer<STATES, SYMBOLS>
+    for TransitionCounts<STATES, SYMBOLS>
+{
+    #[inline(always)]
+    fn on_step(&mut self, state: State<STATES>, symbol: Symbol<SYMBOLS>, _: isize) {
+        self.0[state.get() as usize][symbol.get() as usize] += 1;
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Result of `Runner::step`.
+///
+/// `name` and `code` are stable tags for storing results outside of the program, like `format::LogDecision::code`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum StepResult<const STATES: usize, const SYMBOLS: usize> {
     Ok,
     Halt,
@@ -146,6 +597,41 @@ pub enum StepResult<const STATES: usize, const SYMBOLS: usize> {
     TapeFullRight,
 }
 
+impl<const STATES: usize, const SYMBOLS: usize> StepResult<STATES, SYMBOLS> {
+    pub const ALL: [Self; 4] = [
+        Self::Ok,
+        Self::Halt,
+        Self::TapeFullLeft,
+        Self::TapeFullRight,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Halt => "halt",
+            Self::TapeFullLeft => "tape full left",
+            Self::TapeFullRight => "tape full right",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|result| result.name() == name)
+    }
+
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Ok => b'o',
+            Self::Halt => b'h',
+            Self::TapeFullLeft => b'<',
+            Self::TapeFullRight => b'>',
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|result| result.code() == code)
+    }
+}
+
 #[derive(Clone, Copy, Default)]
 enum Transition_ {
     #[default]
@@ -164,33 +650,113 @@ enum Direction_ {
     Right = 1,
 }
 
+/// Tape of a `Runner`. The runner works with any implementation. Which one is best depends on how the tape is used.
+///
+/// # Safety
+///
+/// `read` and `cell` must return 0 or a symbol that was passed to `write` since the last `reset`. `Runner` only writes symbols of its machine and indexes its transitions with the symbol under the head without checking it.
+pub unsafe trait Tape {
+    /// Set all cells to 0 and move the head to the start cell.
+    fn reset(&mut self);
+
+    /// Position of the head relative to the start cell.
+    fn position(&self) -> isize;
+
+    /// Leftmost and rightmost position of the head since the last reset, relative to the start cell.
+    fn extent(&self) -> (isize, isize);
+
+    fn read(&self) -> u8;
+
+    /// The cell at `position` relative to the start cell. Cells outside of the tape are 0.
+    fn cell(&self, position: isize) -> u8;
+
+    fn write(&mut self, symbol: u8);
+
+    /// Number of cells that are not 0.
+    fn non_blank(&self) -> usize;
+
+    /// Move the head by `offset`, which is -1 or 1. When the head would leave the tape it is not moved.
+    fn move_by(&mut self, offset: isize) -> Result<(), OutOfBounds>;
+
+    /// Make room for at least one more cell on `side`. Returns false if the tape cannot grow.
+    fn grow(&mut self, side: OutOfBounds) -> bool;
+
+    /// Move the head to the cell at the other end of the tape after it tried to leave on `side`. Returns false if the tape has no ends.
+    fn wrap(&mut self, _side: OutOfBounds) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OutOfBounds {
+    Left,
+    Right,
+}
+
+/// Tape that stores every cell in a contiguous buffer. The start cell is in the middle. This is the fastest tape but its memory use is proportional to its length.
 #[derive(Clone)]
-struct Tape<Storage> {
+pub struct DenseTape<Storage> {
     storage: Storage,
     // invariant: valid index into tape
     pos: isize,
+    // Leftmost and rightmost visited index. Cells outside of this range are 0.
+    left: isize,
+    right: isize,
 }
 
-impl<Storage> Tape<Storage>
+impl<Storage> DenseTape<Storage>
 where
     Storage: AsRef<[u8]> + AsMut<[u8]>,
 {
-    fn new(storage: Storage) -> Self {
+    pub fn new(storage: Storage) -> Self {
         let len = storage.as_ref().len();
         assert!(len > 0);
         let len: isize = len.try_into().unwrap();
         Self {
             storage,
             pos: len / 2,
+            // The storage might not be zeroed.
+            left: 0,
+            right: len - 1,
         }
     }
 
+    fn start(&self) -> isize {
+        (self.storage.as_ref().len() / 2) as isize
+    }
+
+    /// The visited cells from left to right. All other cells are 0.
+    pub fn visited(&self) -> &[u8] {
+        &self.storage.as_ref()[self.left as usize..=self.right as usize]
+    }
+}
+
+// `reset` clears the visited cells and cells that were never visited are 0 since `new` treats the whole storage as visited.
+unsafe impl<Storage> Tape for DenseTape<Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Only the visited cells are cleared. This is faster than clearing the whole tape when most machines stop early.
     #[inline(always)]
     fn reset(&mut self) {
-        for s in self.storage.as_mut().iter_mut() {
+        let (left, right) = (self.left as usize, self.right as usize);
+        for s in self.storage.as_mut()[left..=right].iter_mut() {
             *s = 0;
         }
-        self.pos = (self.storage.as_ref().len() / 2).try_into().unwrap();
+        self.pos = self.start();
+        self.left = self.pos;
+        self.right = self.pos;
+    }
+
+    #[inline(always)]
+    fn position(&self) -> isize {
+        self.pos - self.start()
+    }
+
+    #[inline(always)]
+    fn extent(&self) -> (isize, isize) {
+        let start = self.start();
+        (self.left - start, self.right - start)
     }
 
     #[inline(always)]
@@ -200,6 +766,15 @@ where
         *unsafe { storage.get_unchecked(self.pos as usize) }
     }
 
+    fn cell(&self, position: isize) -> u8 {
+        match position.checked_add(self.start()) {
+            Some(index) if (self.left..=self.right).contains(&index) => {
+                self.storage.as_ref()[index as usize]
+            }
+            _ => 0,
+        }
+    }
+
     #[inline(always)]
     fn write(&mut self, symbol: u8) {
         let storage = self.storage.as_mut();
@@ -207,47 +782,546 @@ where
         *unsafe { storage.get_unchecked_mut(self.pos as usize) } = symbol;
     }
 
-    /// Returns whether the move would result in the position being out of bounds. In that case no move is performed.
-    #[allow(clippy::result_unit_err)]
+    fn non_blank(&self) -> usize {
+        let (left, right) = (self.left as usize, self.right as usize);
+        self.storage.as_ref()[left..=right]
+            .iter()
+            .filter(|s| **s != 0)
+            .count()
+    }
+
+    /// The storage bounds are only checked when the head leaves the visited cells, which lie inside the storage. Inside them a move costs the same two comparisons as a plain bounds check.
     #[inline(always)]
-    fn move_(&mut self, direction: Direction_) -> Result<(), OutOfBounds> {
-        let new_pos = self.pos.wrapping_add(direction as isize);
-        if new_pos < 0 {
-            crate::cold();
-            Err(OutOfBounds::Left)
-        } else if new_pos >= self.storage.as_ref().len() as isize {
-            crate::cold();
-            Err(OutOfBounds::Right)
+    fn move_by(&mut self, offset: isize) -> Result<(), OutOfBounds> {
+        let new_pos = self.pos.wrapping_add(offset);
+        if new_pos < self.left {
+            if new_pos < 0 {
+                crate::cold();
+                return Err(OutOfBounds::Left);
+            }
+            self.left = new_pos;
+        } else if new_pos > self.right {
+            if new_pos >= self.storage.as_ref().len() as isize {
+                crate::cold();
+                return Err(OutOfBounds::Right);
+            }
+            self.right = new_pos;
+        }
+        self.pos = new_pos;
+        Ok(())
+    }
+
+    /// The storage has a fixed length.
+    fn grow(&mut self, _: OutOfBounds) -> bool {
+        false
+    }
+
+    fn wrap(&mut self, side: OutOfBounds) -> bool {
+        self.pos = match side {
+            OutOfBounds::Left => self.storage.as_ref().len() as isize - 1,
+            OutOfBounds::Right => 0,
+        };
+        self.left = self.left.min(self.pos);
+        self.right = self.right.max(self.pos);
+        true
+    }
+}
+
+/// Tape that only stores cells that are not 0. Its memory use is proportional to the number of such cells instead of to the length of the tape. This suits machines that move far but leave most of the tape blank. It is slower than `DenseTape`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct SparseTape {
+    cells: HashMap<isize, u8>,
+    // Relative to the start cell.
+    pos: isize,
+    extent: (isize, isize),
+    // Positions outside of this range are out of bounds.
+    bounds: (isize, isize),
+}
+
+#[cfg(feature = "std")]
+impl SparseTape {
+    /// A tape without bounds.
+    pub fn unbounded() -> Self {
+        Self {
+            bounds: (isize::MIN, isize::MAX),
+            ..Self::default()
+        }
+    }
+
+    /// A tape with the same bounds and start cell as a `DenseTape` of length `len`.
+    pub fn bounded(len: usize) -> Self {
+        assert!(len > 0);
+        let len: isize = len.try_into().unwrap();
+        let start = len / 2;
+        Self {
+            bounds: (-start, len - start - 1),
+            ..Self::default()
+        }
+    }
+}
+
+// Missing cells are read as 0 and the others hold what was written.
+#[cfg(feature = "std")]
+unsafe impl Tape for SparseTape {
+    fn reset(&mut self) {
+        self.cells.clear();
+        self.pos = 0;
+        self.extent = (0, 0);
+    }
+
+    fn position(&self) -> isize {
+        self.pos
+    }
+
+    fn extent(&self) -> (isize, isize) {
+        self.extent
+    }
+
+    fn read(&self) -> u8 {
+        self.cell(self.pos)
+    }
+
+    fn cell(&self, position: isize) -> u8 {
+        self.cells.get(&position).copied().unwrap_or(0)
+    }
+
+    fn non_blank(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn write(&mut self, symbol: u8) {
+        if symbol == 0 {
+            self.cells.remove(&self.pos);
         } else {
-            self.pos = new_pos;
-            Ok(())
+            self.cells.insert(self.pos, symbol);
+        }
+    }
+
+    fn move_by(&mut self, offset: isize) -> Result<(), OutOfBounds> {
+        match self.pos.checked_add(offset) {
+            Some(pos) if pos < self.bounds.0 => Err(OutOfBounds::Left),
+            Some(pos) if pos > self.bounds.1 => Err(OutOfBounds::Right),
+            Some(pos) => {
+                self.pos = pos;
+                self.extent = (self.extent.0.min(pos), self.extent.1.max(pos));
+                Ok(())
+            }
+            None if offset < 0 => Err(OutOfBounds::Left),
+            None => Err(OutOfBounds::Right),
         }
     }
+
+    /// Doubles the distance of the bound on `side` from the start cell. Growing an unbounded tape fails.
+    fn grow(&mut self, side: OutOfBounds) -> bool {
+        let bound = match side {
+            OutOfBounds::Left => &mut self.bounds.0,
+            OutOfBounds::Right => &mut self.bounds.1,
+        };
+        let grown = bound.saturating_add(bound.signum() * bound.abs().max(1));
+        let grown = match side {
+            OutOfBounds::Left => grown.min(-1),
+            OutOfBounds::Right => grown.max(1),
+        };
+        let changed = grown != *bound;
+        *bound = grown;
+        changed
+    }
+
+    /// Wrapping an unbounded tape fails.
+    fn wrap(&mut self, side: OutOfBounds) -> bool {
+        if self.bounds == (isize::MIN, isize::MAX) {
+            return false;
+        }
+        self.pos = match side {
+            OutOfBounds::Left => self.bounds.1,
+            OutOfBounds::Right => self.bounds.0,
+        };
+        self.extent = (self.extent.0.min(self.pos), self.extent.1.max(self.pos));
+        true
+    }
 }
 
-enum OutOfBounds {
-    Left,
-    Right,
+/// Tape that keeps a checksum of its cells up to date while it is written. Deciders that look for repeated configurations compare checksums first and only compare the cells when the checksums match, which makes the common case of different configurations O(1).
+///
+/// The checksum is the sum of every cell's symbol times a pseudo random weight of its position. Writing adds the change of the symbol times the weight of the head's position. Equal cells give equal checksums. Different cells give equal checksums with a probability of about 2^-64.
+#[derive(Debug, Clone)]
+pub struct ChecksumTape<T> {
+    tape: T,
+    checksum: u64,
+}
+
+impl<T: Tape> ChecksumTape<T> {
+    pub fn new(mut tape: T) -> Self {
+        tape.reset();
+        Self { tape, checksum: 0 }
+    }
+
+    /// Checksum of the cells. Positions are relative to the start cell, so shifted contents have different checksums.
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.tape
+    }
+}
+
+/// Weight of the cell at `position` in a `ChecksumTape` checksum.
+#[inline(always)]
+pub fn checksum_weight(position: isize) -> u64 {
+    // The output function of SplitMix64. It is a bijection so every position has its own weight.
+    let mut z = (position as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn configuration_checksum(cells: u64, position: isize, state: u8) -> u64 {
+    cells
+        ^ checksum_weight(position).rotate_left(32)
+        ^ (state as u64).wrapping_mul(0x9e3779b97f4a7c15)
+}
+
+// Reads and writes go to the inner tape.
+unsafe impl<T: Tape> Tape for ChecksumTape<T> {
+    fn reset(&mut self) {
+        self.tape.reset();
+        self.checksum = 0;
+    }
+
+    fn position(&self) -> isize {
+        self.tape.position()
+    }
+
+    fn extent(&self) -> (isize, isize) {
+        self.tape.extent()
+    }
+
+    #[inline(always)]
+    fn read(&self) -> u8 {
+        self.tape.read()
+    }
+
+    fn cell(&self, position: isize) -> u8 {
+        self.tape.cell(position)
+    }
+
+    #[inline(always)]
+    fn write(&mut self, symbol: u8) {
+        let change = (symbol as u64).wrapping_sub(self.tape.read() as u64);
+        self.checksum = self
+            .checksum
+            .wrapping_add(change.wrapping_mul(checksum_weight(self.tape.position())));
+        self.tape.write(symbol);
+    }
+
+    fn non_blank(&self) -> usize {
+        self.tape.non_blank()
+    }
+
+    #[inline(always)]
+    fn move_by(&mut self, offset: isize) -> Result<(), OutOfBounds> {
+        self.tape.move_by(offset)
+    }
+
+    fn grow(&mut self, side: OutOfBounds) -> bool {
+        self.tape.grow(side)
+    }
+
+    fn wrap(&mut self, side: OutOfBounds) -> bool {
+        self.tape.wrap(side)
+    }
 }
 
 #[test]
 #[ignore]
 fn speedtest() {
-    let states = crate::format::read_compact(crate::format::BB5_CHAMPION_COMPACT).unwrap();
-    let mut run = Runner::vector_backed(30_000);
-    // let mut run = Runner::<5, 2, [u8; 30_000]>::array_backed();
+    fn run<T: Tape>(name: &str, mut run: Runner<5, 2, T>) {
+        run.set_states(&crate::fixtures::BB5);
+        let start = std::time::Instant::now();
+        let mut steps: u64 = 0;
+        loop {
+            steps += 1;
+            match run.step() {
+                StepResult::Ok => {}
+                other => {
+                    let elapsed = start.elapsed();
+                    println!("{name}: {other:?} time {elapsed:?} steps {steps}");
+                    break;
+                }
+            }
+        }
+    }
+
+    run("vector", Runner::vector_backed(30_000));
+    run("huge pages", Runner::huge_page_backed(30_000));
+}
+
+#[test]
+fn visit_histogram() {
+    let states = crate::format::read_compact::<5, 2>(crate::format::BB4_CHAMPION_COMPACT).unwrap();
+    let mut run = Runner::with_observer(DenseTape::new(vec![0u8; 101]), VisitHistogram::new(4));
     run.set_states(&states);
-    let start = std::time::Instant::now();
-    let mut steps: u64 = 0;
-    loop {
+    let mut steps: u64 = 1;
+    while let StepResult::Ok = run.step() {
         steps += 1;
-        match run.step() {
-            StepResult::Ok => {}
-            other => {
-                let elapsed = start.elapsed();
-                println!("{other:?} time {elapsed:?} steps {steps}");
+    }
+    let histogram = run.observer();
+    // BB(4) runs for 107 steps. The last step is the halting transition which is not counted.
+    assert_eq!(steps, 107);
+    assert_eq!(histogram.steps_left() + histogram.steps_right(), 106);
+    // The champion uses 14 cells, which do not fit into 4 buckets of width 2.
+    assert!(histogram.width() >= 4);
+    let (left, right) = run.extent();
+    assert_eq!(right - left + 1, 14);
+    assert!((left..=right).contains(&run.position()));
+}
+
+#[test]
+fn transition_counts() {
+    let states = crate::format::read_compact::<5, 2>(crate::format::BB4_CHAMPION_COMPACT).unwrap();
+    let mut run =
+        Runner::with_observer(DenseTape::new(vec![0u8; 101]), TransitionCounts::default());
+    run.set_states(&states);
+    while let StepResult::Ok = run.step() {}
+    let counts = run.observer();
+    assert_eq!(counts.total(), 106);
+    // The halting transition of state C is not counted. State E is never reached.
+    let state = |s| State::new(s).unwrap();
+    assert_eq!(counts.get(state(2), Symbol::new(0).unwrap()), 0);
+    assert_eq!(counts.get(state(4), Symbol::new(1).unwrap()), 0);
+    let by_frequency = counts.by_frequency();
+    assert!(by_frequency.windows(2).all(|w| w[0].2 >= w[1].2));
+    run.reset();
+    assert_eq!(run.observer().total(), 0);
+}
+
+#[test]
+fn mirrored_trace() {
+    let check = |states: &States<5, 2>, tape_len: usize, steps: u64| {
+        // An odd length puts the start cell in the middle so that the tape is symmetric.
+        assert_eq!(tape_len % 2, 1);
+        let mut run = Runner::vector_backed(tape_len);
+        run.set_states(states);
+        let mut mirror = Runner::vector_backed(tape_len);
+        mirror.set_states(&states.mirrored());
+        for _ in 0..steps {
+            let result = run.step();
+            let mirror_result = mirror.step();
+            let consistent = matches!(
+                (result, mirror_result),
+                (StepResult::Ok, StepResult::Ok)
+                    | (StepResult::Halt, StepResult::Halt)
+                    | (StepResult::TapeFullLeft, StepResult::TapeFullRight)
+                    | (StepResult::TapeFullRight, StepResult::TapeFullLeft)
+            );
+            assert!(consistent, "{states}: {result:?} {mirror_result:?}");
+            assert_eq!(run.state(), mirror.state(), "{states}");
+            assert_eq!(run.symbol(), mirror.symbol(), "{states}");
+            assert_eq!(run.position(), -mirror.position(), "{states}");
+            if let StepResult::Halt = result {
                 break;
             }
         }
+    };
+    let bb5 = crate::format::read_compact(crate::format::BB5_CHAMPION_COMPACT).unwrap();
+    check(&bb5, 30_001, 100_000);
+    let mut rng = crate::stress::Rng::new(0);
+    for kind in crate::stress::Kind::ALL {
+        for _ in 0..100 {
+            let states = crate::stress::machine(&mut rng, kind);
+            check(&states, 21, 1000);
+        }
+    }
+}
+
+#[test]
+fn sparse_tape() {
+    let states = crate::format::read_compact::<5, 2>(crate::format::BB4_CHAMPION_COMPACT).unwrap();
+    let mut run = Runner::sparse();
+    run.set_states(&states);
+    let mut steps: u64 = 1;
+    while let StepResult::Ok = run.step() {
+        steps += 1;
+    }
+    assert_eq!(steps, 107);
+    // The halting transition is not applied so the 13th one is missing.
+    assert_eq!(run.tape().non_blank(), 12);
+
+    // Blank cells are not stored no matter how far the head moves.
+    let states = crate::format::read_compact::<2, 2>(b"0RA0RA_------").unwrap();
+    let mut run = Runner::sparse();
+    run.set_states(&states);
+    for _ in 0..100_000 {
+        assert!(matches!(run.step(), StepResult::Ok));
+    }
+    assert_eq!(run.position(), 100_000);
+    assert_eq!(run.tape().non_blank(), 0);
+}
+
+#[test]
+fn boundary() {
+    // Writes 1 and moves right forever.
+    let states = crate::format::read_compact::<2, 2>(b"1RA1RA_------").unwrap();
+    let mut run = Runner::vector_backed(3);
+    run.set_states(&states);
+    assert!(matches!(run.step(), StepResult::Ok));
+    assert!(matches!(run.step(), StepResult::TapeFullRight));
+    // The last cell is written although the head stays on it.
+    assert_eq!(run.tape().visited(), [1, 1]);
+
+    for tape_len in [3, 1] {
+        let mut run = Runner::vector_backed(tape_len);
+        run.set_states(&states);
+        run.set_boundary(Boundary::Stop);
+        while let StepResult::Ok = run.step() {}
+        let visited = run.tape().visited().to_vec();
+        assert_eq!(visited.last(), Some(&0));
+        // A tape that cannot grow stops.
+        run.reset();
+        run.set_boundary(Boundary::Grow);
+        while let StepResult::Ok = run.step() {}
+        assert_eq!(run.tape().visited(), visited);
+        assert!(matches!(run.step(), StepResult::TapeFullRight));
+    }
+
+    let mut run = Runner::new(SparseTape::bounded(1));
+    run.set_states(&states);
+    run.set_boundary(Boundary::Grow);
+    for _ in 0..1000 {
+        assert!(matches!(run.step(), StepResult::Ok));
+    }
+    assert_eq!(run.position(), 1000);
+    assert_eq!(run.tape().non_blank(), 1000);
+
+    // On a circular tape the head comes around and the machine never runs out of tape.
+    let mut dense = Runner::vector_backed(3);
+    let mut sparse = Runner::new(SparseTape::bounded(3));
+    dense.set_states(&states);
+    sparse.set_states(&states);
+    dense.set_boundary(Boundary::Wrap);
+    sparse.set_boundary(Boundary::Wrap);
+    for step in 1..=7 {
+        assert!(matches!(dense.step(), StepResult::Ok));
+        assert!(matches!(sparse.step(), StepResult::Ok));
+        assert_eq!(dense.position(), (step + 1) % 3 - 1);
+        assert_eq!(sparse.position(), dense.position());
     }
+    assert_eq!(dense.tape().visited(), [1, 1, 1]);
+    assert_eq!(sparse.tape().non_blank(), 3);
+    let left = crate::format::read_compact::<2, 2>(b"1LA1LA_------").unwrap();
+    dense.set_states(&left);
+    dense.reset();
+    dense.step();
+    dense.step();
+    assert_eq!(dense.position(), 1);
+    // An unbounded tape has no ends.
+    let mut unbounded = Runner::sparse();
+    unbounded.set_states(&states);
+    unbounded.set_boundary(Boundary::Wrap);
+    assert!(matches!(unbounded.step(), StepResult::Ok));
+}
+
+#[test]
+fn step_result_tags() {
+    for result in StepResult::<5, 2>::ALL {
+        assert_eq!(StepResult::from_name(result.name()), Some(result));
+        assert_eq!(StepResult::from_code(result.code()), Some(result));
+    }
+    assert_eq!(StepResult::<5, 2>::from_code(b'x'), None);
+}
+
+#[test]
+fn snapshot() {
+    let states = crate::fixtures::BB5;
+    let mut runner = Runner::<5, 2, _>::vector_backed(1 << 14);
+    runner.set_states(&states);
+    for _ in 0..10_000 {
+        assert_eq!(runner.step(), StepResult::Ok);
+    }
+    let snapshot = runner.snapshot();
+    let mut restored = Runner::<5, 2, _>::sparse();
+    restored.set_states(&states);
+    restored.restore(&snapshot).unwrap();
+    assert_eq!(restored.snapshot(), snapshot);
+    assert_eq!(restored.extent(), runner.extent());
+    for _ in 0..10_000 {
+        assert_eq!(restored.step(), runner.step());
+        assert_eq!(restored.position(), runner.position());
+    }
+    assert_eq!(restored.snapshot(), runner.snapshot());
+
+    let mut small = Runner::<5, 2, _>::vector_backed(8);
+    assert!(matches!(
+        small.restore(&snapshot),
+        Err(crate::Error::Limit(_))
+    ));
+    assert_eq!(small.position(), 0);
+    let invalid = Snapshot {
+        position: snapshot.left - 1,
+        ..snapshot
+    };
+    assert!(matches!(
+        runner.restore(&invalid),
+        Err(crate::Error::Invariant(_))
+    ));
+}
+
+#[test]
+fn checksum_tape() {
+    let cycler = crate::fixtures::get("cycler").unwrap().machine::<3, 2>();
+    let mut runner = Runner::<3, 2, _>::new(ChecksumTape::new(SparseTape::unbounded()));
+    runner.set_states(&cycler);
+    let mut seen = HashMap::<u64, Vec<(u64, Snapshot)>>::new();
+    let mut repeated = None;
+    for step in 0..1000u64 {
+        let snapshot = runner.snapshot();
+        assert_eq!(runner.checksum(), snapshot.checksum());
+        let earlier = seen.entry(runner.checksum()).or_default();
+        // Checksums only match for equal configurations here.
+        assert!(earlier.iter().all(|(_, e)| e.same_configuration(&snapshot)));
+        if let Some((first, _)) = earlier.first() {
+            repeated = Some((*first, step));
+            break;
+        }
+        earlier.push((step, snapshot));
+        assert_eq!(runner.step(), StepResult::Ok);
+    }
+    let (first, second) = repeated.expect("the cycler repeats a configuration");
+    assert!(first < second);
+
+    // Blank cells that were visited do not change the checksum.
+    let mut runner = Runner::<5, 2, _>::new(ChecksumTape::new(DenseTape::new(vec![0u8; 64])));
+    let empty = runner.checksum();
+    runner.tape.write(1);
+    assert_ne!(runner.checksum(), empty);
+    runner.tape.write(0);
+    runner.tape.move_by(1).unwrap();
+    runner.tape.move_by(-1).unwrap();
+    assert_eq!(runner.checksum(), empty);
+    assert!(runner
+        .snapshot()
+        .same_configuration(&Runner::<5, 2, _>::vector_backed(64).snapshot()));
+}
+
+#[test]
+fn dead() {
+    use crate::format::read_compact;
+
+    // B1 is never applied because the machine halts first.
+    let dead = dead_transitions(&read_compact::<2, 2>(b"1RB---_1LA1RA").unwrap(), 100);
+    assert!(dead.halted);
+    assert_eq!(
+        dead.transitions,
+        [(State::new(1).unwrap(), Symbol::new(1).unwrap())]
+    );
+    // Every transition of a fully defined machine that runs forever is applied.
+    let states = read_compact::<2, 2>(b"1RB1LB_1LA1RA").unwrap();
+    let dead = dead_transitions(&states, 100);
+    assert!(!dead.halted);
+    assert!(dead.transitions.is_empty());
+    // Too few steps to reach every transition.
+    let dead = dead_transitions(&crate::fixtures::BB5, 1);
+    assert_eq!(dead.transitions.len(), 8);
 }
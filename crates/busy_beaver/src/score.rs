@@ -0,0 +1,98 @@
+//! Scores of halting machines
+//!
+//! The busy beaver functions rank halting machines by different scores. BB counts steps, Σ counts the symbols left on the tape and BB_SPACE counts the cells the head visits.
+
+use crate::{
+    run::{Observer, Runner, StepResult, Tape},
+    states::States,
+};
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Score {
+    /// Steps including the halting step.
+    pub steps: u64,
+    /// Non blank symbols after halting when the halting transition writes a 1, which is the convention used in the literature.
+    pub ones: u64,
+    /// Number of distinct cells the head has visited.
+    pub space: u64,
+}
+
+/// Run `states` from a blank tape for up to `limit` steps. Returns `None` if the machine does not halt within the limit or runs out of tape.
+pub fn score<const STATES: usize, const SYMBOLS: usize, T: Tape, O: Observer<STATES, SYMBOLS>>(
+    runner: &mut Runner<STATES, SYMBOLS, T, O>,
+    states: &States<STATES, SYMBOLS>,
+    limit: u64,
+) -> Option<Score> {
+    runner.set_states(states);
+    runner.reset();
+    for steps in 1..=limit {
+        match runner.step() {
+            StepResult::Ok => (),
+            StepResult::Halt => return Some(halted(runner, steps)),
+            StepResult::TapeFullLeft | StepResult::TapeFullRight => return None,
+        }
+    }
+    None
+}
+
+/// Score of a runner that has just halted after `steps` steps.
+pub fn halted<const STATES: usize, const SYMBOLS: usize, T: Tape, O: Observer<STATES, SYMBOLS>>(
+    runner: &Runner<STATES, SYMBOLS, T, O>,
+    steps: u64,
+) -> Score {
+    // The halting transition is not applied by the runner. It writes a 1, which only changes the count if the cell is blank.
+    let ones = runner.tape().non_blank() as u64 + (runner.symbol().get() == 0) as u64;
+    Score {
+        steps,
+        ones,
+        space: space(runner),
+    }
+}
+
+/// Number of distinct cells the head has visited since the last reset. The head moves one cell at a time so these are all cells between the leftmost and rightmost position.
+pub fn space<const STATES: usize, const SYMBOLS: usize, T: Tape, O: Observer<STATES, SYMBOLS>>(
+    runner: &Runner<STATES, SYMBOLS, T, O>,
+) -> u64 {
+    let (left, right) = runner.extent();
+    (right - left + 1) as u64
+}
+
+#[test]
+fn fixtures() {
+    use crate::fixtures::{Behavior, FIXTURES};
+    fn check<const STATES: usize, const SYMBOLS: usize>(
+        fixture: &crate::fixtures::Fixture,
+    ) -> Option<Score> {
+        score(
+            &mut Runner::sparse(),
+            &fixture.machine::<STATES, SYMBOLS>(),
+            50_000_000,
+        )
+    }
+    for fixture in FIXTURES {
+        let Behavior::Halt { steps, ones } = fixture.behavior else {
+            continue;
+        };
+        let score = match (fixture.states, fixture.symbols) {
+            (2, 2) => check::<2, 2>(fixture),
+            (3, 2) => check::<3, 2>(fixture),
+            (4, 2) => check::<4, 2>(fixture),
+            (5, 2) => check::<5, 2>(fixture),
+            (2, 3) => check::<2, 3>(fixture),
+            (2, 4) => check::<2, 4>(fixture),
+            size => panic!("fixture {} has untested size {size:?}", fixture.name),
+        }
+        .unwrap();
+        assert_eq!((score.steps, score.ones), (steps, ones), "{}", fixture.name);
+    }
+}
+
+#[test]
+fn space_of_champions() {
+    let bb4 = crate::fixtures::get("bb4").unwrap().machine::<4, 2>();
+    let mut runner = Runner::vector_backed(101);
+    assert_eq!(score(&mut runner, &bb4, 1000).unwrap().space, 14);
+    let bb5 = crate::fixtures::get("bb5").unwrap().machine::<5, 2>();
+    let mut runner = Runner::vector_backed(30_000);
+    assert_eq!(score(&mut runner, &bb5, 50_000_000).unwrap().space, 12289);
+}
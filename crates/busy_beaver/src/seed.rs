@@ -0,0 +1,245 @@
+//! Decisions of bbchallenge's seed run
+//!
+//! The seed run enumerated the 5 state machines in tree normal form and wrote a log line with a decision for every machine. This module decides a single machine the same way, with the seed run's limits, its BB(4) heuristic and its irrelevance checks, so that tools can reproduce a log line without the enumeration of the `seed` crate.
+//!
+//! `is_enumerated` walks the enumeration tree towards a machine to tell whether the seed run enumerated it, which is needed to match machine lists from elsewhere against the log.
+//!
+//! The irrelevance checks look at the transition that the enumeration defined last. It is not part of the machine but can be recovered: every child in the enumeration defines the transition its parent halted on, so the transitions are first used in the order in which they were defined and the changed transition is the one that is used last. Machines that do not use every defined transition do not come from the enumeration and are decided without the irrelevance checks.
+
+use crate::{
+    decider::Decision,
+    run::{DenseTape, Runner, StepResult},
+    states::{DefinedTransition, Direction, State, States, Symbol, Transition},
+};
+
+/// Machines that do not halt within this many steps are undecided. The step count of the BB(5) champion.
+pub const LIMIT_STEPS: u64 = 47_176_870;
+/// Cells in either direction of the start cell. Machines that use more are undecided.
+pub const LIMIT_MEMORY: usize = 12_289;
+/// Machines that run for more than this many steps without visiting every state do not halt. The step count of the BB(4) champion.
+pub const BB4_STEPS: u64 = 107;
+
+/// Decide `states` like the seed run. The decision agrees with the code of its log line.
+pub fn seed_decide(states: &States<5, 2>) -> Decision {
+    let mut runner = Runner::<5, 2, _>::vector_backed(LIMIT_MEMORY * 2);
+    runner.set_states(states);
+    if let Some((state, symbol)) = changed_transition(&mut runner, states) {
+        if is_irrelevant(states, state, symbol) {
+            return Decision::Irrelevant;
+        }
+    }
+    runner.reset();
+    run(&mut runner)
+}
+
+/// Run the machine of `runner` from the start with the seed run's limits and BB(4) heuristic. A halting runner is left on the halting transition.
+fn run(runner: &mut Runner<5, 2, DenseTape<Vec<u8>>>) -> Decision {
+    let mut state_seen: u8 = 0;
+    let mut step: u64 = 0;
+    loop {
+        state_seen |= 1 << runner.state().get();
+        let result = runner.step();
+        if state_seen != 0b11111 && step > BB4_STEPS {
+            return Decision::RunForever;
+        }
+        if step > LIMIT_STEPS {
+            return Decision::Undecided;
+        }
+        step += 1;
+        match result {
+            StepResult::Ok => (),
+            StepResult::Halt => return Decision::Halt,
+            StepResult::TapeFullLeft | StepResult::TapeFullRight => return Decision::Undecided,
+        }
+    }
+}
+
+/// Whether the seed run's tree normal form enumeration contains `states`, so that it has a log line.
+///
+/// The enumeration starts from the machine whose only defined transition is 1RB. A machine that halts and has at least two halting transitions gets a child for every definition of the transition it halted on, and the definitions go to the states that are already used, the halting state and the next unused state. `states` is enumerated if it is reached by always picking the child that agrees with it. Transitions that the machine never uses are not defined by the enumeration, so machines with such transitions are not enumerated.
+pub fn is_enumerated(states: &States<5, 2>) -> bool {
+    let first = Transition::Continue(DefinedTransition {
+        write: Symbol::new(1).unwrap(),
+        move_: Direction::Right,
+        state: State::new(1).unwrap(),
+    });
+    if states.0[0][0] != first {
+        return false;
+    }
+    let mut runner = Runner::<5, 2, _>::vector_backed(LIMIT_MEMORY * 2);
+    let mut node = States([[Transition::Halt; 2]; 5]);
+    node.0[0][0] = first;
+    // The root halts on B0 in its second step.
+    let (mut state, mut symbol) = (State::new(1).unwrap(), Symbol::new(0).unwrap());
+    loop {
+        let Transition::Continue(transition) = *states.get_transition(state, symbol) else {
+            return false;
+        };
+        let largest_defined_state = (0..5u8)
+            .rev()
+            .find(|i| node.0[*i as usize] != [Transition::Halt; 2])
+            .unwrap();
+        if transition.state.get() > (largest_defined_state.max(state.get()) + 1).min(4) {
+            return false;
+        }
+        *node.get_transition_mut(state, symbol) = Transition::Continue(transition);
+        if node == *states {
+            return true;
+        }
+        // Only children that halt have children of their own, and only if they keep a halting transition.
+        if is_irrelevant(&node, state, symbol) {
+            return false;
+        }
+        runner.set_states(&node);
+        runner.reset();
+        let halting_transitions = node.0.iter().flatten().filter(|t| **t == Transition::Halt);
+        if run(&mut runner) != Decision::Halt || halting_transitions.count() < 2 {
+            return false;
+        }
+        (state, symbol) = (runner.state(), runner.symbol());
+    }
+}
+
+/// The transition the enumeration defined last to arrive at `states`, or `None` if `states` does not use every defined transition within the limits.
+pub fn changed_transition(
+    runner: &mut Runner<5, 2, DenseTape<Vec<u8>>>,
+    states: &States<5, 2>,
+) -> Option<(State<5>, Symbol<2>)> {
+    let mut unused = [[false; 2]; 5];
+    let mut remaining = 0;
+    for (state, transitions) in states.0.iter().enumerate() {
+        for (symbol, transition) in transitions.iter().enumerate() {
+            if matches!(transition, Transition::Continue(_)) {
+                unused[state][symbol] = true;
+                remaining += 1;
+            }
+        }
+    }
+    runner.set_states(states);
+    runner.reset();
+    // The parent would have been decided by the BB(4) heuristic instead of halting on the changed transition.
+    let mut state_seen: u8 = 0;
+    for step in 0..=LIMIT_STEPS {
+        let (state, symbol) = (runner.state(), runner.symbol());
+        state_seen |= 1 << state.get();
+        if state_seen != 0b11111 && step > BB4_STEPS {
+            return None;
+        }
+        let cell = &mut unused[state.get() as usize][symbol.get() as usize];
+        if *cell {
+            *cell = false;
+            remaining -= 1;
+            if remaining == 0 {
+                return Some((state, symbol));
+            }
+        }
+        if runner.step() != StepResult::Ok {
+            return None;
+        }
+    }
+    None
+}
+
+// A machine is irrelevant when it does not need to be run in order to find BB(5). The `seed` crate's enumeration uses these checks too.
+
+/// Whether `states` is irrelevant. `changed_state` and `read` select the transition that was defined last. Only the checks that involve this transition are done because the parent machine passed the others.
+#[inline]
+pub fn is_irrelevant(states: &States<5, 2>, changed_state: State<5>, read: Symbol<2>) -> bool {
+    has_equivalent_states(states, changed_state)
+        || has_redundant_transition(states, changed_state, read)
+}
+
+/// Another state does the same as `changed_state`, so one of them can be replaced by the other.
+#[inline]
+fn has_equivalent_states(states: &States<5, 2>, changed_state: State<5>) -> bool {
+    (0u8..5).any(|i| {
+        i != changed_state.get()
+            && are_states_defined_and_equivalent(states, State::new(i).unwrap(), changed_state)
+    })
+}
+
+#[inline]
+fn are_states_defined_and_equivalent(states: &States<5, 2>, a: State<5>, b: State<5>) -> bool {
+    let a_ = states.get_state(a);
+    let b_ = states.get_state(b);
+    let (
+        [Transition::Continue(a0), Transition::Continue(a1)],
+        [Transition::Continue(b0), Transition::Continue(b1)],
+    ) = (a_, b_)
+    else {
+        return false;
+    };
+    (a0.write == b0.write)
+        & (a0.move_ == b0.move_)
+        & (a1.write == b1.write)
+        & (a1.move_ == b1.move_)
+        & ((a0.state == b0.state)
+            | (((a0.state == a) | (a0.state == b)) & ((b0.state == b) | (b0.state == a))))
+        & ((a1.state == b1.state)
+            | (((a1.state == a) | (a1.state == b)) & ((b1.state == b) | (b1.state == a))))
+}
+
+/// The changed transition goes to a state that writes back what it reads and returns in the same state, which undoes the move.
+#[inline]
+fn has_redundant_transition(
+    states: &States<5, 2>,
+    changed_state: State<5>,
+    read: Symbol<2>,
+) -> bool {
+    let Transition::Continue(t) = states.get_transition(changed_state, read) else {
+        return false;
+    };
+    let [Transition::Continue(n0), Transition::Continue(n1)] = states.get_state(t.state) else {
+        return false;
+    };
+    let copies = (n0.write.get() == 0) & (n1.write.get() == 1);
+    let moves_back = (n0.move_ != t.move_) & (n1.move_ != t.move_);
+    let states_back = n0.state == n1.state;
+    copies & moves_back & states_back
+}
+
+#[test]
+fn seed_decisions() {
+    use crate::format::read_compact;
+
+    assert_eq!(seed_decide(&crate::fixtures::BB5), Decision::Halt);
+    let decide = |machine: &str| seed_decide(&read_compact(machine.as_bytes()).unwrap());
+    // Skelet's machines are undecided in the seed run.
+    let skelet = crate::fixtures::get("skelet 1").unwrap().machine();
+    assert_eq!(seed_decide(&skelet), Decision::Undecided);
+    // Never reaches states D and E.
+    assert_eq!(
+        decide("1RB1LA_1LA1RB_------_------_------"),
+        Decision::RunForever
+    );
+    // States C and D are equivalent.
+    assert_eq!(
+        decide("1RB1LA_0LA0RC_1RA1RD_1RA1RD_------"),
+        Decision::Irrelevant
+    );
+    // Machines that do not use every defined transition are not checked for irrelevance.
+    let mut runner = Runner::vector_backed(LIMIT_MEMORY * 2);
+    let unused = read_compact(b"1RB---_------_1RC1RC_------_------").unwrap();
+    assert_eq!(changed_transition(&mut runner, &unused), None);
+    let champion = crate::fixtures::BB5;
+    let (state, symbol) = changed_transition(&mut runner, &champion).unwrap();
+    assert!(champion.get_transition(state, symbol) != &Transition::Halt);
+}
+
+#[test]
+fn enumerated_machines() {
+    use crate::format::read_compact;
+
+    let enumerated = |machine: &str| is_enumerated(&read_compact(machine.as_bytes()).unwrap());
+    assert!(is_enumerated(&crate::fixtures::BB5));
+    assert!(!is_enumerated(&crate::fixtures::BB5.mirrored()));
+    // The root is not enumerated, its first child is.
+    assert!(!enumerated("1RB---_------_------_------_------"));
+    assert!(enumerated("1RB---_1RA---_------_------_------"));
+    // B goes to D before C is used.
+    assert!(!enumerated("1RB---_1RD---_------_------_------"));
+    // A1 is never used.
+    assert!(!enumerated("1RB1RA_1RA---_------_------_------"));
+    // Irrelevant machines are enumerated too.
+    assert!(enumerated("1RB1LA_0LA0RC_1RA1RD_1RA1RD_------"));
+}
@@ -0,0 +1,301 @@
+//! Abstract simulation on a tape segment
+//!
+//! Deciders like the halting segment decider look at a window of the tape around the head and forget everything outside of it. This module implements that idea once as an abstract simulator so that new deciders can be tried by changing parameters instead of writing a new simulator.
+//!
+//! The window holds the exact contents of `window` cells. Every cell can also be unknown, which stands for every symbol. Cells outside of the window are always unknown. The head is either on a cell of the window or somewhere outside to the left or to the right. Reading an unknown cell follows the transitions of every symbol. Writing outside of the window is forgotten, and a head outside of the window that moves towards it can either enter the window or stay outside, so both are followed.
+//!
+//! An abstract configuration is the state, the position of the head and the window. Configurations with the same state and head position are joined into one whose cells are unknown where the joined windows differ. The joined configurations over-approximate every configuration the machine reaches, so if none of them reaches a halting transition, the machine runs forever. The converse does not hold: a reachable halting transition only means that this window is too small or too imprecise.
+//!
+//! The simulation always terminates because a join can only make cells unknown, so every state and head position is revisited at most `window` times.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use crate::{
+    decider::{Decider, Decision},
+    format::write_markdown_table,
+    states::{Direction, States, Transition},
+};
+
+/// Where the head starts in the window.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Position {
+    /// On the leftmost cell. Suits machines that move right.
+    Left,
+    /// In the middle, rounded to the left.
+    Center,
+    /// On the rightmost cell. Suits machines that move left.
+    Right,
+    /// Try every cell and succeed if any of them succeeds.
+    Every,
+}
+
+/// Parameters of the abstract simulation.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Config {
+    /// Number of cells that are tracked exactly. Must not be 0.
+    pub window: usize,
+    pub position: Position,
+}
+
+/// Position of the head relative to the window.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+enum Head {
+    Left,
+    Inside(usize),
+    Right,
+}
+
+/// Result of the abstract simulation.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Outcome {
+    /// No abstract configuration reaches a halting transition. `configurations` is the number of joined configurations.
+    NeverHalts { configurations: usize },
+    /// An abstract configuration reaches a halting transition.
+    MayHalt,
+}
+
+/// Abstract simulator. It keeps its allocations between machines.
+#[derive(Debug)]
+pub struct SegmentSimulator<const STATES: usize, const SYMBOLS: usize> {
+    config: Config,
+    configurations: HashMap<(u8, Head), Vec<u8>>,
+    pending: Vec<(u8, Head)>,
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> SegmentSimulator<STATES, SYMBOLS> {
+    /// Marks an unknown cell.
+    const UNKNOWN: u8 = SYMBOLS as u8;
+
+    pub fn new(config: Config) -> Self {
+        assert!(config.window > 0);
+        Self {
+            config,
+            configurations: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Simulate `states` with every start position of the configured `Position` until one proves that the machine never halts.
+    pub fn run(&mut self, states: &States<STATES, SYMBOLS>) -> Outcome {
+        self.run_with_start(states)
+            .map_or(Outcome::MayHalt, |(outcome, _)| outcome)
+    }
+
+    /// Like `run` but also returns the start position of the proof. The configurations of the proof stay in `self.configurations`.
+    fn run_with_start(&mut self, states: &States<STATES, SYMBOLS>) -> Option<(Outcome, usize)> {
+        let window = self.config.window;
+        let starts = match self.config.position {
+            Position::Left => 0..1,
+            Position::Center => (window - 1) / 2..(window - 1) / 2 + 1,
+            Position::Right => window - 1..window,
+            Position::Every => 0..window,
+        };
+        starts.into_iter().find_map(|start| {
+            let outcome = self.run_from(states, start);
+            (outcome != Outcome::MayHalt).then_some((outcome, start))
+        })
+    }
+
+    /// Short proof sketch in Markdown for posting the result: the machine, the joined configurations and why they are closed. Returns `None` if the simulation does not prove that the machine runs forever.
+    pub fn to_markdown(&mut self, states: &States<STATES, SYMBOLS>) -> Option<String> {
+        let (_, start) = self.run_with_start(states)?;
+        let mut result = format!(
+            "## `{states}` runs forever\n\n{}\n",
+            write_markdown_table(states)
+        );
+        result.push_str(&format!(
+            "The proof simulates the machine on a window of {} cells that starts blank with the head on cell {start}. Cells outside of the window and cells written as `?` can hold any symbol, so reading them follows the transitions of every symbol, and a head outside of the window that moves towards it may or may not enter it. Configurations with the same state and head position are joined by making the cells where they differ unknown. These are the joined configurations:\n\n| State | Head | Window |\n|---|---|---|\n",
+            self.config.window
+        ));
+        let mut configurations: Vec<_> = self.configurations.iter().collect();
+        configurations.sort_by_key(|((state, head), _)| (*state, *head));
+        for ((state, head), window) in configurations {
+            let head = match head {
+                Head::Left => "left of the window".to_string(),
+                Head::Inside(i) => format!("cell {i}"),
+                Head::Right => "right of the window".to_string(),
+            };
+            let window: String = window
+                .iter()
+                .map(|&cell| {
+                    if cell == Self::UNKNOWN {
+                        '?'
+                    } else {
+                        char::from(b'0' + cell)
+                    }
+                })
+                .collect();
+            result.push_str(&format!(
+                "| {} | {head} | `{window}` |\n",
+                char::from(b'A' + state)
+            ));
+        }
+        result.push_str("\nEvery step from one of these configurations leads to a configuration that is already included in them, so they are closed and cover every configuration the machine reaches. None of them reads a halting transition, so the machine runs forever.\n");
+        Some(result)
+    }
+
+    /// Simulate `states` with the head starting on cell `start` of a blank window.
+    pub fn run_from(&mut self, states: &States<STATES, SYMBOLS>, start: usize) -> Outcome {
+        assert!(start < self.config.window);
+        self.configurations.clear();
+        self.pending.clear();
+        self.join(0, Head::Inside(start), vec![0; self.config.window]);
+        while let Some(key) = self.pending.pop() {
+            let (state, head) = key;
+            let window = self.configurations[&key].clone();
+            let read = match head {
+                Head::Inside(i) => window[i],
+                Head::Left | Head::Right => Self::UNKNOWN,
+            };
+            let symbols = if read == Self::UNKNOWN {
+                0..SYMBOLS as u8
+            } else {
+                read..read + 1
+            };
+            for symbol in symbols {
+                let Transition::Continue(t) = states.0[state as usize][symbol as usize] else {
+                    return Outcome::MayHalt;
+                };
+                let next = t.state.get();
+                match head {
+                    Head::Inside(i) => {
+                        let mut written = window.clone();
+                        written[i] = t.write.get();
+                        let moved = match t.move_ {
+                            Direction::Left if i == 0 => Head::Left,
+                            Direction::Left => Head::Inside(i - 1),
+                            Direction::Right if i + 1 == written.len() => Head::Right,
+                            Direction::Right => Head::Inside(i + 1),
+                        };
+                        self.join(next, moved, written);
+                    }
+                    // The distance to the window is unknown, so moving towards it can enter it or not.
+                    Head::Left => {
+                        self.join(next, Head::Left, window.clone());
+                        if t.move_ == Direction::Right {
+                            self.join(next, Head::Inside(0), window.clone());
+                        }
+                    }
+                    Head::Right => {
+                        self.join(next, Head::Right, window.clone());
+                        if t.move_ == Direction::Left {
+                            let last = window.len() - 1;
+                            self.join(next, Head::Inside(last), window.clone());
+                        }
+                    }
+                }
+            }
+        }
+        Outcome::NeverHalts {
+            configurations: self.configurations.len(),
+        }
+    }
+
+    /// Join `window` into the configuration of `state` and `head` and queue it again if it changed.
+    fn join(&mut self, state: u8, head: Head, window: Vec<u8>) {
+        match self.configurations.entry((state, head)) {
+            Entry::Vacant(entry) => {
+                entry.insert(window);
+            }
+            Entry::Occupied(mut entry) => {
+                let mut changed = false;
+                for (joined, cell) in entry.get_mut().iter_mut().zip(window) {
+                    if *joined != cell && *joined != Self::UNKNOWN {
+                        *joined = Self::UNKNOWN;
+                        changed = true;
+                    }
+                }
+                if !changed {
+                    return;
+                }
+            }
+        }
+        self.pending.push((state, head));
+    }
+}
+
+impl Decider for SegmentSimulator<5, 2> {
+    fn decide(&mut self, states: &States<5, 2>) -> Decision {
+        match self.run(states) {
+            Outcome::NeverHalts { .. } => Decision::RunForever,
+            Outcome::MayHalt => Decision::Undecided,
+        }
+    }
+}
+
+#[test]
+fn segment_simulator() {
+    use crate::format::read_compact;
+    // Moves between two blank cells forever.
+    let oscillating = read_compact::<2, 2>(b"0RB---_0LA---").unwrap();
+    let mut simulator = SegmentSimulator::new(Config {
+        window: 2,
+        position: Position::Left,
+    });
+    assert_eq!(
+        simulator.run(&oscillating),
+        Outcome::NeverHalts { configurations: 2 }
+    );
+    let markdown = simulator.to_markdown(&oscillating).unwrap();
+    assert!(markdown.contains("| A | cell 0 | `00` |"));
+    // The head leaves the window, where it could read a 1.
+    let mut simulator = SegmentSimulator::new(Config {
+        window: 2,
+        position: Position::Right,
+    });
+    assert_eq!(simulator.run(&oscillating), Outcome::MayHalt);
+    let mut simulator = SegmentSimulator::new(Config {
+        window: 2,
+        position: Position::Every,
+    });
+    assert!(matches!(
+        simulator.run(&oscillating),
+        Outcome::NeverHalts { .. }
+    ));
+    // Runs right forever but would halt on a 1, which the unknown cells outside of the window can hold.
+    let translated = read_compact::<1, 2>(b"1RA---").unwrap();
+    let mut simulator = SegmentSimulator::new(Config {
+        window: 4,
+        position: Position::Every,
+    });
+    assert_eq!(simulator.run(&translated), Outcome::MayHalt);
+    let mut simulator = SegmentSimulator::new(Config {
+        window: 8,
+        position: Position::Every,
+    });
+    assert_eq!(simulator.decide(&crate::fixtures::BB5), Decision::Undecided);
+}
+
+#[test]
+fn segment_simulator_is_sound() {
+    use crate::{
+        run::{Runner, SparseTape, StepResult},
+        stress::{machine, Kind, Rng},
+    };
+    let mut simulator = SegmentSimulator::<3, 2>::new(Config {
+        window: 4,
+        position: Position::Every,
+    });
+    let mut runner = Runner::new(SparseTape::unbounded());
+    let mut rng = Rng::new(0);
+    let mut never_halts = 0;
+    for _ in 0..3000 {
+        for kind in Kind::ALL {
+            let states = machine::<3, 2>(&mut rng, kind);
+            if simulator.run(&states) == Outcome::MayHalt {
+                continue;
+            }
+            never_halts += 1;
+            runner.set_states(&states);
+            runner.reset();
+            for _ in 0..1000 {
+                assert!(!matches!(runner.step(), StepResult::Halt), "{states}");
+            }
+        }
+    }
+    assert!(never_halts > 0);
+}
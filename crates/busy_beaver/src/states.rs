@@ -1,13 +1,20 @@
 //! Type safe turing machine description
 
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
 
-#[serde_as]
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+/// The derived `Ord` compares the transitions in table order, where halting orders before every other transition and a transition orders by written symbol, then direction with left before right, then state. This is not the byte order of the Bbchallenge seed database, which encodes right as 0 and left as 1. Use `seed_database_cmp` for anything that has to agree with a sorted seed database.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct States<const STATES: usize, const SYMBOLS: usize>(
-    // `serde_as` is needed for the serialization derives because serde cannot handle generic arrays.
-    #[serde_as(as = "[[_; SYMBOLS]; STATES]")] pub [[Transition<STATES, SYMBOLS>; SYMBOLS]; STATES],
+    // `serde_with` is needed for the serialization derives because serde cannot handle generic arrays. The `serde_as` attribute would be shorter but does not work behind `cfg_attr`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<[[serde_with::Same; SYMBOLS]; STATES]>")
+    )]
+    pub [[Transition<STATES, SYMBOLS>; SYMBOLS]; STATES],
 );
 
 impl<const STATES: usize, const SYMBOLS: usize> Default for States<STATES, SYMBOLS> {
@@ -57,15 +64,206 @@ impl<const STATES: usize, const SYMBOLS: usize> States<STATES, SYMBOLS> {
         debug_assert!(state_.get(index).is_some());
         unsafe { state_.get_unchecked_mut(index) }
     }
+
+    /// The machine with left and right swapped in every transition. Run on a mirrored tape it behaves exactly like `self`.
+    pub fn mirrored(&self) -> Self {
+        Self(self.0.map(|state| {
+            state.map(|transition| match transition {
+                Transition::Halt => Transition::Halt,
+                Transition::Continue(t) => Transition::Continue(DefinedTransition {
+                    move_: t.move_.reversed(),
+                    ..t
+                }),
+            })
+        }))
+    }
+
+    /// Transitions that differ between `self` and `other`. Items are the state and symbol of the transition followed by the transition in `self` and in `other`.
+    pub fn diff<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<
+        Item = (
+            State<STATES>,
+            Symbol<SYMBOLS>,
+            Transition<STATES, SYMBOLS>,
+            Transition<STATES, SYMBOLS>,
+        ),
+    > + 'a {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .enumerate()
+            .flat_map(|(state, (a, b))| {
+                a.iter()
+                    .zip(b.iter())
+                    .enumerate()
+                    .filter(|(_, (a, b))| a != b)
+                    .map(move |(symbol, (a, b))| {
+                        let state = unsafe { State::new_unchecked(state as u8) };
+                        let symbol = unsafe { Symbol::new_unchecked(symbol as u8) };
+                        (state, symbol, *a, *b)
+                    })
+            })
+    }
+}
+
+impl States<5, 2> {
+    /// Order like the entries of the Bbchallenge seed database, that is like comparing the bytes of `format::write_seed_database`. Sorted sections of the database, and the binary searches and merges over them, use this order and not `Ord`. Always compares every transition, so the time does not depend on where the machines differ.
+    pub fn seed_database_cmp(&self, other: &Self) -> core::cmp::Ordering {
+        // The bytes of a transition in the database are the written symbol, the direction with right as 0 and the state counting from 1, or all 0 for halting. Packing them into one number with the same order lets the whole table be compared at once.
+        fn key(transition: &Transition<5, 2>) -> u64 {
+            match transition {
+                Transition::Halt => 0,
+                Transition::Continue(t) => {
+                    let (write, direction, state) = t.to_parts();
+                    (write as u64) << 4 | ((direction ^ 1) as u64) << 3 | (state as u64 + 1)
+                }
+            }
+        }
+        let packed = |states: &Self| {
+            states
+                .0
+                .iter()
+                .flatten()
+                .fold(0u64, |packed, transition| packed << 5 | key(transition))
+        };
+        packed(self).cmp(&packed(other))
+    }
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> States<STATES, SYMBOLS> {
+    /// Convert to a quadruple machine. Every transition becomes a write followed by a move. The move is done by an intermediate state that moves the same way on every symbol. Intermediate states are numbered after the states of `self`, one for every combination of direction and next state. The quadruple machine takes twice as many steps.
+    #[cfg(feature = "alloc")]
+    pub fn to_quadruples(&self) -> Vec<Quadruple> {
+        let mut intermediate = Vec::<(Direction, State<STATES>)>::new();
+        let mut result = Vec::new();
+        for (state, transitions) in self.0.iter().enumerate() {
+            for (read, transition) in transitions.iter().enumerate() {
+                let Transition::Continue(t) = transition else {
+                    continue;
+                };
+                let index = match intermediate.iter().position(|i| *i == (t.move_, t.state)) {
+                    Some(index) => index,
+                    None => {
+                        intermediate.push((t.move_, t.state));
+                        intermediate.len() - 1
+                    }
+                };
+                result.push(Quadruple {
+                    state: state as u8,
+                    read: read as u8,
+                    action: QuadrupleAction::Write(t.write.get()),
+                    next: Some((STATES + index) as u8),
+                });
+            }
+        }
+        for (index, (move_, next)) in intermediate.into_iter().enumerate() {
+            for read in 0..SYMBOLS {
+                result.push(Quadruple {
+                    state: (STATES + index) as u8,
+                    read: read as u8,
+                    action: QuadrupleAction::Move(move_),
+                    next: Some(next.get()),
+                });
+            }
+        }
+        result
+    }
+
+    /// Convert from a quadruple machine.
+    ///
+    /// A move becomes a transition that writes back the symbol it read. A write is combined with the rules that follow it until one of them moves. This does not need extra states, so the states of the quadruple machine that only follow writes can be left out by choosing a smaller `STATES`. A write that is followed by halting becomes a halting transition, which loses the written symbol. A state without a rule for a symbol halts.
+    ///
+    /// Returns an error if a transition would go to a state outside of `STATES`, if a state has several rules for the same symbol, or if writes follow each other forever without moving.
+    #[cfg(feature = "std")]
+    pub fn from_quadruples(quadruples: &[Quadruple]) -> crate::Result<Self> {
+        use crate::Error;
+        let mut rules = std::collections::HashMap::<(u8, u8), (QuadrupleAction, Option<u8>)>::new();
+        for q in quadruples {
+            if q.read as usize >= SYMBOLS {
+                return Err(Error::Parse(format!("symbol {} out of range", q.read)));
+            }
+            if let QuadrupleAction::Write(write) = q.action {
+                if write as usize >= SYMBOLS {
+                    return Err(Error::Parse(format!("symbol {write} out of range")));
+                }
+            }
+            if rules
+                .insert((q.state, q.read), (q.action, q.next))
+                .is_some()
+            {
+                return Err(Error::Parse(format!(
+                    "state {} has several rules for symbol {}",
+                    q.state, q.read
+                )));
+            }
+        }
+        let mut states = Self::default();
+        for (state, transitions) in states.0.iter_mut().enumerate() {
+            for (read, transition) in transitions.iter_mut().enumerate() {
+                let (mut current, mut symbol) = (state as u8, read as u8);
+                let mut write = symbol;
+                // Every rule can be followed at most once before a cycle is certain.
+                let mut remaining = rules.len();
+                *transition = loop {
+                    let Some((action, next)) = rules.get(&(current, symbol)) else {
+                        break Transition::Halt;
+                    };
+                    let Some(next) = *next else {
+                        break Transition::Halt;
+                    };
+                    match action {
+                        QuadrupleAction::Move(move_) => {
+                            let target = State::new(next).ok_or_else(|| {
+                                Error::Parse(format!("state {next} out of range"))
+                            })?;
+                            break Transition::Continue(DefinedTransition {
+                                write: Symbol::new(write).unwrap(),
+                                move_: *move_,
+                                state: target,
+                            });
+                        }
+                        QuadrupleAction::Write(w) => {
+                            write = *w;
+                            symbol = *w;
+                            current = next;
+                        }
+                    }
+                    remaining = remaining.checked_sub(1).ok_or_else(|| {
+                        Error::Invariant(format!("state {state} writes forever without moving"))
+                    })?;
+                };
+            }
+        }
+        Ok(states)
+    }
+}
+
+/// A rule of a quadruple (Post) machine. Unlike a `Transition` a rule either writes or moves. States are numbered from 0.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Quadruple {
+    pub state: u8,
+    pub read: u8,
+    pub action: QuadrupleAction,
+    /// `None` halts after the action.
+    pub next: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum QuadrupleAction {
+    Write(u8),
+    Move(Direction),
 }
 
 /// Invariant: Inner value is smaller than COUNT.
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct State<const COUNT: usize>(u8);
 
 impl<const COUNT: usize> State<COUNT> {
     #[inline(always)]
-    pub fn new(state: u8) -> Option<Self> {
+    pub const fn new(state: u8) -> Option<Self> {
         if state as usize >= COUNT {
             return None;
         }
@@ -74,24 +272,25 @@ impl<const COUNT: usize> State<COUNT> {
 
     #[allow(clippy::missing_safety_doc)]
     #[inline(always)]
-    pub unsafe fn new_unchecked(state: u8) -> Self {
+    pub const unsafe fn new_unchecked(state: u8) -> Self {
         debug_assert!(Self::new(state).is_some());
         Self(state)
     }
 
     #[inline(always)]
-    pub fn get(&self) -> u8 {
+    pub const fn get(&self) -> u8 {
         self.0
     }
 }
 
 /// Invariant: Inner value is smaller than COUNT.
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Symbol<const COUNT: usize>(u8);
 
 impl<const COUNT: usize> Symbol<COUNT> {
     #[inline(always)]
-    pub fn new(symbol: u8) -> Option<Self> {
+    pub const fn new(symbol: u8) -> Option<Self> {
         if symbol as usize >= COUNT {
             return None;
         }
@@ -100,35 +299,249 @@ impl<const COUNT: usize> Symbol<COUNT> {
 
     #[allow(clippy::missing_safety_doc)]
     #[inline(always)]
-    pub unsafe fn new_unchecked(symbol: u8) -> Self {
+    pub const unsafe fn new_unchecked(symbol: u8) -> Self {
         debug_assert!(Self::new(symbol).is_some());
         Self(symbol)
     }
 
     #[inline(always)]
-    pub fn get(&self) -> u8 {
+    pub const fn get(&self) -> u8 {
         self.0
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Transition<const STATES: usize, const SYMBOLS: usize> {
     #[default]
     Halt,
     Continue(DefinedTransition<STATES, SYMBOLS>),
 }
 
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DefinedTransition<const STATES: usize, const SYMBOLS: usize> {
     pub write: Symbol<SYMBOLS>,
     pub move_: Direction,
     pub state: State<STATES>,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Ord, PartialOrd, Serialize, Deserialize)]
+impl<const STATES: usize, const SYMBOLS: usize> DefinedTransition<STATES, SYMBOLS> {
+    /// `direction` is 0 for left and 1 for right like the representation of `Direction`. Returns `None` if a part is out of range.
+    #[inline(always)]
+    pub const fn from_parts(write: u8, direction: u8, state: u8) -> Option<Self> {
+        let (Some(write), Some(state)) = (Symbol::new(write), State::new(state)) else {
+            return None;
+        };
+        let move_ = match direction {
+            0 => Direction::Left,
+            1 => Direction::Right,
+            _ => return None,
+        };
+        Some(Self {
+            write,
+            move_,
+            state,
+        })
+    }
+
+    /// The written symbol, the direction and the state as in `from_parts`.
+    #[inline(always)]
+    pub const fn to_parts(self) -> (u8, u8, u8) {
+        (self.write.get(), self.move_ as u8, self.state.get())
+    }
+
+    /// Pack into 6 bits: the state in bits 3 to 5, the direction in bit 2 and the written symbol in bits 0 and 1. Panics for machines with more than 8 states or 4 symbols.
+    pub const fn to_bits(self) -> u8 {
+        assert!(STATES <= 8 && SYMBOLS <= 4, "too large for 6 bits");
+        let (write, direction, state) = self.to_parts();
+        state << 3 | direction << 2 | write
+    }
+
+    /// Inverse of `to_bits`. Returns `None` if a part is out of range.
+    pub const fn from_bits(bits: u8) -> Option<Self> {
+        if bits >= 1 << 6 {
+            return None;
+        }
+        Self::from_parts(bits & 0b11, (bits >> 2) & 1, bits >> 3)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum Direction {
     #[default]
     Left,
     Right,
 }
+
+impl Direction {
+    pub fn reversed(self) -> Self {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+/// Iterator over every `DefinedTransition`, optionally limited to target states up to a maximum. The written symbol changes fastest, then the direction with right first, then the target state. This is the order in which the seed run enumerates the children of a machine.
+#[derive(Debug, Clone)]
+pub struct DefinedTransitions<const STATES: usize, const SYMBOLS: usize> {
+    exhausted: bool,
+    max_state: u8,
+    symbol: u8,
+    direction: u8,
+    state: u8,
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> DefinedTransitions<STATES, SYMBOLS> {
+    pub fn all() -> Self {
+        Self::up_to(State::new(STATES as u8 - 1).unwrap())
+    }
+
+    /// Only transitions to `max_state` and smaller states.
+    #[inline(always)]
+    pub fn up_to(max_state: State<STATES>) -> Self {
+        Self {
+            exhausted: SYMBOLS == 0,
+            max_state: max_state.get(),
+            symbol: 0,
+            direction: 0,
+            state: 0,
+        }
+    }
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> Iterator for DefinedTransitions<STATES, SYMBOLS> {
+    type Item = DefinedTransition<STATES, SYMBOLS>;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+        let result = DefinedTransition {
+            // The counters stay below their maximum, which is smaller than `STATES` and `SYMBOLS`.
+            state: unsafe { State::new_unchecked(self.state) },
+            move_: if self.direction == 0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            },
+            write: unsafe { Symbol::new_unchecked(self.symbol) },
+        };
+        // Mixed radix counter with the symbol as the least significant digit.
+        self.exhausted = true;
+        for (current, max) in [&mut self.symbol, &mut self.direction, &mut self.state]
+            .into_iter()
+            .zip([SYMBOLS as u8 - 1, 1, self.max_state])
+        {
+            if *current < max {
+                self.exhausted = false;
+                *current += 1;
+                break;
+            } else {
+                *current = 0;
+            }
+        }
+        Some(result)
+    }
+}
+
+#[test]
+fn defined_transitions() {
+    let all: Vec<String> = DefinedTransitions::<3, 2>::all()
+        .map(|t| Transition::Continue(t).to_string())
+        .collect();
+    assert_eq!(
+        all,
+        ["0RA", "1RA", "0LA", "1LA", "0RB", "1RB", "0LB", "1LB", "0RC", "1RC", "0LC", "1LC"]
+    );
+    assert_eq!(
+        DefinedTransitions::<5, 3>::up_to(State::new(1).unwrap()).count(),
+        3 * 2 * 2
+    );
+    assert_eq!(DefinedTransitions::<1, 1>::all().count(), 2);
+}
+
+#[test]
+fn defined_transition_parts() {
+    for t in DefinedTransitions::<5, 2>::all() {
+        let (write, direction, state) = t.to_parts();
+        assert_eq!(
+            DefinedTransition::from_parts(write, direction, state),
+            Some(t)
+        );
+        assert_eq!(DefinedTransition::from_bits(t.to_bits()), Some(t));
+    }
+    let bits: std::collections::HashSet<u8> = DefinedTransitions::<8, 4>::all()
+        .map(|t| t.to_bits())
+        .collect();
+    assert_eq!(bits.len(), 64);
+    assert_eq!(DefinedTransition::<5, 2>::from_parts(2, 0, 0), None);
+    assert_eq!(DefinedTransition::<5, 2>::from_parts(0, 2, 0), None);
+    assert_eq!(DefinedTransition::<5, 2>::from_parts(0, 0, 5), None);
+    assert_eq!(DefinedTransition::<5, 2>::from_bits(5 << 3), None);
+    assert_eq!(DefinedTransition::<8, 4>::from_bits(64), None);
+}
+
+#[test]
+fn mirrored() {
+    let states = crate::format::read_compact::<2, 2>(b"1RB1LB_1LA---").unwrap();
+    assert_eq!(states.mirrored().to_string(), "1LB1RB_1RA---");
+    assert_eq!(states.mirrored().mirrored(), states);
+}
+
+#[test]
+fn diff() {
+    let a = States::<2, 2>::default();
+    let mut b = a;
+    assert_eq!(a.diff(&b).count(), 0);
+    let transition = Transition::Continue(DefinedTransition {
+        write: Symbol::new(1).unwrap(),
+        move_: Direction::Right,
+        state: State::new(1).unwrap(),
+    });
+    b.0[1][0] = transition;
+    let diff: Vec<_> = a.diff(&b).collect();
+    assert_eq!(
+        diff,
+        [(
+            State::new(1).unwrap(),
+            Symbol::new(0).unwrap(),
+            Transition::Halt,
+            transition
+        )]
+    );
+}
+
+#[test]
+fn seed_database_order() {
+    use crate::{
+        format::{read_compact, write_seed_database},
+        stress::{machine, Kind, Rng},
+    };
+    use core::cmp::Ordering;
+
+    // The derived order puts left first, the database puts right first.
+    let left = read_compact::<5, 2>(b"1LB---_------_------_------_------").unwrap();
+    let right = read_compact::<5, 2>(b"1RB---_------_------_------_------").unwrap();
+    assert_eq!(left.cmp(&right), Ordering::Less);
+    assert_eq!(left.seed_database_cmp(&right), Ordering::Greater);
+
+    let mut rng = Rng::new(0);
+    let machines: Vec<States<5, 2>> = (0..200)
+        .flat_map(|_| Kind::ALL.map(|kind| machine(&mut rng, kind)))
+        .collect();
+    for a in &machines {
+        for b in machines.iter().step_by(7) {
+            assert_eq!(
+                a.seed_database_cmp(b),
+                write_seed_database(a).cmp(&write_seed_database(b)),
+                "{a} {b}"
+            );
+            assert_eq!(a.seed_database_cmp(b) == Ordering::Equal, a == b);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Randomized consistency checks for `Runner`
+//!
+//! `Runner` is optimized with unchecked indexing and a custom transition representation. This module compares it against a straightforward reference simulation on generated machines. The generator favors machines that stress the tape boundaries: machines that run straight off the tape and machines that oscillate around one cell. Tapes are short so that most runs hit the boundary many times, on linear and on circular tapes.
+//!
+//! The generator is seeded so that a failure can be reproduced from the seed alone.
+
+use crate::error::{Context, Error, Result};
+
+use crate::{
+    run::{Boundary, DenseTape, Runner, SparseTape, StepResult, Tape},
+    states::{DefinedTransition, Direction, State, States, Symbol, Transition},
+};
+
+/// Small deterministic random number generator (SplitMix64). The output for a seed never changes, unlike with external crates whose algorithms can change between versions.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`. `n` must not be 0.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Kind {
+    /// Every transition is random. Some are halting.
+    Random,
+    /// Every transition moves in the same direction so the head runs off the tape.
+    Excursion,
+    /// Transitions alternate the direction with every state change so the head stays close to where it is. On a short tape this loops across the boundary.
+    Oscillating,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Random, Kind::Excursion, Kind::Oscillating];
+}
+
+/// Generate a machine of the given kind.
+pub fn machine<const STATES: usize, const SYMBOLS: usize>(
+    rng: &mut Rng,
+    kind: Kind,
+) -> States<STATES, SYMBOLS> {
+    let direction = |right: bool| {
+        if right {
+            Direction::Right
+        } else {
+            Direction::Left
+        }
+    };
+    let excursion_direction = direction(rng.below(2) == 0);
+    let mut states = States::<STATES, SYMBOLS>::default();
+    for (state, transitions) in states.0.iter_mut().enumerate() {
+        for transition in transitions.iter_mut() {
+            if kind == Kind::Random && rng.below(8) == 0 {
+                *transition = Transition::Halt;
+                continue;
+            }
+            let write = Symbol::new(rng.below(SYMBOLS as u64) as u8).unwrap();
+            let (move_, next) = match kind {
+                Kind::Random => (
+                    direction(rng.below(2) == 0),
+                    rng.below(STATES as u64) as usize,
+                ),
+                Kind::Excursion => (excursion_direction, rng.below(STATES as u64) as usize),
+                // Even states move right and go to an odd state. Odd states move left and go to an even state.
+                Kind::Oscillating => {
+                    let next = (state + 1 + 2 * rng.below(STATES as u64) as usize) % STATES;
+                    (direction(state % 2 == 0), next)
+                }
+            };
+            *transition = Transition::Continue(DefinedTransition {
+                write,
+                move_,
+                state: State::new(next as u8).unwrap(),
+            });
+        }
+    }
+    states
+}
+
+/// Run `states` on a `Runner` with each kind of tape and on the reference simulation for up to `steps` steps and return an error at the first difference. Running continues after the tape is full because a runner has to stay consistent when it is stepped again. The machine is run twice on the same runner to check that resetting the runner leaves no trace of the first run. Every tape is also checked as a circular tape with `Boundary::Wrap`.
+pub fn check_runner<const STATES: usize, const SYMBOLS: usize>(
+    states: &States<STATES, SYMBOLS>,
+    tape_len: usize,
+    steps: u64,
+) -> Result<()> {
+    for boundary in [Boundary::Saturate, Boundary::Wrap] {
+        let dense = Runner::new(DenseTape::new(vec![0u8; tape_len]));
+        compare(states, dense, boundary, tape_len, steps)
+            .with_context(|| format!("dense tape, {boundary:?}"))?;
+        let sparse = Runner::new(SparseTape::bounded(tape_len));
+        compare(states, sparse, boundary, tape_len, steps)
+            .with_context(|| format!("sparse tape, {boundary:?}"))?;
+    }
+    Ok(())
+}
+
+fn compare<const STATES: usize, const SYMBOLS: usize, T: Tape>(
+    states: &States<STATES, SYMBOLS>,
+    mut runner: Runner<STATES, SYMBOLS, T>,
+    boundary: Boundary,
+    tape_len: usize,
+    steps: u64,
+) -> Result<()> {
+    runner.set_states(states);
+    runner.set_boundary(boundary);
+    for run in 1..=2 {
+        runner.reset();
+        compare_run(states, &mut runner, tape_len, steps).with_context(|| format!("run {run}"))?;
+    }
+    Ok(())
+}
+
+fn compare_run<const STATES: usize, const SYMBOLS: usize, T: Tape>(
+    states: &States<STATES, SYMBOLS>,
+    runner: &mut Runner<STATES, SYMBOLS, T>,
+    tape_len: usize,
+    steps: u64,
+) -> Result<()> {
+    let mut reference = Reference::new(tape_len, runner.boundary() == Boundary::Wrap);
+    for step in 1..=steps {
+        let expected = reference.step(states);
+        let actual = runner.step();
+        let actual_position = (reference.start as isize + runner.position()) as usize;
+        let consistent = same_result(actual, expected)
+            && runner.extent() == reference.extent()
+            && runner.state().get() as usize == reference.state
+            && actual_position == reference.position
+            && runner.symbol().get() == reference.tape[reference.position];
+        if !consistent {
+            return Err(Error::Invariant(format!(
+                "step {step}: runner {actual:?} state {} position {} symbol {}, reference {expected:?} state {} position {} symbol {}",
+                runner.state().get(),
+                actual_position,
+                runner.symbol().get(),
+                reference.state,
+                reference.position,
+                reference.tape[reference.position],
+            )));
+        }
+        if let StepResult::Halt = expected {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Generate `machines` machines of every kind from `seed` and check each of them on tapes of several lengths including the shortest possible.
+pub fn stress<const STATES: usize, const SYMBOLS: usize>(
+    seed: u64,
+    machines: u64,
+    steps: u64,
+) -> Result<()> {
+    let mut rng = Rng::new(seed);
+    for i in 0..machines {
+        for kind in Kind::ALL {
+            let states = machine::<STATES, SYMBOLS>(&mut rng, kind);
+            let tape_len = match rng.below(4) {
+                0 => 1,
+                1 => 2,
+                _ => 3 + rng.below(30) as usize,
+            };
+            check_runner(&states, tape_len, steps).with_context(|| {
+                format!("seed {seed}, machine {i}, {kind:?} {states}, tape length {tape_len}")
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// The simplest simulation that follows the documented semantics of `Runner::step`: when the head would move out of bounds the transition is still applied but the head is not moved, or on a circular tape it moves to the other end.
+struct Reference {
+    tape: Vec<u8>,
+    start: usize,
+    position: usize,
+    visited: (usize, usize),
+    state: usize,
+    circular: bool,
+}
+
+impl Reference {
+    fn new(tape_len: usize, circular: bool) -> Self {
+        Self {
+            tape: vec![0; tape_len],
+            start: tape_len / 2,
+            position: tape_len / 2,
+            visited: (tape_len / 2, tape_len / 2),
+            state: 0,
+            circular,
+        }
+    }
+
+    fn extent(&self) -> (isize, isize) {
+        (
+            self.visited.0 as isize - self.start as isize,
+            self.visited.1 as isize - self.start as isize,
+        )
+    }
+
+    fn step<const STATES: usize, const SYMBOLS: usize>(
+        &mut self,
+        states: &States<STATES, SYMBOLS>,
+    ) -> StepResult<STATES, SYMBOLS> {
+        let Transition::Continue(transition) =
+            states.0[self.state][self.tape[self.position] as usize]
+        else {
+            return StepResult::Halt;
+        };
+        self.tape[self.position] = transition.write.get();
+        self.state = transition.state.get() as usize;
+        let len = self.tape.len();
+        let result = match transition.move_ {
+            Direction::Left if self.position == 0 && !self.circular => StepResult::TapeFullLeft,
+            Direction::Left => {
+                self.position = (self.position + len - 1) % len;
+                StepResult::Ok
+            }
+            Direction::Right if self.position + 1 == len && !self.circular => {
+                StepResult::TapeFullRight
+            }
+            Direction::Right => {
+                self.position = (self.position + 1) % len;
+                StepResult::Ok
+            }
+        };
+        self.visited = (
+            self.visited.0.min(self.position),
+            self.visited.1.max(self.position),
+        );
+        result
+    }
+}
+
+fn same_result<const STATES: usize, const SYMBOLS: usize>(
+    a: StepResult<STATES, SYMBOLS>,
+    b: StepResult<STATES, SYMBOLS>,
+) -> bool {
+    std::mem::discriminant(&a) == std::mem::discriminant(&b)
+}
+
+#[test]
+fn stress_runner() {
+    stress::<2, 2>(0, 200, 200).unwrap();
+    stress::<5, 2>(1, 200, 200).unwrap();
+    stress::<2, 4>(2, 200, 200).unwrap();
+}
+
+/// Longer run for checking refactors of `Runner`. Pass a different seed with `STRESS_SEED` to explore other machines.
+#[test]
+#[ignore]
+fn stress_runner_long() {
+    let seed = std::env::var("STRESS_SEED")
+        .map(|seed| seed.parse().unwrap())
+        .unwrap_or(0);
+    stress::<5, 2>(seed, 100_000, 10_000).unwrap();
+    stress::<2, 4>(seed, 100_000, 10_000).unwrap();
+}
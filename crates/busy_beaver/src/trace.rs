@@ -0,0 +1,268 @@
+//! Compact records of runs
+//!
+//! Looking at how a machine got into a configuration means going back in its run. Storing the configuration of every step is out of the question for runs of millions of steps and even storing the state, symbol and position of every step takes a dozen bytes per step. A `Trace` stores only which transition every step applied, in as few bits as the number of transitions needs: 4 bits for 5 states and 2 symbols, so 10 million steps take 5 MB. Together with the machine the transition determines everything else. The symbol that is written, the direction and the next state are those of the transition, and the state and the symbol that the transition was selected by are what stepping backward needs to restore.
+//!
+//! A trace is recorded by passing it as the observer of a `Runner`. `Replay` moves through the recorded run in both directions.
+
+use crate::{
+    error::{Error, Result},
+    run::{Observer, Snapshot, SparseTape, Tape},
+    states::{DefinedTransition, Direction, State, States, Symbol, Transition},
+};
+
+/// The transitions applied by a run, packed into `BITS_PER_STEP` bits each.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Trace<const STATES: usize, const SYMBOLS: usize> {
+    words: Vec<u64>,
+    len: u64,
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> Trace<STATES, SYMBOLS> {
+    /// Bits that a step takes. The smallest power of two that fits every transition, so steps never straddle words.
+    pub const BITS_PER_STEP: u32 = {
+        let transitions = STATES * SYMBOLS;
+        let needed = if transitions <= 1 {
+            1
+        } else {
+            (transitions - 1).ilog2() + 1
+        };
+        needed.next_power_of_two()
+    };
+
+    const STEPS_PER_WORD: u64 = 64 / Self::BITS_PER_STEP as u64;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of recorded steps.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Memory used by the recorded steps.
+    pub fn bytes(&self) -> usize {
+        self.words.len() * 8
+    }
+
+    pub fn push(&mut self, state: State<STATES>, symbol: Symbol<SYMBOLS>) {
+        let transition = state.get() as u64 * SYMBOLS as u64 + symbol.get() as u64;
+        let (word, shift) = self.location(self.len);
+        if word == self.words.len() {
+            self.words.push(0);
+        }
+        self.words[word] |= transition << shift;
+        self.len += 1;
+    }
+
+    /// The state and the symbol that selected the transition of `step`, counting from 0.
+    pub fn get(&self, step: u64) -> Option<(State<STATES>, Symbol<SYMBOLS>)> {
+        if step >= self.len {
+            return None;
+        }
+        let (word, shift) = self.location(step);
+        let mask = (1u64 << Self::BITS_PER_STEP) - 1;
+        let transition = (self.words[word] >> shift) & mask;
+        let state = State::new((transition / SYMBOLS as u64) as u8)?;
+        let symbol = Symbol::new((transition % SYMBOLS as u64) as u8)?;
+        Some((state, symbol))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (State<STATES>, Symbol<SYMBOLS>)> + '_ {
+        (0..self.len).map(|step| self.get(step).unwrap())
+    }
+
+    fn location(&self, step: u64) -> (usize, u32) {
+        let word = (step / Self::STEPS_PER_WORD) as usize;
+        let shift = (step % Self::STEPS_PER_WORD) as u32 * Self::BITS_PER_STEP;
+        (word, shift)
+    }
+}
+
+impl<const STATES: usize, const SYMBOLS: usize> Observer<STATES, SYMBOLS>
+    for Trace<STATES, SYMBOLS>
+{
+    fn on_step(&mut self, state: State<STATES>, symbol: Symbol<SYMBOLS>, _: isize) {
+        self.push(state, symbol);
+    }
+
+    fn reset(&mut self) {
+        self.words.clear();
+        self.len = 0;
+    }
+}
+
+/// Configuration of a recorded run at any of its steps. Starts at step 0 with the blank tape.
+#[derive(Debug, Clone)]
+pub struct Replay<'a, const STATES: usize, const SYMBOLS: usize> {
+    states: States<STATES, SYMBOLS>,
+    trace: &'a Trace<STATES, SYMBOLS>,
+    step: u64,
+    state: State<STATES>,
+    tape: SparseTape,
+}
+
+impl<'a, const STATES: usize, const SYMBOLS: usize> Replay<'a, STATES, SYMBOLS> {
+    pub fn new(states: &States<STATES, SYMBOLS>, trace: &'a Trace<STATES, SYMBOLS>) -> Self {
+        Self {
+            states: *states,
+            trace,
+            step: 0,
+            state: State::new(0).unwrap(),
+            tape: SparseTape::unbounded(),
+        }
+    }
+
+    /// Number of steps from the start.
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    pub fn state(&self) -> State<STATES> {
+        self.state
+    }
+
+    /// Position of the head relative to the start cell.
+    pub fn position(&self) -> isize {
+        self.tape.position()
+    }
+
+    pub fn tape(&self) -> &SparseTape {
+        &self.tape
+    }
+
+    /// The configuration in the same form as `Runner::snapshot`. The visited cells include those visited by steps that were undone.
+    pub fn snapshot(&self) -> Snapshot {
+        let (left, right) = self.tape.extent();
+        Snapshot {
+            state: self.state.get(),
+            position: self.tape.position(),
+            left,
+            cells: (left..=right)
+                .map(|position| self.tape.cell(position))
+                .collect(),
+        }
+    }
+
+    /// Apply the next recorded step. Returns false at the end of the trace. Fails if the step does not fit the configuration, which means that the trace was not recorded from this machine.
+    pub fn forward(&mut self) -> Result<bool> {
+        let Some((state, symbol)) = self.trace.get(self.step) else {
+            return Ok(false);
+        };
+        if state != self.state || symbol.get() != self.tape.read() {
+            return Err(self.mismatch());
+        }
+        let transition = self.transition(state, symbol)?;
+        self.tape.write(transition.write.get());
+        self.tape
+            .move_by(offset(transition.move_))
+            .map_err(|_| Error::Limit("the head left the tape".into()))?;
+        self.state = transition.state;
+        self.step += 1;
+        Ok(true)
+    }
+
+    /// Undo the previous step. Returns false at step 0.
+    pub fn backward(&mut self) -> Result<bool> {
+        let Some(previous) = self.step.checked_sub(1) else {
+            return Ok(false);
+        };
+        let (state, symbol) = self.trace.get(previous).unwrap();
+        let transition = self.transition(state, symbol)?;
+        if transition.state != self.state {
+            return Err(self.mismatch());
+        }
+        self.tape
+            .move_by(-offset(transition.move_))
+            .map_err(|_| Error::Limit("the head left the tape".into()))?;
+        self.tape.write(symbol.get());
+        self.state = state;
+        self.step = previous;
+        Ok(true)
+    }
+
+    /// Step forward or backward until `step` is reached or the trace ends. Returns whether `step` was reached.
+    pub fn seek(&mut self, step: u64) -> Result<bool> {
+        while self.step > step {
+            self.backward()?;
+        }
+        while self.step < step {
+            if !self.forward()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn transition(
+        &self,
+        state: State<STATES>,
+        symbol: Symbol<SYMBOLS>,
+    ) -> Result<DefinedTransition<STATES, SYMBOLS>> {
+        match *self.states.get_transition(state, symbol) {
+            Transition::Continue(transition) => Ok(transition),
+            Transition::Halt => Err(self.mismatch()),
+        }
+    }
+
+    fn mismatch(&self) -> Error {
+        Error::Invariant(format!(
+            "step {} of the trace does not match the machine",
+            self.step
+        ))
+    }
+}
+
+fn offset(direction: Direction) -> isize {
+    match direction {
+        Direction::Left => -1,
+        Direction::Right => 1,
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    use crate::run::{Runner, StepResult};
+
+    const STEPS: u64 = 100_000;
+    let states = crate::fixtures::BB5;
+    let mut runner = Runner::with_observer(SparseTape::unbounded(), Trace::<5, 2>::new());
+    runner.set_states(&states);
+    let mut middle = None;
+    for step in 0..STEPS {
+        if step == STEPS / 2 {
+            middle = Some(runner.snapshot());
+        }
+        assert_eq!(runner.step(), StepResult::Ok);
+    }
+    let trace = runner.observer();
+    assert_eq!(Trace::<5, 2>::BITS_PER_STEP, 4);
+    assert_eq!(trace.len(), STEPS);
+    assert_eq!(trace.bytes() as u64, STEPS / 2);
+
+    let mut replay = Replay::new(&states, trace);
+    assert!(replay.seek(STEPS).unwrap());
+    assert!(!replay.forward().unwrap());
+    assert!(replay.snapshot().same_configuration(&runner.snapshot()));
+    assert!(replay.seek(STEPS / 2).unwrap());
+    assert!(replay.snapshot().same_configuration(&middle.unwrap()));
+    assert!(replay.seek(0).unwrap());
+    assert!(!replay.backward().unwrap());
+    assert_eq!(replay.tape().non_blank(), 0);
+    assert_eq!(replay.position(), 0);
+
+    // A trace of another machine does not fit.
+    let other = crate::fixtures::get("skelet 1").unwrap().machine();
+    let mut replay = Replay::new(&other, trace);
+    assert!(replay.seek(STEPS).is_err());
+
+    // Resetting the runner clears the trace.
+    runner.reset();
+    assert!(runner.observer().is_empty());
+    assert_eq!(Trace::<2, 2>::BITS_PER_STEP, 2);
+    assert_eq!(Trace::<3, 2>::BITS_PER_STEP, 4);
+}
@@ -0,0 +1,200 @@
+// Clustering of undecided machines by behavior.
+//
+// Every undecided machine in a log, seed database or other machine list is simulated for a bounded number of steps. The simulation produces a coarse behavior signature. Machines with the same signature form a cluster. The largest clusters tell decider authors which behavioral families dominate the holdout set.
+
+use std::{collections::HashMap, fmt::Display, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::{
+    format::{LogDecision, MachineList},
+    run::StepResult,
+};
+
+use crate::enumerate::{Runner, States};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Machines to cluster. Any format of `MachineList` is accepted: the log written by the seed run, a seed database or a text file of machines. Only undecided machines of a log are clustered.
+    #[arg(default_value = "log")]
+    input: PathBuf,
+    /// Index file selecting machines of the seed database given as input.
+    #[arg(long)]
+    index: Option<PathBuf>,
+    /// Number of steps every machine is simulated for.
+    #[arg(long, default_value_t = 10_000)]
+    steps: u32,
+    /// Stop after this many undecided machines have been read.
+    #[arg(long)]
+    limit: Option<u64>,
+    /// Number of clusters to print.
+    #[arg(long, default_value_t = 20)]
+    top: usize,
+}
+
+/// Machines are simulated in batches of this size. Each batch is split between all threads.
+const BATCH_LEN: usize = 1 << 16;
+
+/// Coarse description of how a machine behaves in its first steps.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum Signature {
+    /// The machine halted or ran out of tape. This should not happen for undecided machines unless the log was produced with different limits.
+    Stopped,
+    Running {
+        /// How fast the used tape grows, as the exponent `e` in `width ~ steps^e`, in quarters. Cyclers and counters are close to 0, bouncers close to 2 and translated cyclers close to 4.
+        growth: i8,
+        /// Final head position divided by the number of steps, in tenths.
+        drift: i8,
+        /// Share of steps spent in each state, in fifths.
+        states: [u8; 5],
+    },
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Signature::Running {
+            growth,
+            drift,
+            states,
+        } = self
+        else {
+            return write!(f, "stopped");
+        };
+        let growth = *growth as f32 / 4.;
+        let drift = *drift as f32 / 10.;
+        write!(f, "growth {growth:.2} drift {drift:+.1} states")?;
+        for (i, share) in states.iter().enumerate() {
+            let state = char::from(b'A' + i as u8);
+            write!(f, " {state}{}%", *share as u32 * 20)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Cluster {
+    count: u64,
+    example: States,
+}
+
+type Clusters = HashMap<Signature, Cluster>;
+
+pub fn main(args: Args) -> Result<()> {
+    if args.steps < 2 {
+        return Err(anyhow!("need at least 2 steps"));
+    }
+    let mut machines = match &args.index {
+        Some(index) => MachineList::open_indexed(&args.input, index)?,
+        None => MachineList::open(&args.input)?,
+    };
+    let thread_count = num_cpus::get();
+    let limit = args.limit.unwrap_or(u64::MAX);
+
+    let mut clusters = Clusters::new();
+    let mut batch = Vec::<States>::with_capacity(BATCH_LEN);
+    let mut undecided: u64 = 0;
+    loop {
+        let record = if undecided < limit {
+            machines.next().transpose().context("read machines")?
+        } else {
+            None
+        };
+        let at_end = record.is_none();
+        if let Some(record) = record {
+            if matches!(record.decision, None | Some(LogDecision::Undecided)) {
+                batch.push(record.states);
+                undecided += 1;
+            }
+        }
+        if batch.len() == BATCH_LEN || (at_end && !batch.is_empty()) {
+            let chunk_len = batch.len().div_ceil(thread_count);
+            let results: Vec<Clusters> = std::thread::scope(|scope| {
+                let threads: Vec<_> = batch
+                    .chunks(chunk_len)
+                    .map(|chunk| scope.spawn(|| cluster_machines(chunk, args.steps)))
+                    .collect();
+                threads.into_iter().map(|t| t.join().unwrap()).collect()
+            });
+            for result in results {
+                merge(&mut clusters, result);
+            }
+            batch.clear();
+            println!("Clustered {undecided} undecided machines.");
+        }
+        if at_end {
+            break;
+        }
+    }
+
+    let mut clusters: Vec<(Signature, Cluster)> = clusters.into_iter().collect();
+    clusters.sort_unstable_by_key(|(_, cluster)| std::cmp::Reverse(cluster.count));
+    println!(
+        "{} clusters. Largest {}:",
+        clusters.len(),
+        args.top.min(clusters.len())
+    );
+    for (signature, cluster) in clusters.iter().take(args.top) {
+        let share = cluster.count as f64 / undecided as f64 * 100.;
+        println!(
+            "{:>10} {share:>5.1}% {} {signature}",
+            cluster.count, cluster.example
+        );
+    }
+    Ok(())
+}
+
+fn cluster_machines(machines: &[States], steps: u32) -> Clusters {
+    // The tape is large enough that the head cannot leave it in `steps` steps.
+    let mut runner = Runner::vector_backed(2 * steps as usize + 3);
+    let mut clusters = Clusters::new();
+    for states in machines {
+        let signature = signature(&mut runner, states, steps);
+        clusters
+            .entry(signature)
+            .or_insert(Cluster {
+                count: 0,
+                example: *states,
+            })
+            .count += 1;
+    }
+    clusters
+}
+
+fn merge(clusters: &mut Clusters, other: Clusters) {
+    for (signature, cluster) in other {
+        clusters
+            .entry(signature)
+            .and_modify(|c| c.count += cluster.count)
+            .or_insert(cluster);
+    }
+}
+
+fn signature(runner: &mut Runner, states: &States, steps: u32) -> Signature {
+    runner.set_states(states);
+    runner.reset();
+    let mut histogram = [0u32; 5];
+    let (mut left, mut right) = (0isize, 0isize);
+    let mut half_width = 0;
+    for step in 0..steps {
+        if step == steps / 2 {
+            half_width = right - left + 1;
+        }
+        histogram[runner.state().get() as usize] += 1;
+        match runner.step() {
+            StepResult::Ok => (),
+            StepResult::Halt | StepResult::TapeFullLeft | StepResult::TapeFullRight => {
+                return Signature::Stopped
+            }
+        }
+        let position = runner.position();
+        left = left.min(position);
+        right = right.max(position);
+    }
+    let width = right - left + 1;
+    let growth = (width as f64 / half_width as f64).log2() * 4.;
+    let drift = runner.position() as f64 / steps as f64 * 10.;
+    Signature::Running {
+        growth: growth.round() as i8,
+        drift: drift.round() as i8,
+        states: histogram.map(|count| (count as f64 / steps as f64 * 5.).round() as u8),
+    }
+}
@@ -0,0 +1,168 @@
+// Checking a log against the seed database.
+//
+// A run reproduces the seed run if the machines it leaves undecided are exactly the machines of the seed database. `symdiff` finds the differences between any two lists but sorts both first. The official database is already sorted, so here it is mapped and every log entry is looked up with a binary search, which takes minutes instead of hours.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::format::{open_log, LogDecision, LogReader};
+
+use crate::{enumerate::States, mapped::MappedDatabase};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Uncompressed and sorted seed database.
+    database: PathBuf,
+    /// Log or log manifest to check.
+    #[arg(default_value = "log")]
+    log: PathBuf,
+    /// Number of threads. Defaults to all cores.
+    #[arg(long)]
+    threads: Option<usize>,
+}
+
+/// Number of log entries that are looked up together.
+const BATCH_LEN: usize = 1 << 20;
+
+/// Number of mismatches that are printed.
+const PRINTED_MISMATCHES: usize = 10;
+
+pub fn main(args: Args) -> Result<()> {
+    let database = MappedDatabase::open(&args.database)?;
+    let mut log = LogReader::new(open_log(&args.log)?);
+    let thread_count = args.threads.unwrap_or_else(num_cpus::get).max(1);
+    let mut batch = Vec::<(u64, States, bool)>::with_capacity(BATCH_LEN);
+    let (mut undecided, mut missing, mut unexpected) = (0u64, 0u64, 0u64);
+    loop {
+        batch.clear();
+        for record in log.by_ref().take(BATCH_LEN) {
+            let record = record.context("read log")?;
+            let is_undecided = record.decision == LogDecision::Undecided;
+            undecided += is_undecided as u64;
+            batch.push((record.line, record.states, is_undecided));
+        }
+        if batch.is_empty() {
+            break;
+        }
+        for (line, states, is_undecided) in mismatches(&database, &batch, thread_count)? {
+            if missing + unexpected < PRINTED_MISMATCHES as u64 {
+                let problem = if is_undecided {
+                    "is undecided but not in the database"
+                } else {
+                    "is decided but in the database"
+                };
+                println!("Line {line}: {states} {problem}.");
+            }
+            if is_undecided {
+                missing += 1;
+            } else {
+                unexpected += 1;
+            }
+        }
+    }
+    println!(
+        "{} log entries, {undecided} undecided, {} machines in the database.",
+        log.line(),
+        database.len()
+    );
+    // Every undecided machine was found in the database, so equal counts mean equal sets unless the log has duplicates.
+    let absent = database.len() as u64 - (undecided - missing).min(database.len() as u64);
+    if missing != 0 || unexpected != 0 || absent != 0 {
+        return Err(anyhow!("{missing} undecided machines are not in the database, {unexpected} decided machines are in the database and {absent} machines of the database are not undecided in the log"));
+    }
+    println!("The undecided machines of the log are exactly the machines of the database.");
+    Ok(())
+}
+
+/// Entries of `batch` whose presence in the database does not match whether they are undecided.
+fn mismatches(
+    database: &MappedDatabase,
+    batch: &[(u64, States, bool)],
+    thread_count: usize,
+) -> Result<Vec<(u64, States, bool)>> {
+    let chunk_len = batch.len().div_ceil(thread_count).max(1);
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = batch
+            .chunks(chunk_len)
+            .map(|chunk| {
+                scope.spawn(move || -> Result<Vec<_>> {
+                    let mut mismatches = Vec::new();
+                    for entry in chunk {
+                        let (_, states, is_undecided) = entry;
+                        if database.find(states)?.is_some() != *is_undecided {
+                            mismatches.push(*entry);
+                        }
+                    }
+                    Ok(mismatches)
+                })
+            })
+            .collect();
+        let mut mismatches = Vec::new();
+        for thread in threads {
+            mismatches.extend(thread.join().unwrap()?);
+        }
+        Ok(mismatches)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapped::{test_sections, write_test_database};
+
+    #[test]
+    fn logs_are_compared() {
+        let (time_limit, space_limit, decided) = test_sections(20);
+        let directory = std::env::temp_dir().join(format!("seed-compare-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let args = Args {
+            database: directory.join("database"),
+            log: directory.join("log"),
+            threads: Some(2),
+        };
+        write_test_database(&args.database, &time_limit, &space_limit, true);
+        let undecided: Vec<States> = space_limit.iter().chain(&time_limit).copied().collect();
+        let check = |undecided: &[States], decided: &[States]| {
+            let log: String = undecided
+                .iter()
+                .map(|states| format!("{states} u\n"))
+                .chain(decided.iter().map(|states| format!("{states} h\n")))
+                .collect();
+            std::fs::write(&args.log, log).unwrap();
+            main(Args {
+                database: args.database.clone(),
+                log: args.log.clone(),
+                threads: args.threads,
+            })
+        };
+        check(&undecided, &decided).unwrap();
+        // An undecided machine that is not in the database.
+        check(&[&undecided[..], &decided[..1]].concat(), &decided[1..]).unwrap_err();
+        // A decided machine that is in the database.
+        check(&undecided[1..], &[&decided[..], &undecided[..1]].concat()).unwrap_err();
+        // A machine of the database that is not in the log.
+        check(&undecided[1..], &decided).unwrap_err();
+        // Lookups in batches of any size find the same mismatches.
+        let database = MappedDatabase::open(&args.database).unwrap();
+        let batch: Vec<(u64, States, bool)> = undecided
+            .iter()
+            .chain(&decided)
+            .enumerate()
+            .map(|(line, states)| (line as u64, *states, line.is_multiple_of(2)))
+            .collect();
+        let expected: Vec<u64> = batch
+            .iter()
+            .filter(|(line, _, is_undecided)| (*line < undecided.len() as u64) != *is_undecided)
+            .map(|(line, ..)| *line)
+            .collect();
+        for thread_count in [1, 3, 64] {
+            let lines: Vec<u64> = mismatches(&database, &batch, thread_count)
+                .unwrap()
+                .iter()
+                .map(|(line, ..)| *line)
+                .collect();
+            assert_eq!(lines, expected);
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
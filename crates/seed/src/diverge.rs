@@ -0,0 +1,65 @@
+// Finding the first difference between two logs.
+//
+// Changes that are meant to only improve performance must not change the log. Comparing the log of the old and the new binary checks this. When they differ, the first differing entry is the best starting point for debugging.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use busy_beaver::format::{open_log, read_log_entry, LogReader, LOG_ENTRY_LEN};
+
+#[derive(clap::Args)]
+pub struct Args {
+    a: PathBuf,
+    b: PathBuf,
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let open = |path: &PathBuf| -> Result<_> { Ok(LogReader::new(open_log(path)?)) };
+    let mut a = open(&args.a)?;
+    let mut b = open(&args.b)?;
+    loop {
+        // Entries are compared as bytes so that unparsable entries are reported instead of aborting the comparison.
+        let entry_a = a.next_raw().context("read first log")?.copied();
+        let entry_b = b.next_raw().context("read second log")?.copied();
+        match (entry_a, entry_b) {
+            (None, None) => {
+                println!("The logs are identical. Both have {} entries.", a.line());
+                return Ok(());
+            }
+            (Some(entry_a), Some(entry_b)) if entry_a == entry_b => continue,
+            _ => (),
+        }
+        println!("The logs diverge at line {}.", a.line().max(b.line()));
+        for (path, entry) in [(&args.a, &entry_a), (&args.b, &entry_b)] {
+            print!("{}: ", path.display());
+            match entry {
+                Some(entry) => describe(entry),
+                None => println!("end of log"),
+            }
+        }
+        if let (Some(entry_a), Some(entry_b)) = (entry_a, entry_b) {
+            describe_difference(&entry_a, &entry_b);
+        }
+        return Ok(());
+    }
+}
+
+fn describe(entry: &[u8; LOG_ENTRY_LEN]) {
+    match read_log_entry(entry) {
+        Ok((states, decision)) => println!("{states} {}", decision.name()),
+        Err(err) => println!(
+            "unparsable entry {:?} ({err:#})",
+            String::from_utf8_lossy(entry).trim_end()
+        ),
+    }
+}
+
+fn describe_difference(a: &[u8; LOG_ENTRY_LEN], b: &[u8; LOG_ENTRY_LEN]) {
+    let (Ok((a, _)), Ok((b, _))) = (read_log_entry(a), read_log_entry(b)) else {
+        return;
+    };
+    for (state, symbol, a, b) in a.diff(&b) {
+        let state = char::from(b'A' + state.get());
+        println!("Transition {state}{} differs: {a} != {b}", symbol.get());
+    }
+}
@@ -1,8 +1,21 @@
 // This module defines the structure of enumerating turing machines in tree normal form in order to find BB(5). This structure can be used in several ways. One use is the optimized multi threaded version in `main.rs`. Another use is the tests in this module.
 
-use std::hint::unreachable_unchecked;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use busy_beaver::{run::StepResult, states::Direction};
+use anyhow::{anyhow, Result};
+use busy_beaver::{
+    decider::cyclers::{self, CyclersDecider},
+    format::LogDecision,
+    run::{DenseTape, Snapshot, StepResult},
+    seed::is_irrelevant,
+    segment::{self, SegmentSimulator},
+    states::{DefinedTransitions, Direction},
+};
 use serde::{Deserialize, Serialize};
 
 // The module could be generic over all kinds of turing machines but for now we only care about 5 symbols, 2 states.
@@ -12,26 +25,85 @@ pub type State = busy_beaver::states::State<5>;
 pub type Symbol = busy_beaver::states::Symbol<2>;
 pub type Transition = busy_beaver::states::Transition<5, 2>;
 pub type DefinedTransition = busy_beaver::states::DefinedTransition<5, 2>;
-pub type Runner = busy_beaver::run::Runner<5, 2, Vec<u8>>;
+pub type Runner = busy_beaver::run::Runner<5, 2, DenseTape<Vec<u8>>>;
 
 // The enumeration process builds a tree of turing machines. Every enumerated machines belongs into exactly one of the following categories.
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Decision {
-    /// The machine halts.
-    Halt(HaltingTransitionIndex),
+    /// The machine halts with this transition after this many steps including the halting step.
+    Halt(HaltingTransitionIndex, u64),
     /// The machine runs forever.
-    Loop,
+    Loop(LoopReason),
     /// The machine could not be decided.
-    Undecided,
+    Undecided(UndecidedReason),
     /// The machine is irrelevant for finding BB(5).
     Irrelevant,
 }
 
+/// How a machine was found to run forever.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum LoopReason {
+    /// The machine ran for more steps than `Limits::bb4_steps` without visiting every state.
+    UnvisitedState,
+    /// A check of the `Schedule`.
+    Check,
+}
+
+/// Which limit a machine reached when it could not be decided. Machines that need more steps and machines that need more space call for different deciders.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum UndecidedReason {
+    /// The machine ran for `Limits::steps` steps.
+    Steps,
+    /// The head would have left the tape on the left.
+    SpaceLeft,
+    /// The head would have left the tape on the right.
+    SpaceRight,
+}
+
+impl Decision {
+    /// The category as it is written to the log. Its `code` and `name` are the stable tags of the decision.
+    pub fn log_decision(self) -> LogDecision {
+        match self {
+            Decision::Halt(..) => LogDecision::Halt,
+            Decision::Loop(_) => LogDecision::Loop,
+            Decision::Undecided(_) => LogDecision::Undecided,
+            Decision::Irrelevant => LogDecision::Irrelevant,
+        }
+    }
+}
+
+impl LoopReason {
+    pub const ALL: [LoopReason; 2] = [LoopReason::UnvisitedState, LoopReason::Check];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LoopReason::UnvisitedState => "unvisited state",
+            LoopReason::Check => "check",
+        }
+    }
+}
+
+impl UndecidedReason {
+    pub const ALL: [UndecidedReason; 3] = [
+        UndecidedReason::Steps,
+        UndecidedReason::SpaceLeft,
+        UndecidedReason::SpaceRight,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            UndecidedReason::Steps => "steps",
+            UndecidedReason::SpaceLeft => "left space",
+            UndecidedReason::SpaceRight => "right space",
+        }
+    }
+}
+
 // Each node in the tree that is built by the enumeration process is a turing machine description (an assignment of states).
 
 /// Invariants: The first transition is 1RB. There is at least one halting transition.
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Node(pub States);
 
 // The root of the tree is the machine with all halting transitions and 1RB as the first transition.
@@ -50,7 +122,7 @@ impl Node {
 
 // When running the root node, we see that it encounters a halting transition in the second step. We are going to replace this transition with all possible choices non halting transitions (also called defined transitions). This creates new machines. They are the child nodes of the current node. Child nodes are enumerated in the same fashion until the whole tree is explored.
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct HaltingTransitionIndex(pub State, pub Symbol);
 
 impl HaltingTransitionIndex {
@@ -60,44 +132,112 @@ impl HaltingTransitionIndex {
 }
 
 fn assert_invariants(node: &Node, branch: HaltingTransitionIndex) {
-    assert_eq!(
-        node.0 .0[0][0],
-        Transition::Continue(DefinedTransition {
+    if let Err(err) = validate_node(node, branch) {
+        panic!("{err}");
+    }
+}
+
+/// An invariant of the enumeration that a node and its branch break.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InvariantViolation {
+    /// A state or symbol is not a valid state or symbol of a 5 state, 2 symbol machine.
+    OutOfRange,
+    /// The first transition is this instead of 1RB.
+    FirstTransition(Transition),
+    /// The branch is a defined transition instead of a halting one.
+    BranchNotHalting(HaltingTransitionIndex),
+    /// The node has this many halting transitions instead of between 2 and 9.
+    HaltingTransitionCount(u8),
+    /// The largest state with a defined transition is out of range.
+    LargestPartiallyDefinedState(State),
+}
+
+impl Display for InvariantViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange => write!(f, "state or symbol out of range"),
+            Self::FirstTransition(t) => write!(f, "first transition is {t:?} instead of 1RB"),
+            Self::BranchNotHalting(branch) => {
+                write!(f, "branch {branch:?} is not a halting transition")
+            }
+            Self::HaltingTransitionCount(count) => {
+                write!(f, "halting transition count {count} is not between 2 and 9")
+            }
+            Self::LargestPartiallyDefinedState(state) => write!(
+                f,
+                "largest partially defined state {} is out of range",
+                state.get()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+/// Check the invariants that the enumeration relies on. Debug builds check every node. Nodes that come from outside of the enumeration, like the tasks of the resume file, a work unit or a shard plan, could be corrupt and must be validated before they are used.
+pub fn validate_node(
+    node: &Node,
+    branch: HaltingTransitionIndex,
+) -> std::result::Result<(), InvariantViolation> {
+    // Deserialization does not uphold the invariants of `State` and `Symbol`. They need to be checked before the unchecked indexing in the other checks.
+    let in_range = |state: State, symbol: Symbol| (state.get() < 5) & (symbol.get() < 2);
+    let transitions_in_range = node.0 .0.iter().flatten().all(|t| match t {
+        Transition::Halt => true,
+        Transition::Continue(t) => in_range(t.state, t.write),
+    });
+    if !transitions_in_range || !in_range(branch.0, branch.1) {
+        return Err(InvariantViolation::OutOfRange);
+    }
+    let first = node.0 .0[0][0];
+    if first
+        != Transition::Continue(DefinedTransition {
             write: Symbol::new(1).unwrap(),
             move_: Direction::Right,
             state: State::new(1).unwrap(),
         })
-    );
-    assert_eq!(*node.0.get_transition(branch.0, branch.1), Transition::Halt);
-    assert!((2..=9).contains(&node.halting_transition_count()));
-    assert!((0..=5).contains(&node.largest_partially_defined_state().get()));
+    {
+        return Err(InvariantViolation::FirstTransition(first));
+    }
+    if *node.0.get_transition(branch.0, branch.1) != Transition::Halt {
+        return Err(InvariantViolation::BranchNotHalting(branch));
+    }
+    let count = node.halting_transition_count();
+    if !(2..=9).contains(&count) {
+        return Err(InvariantViolation::HaltingTransitionCount(count));
+    }
+    let largest = node.largest_partially_defined_state();
+    if !(0..=5).contains(&largest.get()) {
+        return Err(InvariantViolation::LargestPartiallyDefinedState(largest));
+    }
+    Ok(())
 }
 
 // The enumeration can be expressed as a recursive function as seen below. Here we use `trace` as a callback for every enumerated machine. `trace` can also inform the recursion to stop early, which is useful for testing.
 //
 // Machines that do not halt are leaf nodes. They do not have child nodes. The recursion ends with them. The remaining halting transitions do not need to be explored because they are unreachable.
 //
-// This function enumerates the machines in the same order as the seed run when go-routines are disabled. This is useful for testing.
+// This function enumerates the machines in the same order as the seed run when go-routines are disabled and the limits are the default ones. This is useful for testing and for enumerating small parts of the tree like the tasks of a work unit. The schedule must be the one of the run whose part is enumerated because its checks decide which machines have children.
 
-#[allow(dead_code)]
 #[inline(always)]
-fn enumerate_recursively(
+pub fn enumerate_recursively(
     mut node: Node,
     branch: HaltingTransitionIndex,
     runner: &mut Runner,
+    limits: &Limits,
+    schedule: &Schedule,
     trace: &mut impl FnMut(&States, Decision) -> bool,
 ) -> bool {
     for transition in ChildNodes::new(&node, branch) {
         *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
-        let decision = decide(runner, &node.0, branch);
+        let decision = decide(runner, &node.0, branch, limits, schedule);
         if trace(&node.0, decision) {
             crate::cold();
             return true;
         }
-        if let Decision::Halt(branch) = decision {
+        if let Decision::Halt(branch, _) = decision {
             // There is no point in continuing with 1 halting transition. In the next step it would be turned into a non halting transition, which would leave the machine with no halting transition.
             if node.halting_transition_count() >= 2 {
-                let stop = enumerate_recursively(node, branch, runner, trace);
+                let stop = enumerate_recursively(node, branch, runner, limits, schedule, trace);
                 if stop {
                     return true;
                 }
@@ -128,12 +268,18 @@ fn enumerate_iteratively(
             continue;
         };
         *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
-        let decision = decide(runner, &node.0, *branch);
+        let decision = decide(
+            runner,
+            &node.0,
+            *branch,
+            &Limits::default(),
+            &Schedule::default(),
+        );
         if trace(&node.0, decision) {
             crate::cold();
             return;
         }
-        if let Decision::Halt(branch) = decision {
+        if let Decision::Halt(branch, _) = decision {
             if node.halting_transition_count() >= 2 {
                 let element = (ChildNodes::new(&node, branch), branch);
                 unsafe { stack.push_unchecked(element) };
@@ -142,6 +288,74 @@ fn enumerate_iteratively(
     }
 }
 
+// Counting the machines in a subtree takes the same decisions as enumerating it, but nothing is logged. Progress estimates, shard balancing and ordinals count the subtrees of the same shallow nodes again and again, so their counts are kept.
+
+/// Nodes with at most this many defined transitions keep the counts of their subtrees. There are few of them and their subtrees are the expensive ones.
+const CACHED_DEFINED_TRANSITIONS: u8 = 4;
+
+/// Counts machines in subtrees of the enumeration with the given limits.
+pub struct SubtreeCounter {
+    limits: Limits,
+    runner: Runner,
+    cache: HashMap<(Node, HaltingTransitionIndex), u64>,
+}
+
+impl SubtreeCounter {
+    pub fn new(limits: &Limits) -> Self {
+        Self {
+            limits: *limits,
+            runner: create_runner(limits),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Number of machines that are enumerated by defining `branch` of `node`, including the machines below them.
+    pub fn count(&mut self, node: &Node, branch: HaltingTransitionIndex) -> u64 {
+        let cached = 10 - node.halting_transition_count() <= CACHED_DEFINED_TRANSITIONS;
+        if cached {
+            if let Some(count) = self.cache.get(&(*node, branch)) {
+                return *count;
+            }
+        }
+        let mut child = *node;
+        let mut count = 0;
+        for transition in ChildNodes::new(node, branch) {
+            *child.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+            count += 1;
+            if let Some(next) = self.expand(&child, branch) {
+                count += self.count(&child, next);
+            }
+        }
+        if cached {
+            self.cache.insert((*node, branch), count);
+        }
+        count
+    }
+
+    /// The branch of `node` below which the enumeration continues, or `None` if `node` is a leaf. `changed` is the transition that was defined last.
+    pub fn expand(
+        &mut self,
+        node: &Node,
+        changed: HaltingTransitionIndex,
+    ) -> Option<HaltingTransitionIndex> {
+        match decide(
+            &mut self.runner,
+            &node.0,
+            changed,
+            &self.limits,
+            &Schedule::default(),
+        ) {
+            Decision::Halt(branch, _) if node.halting_transition_count() >= 2 => Some(branch),
+            _ => None,
+        }
+    }
+}
+
+/// Number of machines that are enumerated by defining `branch` of `node`, including the machines below them. Use a `SubtreeCounter` to keep the counts of shallow nodes between calls.
+pub fn count_subtree(node: &Node, branch: HaltingTransitionIndex, limits: &Limits) -> u64 {
+    SubtreeCounter::new(limits).count(node, branch)
+}
+
 // There are some things we commonly want to know about the current node.
 
 impl Node {
@@ -156,6 +370,11 @@ impl Node {
             .fold(0, |acc, t| acc + (*t == Transition::Halt) as u8)
     }
 
+    /// Number of transitions that were defined below the root to arrive at this node. The root has depth 0 and 9 halting transitions.
+    pub fn depth(&self) -> u32 {
+        9 - self.halting_transition_count() as u32
+    }
+
     #[inline(always)]
     pub fn largest_partially_defined_state(&self) -> State {
         let result = self
@@ -177,6 +396,8 @@ pub fn decide(
     runner: &mut Runner,
     states: &States,
     changed_transition: HaltingTransitionIndex,
+    limits: &Limits,
+    schedule: &Schedule,
 ) -> Decision {
     if is_irrelevant(states, changed_transition.0, changed_transition.1) {
         crate::cold();
@@ -184,128 +405,384 @@ pub fn decide(
     }
     runner.set_states(states);
     runner.reset();
-    run(runner)
+    match run(runner, states, limits, schedule, 0, 0, None) {
+        Ok(decision) => decision,
+        Err(_) => unreachable!("a run without abort flag cannot pause"),
+    }
 }
 
-// A machine is irrelevant when it does not needed to be ran in order to find BB(5).
+// Machines that run close to the step limit take seconds. When a run is aborted while they are simulated, they can be paused instead of simulating them again from the start after resuming.
 
-/// `changed_state` is the state which was modified in `states` to arrive at this machine. Knowing it allows us to be more efficient and not repeat checks that were already done before.
-#[inline(always)]
-fn is_irrelevant(states: &States, changed_state: State, read: Symbol) -> bool {
-    has_equivalent_states(states, changed_state)
-        || has_redundant_transition(states, changed_state, read)
-}
+/// Steps between the checks whether a pausable simulation is aborted. Machines that halt or are decided earlier are never paused.
+pub const PAUSE_INTERVAL: u64 = 1 << 20;
 
-#[inline(always)]
-fn has_equivalent_states(states: &States, changed_state: State) -> bool {
-    (0u8..5).any(|i| {
-        i != changed_state.get()
-            && are_states_defined_and_equivalent(
-                states,
-                unsafe { State::new_unchecked(i) },
-                changed_state,
-            )
-    })
+/// A simulation that was paused. `decide_pausable` continues it where it stopped.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Paused {
+    pub step: u64,
+    /// Bit set of the states the machine has been in.
+    pub state_seen: u8,
+    pub snapshot: Snapshot,
 }
 
-#[inline(always)]
-fn are_states_defined_and_equivalent(states: &States, a: State, b: State) -> bool {
-    let a_ = states.get_state(a);
-    let b_ = states.get_state(b);
-    let (
-        [Transition::Continue(a0), Transition::Continue(a1)],
-        [Transition::Continue(b0), Transition::Continue(b1)],
-    ) = (a_, b_)
-    else {
-        return false;
+/// Like `decide` but the simulation pauses when `abort` is set. The result is the same as that of `decide`, also when the simulation is continued from `from`, which must be a pause of the same machine with the same limits and schedule. A pause that does not fit on the tape is simulated again from the start.
+pub fn decide_pausable(
+    runner: &mut Runner,
+    states: &States,
+    changed_transition: HaltingTransitionIndex,
+    limits: &Limits,
+    schedule: &Schedule,
+    from: Option<&Paused>,
+    abort: &AtomicBool,
+) -> Result<Decision, Paused> {
+    if is_irrelevant(states, changed_transition.0, changed_transition.1) {
+        crate::cold();
+        return Ok(Decision::Irrelevant);
+    }
+    runner.set_states(states);
+    let (step, state_seen) = match from {
+        Some(paused) if runner.restore(&paused.snapshot).is_ok() => {
+            (paused.step, paused.state_seen)
+        }
+        _ => {
+            runner.reset();
+            (0, 0)
+        }
     };
-    (a0.write == b0.write)
-        & (a0.move_ == b0.move_)
-        & (a1.write == b1.write)
-        & (a1.move_ == b1.move_)
-        & ((a0.state == b0.state)
-            | (((a0.state == a) | (a0.state == b)) & ((b0.state == b) | (b0.state == a))))
-        & ((a1.state == b1.state)
-            | (((a1.state == a) | (a1.state == b)) & ((b1.state == b) | (b1.state == a))))
+    run(
+        runner,
+        states,
+        limits,
+        schedule,
+        step,
+        state_seen,
+        Some(abort),
+    )
+    .map_err(|(step, state_seen)| Paused {
+        step,
+        state_seen,
+        snapshot: runner.snapshot(),
+    })
 }
 
-#[inline(always)]
-fn has_redundant_transition(states: &States, changed_state: State, read: Symbol) -> bool {
-    let Transition::Continue(t) = states.get_transition(changed_state, read) else {
-        debug_assert!(false, "unreachable");
-        unsafe { unreachable_unchecked() };
-    };
-    let [Transition::Continue(n0), Transition::Continue(n1)] = states.get_state(t.state) else {
-        return false;
-    };
-    let copies = (n0.write.get() == 0) & (n1.write.get() == 1);
-    let moves_back = (n0.move_ != t.move_) & (n1.move_ != t.move_);
-    let states_back = n0.state == n1.state;
-    copies & moves_back & states_back
+/// Like `decide` but for machines that do not come from the enumeration. There is no changed transition so the irrelevance checks are skipped.
+pub fn decide_standalone(
+    runner: &mut Runner,
+    states: &States,
+    limits: &Limits,
+    schedule: &Schedule,
+) -> Decision {
+    runner.set_states(states);
+    runner.reset();
+    match run(runner, states, limits, schedule, 0, 0, None) {
+        Ok(decision) => decision,
+        Err(_) => unreachable!("a run without abort flag cannot pause"),
+    }
 }
 
+// A machine is irrelevant when it does not needed to be ran in order to find BB(5). The checks are in `busy_beaver::seed` so that other tools can decide machines like the seed run.
+
 // When running a turing machine, we need to stop eventually in case it runs forever. These limits are given by the following constants. If they are reached, the machine is undecided.
 
-const LIMIT_STEPS: u32 = 47176870;
-const LIMIT_MEMORY: isize = 12289;
-const TAPE_SIZE: usize = LIMIT_MEMORY as usize * 2;
+const LIMIT_STEPS: u64 = busy_beaver::seed::LIMIT_STEPS;
+const LIMIT_MEMORY: isize = busy_beaver::seed::LIMIT_MEMORY as isize;
 
 // While running we can detect some cases of never halting through the known limits of BB(4).
 
-const BB4_STEPS: u32 = 107;
+const BB4_STEPS: u64 = busy_beaver::seed::BB4_STEPS;
 #[allow(dead_code)]
 const BB4_SPACE: isize = 16;
 
-pub fn create_runner() -> Runner {
-    Runner::vector_backed(TAPE_SIZE)
+/// The limits used when running machines. The default values are the constants above, which reproduce the seed run. Other values are useful for experiments with tighter or looser bounds and for other BB targets.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Machines that do not halt within this many steps are undecided.
+    pub steps: u64,
+    /// Space in cells in either direction of the start cell. Machines that use more are undecided.
+    pub memory: usize,
+    /// Machines that run for more than this many steps without having visited every state do not halt. The default is the step count of the BB(4) champion.
+    pub bb4_steps: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            steps: LIMIT_STEPS,
+            memory: LIMIT_MEMORY as usize,
+            bb4_steps: BB4_STEPS,
+        }
+    }
+}
+
+impl Limits {
+    /// Only valid limits have a tape size, see `validate`.
+    pub fn tape_size(&self) -> usize {
+        self.memory * 2
+    }
+
+    /// Limits from outside of the program, like those of a work unit or a shard plan, could be 0 or too large for a tape, which the runner does not support.
+    pub fn validate(&self) -> Result<()> {
+        if self.steps == 0 {
+            return Err(anyhow!("the step limit must be positive"));
+        }
+        if self.memory == 0 {
+            return Err(anyhow!("the memory limit must be positive"));
+        }
+        // The tape addresses its cells with `isize`.
+        if self
+            .memory
+            .checked_mul(2)
+            .is_none_or(|size| size > isize::MAX as usize)
+        {
+            return Err(anyhow!("the memory limit {} is too large", self.memory));
+        }
+        Ok(())
+    }
+}
+
+pub fn create_runner(limits: &Limits) -> Runner {
+    Runner::vector_backed(limits.tape_size())
+}
+
+// Most machines that reach the step limit could be decided much earlier by a cheap check. Instead of running them to the limit right away, they are run in phases with growing step budgets and the checks are applied between the phases.
+
+/// A check that is applied between the phases of a `Schedule`. It sees the runner after the machine has used up the budget of the phase and can decide the machine early.
+pub type Check = fn(&Runner, &States) -> Option<Decision>;
+
+/// Cells of the window of the segment check. Larger windows decide more machines but the simulation grows with the window.
+const SEGMENT_WINDOW: usize = 4;
+
+/// A decider that a `Schedule` applies between its phases. Machines it decides run forever and are logged as loops with `LoopReason::Check`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleDecider {
+    /// The cyclers decider of `busy_beaver::decider::cyclers` with bbchallenge's parameters.
+    Cyclers,
+    /// The abstract simulation of `busy_beaver::segment` on a window of 4 cells with every start position.
+    Segment,
+}
+
+impl ScheduleDecider {
+    pub const ALL: [ScheduleDecider; 2] = [ScheduleDecider::Cyclers, ScheduleDecider::Segment];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScheduleDecider::Cyclers => "cyclers",
+            ScheduleDecider::Segment => "segment",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|decider| decider.name() == name)
+    }
+
+    fn check(self) -> Check {
+        match self {
+            ScheduleDecider::Cyclers => cyclers_check,
+            ScheduleDecider::Segment => segment_check,
+        }
+    }
+}
+
+impl Display for ScheduleDecider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// The deciders start from the blank tape and ignore the runner. They only see machines that are still running after a budget, so their cost matters much less than that of a step. Every thread keeps its own decider to reuse the allocations.
+
+fn cyclers_check(_: &Runner, states: &States) -> Option<Decision> {
+    thread_local! {
+        static DECIDER: RefCell<CyclersDecider<5, 2>> =
+            RefCell::new(CyclersDecider::new(cyclers::Config::default()));
+    }
+    DECIDER.with_borrow_mut(|decider| match decider.run(states) {
+        cyclers::Outcome::Cycles { .. } => Some(Decision::Loop(LoopReason::Check)),
+        cyclers::Outcome::Halts { .. } | cyclers::Outcome::MayHalt => None,
+    })
+}
+
+fn segment_check(_: &Runner, states: &States) -> Option<Decision> {
+    thread_local! {
+        static SIMULATOR: RefCell<SegmentSimulator<5, 2>> =
+            RefCell::new(SegmentSimulator::new(segment::Config {
+                window: SEGMENT_WINDOW,
+                position: segment::Position::Every,
+            }));
+    }
+    SIMULATOR.with_borrow_mut(|simulator| match simulator.run(states) {
+        segment::Outcome::NeverHalts { .. } => Some(Decision::Loop(LoopReason::Check)),
+        segment::Outcome::MayHalt => None,
+    })
+}
+
+/// Step budgets for running a machine.
+///
+/// A machine is first run until the first budget is used up. Then the checks are applied. If none of them decides the machine, it continues running until the next budget and so on. Running continues where the previous phase stopped. The step limit of `Limits` ends the last phase, so without checks the schedule does not change any decision. The default schedule has a single phase.
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    /// Strictly ascending. Budgets at or above the step limit are ignored.
+    budgets: Vec<u64>,
+    checks: Vec<Check>,
+    /// The deciders among `checks`, in the order they were added.
+    deciders: Vec<ScheduleDecider>,
+}
+
+impl Schedule {
+    /// `budgets` are the step counts after which the checks are applied. They must be strictly ascending and below the step limit.
+    pub fn new(budgets: &[u64], limits: &Limits) -> Result<Self> {
+        if !budgets.windows(2).all(|w| w[0] < w[1]) {
+            return Err(anyhow!("budgets are not strictly ascending"));
+        }
+        if budgets.last().is_some_and(|last| *last >= limits.steps) {
+            return Err(anyhow!(
+                "budgets must be below the step limit {}",
+                limits.steps
+            ));
+        }
+        Ok(Self {
+            budgets: budgets.to_vec(),
+            checks: Vec::new(),
+            deciders: Vec::new(),
+        })
+    }
+
+    /// Budgets that start at `first` and are multiplied by `factor` until they reach the step limit.
+    pub fn exponential(first: u64, factor: u64, limits: &Limits) -> Result<Self> {
+        if first == 0 || factor < 2 {
+            return Err(anyhow!(
+                "first budget must be positive and factor at least 2"
+            ));
+        }
+        let budgets: Vec<u64> =
+            std::iter::successors(Some(first), |budget| budget.checked_mul(factor))
+                .take_while(|budget| *budget < limits.steps)
+                .collect();
+        Self::new(&budgets, limits)
+    }
+
+    pub fn with_check(mut self, check: Check) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// Apply `deciders` between the phases. Duplicates are applied once.
+    pub fn with_deciders(mut self, deciders: &[ScheduleDecider]) -> Self {
+        for decider in deciders {
+            if !self.deciders.contains(decider) {
+                self.deciders.push(*decider);
+                self = self.with_check(decider.check());
+            }
+        }
+        self
+    }
+
+    pub fn budgets(&self) -> &[u64] {
+        &self.budgets
+    }
+
+    pub fn deciders(&self) -> &[ScheduleDecider] {
+        &self.deciders
+    }
+
+    #[inline(never)]
+    fn check(&self, runner: &Runner, states: &States) -> Option<Decision> {
+        self.checks.iter().find_map(|check| check(runner, states))
+    }
 }
 
 // This function is the most important factor in the speed of the enumeration process. Many machines are run until the step or space limit is reached. In order to optimize this function, some changes were made from the seed run:
 //
 // Exact tape space limits have been removed. The original code checks used space against BB4 and conjectured BB5. We remove this check because we already have a space limit check in `Runner`. This check is less precise because the total tape size is two times the conjectured space limit. The loss in precision is made up by faster execution speed. For machines that are decided as non halting by the BB4 space limit this doesn't change correctness because any machine decided as non halting by the BB4 space limit will also be decided as non halting by the BB4 step limit. There could be a change in behavior compared to the original code if a machine halts while using more space than the conjectured BB5 space limit and less space than our less precise space limit. In this case the original code would treat the machine as undecided while this code would treat it as halting.
+//
+// Pausing is checked in the same cold path as the budgets. The loop only compares the step with the nearest checkpoint, which is the budget unless the run can pause.
 
+/// Runs from `step` with the states in `state_seen` seen so far. Returns the step and the seen states to continue from when it paused because `abort` is set.
 #[inline(always)]
-fn run(runner: &mut Runner) -> Decision {
-    let mut state_seen: u8 = 0;
-    let mut step: u32 = 0;
+fn run(
+    runner: &mut Runner,
+    states: &States,
+    limits: &Limits,
+    schedule: &Schedule,
+    mut step: u64,
+    mut state_seen: u8,
+    abort: Option<&AtomicBool>,
+) -> Result<Decision, (u64, u8)> {
+    // Budgets that were used up before `step` have already been checked.
+    let start = step;
+    let mut budgets = schedule
+        .budgets
+        .iter()
+        .copied()
+        .take_while(|budget| *budget < limits.steps)
+        .chain(std::iter::once(limits.steps))
+        .skip_while(move |budget| budget.saturating_add(1) < start);
+    let mut budget = budgets.next().unwrap_or(limits.steps);
+    let mut pause = match abort {
+        Some(_) => (step / PAUSE_INTERVAL + 1) * PAUSE_INTERVAL,
+        None => u64::MAX,
+    };
+    let mut checkpoint = budget.min(pause);
     loop {
         state_seen |= 1 << runner.state().get();
         let all_states_seen = state_seen == 0b00011111;
         // Moving this here is faster than any other place. I am not sure why. It might influence how the compiler can rewrite the loop because `step()` happening here is an observable side effect.
         let result = runner.step();
-        let bb4_exceeded = (!all_states_seen) & (step > BB4_STEPS);
+        let bb4_exceeded = (!all_states_seen) & (step > limits.bb4_steps);
         if bb4_exceeded {
             crate::cold();
-            return Decision::Loop;
+            return Ok(Decision::Loop(LoopReason::UnvisitedState));
         }
-        let bb5_exceeded = step > LIMIT_STEPS;
-        if bb5_exceeded {
+        // With the default schedule and no abort flag there is only one checkpoint and this is the step limit.
+        let checkpoint_exceeded = step > checkpoint;
+        if checkpoint_exceeded {
             crate::cold();
-            return Decision::Undecided;
+            if step > budget {
+                let Some(next) = budgets.next() else {
+                    return Ok(Decision::Undecided(UndecidedReason::Steps));
+                };
+                budget = next;
+                // A machine that halted or ran out of tape in this step is handled below.
+                if matches!(result, StepResult::Ok) {
+                    if let Some(decision) = schedule.check(runner, states) {
+                        return Ok(decision);
+                    }
+                }
+            }
+            if step > pause {
+                pause += PAUSE_INTERVAL;
+                let aborted = abort.is_some_and(|abort| abort.load(Ordering::Relaxed));
+                if aborted && matches!(result, StepResult::Ok) {
+                    return Err((step + 1, state_seen));
+                }
+            }
+            checkpoint = budget.min(pause);
         }
         step += 1;
         match result {
             StepResult::Ok => (),
             StepResult::Halt => {
                 crate::cold();
-                return Decision::Halt(HaltingTransitionIndex(runner.state(), runner.symbol()));
+                return Ok(Decision::Halt(
+                    HaltingTransitionIndex(runner.state(), runner.symbol()),
+                    step,
+                ));
             }
-            StepResult::TapeFullLeft | StepResult::TapeFullRight => {
+            StepResult::TapeFullLeft => {
                 crate::cold();
-                return Decision::Undecided;
+                return Ok(Decision::Undecided(UndecidedReason::SpaceLeft));
+            }
+            StepResult::TapeFullRight => {
+                crate::cold();
+                return Ok(Decision::Undecided(UndecidedReason::SpaceRight));
             }
         }
     }
 }
 
 /// Iterator over a halting node's child nodes.
-pub struct ChildNodes {
-    exhausted: bool,
-    max_state: u8,
-    symbol: u8,
-    direction: u8,
-    state: u8,
-}
+pub struct ChildNodes(DefinedTransitions<5, 2>);
 
 impl ChildNodes {
     #[inline(always)]
@@ -320,13 +797,9 @@ impl ChildNodes {
             .max(branch.0.get());
         let target_states_end = (largest_partially_defined_state + 1).min(4);
 
-        Self {
-            exhausted: false,
-            max_state: target_states_end,
-            state: 0,
-            direction: 0,
-            symbol: 0,
-        }
+        Self(DefinedTransitions::up_to(unsafe {
+            State::new_unchecked(target_states_end)
+        }))
     }
 }
 
@@ -335,36 +808,7 @@ impl Iterator for ChildNodes {
 
     #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
-        if self.exhausted {
-            crate::cold();
-            return None;
-        }
-        let result = DefinedTransition {
-            state: unsafe { State::new_unchecked(self.state) },
-            move_: match self.direction {
-                0 => Direction::Right,
-                1 => Direction::Left,
-                _ => {
-                    debug_assert!(false, "unreachable");
-                    unsafe { unreachable_unchecked() }
-                }
-            },
-            write: unsafe { Symbol::new_unchecked(self.symbol) },
-        };
-        self.exhausted = true;
-        for (current, max) in [&mut self.symbol, &mut self.direction, &mut self.state]
-            .into_iter()
-            .zip([1, 1, self.max_state])
-        {
-            if *current < max {
-                self.exhausted = false;
-                *current += 1;
-                break;
-            } else {
-                *current = 0;
-            }
-        }
-        Some(result)
+        self.0.next()
     }
 }
 
@@ -377,13 +821,86 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn invariant_violations() {
+        let root = Node::root();
+        let branch = HaltingTransitionIndex::root();
+        assert_eq!(validate_node(&root, branch), Ok(()));
+        let a0 = HaltingTransitionIndex(State::new(0).unwrap(), Symbol::new(0).unwrap());
+        assert_eq!(
+            validate_node(&root, a0),
+            Err(InvariantViolation::BranchNotHalting(a0))
+        );
+        let mut mirrored = root;
+        let Transition::Continue(first) = &mut mirrored.0 .0[0][0] else {
+            unreachable!()
+        };
+        first.move_ = Direction::Left;
+        assert!(matches!(
+            validate_node(&mirrored, branch),
+            Err(InvariantViolation::FirstTransition(_))
+        ));
+        // Nodes with a single halting transition have no children.
+        let champion = Node(busy_beaver::fixtures::BB5);
+        let e0 = HaltingTransitionIndex(State::new(4).unwrap(), Symbol::new(0).unwrap());
+        assert_eq!(
+            validate_node(&champion, e0),
+            Err(InvariantViolation::HaltingTransitionCount(1))
+        );
+    }
+
+    #[test]
+    fn decision_serialization() {
+        let decisions = [
+            Decision::Halt(
+                HaltingTransitionIndex(State::new(2).unwrap(), Symbol::new(1).unwrap()),
+                7,
+            ),
+            Decision::Loop(LoopReason::Check),
+            Decision::Undecided(UndecidedReason::SpaceLeft),
+            Decision::Irrelevant,
+        ];
+        for decision in decisions {
+            let bytes = bincode::serialize(&decision).unwrap();
+            assert_eq!(bincode::deserialize::<Decision>(&bytes).unwrap(), decision);
+            let code = decision.log_decision().code();
+            assert_eq!(LogDecision::from_code(code), Some(decision.log_decision()));
+        }
+    }
+
+    #[test]
+    fn count_subtrees() {
+        let limits = Limits {
+            steps: 3,
+            ..Limits::default()
+        };
+        let root = Node::root();
+        let mut counter = SubtreeCounter::new(&limits);
+        let total = counter.count(&root, HaltingTransitionIndex::root());
+        assert_eq!(total, 1004);
+        assert!(!counter.cache.is_empty());
+        // The root's count comes from the cache now and the subtrees of its children add up to it.
+        assert_eq!(counter.count(&root, HaltingTransitionIndex::root()), total);
+        let mut sum = 0;
+        let mut child = root;
+        let branch = HaltingTransitionIndex::root();
+        for transition in ChildNodes::new(&root, branch) {
+            *child.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+            sum += 1;
+            if let Some(next) = counter.expand(&child, branch) {
+                sum += count_subtree(&child, next, &limits);
+            }
+        }
+        assert_eq!(sum, total);
+    }
+
     // Test that traces an execution and compares it with a previously recorded trace.
 
     fn write_trace(mut out: impl Write, states: &States, trace: Decision) -> std::io::Result<()> {
         let trace = match trace {
             Decision::Halt(..) => "Halt",
-            Decision::Loop => "Loop",
-            Decision::Undecided => "Undecided",
+            Decision::Loop(_) => "Loop",
+            Decision::Undecided(_) => "Undecided",
             Decision::Irrelevant => "Irrelevant",
         };
         writeln!(&mut out, "{states} {trace}")
@@ -451,6 +968,179 @@ mod tests {
         println!("{:.1e}", end.as_secs_f32());
     }
 
+    #[test]
+    fn schedule() {
+        let limits = Limits::default();
+        assert!(Schedule::new(&[10, 10], &limits).is_err());
+        assert!(Schedule::new(&[LIMIT_STEPS], &limits).is_err());
+        assert!(Schedule::exponential(1000, 1, &limits).is_err());
+        let schedule = Schedule::exponential(1000, 10, &limits).unwrap();
+        assert_eq!(
+            schedule.budgets(),
+            [1000, 10_000, 100_000, 1_000_000, 10_000_000]
+        );
+
+        let schedule = Schedule::new(&[1000], &limits)
+            .unwrap()
+            .with_check(|_, _| Some(Decision::Loop(LoopReason::Check)));
+        let mut runner = create_runner(&limits);
+        // The root halts in the second step, before the first budget is used up, so the check is not applied.
+        let root = Node::root().0;
+        assert!(matches!(
+            decide_standalone(&mut runner, &root, &limits, &schedule),
+            Decision::Halt(_, 2)
+        ));
+        let skelet = busy_beaver::fixtures::get("skelet 1").unwrap().machine();
+        assert_eq!(
+            decide_standalone(&mut runner, &skelet, &limits, &schedule),
+            Decision::Loop(LoopReason::Check)
+        );
+    }
+
+    #[test]
+    fn schedule_deciders() {
+        let limits = Limits::default();
+        let mut runner = create_runner(&limits);
+        let decide = |runner: &mut Runner, machine: &str, deciders: &[ScheduleDecider]| {
+            let machine: States = busy_beaver::format::read_compact(machine.as_bytes()).unwrap();
+            // The budget is below `bb4_steps` so that the deciders are applied before the machine is decided for not visiting every state.
+            let schedule = Schedule::new(&[50], &limits)
+                .unwrap()
+                .with_deciders(deciders);
+            decide_standalone(runner, &machine, &limits, &schedule)
+        };
+        let check = Decision::Loop(LoopReason::Check);
+        let unvisited = Decision::Loop(LoopReason::UnvisitedState);
+        // Returns to the blank tape every two steps.
+        let cycler = "1RB1RB_0LA---_------_------_------";
+        // Writes ones to the left forever.
+        let translated = "1RB1LB_1LA0RB_------_------_------";
+        for (machine, deciders, decision) in [
+            (cycler, &[][..], unvisited),
+            (cycler, &[ScheduleDecider::Cyclers], check),
+            (cycler, &[ScheduleDecider::Segment], check),
+            (translated, &[ScheduleDecider::Cyclers], unvisited),
+            (translated, &[ScheduleDecider::Segment], check),
+            (translated, &ScheduleDecider::ALL, check),
+        ] {
+            assert_eq!(
+                decide(&mut runner, machine, deciders),
+                decision,
+                "{machine} {deciders:?}"
+            );
+        }
+        let schedule = Schedule::default().with_deciders(&[ScheduleDecider::Segment; 2]);
+        assert_eq!(schedule.deciders(), [ScheduleDecider::Segment]);
+    }
+
+    #[test]
+    fn pause() {
+        let limits = Limits {
+            steps: 3 * PAUSE_INTERVAL + 5,
+            ..Limits::default()
+        };
+        let wide = |runner: &Runner, _: &States| {
+            let (left, right) = runner.extent();
+            (right - left > 1000).then_some(Decision::Loop(LoopReason::Check))
+        };
+        let schedules = [
+            Schedule::default(),
+            Schedule::new(&[1000, PAUSE_INTERVAL + PAUSE_INTERVAL / 2], &limits)
+                .unwrap()
+                .with_check(wide),
+        ];
+        let states = busy_beaver::fixtures::BB5;
+        let changed = HaltingTransitionIndex(State::new(3).unwrap(), Symbol::new(1).unwrap());
+        let mut runner = create_runner(&limits);
+        let abort = AtomicBool::new(true);
+        for (schedule, expected) in schedules.iter().zip([3, 1]) {
+            let decision = decide(&mut runner, &states, changed, &limits, schedule);
+            let mut paused = None;
+            let mut pauses = 0;
+            let resumed = loop {
+                match decide_pausable(
+                    &mut runner,
+                    &states,
+                    changed,
+                    &limits,
+                    schedule,
+                    paused.as_ref(),
+                    &abort,
+                ) {
+                    Ok(decision) => break decision,
+                    Err(next) => {
+                        assert!(next.step > PAUSE_INTERVAL * (pauses + 1));
+                        paused = Some(
+                            bincode::deserialize(&bincode::serialize(&next).unwrap()).unwrap(),
+                        );
+                        pauses += 1;
+                    }
+                }
+            };
+            assert_eq!(resumed, decision);
+            assert_eq!(pauses, expected);
+        }
+        assert!(matches!(
+            decide_pausable(
+                &mut runner,
+                &states,
+                changed,
+                &limits,
+                &schedules[0],
+                None,
+                &AtomicBool::new(false)
+            ),
+            Ok(Decision::Undecided(UndecidedReason::Steps))
+        ));
+    }
+
+    #[test]
+    fn undecided_reasons() {
+        let limits = Limits {
+            steps: 50,
+            memory: 4,
+            bb4_steps: 107,
+        };
+        let mut runner = create_runner(&limits);
+        for (machine, reason) in [
+            (
+                "1RA1RA_------_------_------_------",
+                UndecidedReason::SpaceRight,
+            ),
+            (
+                "1LA1LA_------_------_------_------",
+                UndecidedReason::SpaceLeft,
+            ),
+            ("1RB1RB_1LA1LA_------_------_------", UndecidedReason::Steps),
+        ] {
+            let states = busy_beaver::format::read_compact(machine.as_bytes()).unwrap();
+            let decision = decide_standalone(&mut runner, &states, &limits, &Schedule::default());
+            assert_eq!(decision, Decision::Undecided(reason), "{machine}");
+            assert_eq!(crate::verify::reference_decide(&states, &limits), decision);
+        }
+    }
+
+    #[test]
+    fn library_decides_like_the_enumeration() {
+        let mut callback = |states: &States, decision: Decision| {
+            let expected = decision.log_decision().code();
+            assert_eq!(
+                busy_beaver::seed_decide(states).code(),
+                expected,
+                "{states}"
+            );
+        };
+        enumerate_for_tests(&mut callback, 300);
+    }
+
+    #[test]
+    fn library_recognizes_enumerated_machines() {
+        let mut callback = |states: &States, _| {
+            assert!(busy_beaver::seed::is_enumerated(states), "{states}");
+        };
+        enumerate_for_tests(&mut callback, 1500);
+    }
+
     /// Initiate the enumeration procedure and run until `steps` machines have been enumerated.
     fn enumerate_for_tests(trace: &mut impl FnMut(&States, Decision), steps: u64) {
         let mut step: u64 = 0;
@@ -462,7 +1152,7 @@ mod tests {
         enumerate_iteratively(
             Node::root(),
             HaltingTransitionIndex::root(),
-            &mut Runner::vector_backed(TAPE_SIZE),
+            &mut create_runner(&Limits::default()),
             &mut trace,
         );
     }
@@ -0,0 +1,478 @@
+// Running a set of related runs described by a config file.
+//
+// Parameter sweeps, for example over step budgets, consist of many runs that differ in a few settings. The config file lists the runs. Every run gets its own directory in the experiment's output directory. A run either enumerates machines like the seed run, in which case this binary is started again in the run's directory, or decides the machines of a machine list and writes a log of the decisions.
+//
+// An example config:
+//
+// ```toml
+// output = "experiments/budgets"
+// parallel = true
+//
+// [[run]]
+// name = "small"
+// machines = "holdouts.txt"
+// limit_steps = 100000
+//
+// [[run]]
+// name = "large"
+// machines = "holdouts.txt"
+// limit_steps = 10000000
+// first_budget = 1000
+// deciders = ["cyclers"]
+// ```
+
+use std::{
+    collections::HashSet,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::format::{LogDecision, MachineList};
+use serde::Deserialize;
+
+use crate::enumerate::{
+    create_runner, decide_standalone, Decision, Limits, Schedule, ScheduleDecider, States,
+};
+
+/// Machines of a list that are decided at a time. Lists of holdouts can have millions of machines, so they are not read at once.
+const CHUNK_LEN: usize = 1 << 16;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Experiment config in TOML. See the module documentation of `experiment` for the format.
+    config: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Experiment {
+    /// Directory that gets a subdirectory for every run. Defaults to `experiments/` followed by the name of the config file without extension.
+    output: Option<PathBuf>,
+    /// Execute the runs at the same time instead of one after another.
+    #[serde(default)]
+    parallel: bool,
+    #[serde(rename = "run")]
+    runs: Vec<RunConfig>,
+}
+
+/// Paths are relative to the directory of the config file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RunConfig {
+    /// Name of the run's directory.
+    name: String,
+    /// Decide the machines of this list instead of enumerating. Only undecided machines of a log are decided.
+    machines: Option<PathBuf>,
+    /// Index file selecting machines of the seed database given with `machines`.
+    index: Option<PathBuf>,
+    limit_steps: Option<u64>,
+    limit_memory: Option<usize>,
+    bb4_steps: Option<u64>,
+    #[serde(default)]
+    budgets: Vec<u64>,
+    first_budget: Option<u64>,
+    budget_factor: Option<u64>,
+    /// Deciders that are applied between the budgets, like `--deciders`. Defaults to all of them.
+    deciders: Option<Vec<ScheduleDecider>>,
+    /// Number of threads. Defaults to all cores.
+    threads: Option<usize>,
+    /// Further arguments for enumerating runs, for example `["--track-space"]`.
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl RunConfig {
+    fn limits(&self) -> Limits {
+        let default = Limits::default();
+        Limits {
+            steps: self.limit_steps.unwrap_or(default.steps),
+            memory: self.limit_memory.unwrap_or(default.memory),
+            bb4_steps: self.bb4_steps.unwrap_or(default.bb4_steps),
+        }
+    }
+
+    fn schedule(&self) -> Result<Schedule> {
+        let limits = self.limits();
+        match self.first_budget {
+            Some(first) => {
+                if !self.budgets.is_empty() {
+                    return Err(anyhow!("`budgets` and `first_budget` exclude each other"));
+                }
+                Schedule::exponential(first, self.budget_factor.unwrap_or(10), &limits)
+            }
+            None => Schedule::new(&self.budgets, &limits),
+        }
+        .map(|schedule| match &self.deciders {
+            Some(deciders) => schedule.with_deciders(deciders),
+            None => schedule.with_deciders(&ScheduleDecider::ALL),
+        })
+    }
+
+    /// Arguments for an enumerating run of this binary.
+    fn run_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(format!("--{name}"));
+                args.push(value);
+            }
+        };
+        push("limit-steps", self.limit_steps.map(|v| v.to_string()));
+        push("limit-memory", self.limit_memory.map(|v| v.to_string()));
+        push("bb4-steps", self.bb4_steps.map(|v| v.to_string()));
+        let budgets: Vec<String> = self.budgets.iter().map(|b| b.to_string()).collect();
+        push("budgets", (!budgets.is_empty()).then(|| budgets.join(",")));
+        push("first-budget", self.first_budget.map(|v| v.to_string()));
+        push("budget-factor", self.budget_factor.map(|v| v.to_string()));
+        let deciders = self.deciders.as_ref().map(|deciders| {
+            let names: Vec<&str> = deciders.iter().map(|d| d.name()).collect();
+            names.join(",")
+        });
+        push("deciders", deciders);
+        push("threads", self.threads.map(|v| v.to_string()));
+        args.extend(self.args.iter().cloned());
+        args
+    }
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let config = std::fs::read_to_string(&args.config).context("read config")?;
+    let experiment: Experiment = toml::from_str(&config).context("parse config")?;
+    let base = args.config.parent().unwrap_or(Path::new(""));
+    validate(&experiment)?;
+    let output = match &experiment.output {
+        Some(output) => base.join(output),
+        None => {
+            let name = args.config.file_stem().context("config file has no name")?;
+            base.join("experiments").join(name)
+        }
+    };
+    std::fs::create_dir_all(&output).context("create output directory")?;
+    // Keep the config next to the results so that the results can be traced back to their settings.
+    std::fs::write(output.join("experiment.toml"), &config).context("copy config")?;
+
+    let execute = |run: &RunConfig| {
+        let directory = output.join(&run.name);
+        let result = std::fs::create_dir_all(&directory)
+            .context("create run directory")
+            .and_then(|()| match &run.machines {
+                Some(machines) => decide_list(run, base, machines, &directory),
+                None => enumerate(run, &directory),
+            });
+        match &result {
+            Ok(()) => println!("Run {} finished.", run.name),
+            Err(err) => println!("Run {} failed: {err:#}", run.name),
+        }
+        result
+    };
+    let results: Vec<Result<()>> = if experiment.parallel {
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = experiment
+                .runs
+                .iter()
+                .map(|run| scope.spawn(|| execute(run)))
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        })
+    } else {
+        experiment.runs.iter().map(execute).collect()
+    };
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    if failed != 0 {
+        return Err(anyhow!("{failed} of {} runs failed", results.len()));
+    }
+    println!(
+        "All {} runs finished. Results are in {}.",
+        results.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Check all runs before starting any so that a typo does not surface after hours of running.
+fn validate(experiment: &Experiment) -> Result<()> {
+    let mut names = HashSet::new();
+    for run in &experiment.runs {
+        let is_plain = !run.name.is_empty()
+            && run
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+            && run.name != "."
+            && run.name != "..";
+        if !is_plain {
+            return Err(anyhow!(
+                "run name {:?} is not a plain directory name",
+                run.name
+            ));
+        }
+        if !names.insert(&run.name) {
+            return Err(anyhow!("two runs are named {}", run.name));
+        }
+        run.limits()
+            .validate()
+            .with_context(|| format!("limits of run {}", run.name))?;
+        run.schedule()
+            .with_context(|| format!("step budgets of run {}", run.name))?;
+        if run.machines.is_some() && !run.args.is_empty() {
+            return Err(anyhow!(
+                "run {}: `args` only apply to enumerating runs",
+                run.name
+            ));
+        }
+        if run.index.is_some() && run.machines.is_none() {
+            return Err(anyhow!("run {}: `index` requires `machines`", run.name));
+        }
+        // The command line has no way to name an empty list of deciders. Leaving out the budgets has the same effect.
+        if run.machines.is_none() && run.deciders.as_ref().is_some_and(Vec::is_empty) {
+            return Err(anyhow!(
+                "run {}: `deciders` of enumerating runs must not be empty, leave out the budgets instead",
+                run.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Start this binary in `directory`. An interrupted run resumes when the experiment is executed again. The output of the run goes to the file `output` in the directory.
+fn enumerate(run: &RunConfig, directory: &Path) -> Result<()> {
+    let output = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(directory.join("output"))
+        .context("open output file")?;
+    let executable = std::env::current_exe().context("find executable")?;
+    let status = std::process::Command::new(executable)
+        .args(run.run_args())
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .stdout(output.try_clone().context("open output file")?)
+        .stderr(output)
+        .status()
+        .context("start run")?;
+    if !status.success() {
+        return Err(anyhow!("run exited with {status}"));
+    }
+    Ok(())
+}
+
+/// Decide the machines of a list and write the decisions to the file `log` in `directory` in the format of the seed run's log.
+fn decide_list(run: &RunConfig, base: &Path, machines: &Path, directory: &Path) -> Result<()> {
+    let machines = base.join(machines);
+    let list = match &run.index {
+        Some(index) => MachineList::open_indexed(&machines, &base.join(index))?,
+        None => MachineList::open(&machines)?,
+    };
+    let undecided = list.filter_map(|machine| match machine {
+        Ok(machine) => matches!(machine.decision, None | Some(LogDecision::Undecided))
+            .then_some(Ok(machine.states)),
+        Err(err) => Some(Err(err)),
+    });
+    let log = std::fs::File::create(directory.join("log")).context("create log")?;
+    let thread_count = run.threads.unwrap_or_else(num_cpus::get).max(1);
+    let counts = decide_machines(
+        undecided,
+        &run.limits(),
+        &run.schedule()?,
+        thread_count,
+        CHUNK_LEN,
+        log,
+    )?;
+    let [halt, loop_, undecided, _] = counts;
+    println!(
+        "Run {}: {} machines, {halt} halt, {loop_} loop, {undecided} undecided.",
+        run.name,
+        counts.iter().sum::<u64>()
+    );
+    Ok(())
+}
+
+/// Decide `machines` in chunks of `chunk_len` and write log entries to `log` in the order of `machines`. Returns the entries by `LogDecision`.
+fn decide_machines(
+    machines: impl Iterator<Item = busy_beaver::Result<States>>,
+    limits: &Limits,
+    schedule: &Schedule,
+    thread_count: usize,
+    chunk_len: usize,
+    log: impl Write,
+) -> Result<[u64; 4]> {
+    let mut machines = machines.fuse();
+    let mut log = BufWriter::new(log);
+    let mut counts = [0u64; 4];
+    let mut chunk = Vec::<States>::with_capacity(chunk_len);
+    loop {
+        chunk.clear();
+        for machine in machines.by_ref().take(chunk_len) {
+            chunk.push(machine.context("read machines")?);
+        }
+        if chunk.is_empty() {
+            break;
+        }
+        let thread_len = chunk.len().div_ceil(thread_count);
+        let decisions: Vec<Decision> = std::thread::scope(|scope| {
+            let threads: Vec<_> = chunk
+                .chunks(thread_len)
+                .map(|part| {
+                    scope.spawn(|| {
+                        let mut runner = create_runner(limits);
+                        part.iter()
+                            .map(|states| decide_standalone(&mut runner, states, limits, schedule))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .flat_map(|t| t.join().unwrap())
+                .collect()
+        });
+        for (states, decision) in chunk.iter().zip(&decisions) {
+            let decision = decision.log_decision();
+            counts[decision as usize] += 1;
+            writeln!(log, "{states} {}", decision.code() as char).context("write log")?;
+        }
+    }
+    log.flush().context("flush log")?;
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn parse(config: &str) -> Result<Experiment> {
+        let experiment: Experiment = toml::from_str(config)?;
+        validate(&experiment)?;
+        Ok(experiment)
+    }
+
+    #[test]
+    fn run_args_round_trip() {
+        let experiment = parse(
+            r#"
+            [[run]]
+            name = "budgets"
+            limit_steps = 100000
+            limit_memory = 1000
+            bb4_steps = 50
+            budgets = [100, 1000]
+            deciders = ["segment"]
+            threads = 2
+            args = ["--track-space"]
+
+            [[run]]
+            name = "exponential"
+            limit_steps = 1000
+            first_budget = 10
+            budget_factor = 4
+            "#,
+        )
+        .unwrap();
+        for run in &experiment.runs {
+            let args = crate::Args::try_parse_from(
+                std::iter::once("seed".to_string()).chain(run.run_args()),
+            )
+            .unwrap()
+            .run;
+            assert_eq!(args.limits(), run.limits());
+            let (schedule, expected) = (
+                args.schedule(&args.limits()).unwrap(),
+                run.schedule().unwrap(),
+            );
+            assert_eq!(schedule.budgets(), expected.budgets());
+            assert_eq!(schedule.deciders(), expected.deciders());
+        }
+        let budgets = &experiment.runs[0];
+        assert_eq!(budgets.schedule().unwrap().budgets(), [100, 1000]);
+        assert_eq!(
+            budgets.schedule().unwrap().deciders(),
+            [ScheduleDecider::Segment]
+        );
+        assert_eq!(
+            experiment.runs[1].schedule().unwrap().budgets(),
+            [10, 40, 160, 640]
+        );
+    }
+
+    #[test]
+    fn bad_configs_are_rejected() {
+        for config in [
+            // Unknown fields.
+            "[[run]]\nname = \"a\"\nlimit_step = 10",
+            "colour = 1\n[[run]]\nname = \"a\"",
+            // Names that are not plain directory names or not unique.
+            "[[run]]\nname = \"\"",
+            "[[run]]\nname = \"..\"",
+            "[[run]]\nname = \"a/b\"",
+            "[[run]]\nname = \"a\"\n[[run]]\nname = \"a\"",
+            // Invalid limits and budgets.
+            "[[run]]\nname = \"a\"\nlimit_memory = 0",
+            "[[run]]\nname = \"a\"\nlimit_memory = 9223372036854775807",
+            "[[run]]\nname = \"a\"\nbudgets = [10, 5]",
+            "[[run]]\nname = \"a\"\nbudgets = [10]\nfirst_budget = 5",
+            "[[run]]\nname = \"a\"\nfirst_budget = 5\nbudget_factor = 1",
+            // Unknown deciders and no deciders for an enumerating run.
+            "[[run]]\nname = \"a\"\nbudgets = [10]\ndeciders = [\"cycler\"]",
+            "[[run]]\nname = \"a\"\nbudgets = [10]\ndeciders = []",
+            // Settings that only apply to the other kind of run.
+            "[[run]]\nname = \"a\"\nmachines = \"m\"\nargs = [\"--track-space\"]",
+            "[[run]]\nname = \"a\"\nindex = \"i\"",
+        ] {
+            assert!(parse(config).is_err(), "{config}");
+        }
+        // An empty list of deciders is fine when the machines are decided in this process.
+        parse("[[run]]\nname = \"a\"\nmachines = \"m\"\nbudgets = [10]\ndeciders = []").unwrap();
+    }
+
+    #[test]
+    fn machines_are_decided_in_chunks() {
+        let limits = Limits {
+            steps: 1000,
+            ..Limits::default()
+        };
+        let schedule = Schedule::new(&[100], &limits)
+            .unwrap()
+            .with_deciders(&ScheduleDecider::ALL);
+        let machines: Vec<States> = busy_beaver::fixtures::with_size(5, 2)
+            .map(|fixture| fixture.machine())
+            .chain(std::iter::once(
+                busy_beaver::format::read_compact(b"1RB1LB_1LA0RB_------_------_------").unwrap(),
+            ))
+            .collect();
+        let mut expected = Vec::new();
+        let mut runner = create_runner(&limits);
+        for states in &machines {
+            let decision = decide_standalone(&mut runner, states, &limits, &schedule);
+            writeln!(
+                expected,
+                "{states} {}",
+                decision.log_decision().code() as char
+            )
+            .unwrap();
+        }
+        for (thread_count, chunk_len) in [(1, 1), (2, 3), (3, machines.len())] {
+            let mut log = Vec::new();
+            let counts = decide_machines(
+                machines.iter().copied().map(Ok),
+                &limits,
+                &schedule,
+                thread_count,
+                chunk_len,
+                &mut log,
+            )
+            .unwrap();
+            assert_eq!(log, expected);
+            assert_eq!(counts.iter().sum::<u64>(), machines.len() as u64);
+        }
+        // Errors of the list end the run.
+        let broken = [
+            Ok(machines[0]),
+            Err(busy_beaver::Error::Parse("broken".into())),
+        ];
+        assert!(decide_machines(broken.into_iter(), &limits, &schedule, 1, 1, Vec::new()).is_err());
+    }
+}
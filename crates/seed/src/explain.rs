@@ -0,0 +1,367 @@
+// Report on a single machine.
+//
+// When someone asks about a machine, the first questions are always the same: is it the same machine as a known one up to normalization, which states can it reach, which transitions does it use and what does the seed run decide for it. This tool answers all of them at once so that the report can be pasted into an issue.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use busy_beaver::{
+    counter::{self, CounterDecider},
+    format::read_compact,
+    normalize::{is_normal, normalize_recorded},
+    proof::Proof,
+    run::{Runner, SparseTape, StepResult, TransitionCounts},
+    score::score,
+    segment::{self, SegmentSimulator},
+    states::Direction,
+};
+
+use crate::enumerate::{
+    create_runner, decide_standalone, Decision, Limits, LoopReason, Schedule, State, States,
+    Symbol, Transition, UndecidedReason,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The machine in the compact format, like `1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA`.
+    machine: String,
+    /// Number of steps for which the used transitions are counted.
+    #[arg(long, default_value_t = 1_000_000)]
+    steps: u64,
+    /// Step limit of the decision. Defaults to the seed run's value.
+    #[arg(long)]
+    limit_steps: Option<u64>,
+    /// Space limit of the decision in cells in either direction. Defaults to the seed run's value.
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    limit_memory: Option<usize>,
+    /// BB(4) step heuristic of the decision. Defaults to the step count of the BB(4) champion.
+    #[arg(long)]
+    bb4_steps: Option<u64>,
+    /// Append a proof sketch in Markdown that can be posted as is: a halting proof for machines that halt, otherwise the proof of the counter decider or the segment simulator if one of them finds one.
+    #[arg(long)]
+    markdown: bool,
+}
+
+pub fn main(args: Args) -> Result<()> {
+    report(&args, &mut std::io::stdout().lock())
+}
+
+/// Write the report on `args.machine` to `w`.
+fn report(args: &Args, w: &mut impl Write) -> Result<()> {
+    let states: States = read_compact(args.machine.as_bytes()).context("invalid machine")?;
+    let default = Limits::default();
+    let limits = Limits {
+        steps: args.limit_steps.unwrap_or(default.steps),
+        memory: args.limit_memory.unwrap_or(default.memory),
+        bb4_steps: args.bb4_steps.unwrap_or(default.bb4_steps),
+    };
+
+    writeln!(w, "Machine: {states}")?;
+    let mut normalized = states;
+    let normalization = normalize_recorded(&mut normalized);
+    if is_normal(&states) {
+        writeln!(w, "Normalized: {normalized} (already normal)")?;
+    } else {
+        let mut changes = Vec::new();
+        if normalization.mirrored {
+            changes.push("directions reversed".to_string());
+        }
+        let renamed: Vec<String> = (0..5u8)
+            .map(|state| State::new(state).unwrap())
+            .filter(|state| normalization.normalized_state(*state) != *state)
+            .map(|state| {
+                format!(
+                    "{}->{}",
+                    state_name(state),
+                    state_name(normalization.normalized_state(state))
+                )
+            })
+            .collect();
+        if !renamed.is_empty() {
+            changes.push(format!("states renamed {}", renamed.join(" ")));
+        }
+        writeln!(w, "Normalized: {normalized} ({})", changes.join(", "))?;
+    }
+
+    let reachable = reachable_states(&states);
+    let unreachable: Vec<char> = (0..5u8)
+        .filter(|state| !reachable[*state as usize])
+        .map(|state| state_name(State::new(state).unwrap()))
+        .collect();
+    writeln!(
+        w,
+        "Reachable states: {} of 5{}",
+        reachable.iter().filter(|r| **r).count(),
+        if unreachable.is_empty() {
+            String::new()
+        } else {
+            format!(", unreachable {}", unreachable.iter().collect::<String>())
+        }
+    )?;
+
+    let (counts, halted) = count_transitions(&states, args.steps);
+    let halting: Vec<String> = transitions(&states)
+        .filter(|(_, _, t)| matches!(t, Transition::Halt))
+        .map(|(state, symbol, _)| format!("{}{}", state_name(state), symbol.get()))
+        .collect();
+    writeln!(
+        w,
+        "Transitions: {} defined, {} halting{}",
+        10 - halting.len(),
+        halting.len(),
+        if halting.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", halting.join(" "))
+        }
+    )?;
+    match halted {
+        Some(step) => writeln!(w, "  Uses in the {step} steps until halting:")?,
+        None => writeln!(w, "  Uses in the first {} steps:", args.steps)?,
+    }
+    for (state, symbol, transition) in transitions(&states) {
+        let description = match transition {
+            Transition::Halt => "---".to_string(),
+            Transition::Continue(t) => format!(
+                "{}{}{}",
+                t.write.get(),
+                match t.move_ {
+                    Direction::Left => 'L',
+                    Direction::Right => 'R',
+                },
+                state_name(t.state)
+            ),
+        };
+        writeln!(
+            w,
+            "  {}{} {description} {:>12}",
+            state_name(state),
+            symbol.get(),
+            counts.get(state, symbol)
+        )?;
+    }
+
+    // There is no pipeline of deciders yet. The decision is that of the seed run: the simulation within the limits and the BB(4) heuristic. The irrelevance checks depend on how the machine was reached in the enumeration and are not applied.
+    writeln!(w, "Decision with {limits:?}:")?;
+    let mut runner = create_runner(&limits);
+    let decision = decide_standalone(&mut runner, &states, &limits, &Schedule::default());
+    match decision {
+        Decision::Halt(index, steps) => writeln!(
+            w,
+            "  halts with transition {}{} after {steps} steps",
+            state_name(index.0),
+            index.1.get()
+        )?,
+        Decision::Loop(LoopReason::UnvisitedState) => writeln!(
+            w,
+            "  runs forever: not every state was visited within {} steps",
+            limits.bb4_steps
+        )?,
+        Decision::Loop(reason) => writeln!(w, "  runs forever: {}", reason.name())?,
+        Decision::Undecided(UndecidedReason::Steps) => {
+            writeln!(w, "  undecided: reached the step limit of {}", limits.steps)?
+        }
+        Decision::Undecided(reason) => writeln!(
+            w,
+            "  undecided: reached the {} limit of {} cells",
+            reason.name(),
+            limits.memory
+        )?,
+        Decision::Irrelevant => writeln!(w, "  irrelevant")?,
+    }
+
+    if let Decision::Halt(_, steps) = decision {
+        let mut runner = Runner::new(SparseTape::unbounded());
+        let score = score(&mut runner, &states, steps).context("score halting machine")?;
+        writeln!(
+            w,
+            "Score: steps {}, sigma {}, space {}",
+            score.steps, score.ones, score.space
+        )?;
+    }
+
+    if args.markdown {
+        writeln!(w)?;
+        match proof_sketch(&states, decision) {
+            Some(sketch) => write!(w, "{sketch}")?,
+            None => writeln!(w, "No proof found for a sketch.")?,
+        }
+    }
+    Ok(())
+}
+
+fn proof_sketch(states: &States, decision: Decision) -> Option<String> {
+    if let Decision::Halt(_, steps) = decision {
+        let proof = Proof::record(states, crate::proof::DEFAULT_INTERVAL, steps).ok()?;
+        return Some(proof.to_markdown());
+    }
+    let counter = CounterDecider::new(counter::Config::default());
+    if let Some(certificate) = counter.prove(states) {
+        return certificate.to_markdown(states);
+    }
+    SegmentSimulator::new(segment::Config {
+        window: 8,
+        position: segment::Position::Every,
+    })
+    .to_markdown(states)
+}
+
+fn state_name(state: State) -> char {
+    char::from(b'A' + state.get())
+}
+
+fn transitions(states: &States) -> impl Iterator<Item = (State, Symbol, Transition)> + '_ {
+    states
+        .0
+        .iter()
+        .enumerate()
+        .flat_map(|(state, transitions)| {
+            transitions.iter().enumerate().map(move |(symbol, t)| {
+                (
+                    State::new(state as u8).unwrap(),
+                    Symbol::new(symbol as u8).unwrap(),
+                    *t,
+                )
+            })
+        })
+}
+
+/// States that can be reached from the start state through the transition table, regardless of the tape.
+fn reachable_states(states: &States) -> [bool; 5] {
+    let mut reachable = [false; 5];
+    reachable[0] = true;
+    let mut stack = vec![0usize];
+    while let Some(state) = stack.pop() {
+        for transition in states.0[state] {
+            if let Transition::Continue(t) = transition {
+                let next = t.state.get() as usize;
+                if !reachable[next] {
+                    reachable[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    reachable
+}
+
+/// How often each transition is used in the first `steps` steps, and the step at which the machine halted.
+fn count_transitions(states: &States, steps: u64) -> (TransitionCounts<5, 2>, Option<u64>) {
+    let mut runner = Runner::with_observer(SparseTape::unbounded(), TransitionCounts::default());
+    runner.set_states(states);
+    for step in 1..=steps {
+        if let StepResult::Halt = runner.step() {
+            return (*runner.observer(), Some(step));
+        }
+    }
+    (*runner.observer(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report_of(machine: &str, markdown: bool) -> String {
+        let args = Args {
+            machine: machine.to_string(),
+            steps: 1000,
+            limit_steps: Some(10_000),
+            limit_memory: None,
+            bb4_steps: None,
+            markdown,
+        };
+        let mut output = Vec::new();
+        report(&args, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn reachable() {
+        let reachable =
+            |machine: &str| reachable_states(&read_compact(machine.as_bytes()).unwrap());
+        assert_eq!(
+            reachable("1RB1LB_1LA---_------_------_------"),
+            [true, true, false, false, false]
+        );
+        // C and E only reach each other.
+        assert_eq!(
+            reachable("1RB---_1LA---_1RE---_1LA---_1LC---"),
+            [true, true, false, false, false]
+        );
+        assert_eq!(reachable("1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA"), [true; 5]);
+    }
+
+    #[test]
+    fn transition_counts() {
+        let bb2: States = read_compact(b"1RB1LB_1LA---_------_------_------").unwrap();
+        let (counts, halted) = count_transitions(&bb2, 1000);
+        assert_eq!(halted, Some(6));
+        let a = State::new(0).unwrap();
+        let b = State::new(1).unwrap();
+        let (zero, one) = (Symbol::new(0).unwrap(), Symbol::new(1).unwrap());
+        assert_eq!(
+            [
+                counts.get(a, zero),
+                counts.get(a, one),
+                counts.get(b, zero),
+                counts.get(b, one)
+            ],
+            [2, 1, 2, 0]
+        );
+        let translated: States = read_compact(b"1RB1LB_1LA0RB_------_------_------").unwrap();
+        let (counts, halted) = count_transitions(&translated, 1000);
+        assert_eq!(halted, None);
+        let total: u64 = transitions(&translated)
+            .map(|(state, symbol, _)| counts.get(state, symbol))
+            .sum();
+        assert_eq!(total, 1000);
+    }
+
+    #[test]
+    fn reports() {
+        let bb2 = report_of("1RB1LB_1LA---_------_------_------", false);
+        for line in [
+            "Normalized: 1RB1LB_1LA---_------_------_------ (already normal)",
+            "Reachable states: 2 of 5, unreachable CDE",
+            "Transitions: 3 defined, 7 halting (B1 C0 C1 D0 D1 E0 E1)",
+            "  Uses in the 6 steps until halting:",
+            "  A0 1RB            2",
+            "  B1 ---            0",
+            "  halts with transition B1 after 6 steps",
+            "Score: steps 6, sigma 4, space 4",
+        ] {
+            assert!(bb2.lines().any(|l| l == line), "{line:?} in\n{bb2}");
+        }
+        assert!(!bb2.contains("##"));
+        let mirrored = report_of("1LB1RB_1RA---_------_------_------", false);
+        assert!(mirrored
+            .contains("Normalized: 1RB1LB_1LA---_------_------_------ (directions reversed)"));
+        let renamed = report_of("1RC---_------_1LA---_------_------", false);
+        assert!(
+            renamed.contains(
+                "Normalized: 1RB---_1LA---_------_------_------ (states renamed B->C C->B)"
+            ),
+            "{renamed}"
+        );
+        let runaway = report_of("1RB1RB_1RC1RC_1RD1RD_1RE1RE_1RA1RA", false);
+        assert!(runaway.contains("Transitions: 10 defined, 0 halting\n"));
+        assert!(runaway.contains("  Uses in the first 1000 steps:"));
+        assert!(runaway.contains("  undecided: reached the step limit of 10000"));
+        let translated = report_of("1RB1LB_1LA0RB_------_------_------", false);
+        assert!(translated.contains("  runs forever: not every state was visited within 107 steps"));
+    }
+
+    #[test]
+    fn markdown() {
+        let halting = report_of("1RB1LB_1LA---_------_------_------", true);
+        assert!(halting.contains("## `1RB1LB_1LA---_------_------_------` halts after 6 steps"));
+        assert!(halting.contains("| 6 | B | -1 | 4 |"));
+        // Proved by the segment simulator.
+        let translated = report_of("1RB1LB_1LA0RB_------_------_------", true);
+        assert!(translated.contains("## `1RB1LB_1LA0RB_------_------_------` runs forever"));
+        assert!(translated.contains("No rule halts, so the machine runs forever."));
+        // Neither decider proves Skelet's first machine.
+        let skelet = report_of("1RB1RD_1LC0RC_1RA1LD_0RE0LB_---1RC", true);
+        assert!(skelet.ends_with("\nNo proof found for a sketch.\n"));
+    }
+}
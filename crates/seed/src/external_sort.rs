@@ -0,0 +1,199 @@
+// Sorting fixed size records that do not fit into memory.
+//
+// Seed databases, the entries of logs and the lists derived from them can be larger than memory. The records are read in chunks that fit into the memory budget. Every chunk is sorted and written to a temporary file, a run. The runs are then merged. When there are more runs than can be open at once, groups of runs are merged into longer runs first. Records are ordered by their bytes.
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+/// Most runs that are merged at once. Every run needs an open file and a read buffer.
+const MAX_MERGE_WIDTH: usize = 128;
+
+/// External merge sort of records of `N` bytes.
+pub struct Sorter<const N: usize> {
+    memory: usize,
+    temp_prefix: PathBuf,
+    dedup: bool,
+}
+
+impl<const N: usize> Sorter<N> {
+    /// Sort with chunks of up to `memory` bytes. The runs are written to temporary files whose paths start with `temp_prefix` and are removed when sorting ends. Duplicates are kept.
+    pub fn new(memory: usize, temp_prefix: &Path) -> Self {
+        Self {
+            memory,
+            temp_prefix: temp_prefix.to_owned(),
+            dedup: false,
+        }
+    }
+
+    /// Write only the first of equal records.
+    pub fn dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+
+    /// Sort `records` into `output`. `on_duplicate` is called for every removed duplicate. Returns the number of written records.
+    pub fn sort(
+        &self,
+        mut records: impl Iterator<Item = Result<[u8; N]>>,
+        output: &mut impl Write,
+        mut on_duplicate: impl FnMut(&[u8; N]),
+    ) -> Result<u64> {
+        let chunk_len = (self.memory / N).max(1);
+        let mut runs = Vec::<PathBuf>::new();
+        let mut chunk = Vec::<[u8; N]>::new();
+        let result = (|| {
+            loop {
+                chunk.clear();
+                for record in records.by_ref().take(chunk_len) {
+                    chunk.push(record?);
+                }
+                if chunk.is_empty() {
+                    break;
+                }
+                chunk.sort_unstable();
+                let path = self.run_path(runs.len());
+                runs.push(path.clone());
+                let mut file = create(&path)?;
+                for record in &chunk {
+                    file.write_all(record).context("write run")?;
+                }
+                file.flush().context("flush run")?;
+            }
+            drop(std::mem::take(&mut chunk));
+            let mut next_run = runs.len();
+            // Duplicates are only removed in the final merge so that `on_duplicate` sees each once.
+            while runs.len() > MAX_MERGE_WIDTH {
+                let group: Vec<PathBuf> = runs.drain(..MAX_MERGE_WIDTH).collect();
+                let path = self.run_path(next_run);
+                next_run += 1;
+                let mut file = create(&path)?;
+                runs.push(path);
+                let merged = merge(&group, &mut file, false, &mut |_: &[u8; N]| ())
+                    .and_then(|_| file.flush().context("flush run"));
+                for run in group {
+                    let _ = std::fs::remove_file(run);
+                }
+                merged?;
+            }
+            merge(&runs, output, self.dedup, &mut on_duplicate)
+        })();
+        for run in runs {
+            let _ = std::fs::remove_file(run);
+        }
+        result
+    }
+
+    fn run_path(&self, number: usize) -> PathBuf {
+        PathBuf::from(format!("{}{number}", self.temp_prefix.display()))
+    }
+}
+
+fn create(path: &Path) -> Result<BufWriter<std::fs::File>> {
+    let file = std::fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("create {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+fn merge<const N: usize>(
+    runs: &[PathBuf],
+    output: &mut impl Write,
+    dedup: bool,
+    on_duplicate: &mut impl FnMut(&[u8; N]),
+) -> Result<u64> {
+    let mut readers = runs
+        .iter()
+        .map(|path| {
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .open(path)
+                .with_context(|| format!("open {}", path.display()))?;
+            Ok(BufReader::new(file))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::<Reverse<([u8; N], usize)>>::new();
+    let mut record = [0u8; N];
+    let mut read_next = |heap: &mut BinaryHeap<_>, run: usize| -> Result<()> {
+        match readers[run].read_exact(&mut record) {
+            Ok(()) => heap.push(Reverse((record, run))),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => (),
+            Err(err) => return Err(err).context("read run"),
+        }
+        Ok(())
+    };
+    for run in 0..runs.len() {
+        read_next(&mut heap, run)?;
+    }
+    let mut written: u64 = 0;
+    let mut previous: Option<[u8; N]> = None;
+    while let Some(Reverse((record, run))) = heap.pop() {
+        if dedup && previous == Some(record) {
+            on_duplicate(&record);
+        } else {
+            output.write_all(&record).context("write record")?;
+            written += 1;
+            previous = Some(record);
+        }
+        read_next(&mut heap, run)?;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use busy_beaver::stress::Rng;
+
+    use super::*;
+
+    #[test]
+    fn sort_in_many_runs() {
+        let directory =
+            std::env::temp_dir().join(format!("seed-external-sort-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut rng = Rng::new(0);
+        let records: Vec<[u8; 3]> = (0..5000)
+            .map(|_| {
+                [
+                    rng.below(4) as u8,
+                    rng.below(256) as u8,
+                    rng.below(256) as u8,
+                ]
+            })
+            .collect();
+        let mut expected = records.clone();
+        expected.sort_unstable();
+
+        // Two records per run give more runs than are merged at once.
+        let sorter = Sorter::<3>::new(6, &directory.join("run"));
+        let mut output = Vec::new();
+        let written = sorter
+            .sort(records.iter().copied().map(Ok), &mut output, |_| ())
+            .unwrap();
+        assert_eq!(written, records.len() as u64);
+        assert_eq!(output, expected.concat());
+
+        let mut duplicates = 0;
+        let mut output = Vec::new();
+        let written = sorter
+            .dedup()
+            .sort(records.iter().copied().map(Ok), &mut output, |_| {
+                duplicates += 1
+            })
+            .unwrap();
+        expected.dedup();
+        assert_eq!(output, expected.concat());
+        assert_eq!(written, expected.len() as u64);
+        assert_eq!(written + duplicates, records.len() as u64);
+        // All runs were removed.
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
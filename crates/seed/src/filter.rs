@@ -0,0 +1,89 @@
+// Select machines by the pattern of their transitions.
+//
+// Families of machines often share transitions, for example a state that always moves in the same direction. Filtering a holdout set by such a pattern gives a smaller set that is easier to study. The output is a machine list that the other tools accept.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::{
+    format::{LogDecision, MachineList},
+    pattern::Pattern,
+    run::dead_transitions,
+    states::{State, States, Transition},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Pattern in the format of `busy_beaver::pattern`: a machine in the compact format in which any character can be `.` to match anything.
+    pattern: String,
+    /// Machines to filter in any format of `MachineList`.
+    #[arg(default_value = "log")]
+    input: PathBuf,
+    /// Index file selecting machines of the seed database given as input.
+    #[arg(long)]
+    index: Option<PathBuf>,
+    /// Only match machines with exactly this many halting transitions.
+    #[arg(long)]
+    halting: Option<usize>,
+    /// Only match machines with exactly this many halting transitions in a state, written as the state and the count like `E=1`. Can be given several times.
+    #[arg(long, value_parser = parse_halting_in)]
+    halting_in: Vec<(State<5>, usize)>,
+    /// Skip machines that a log marks as decided.
+    #[arg(long)]
+    undecided: bool,
+    /// Only match spurious machines: machines that define every transition but do not apply all of them within this many steps. They behave like the machine in which the unused transitions halt, at least for these steps.
+    #[arg(long)]
+    spurious: Option<u64>,
+}
+
+fn parse_halting_in(s: &str) -> Result<(State<5>, usize)> {
+    let (state, count) = s.split_once('=').context("expected `STATE=COUNT`")?;
+    let state = match state.as_bytes() {
+        [c] => State::new(c.wrapping_sub(b'A')),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("invalid state {state}"))?;
+    Ok((state, count.parse().context("invalid count")?))
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let mut pattern = Pattern::<5, 2>::parse(args.pattern.as_bytes()).context("invalid pattern")?;
+    if let Some(count) = args.halting {
+        pattern = pattern.halting(count);
+    }
+    for (state, count) in args.halting_in {
+        pattern = pattern.halting_in(state, count);
+    }
+    let machines = match &args.index {
+        Some(index) => MachineList::open_indexed(&args.input, index)?,
+        None => MachineList::open(&args.input)?,
+    };
+    let mut stdout = std::io::stdout().lock();
+    let (mut read, mut matched) = (0u64, 0u64);
+    for machine in machines {
+        let machine = machine.context("read machines")?;
+        if args.undecided && !matches!(machine.decision, None | Some(LogDecision::Undecided)) {
+            continue;
+        }
+        read += 1;
+        if pattern.matches(&machine.states)
+            && args
+                .spurious
+                .is_none_or(|steps| is_spurious(&machine.states, steps))
+        {
+            matched += 1;
+            writeln!(stdout, "{}", machine.states)?;
+        }
+    }
+    eprintln!("Matched {matched} of {read} machines.");
+    Ok(())
+}
+
+fn is_spurious(states: &States<5, 2>, steps: u64) -> bool {
+    states
+        .0
+        .iter()
+        .flatten()
+        .all(|t| matches!(t, Transition::Continue(_)))
+        && !dead_transitions(states, steps).transitions.is_empty()
+}
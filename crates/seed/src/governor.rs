@@ -0,0 +1,234 @@
+// Throttling of the worker threads so that a run can share a machine with other work.
+//
+// The governor limits how many worker threads take tasks and how many machines per second they enumerate. Both limits can change while the run is going, either from a schedule file or not at all. Parked threads give up their core entirely. The rate limit is applied by sleeping between machines, which is imprecise for single machines but accurate over seconds.
+
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+/// The limits that apply at some time of day.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Setting {
+    /// Number of worker threads that take tasks. `None` means all threads.
+    pub threads: Option<usize>,
+    /// Maximum number of machines enumerated per second by all threads together. `None` means unlimited.
+    pub max_rate: Option<u64>,
+}
+
+/// Settings that change with the time of day.
+///
+/// The file has one entry per line in the form `HH:MM threads [max-rate]`, where `threads` is a number or `all`. Times are UTC. An entry applies from its time until the time of the next entry. Before the first entry of the day the last entry of the previous day applies. Empty lines and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DaySchedule {
+    /// Minute of the day and the setting starting at that minute, sorted by minute.
+    entries: Vec<(u32, Setting)>,
+}
+
+impl DaySchedule {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_entry(line).with_context(|| format!("line {}", i + 1))?;
+            entries.push(entry);
+        }
+        entries.sort_by_key(|(minute, _)| *minute);
+        if entries.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(anyhow!("two entries have the same time"));
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let s = std::fs::read_to_string(path).context("read schedule file")?;
+        Self::parse(&s)
+    }
+
+    /// The setting at `minute` of the day. `None` if the schedule is empty.
+    pub fn at(&self, minute: u32) -> Option<Setting> {
+        let current = self
+            .entries
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= minute)
+            .or(self.entries.last())?;
+        Some(current.1)
+    }
+
+    /// The setting now.
+    pub fn now(&self) -> Option<Setting> {
+        let seconds = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.at(((seconds / 60) % (24 * 60)) as u32)
+    }
+}
+
+fn parse_entry(line: &str) -> Result<(u32, Setting)> {
+    let mut fields = line.split_whitespace();
+    let time = fields.next().context("missing time")?;
+    let (hours, minutes) = time.split_once(':').context("expected `HH:MM`")?;
+    let hours: u32 = hours.parse().context("invalid hours")?;
+    let minutes: u32 = minutes.parse().context("invalid minutes")?;
+    if hours >= 24 || minutes >= 60 {
+        return Err(anyhow!("invalid time {time}"));
+    }
+    let threads = match fields.next().context("missing thread count")? {
+        "all" => None,
+        threads => Some(parse_threads(threads)?),
+    };
+    let max_rate = fields
+        .next()
+        .map(|rate| rate.parse().context("invalid max rate"))
+        .transpose()?;
+    if fields.next().is_some() {
+        return Err(anyhow!("too many fields"));
+    }
+    Ok((hours * 60 + minutes, Setting { threads, max_rate }))
+}
+
+fn parse_threads(s: &str) -> Result<usize> {
+    match s.parse() {
+        Ok(0) => Err(anyhow!("need at least 1 thread")),
+        Ok(threads) => Ok(threads),
+        Err(_) => Err(anyhow!("invalid thread count")),
+    }
+}
+
+/// Limits shared by the main thread, which sets them, and the worker threads, which follow them.
+pub struct Governor {
+    thread_count: usize,
+    threads: AtomicUsize,
+    /// 0 means unlimited.
+    max_rate: AtomicU64,
+}
+
+impl Governor {
+    pub fn new(thread_count: usize) -> Self {
+        Self {
+            thread_count,
+            threads: AtomicUsize::new(thread_count),
+            max_rate: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set(&self, setting: Setting) {
+        let threads = setting
+            .threads
+            .unwrap_or(self.thread_count)
+            .clamp(1, self.thread_count);
+        self.threads.store(threads, Ordering::Relaxed);
+        self.max_rate
+            .store(setting.max_rate.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn setting(&self) -> Setting {
+        let threads = self.threads.load(Ordering::Relaxed);
+        let max_rate = self.max_rate.load(Ordering::Relaxed);
+        Setting {
+            threads: (threads != self.thread_count).then_some(threads),
+            max_rate: (max_rate != 0).then_some(max_rate),
+        }
+    }
+
+    /// Whether the worker thread with this index has to stay parked.
+    pub fn is_parked(&self, thread: usize) -> bool {
+        thread >= self.threads.load(Ordering::Relaxed)
+    }
+
+    /// Called by a worker thread after every machine. Sleeps as long as needed to keep the thread's share of the maximum rate.
+    pub fn pace(&self, pace: &mut Pace) {
+        let max_rate = self.max_rate.load(Ordering::Relaxed);
+        if max_rate == 0 {
+            return;
+        }
+        let threads = self.threads.load(Ordering::Relaxed);
+        pace.count += 1;
+        let due = Duration::from_secs_f64(pace.count as f64 * threads as f64 / max_rate as f64);
+        let elapsed = pace.start.elapsed();
+        if due > elapsed {
+            std::thread::sleep(due - elapsed);
+        }
+        // Start a new window regularly so that changes of the setting take effect and time spent without machines, like waiting for tasks, does not build up a credit.
+        if elapsed > Pace::WINDOW {
+            *pace = Pace::default();
+        }
+    }
+}
+
+/// Per thread state of `Governor::pace`.
+pub struct Pace {
+    start: Instant,
+    count: u64,
+}
+
+impl Pace {
+    const WINDOW: Duration = Duration::from_secs(10);
+}
+
+impl Default for Pace {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            count: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day_schedule() {
+        let schedule = DaySchedule::parse("# weekdays\n22:00 all\n08:30 2 5000\n").unwrap();
+        let day = Setting {
+            threads: Some(2),
+            max_rate: Some(5000),
+        };
+        let night = Setting {
+            threads: None,
+            max_rate: None,
+        };
+        assert_eq!(schedule.at(8 * 60 + 30), Some(day));
+        assert_eq!(schedule.at(21 * 60 + 59), Some(day));
+        assert_eq!(schedule.at(22 * 60), Some(night));
+        // Before the first entry of the day the last entry of the previous day applies.
+        assert_eq!(schedule.at(60), Some(night));
+        assert_eq!(DaySchedule::default().at(0), None);
+
+        assert!(DaySchedule::parse("24:00 all").is_err());
+        assert!(DaySchedule::parse("10:00 0").is_err());
+        assert!(DaySchedule::parse("10:00 all\n10:00 2").is_err());
+    }
+
+    #[test]
+    fn governor() {
+        let governor = Governor::new(4);
+        governor.set(Setting {
+            threads: Some(8),
+            max_rate: None,
+        });
+        assert!(!governor.is_parked(3));
+        governor.set(Setting {
+            threads: Some(2),
+            max_rate: Some(100),
+        });
+        assert!(governor.is_parked(2));
+        assert_eq!(
+            governor.setting(),
+            Setting {
+                threads: Some(2),
+                max_rate: Some(100)
+            }
+        );
+    }
+}
@@ -0,0 +1,193 @@
+// Transition graph of a bounded run.
+//
+// Understanding a holdout machine usually starts with finding out which transitions it actually uses and how that changes over the run. A raw trace is too long to read. The graph has a node for every state and an edge for every transition, weighted by how often the transition fired. Splitting the run into phases shows how the behavior changes, for example a machine that settles into a loop of a few transitions after an irregular start.
+//
+// With `--window` the graph is instead the configuration graph of `busy_beaver::configuration_graph`, which has a node for every configuration on a window of the tape.
+
+use std::{
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::{
+    configuration_graph::{explore, Config},
+    format::read_compact,
+    run::{Runner, SparseTape, StepResult, TransitionCounts},
+    states::Direction,
+};
+
+use crate::enumerate::{State, States, Symbol, Transition};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The machine in the compact format, like `1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA`.
+    machine: String,
+    /// Number of steps to simulate. The run stops earlier if the machine halts.
+    #[arg(long, default_value_t = 1_000_000)]
+    steps: u64,
+    /// Number of phases of equal length the run is split into. Edge labels show the count of each phase.
+    #[arg(long, default_value_t = 1)]
+    phases: u64,
+    /// File to write the DOT graph to. Defaults to stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Write the graph of the configurations on a window of this many cells instead, and report whether it is closed, which proves that the machine runs forever.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..64), conflicts_with = "phases")]
+    window: Option<u64>,
+    /// Cell of the window the head starts on with `--window`. Defaults to the middle.
+    #[arg(long, requires = "window")]
+    start: Option<usize>,
+    /// Stop the search of `--window` after this many configurations.
+    #[arg(long, default_value_t = 10_000, requires = "window")]
+    max_nodes: usize,
+}
+
+struct Usage {
+    /// Counts of each phase that was started.
+    phases: Vec<TransitionCounts<5, 2>>,
+    /// The halting transition and the step at which it was reached.
+    halted: Option<(State, Symbol, u64)>,
+}
+
+pub fn main(args: Args) -> Result<()> {
+    if args.phases == 0 || args.phases > args.steps {
+        return Err(anyhow!("need between 1 and `steps` phases"));
+    }
+    let states: States = read_compact(args.machine.as_bytes()).context("invalid machine")?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            std::fs::File::create(path).context("create output file")?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    if let Some(window) = args.window {
+        let window = window as usize;
+        let start = args.start.unwrap_or((window - 1) / 2);
+        if start >= window {
+            return Err(anyhow!("the start must be inside the window"));
+        }
+        let graph = explore(
+            &states,
+            Config {
+                window,
+                start,
+                max_nodes: args.max_nodes,
+            },
+        );
+        if !graph.complete {
+            eprintln!("Stopped after {} configurations.", graph.nodes.len());
+        } else if graph.is_closed() {
+            eprintln!(
+                "Closed with {} configurations. The machine runs forever.",
+                graph.nodes.len()
+            );
+        } else {
+            eprintln!(
+                "{} configurations, {} of which can halt.",
+                graph.nodes.len(),
+                graph.halting.len()
+            );
+        }
+        graph.write_dot(&mut output).context("write graph")?;
+        output.flush().context("flush output")?;
+        return Ok(());
+    }
+    let usage = simulate(&states, args.steps, args.phases);
+    match usage.halted {
+        Some((_, _, step)) => eprintln!("Halted after {step} steps."),
+        None => eprintln!("Did not halt within {} steps.", args.steps),
+    }
+
+    write_dot(&mut output, &states, &usage).context("write graph")?;
+    output.flush().context("flush output")?;
+    Ok(())
+}
+
+fn simulate(states: &States, steps: u64, phases: u64) -> Usage {
+    let mut runner = Runner::with_observer(SparseTape::unbounded(), TransitionCounts::default());
+    runner.set_states(states);
+    let mut usage = Usage {
+        phases: Vec::new(),
+        halted: None,
+    };
+    let mut step = 0;
+    for phase in 0..phases {
+        let end = steps * (phase + 1) / phases;
+        *runner.observer_mut() = TransitionCounts::default();
+        while step < end {
+            step += 1;
+            if let StepResult::Halt = runner.step() {
+                usage.halted = Some((runner.state(), runner.symbol(), step));
+                break;
+            }
+        }
+        usage.phases.push(*runner.observer());
+        if usage.halted.is_some() {
+            break;
+        }
+    }
+    usage
+}
+
+/// Edges are labeled like `0/1R`: the read symbol, the written symbol and the move, followed by the counts. Unused transitions are dashed. Edge width grows with the total count.
+fn write_dot(output: &mut impl Write, states: &States, usage: &Usage) -> Result<()> {
+    let total = |state: State, symbol: Symbol| -> u64 {
+        usage.phases.iter().map(|p| p.get(state, symbol)).sum()
+    };
+    let max = (0..5u8)
+        .flat_map(|state| (0..2u8).map(move |symbol| (state, symbol)))
+        .map(|(state, symbol)| total(State::new(state).unwrap(), Symbol::new(symbol).unwrap()))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    writeln!(output, "digraph transitions {{")?;
+    writeln!(output, "  node [shape=circle, fontname=monospace];")?;
+    writeln!(output, "  edge [fontname=monospace];")?;
+    for (state, transitions) in states.0.iter().enumerate() {
+        let state = State::new(state as u8).unwrap();
+        let name = char::from(b'A' + state.get());
+        for (symbol, transition) in transitions.iter().enumerate() {
+            let symbol = Symbol::new(symbol as u8).unwrap();
+            let Transition::Continue(t) = transition else {
+                if usage
+                    .halted
+                    .is_some_and(|(s, y, _)| (s, y) == (state, symbol))
+                {
+                    writeln!(output, "  halt [shape=doublecircle];")?;
+                    writeln!(
+                        output,
+                        "  {name} -> halt [label=\"{}\", color=red, penwidth=3];",
+                        symbol.get()
+                    )?;
+                }
+                continue;
+            };
+            let counts: Vec<String> = usage
+                .phases
+                .iter()
+                .map(|p| p.get(state, symbol).to_string())
+                .collect();
+            let count = total(state, symbol);
+            let style = if count == 0 {
+                "style=dashed, color=gray".to_string()
+            } else {
+                format!("penwidth={:.1}", 1. + 4. * count as f64 / max as f64)
+            };
+            let move_ = match t.move_ {
+                Direction::Left => 'L',
+                Direction::Right => 'R',
+            };
+            writeln!(
+                output,
+                "  {name} -> {} [label=\"{}/{}{move_}\\n{}\", {style}];",
+                char::from(b'A' + t.state.get()),
+                symbol.get(),
+                t.write.get(),
+                counts.join(" | ")
+            )?;
+        }
+    }
+    writeln!(output, "}}")?;
+    Ok(())
+}
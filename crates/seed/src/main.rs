@@ -1,10 +1,37 @@
+mod cluster;
+mod compare;
+mod diverge;
 mod enumerate;
+mod experiment;
+mod explain;
+mod external_sort;
+mod filter;
+mod governor;
+mod graph;
+mod mapped;
+mod ordinal;
+mod passes;
+mod proof;
+mod rank;
+mod resume;
+mod rotate;
+mod shard;
+mod skelet;
+mod slice;
+mod sort;
+mod symdiff;
+mod tree;
+mod verify;
+mod work_unit;
 
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     io::{BufWriter, Seek, SeekFrom, Write},
+    path::PathBuf,
+    process::ExitCode,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread::JoinHandle,
     time::{Duration, Instant},
@@ -12,23 +39,144 @@ use std::{
 
 use anyhow::{anyhow, Context, Result};
 use arrayvec::ArrayVec;
-use bincode::Options;
+use busy_beaver::{
+    format::{
+        open_log, read_log_header, write_log_header, write_seed_database, LogReader, LOG_ENTRY_LEN,
+        SEED_DATABASE_ENTRY_LEN, SEED_DATABASE_HEADER_LEN,
+    },
+    normalize::normalize,
+};
+use clap::{Parser, Subcommand};
 use crossbeam_queue::SegQueue;
 use enumerate::{
-    create_runner, decide, ChildNodes, Decision, HaltingTransitionIndex, Node, States, Transition,
+    create_runner, decide, decide_pausable, validate_node, ChildNodes, Decision,
+    HaltingTransitionIndex, Limits, LoopReason, Node, Paused, Schedule, ScheduleDecider, States,
+    Transition, UndecidedReason,
 };
+use governor::{DaySchedule, Governor, Pace, Setting};
+use resume::Resume;
+use rotate::RotatingLog;
 use serde::{Deserialize, Serialize};
+use verify::Verifier;
 
 type Task = (Node, HaltingTransitionIndex);
 type TaskResult = (States, Decision);
+/// Halting machine that visits the most cells and the number of cells.
+type SpaceChampion = (u64, States);
+
+/// Order in which worker threads take tasks from the global queue. See `RunArgs::task_order`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, clap::ValueEnum)]
+enum TaskOrder {
+    /// Oldest task first.
+    #[default]
+    Fifo,
+    /// Newest task first.
+    Lifo,
+    /// Any task with equal probability.
+    Random,
+}
+
+/// Storage of the global task queue for each `TaskOrder`. The default order uses a lock free queue. Worker threads only take a task after finishing the previous one, so the lock of the other orders is rarely contended.
+// There is only one queue so the size does not matter.
+#[allow(clippy::large_enum_variant)]
+enum Tasks {
+    Fifo(SegQueue<Task>),
+    Lifo(Mutex<Vec<Task>>),
+    /// The tasks and the state of `random_below`.
+    Random(Mutex<(Vec<Task>, u64)>),
+}
+
+/// The global task queue. It counts its tasks by their number of halting transitions, which shrinks with the depth in the tree, so that the stats show whether the run is in a broad shallow phase or grinding through few deep subtrees.
+struct TaskQueue {
+    queue: Tasks,
+    by_halting_transitions: [AtomicUsize; 11],
+}
+
+impl TaskQueue {
+    fn new(order: TaskOrder) -> Self {
+        let queue = match order {
+            TaskOrder::Fifo => Tasks::Fifo(SegQueue::new()),
+            TaskOrder::Lifo => Tasks::Lifo(Mutex::new(Vec::new())),
+            TaskOrder::Random => Tasks::Random(Mutex::new((Vec::new(), 0))),
+        };
+        Self {
+            queue,
+            by_halting_transitions: Default::default(),
+        }
+    }
+
+    fn push(&self, task: Task) {
+        self.by_halting_transitions[task.0.halting_transition_count() as usize]
+            .fetch_add(1, Ordering::Relaxed);
+        match &self.queue {
+            Tasks::Fifo(queue) => queue.push(task),
+            Tasks::Lifo(stack) => stack.lock().unwrap().push(task),
+            Tasks::Random(tasks) => tasks.lock().unwrap().0.push(task),
+        }
+    }
+
+    fn pop(&self) -> Option<Task> {
+        let task = match &self.queue {
+            Tasks::Fifo(queue) => queue.pop(),
+            Tasks::Lifo(stack) => stack.lock().unwrap().pop(),
+            Tasks::Random(tasks) => {
+                let (tasks, random) = &mut *tasks.lock().unwrap();
+                match tasks.len() {
+                    0 => None,
+                    len => Some(tasks.swap_remove(random_below(random, len))),
+                }
+            }
+        }?;
+        self.by_halting_transitions[task.0.halting_transition_count() as usize]
+            .fetch_sub(1, Ordering::Relaxed);
+        Some(task)
+    }
+
+    fn len(&self) -> usize {
+        match &self.queue {
+            Tasks::Fifo(queue) => queue.len(),
+            Tasks::Lifo(stack) => stack.lock().unwrap().len(),
+            Tasks::Random(tasks) => tasks.lock().unwrap().0.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of queued tasks for every number of halting transitions that has some. The counts are updated separately from the queue, so while worker threads are running they can be off by the tasks that are being pushed or popped.
+    fn histogram(&self) -> Vec<(usize, usize)> {
+        self.by_halting_transitions
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .enumerate()
+            .filter(|(_, count)| *count != 0)
+            .collect()
+    }
+
+    /// The remaining tasks in the order in which pushing them again restores the queue.
+    fn into_inner(self) -> Vec<Task> {
+        match self.queue {
+            Tasks::Fifo(queue) => queue.into_iter().collect(),
+            Tasks::Lifo(stack) => stack.into_inner().unwrap(),
+            Tasks::Random(tasks) => tasks.into_inner().unwrap().0,
+        }
+    }
+}
 
-/// Nodes with up to this many halting transitions are handled locally in thread. Other nodes are handled by the global task queue. The downside of a lower value is higher thread synchronization overhead and higher memory usage and a larger resume file. The upside of a lower value is that individual tasks finish quicker, which gives more fine-grained feedback.
-const MAX_LOCAL_HALTING_TRANSITIONS: u8 = 3;
+/// Nodes with up to `--local-halting-transitions` halting transitions are handled locally in thread. Other nodes are handled by the global task queue. The downside of a lower value is higher thread synchronization overhead and higher memory usage and a larger resume file. The upside of a lower value is that individual tasks finish quicker, which gives more fine-grained feedback and keeps all threads busy near the end of the run.
+const DEFAULT_LOCAL_HALTING_TRANSITIONS: u8 = 3;
+/// Largest value of `--local-halting-transitions`. It sets the capacity of the per thread stack.
+const MAX_LOCAL_HALTING_TRANSITIONS: u8 = 5;
+/// Halting transitions of the root of the sample subtree of `--calibrate`.
+const CALIBRATION_HALTING_TRANSITIONS: u8 = 6;
+/// Step limit of `--calibrate`. With the run's limit the undecided machines would make the sample take minutes. With this limit it takes seconds per value.
+const CALIBRATION_STEPS: u64 = 10_000;
 
-/// One line in the log file is this many bytes including the newline character.
-const LOG_ENTRY_LEN: usize = 37;
+/// The stats are printed every second for this long after the start of a session. Afterwards the interval doubles with every line up to `--stats-interval`.
+const STATS_WARMUP: Duration = Duration::from_secs(300);
 
-#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 struct Stats {
     halt: u64,
     loop_: u64,
@@ -40,68 +188,531 @@ impl Stats {
     fn total(&self) -> u64 {
         self.halt + self.loop_ + self.undecided + self.irrelevant
     }
+
+    /// Describe every category in which `self` differs from `expected`.
+    fn discrepancies(&self, expected: &Stats) -> Vec<String> {
+        [
+            ("halt", self.halt, expected.halt),
+            ("loop", self.loop_, expected.loop_),
+            ("undecided", self.undecided, expected.undecided),
+            ("irrelevant", self.irrelevant, expected.irrelevant),
+            ("total", self.total(), expected.total()),
+        ]
+        .into_iter()
+        .filter(|(_, actual, expected)| actual != expected)
+        .map(|(name, actual, expected)| {
+            let difference = actual as i64 - expected as i64;
+            format!("{name}: {actual} instead of {expected} ({difference:+})")
+        })
+        .collect()
+    }
 }
 
-/// Resume data saved on disk.
-#[derive(Default, Serialize, Deserialize)]
-struct Resume {
-    stats: Stats,
-    tasks: Vec<Task>,
+/// Stats of a complete run with the default limits as published for the original seed run.
+const PUBLISHED_STATS: Stats = Stats {
+    halt: 34104723,
+    loop_: 2711166,
+    undecided: 88664064,
+    irrelevant: 944579,
+};
+
+/// Distributions of the results, for the report at the end of a run.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+struct Distributions {
+    /// Bucket `i` counts the halting machines whose step count is in `2^i..2^(i+1)`. Trailing empty buckets are left out.
+    halt_steps: Vec<u64>,
+    /// Looping machines by `LoopReason`, in the order of `LoopReason::ALL`.
+    loop_reasons: [u64; LoopReason::ALL.len()],
 }
 
-fn main() -> Result<()> {
-    let bincode_config = bincode::options();
+/// Undecided machines by `UndecidedReason`, in the order of `UndecidedReason::ALL`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+struct UndecidedReasons([u64; UndecidedReason::ALL.len()]);
 
-    let mut resume_file = std::fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .read(true)
-        .open("resume")
-        .context("open resume file")?;
-    let resume_len = resume_file
-        .metadata()
-        .context("read resume file metadata")?
-        .len();
-    let mut log_file = std::fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open("log")
-        .context("open `log` file")?;
-    // Seek to the end because we want to append.
-    let log_len = log_file
-        .seek(SeekFrom::End(0))
-        .context("seek log file to end")?;
-
-    let mut resume: Resume = if resume_len == 0 {
-        println!("Resume file has been newly created or was blank. Starting new run.");
-        Resume::default()
-    } else {
-        println!("Resume file exists. Continuing previous run.\nReading resume file.");
-        bincode_config
-            .deserialize_from(&resume_file)
-            .context("deserialize resume file")?
-    };
+impl UndecidedReasons {
+    fn add(&mut self, decision: Decision) {
+        if let Decision::Undecided(reason) = decision {
+            let index = UndecidedReason::ALL
+                .iter()
+                .position(|r| *r == reason)
+                .unwrap();
+            self.0[index] += 1;
+        }
+    }
 
-    if (resume.stats.total() == 0) != (resume.tasks.is_empty()) {
-        return Err(anyhow!("Resume file stats disagrees with resume file task list about whether this is a fresh run. Try deleting the resume fiel and the log file."));
+    fn print(&self) {
+        println!("Undecided machines by the limit they reached:");
+        for (reason, count) in UndecidedReason::ALL.iter().zip(self.0) {
+            println!("{:>15}: {count}", reason.name());
+        }
     }
-    let expected_log_len = resume.stats.total() * LOG_ENTRY_LEN as u64;
-    if log_len != expected_log_len {
-        return Err(anyhow!("The expected number of entries in the log file based on the stats in the resume file do not match the actual number of of entries. Try deleting the resume file and the log file."));
+}
+
+impl Distributions {
+    fn add(&mut self, decision: Decision) {
+        match decision {
+            Decision::Halt(_, steps) => {
+                let bucket = steps.max(1).ilog2() as usize;
+                if self.halt_steps.len() <= bucket {
+                    self.halt_steps.resize(bucket + 1, 0);
+                }
+                self.halt_steps[bucket] += 1;
+            }
+            Decision::Loop(reason) => {
+                let index = LoopReason::ALL.iter().position(|r| *r == reason).unwrap();
+                self.loop_reasons[index] += 1;
+            }
+            Decision::Undecided(_) | Decision::Irrelevant => (),
+        }
+    }
+
+    fn print(&self) {
+        println!("Halting machines by step count:");
+        for (bucket, count) in self.halt_steps.iter().enumerate() {
+            if *count != 0 {
+                let (start, end) = (1u128 << bucket, (1u128 << (bucket + 1)) - 1);
+                println!("{start:>12} to {end:>12} steps: {count}");
+            }
+        }
+        println!("Looping machines by reason:");
+        for (reason, count) in LoopReason::ALL.iter().zip(self.loop_reasons) {
+            println!("{:>15}: {count}", reason.name());
+        }
     }
+}
+
+#[derive(Parser)]
+#[command(
+    about = "Reproduce Bbchallenge's seed run. Without a subcommand the run is started or resumed."
+)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
+    /// Machines that do not halt within this many steps are undecided. Defaults to the seed run's value. A run has to be resumed with the limits it was started with.
+    #[arg(long)]
+    limit_steps: Option<u64>,
+    /// Machines that use more than this many cells in either direction are undecided. Defaults to the seed run's value.
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    limit_memory: Option<usize>,
+    /// Machines that run for more than this many steps without visiting every state do not halt. Defaults to the step count of the BB(4) champion.
+    #[arg(long)]
+    bb4_steps: Option<u64>,
+    /// Comma separated step budgets after which machines that are still running are checked. The step limit is always the last budget.
+    #[arg(long, value_delimiter = ',', conflicts_with = "first_budget")]
+    budgets: Vec<u64>,
+    /// Use exponentially growing step budgets starting at this value.
+    #[arg(long)]
+    first_budget: Option<u64>,
+    /// Factor between consecutive budgets when using `--first-budget`.
+    #[arg(long, default_value_t = 10, requires = "first_budget")]
+    budget_factor: u64,
+    /// Comma separated deciders that are applied between the budgets. Without budgets there is a single phase and they are not applied.
+    #[arg(long, value_delimiter = ',', default_values_t = ScheduleDecider::ALL)]
+    deciders: Vec<ScheduleDecider>,
+    /// Find the halting machine that visits the most cells. Only machines enumerated in this session are considered.
+    #[arg(long)]
+    track_space: bool,
+    /// Append machines that halt after more than this many steps to the file given by `--halting-file` together with their step count.
+    #[arg(long)]
+    collect_halting: Option<u64>,
+    /// File for `--collect-halting`.
+    #[arg(long, default_value = "halting", requires = "collect_halting")]
+    halting_file: PathBuf,
+    /// Append undecided machines to the file given by `--undecided-file` together with the limit they reached: `steps`, `left space` or `right space`.
+    #[arg(long)]
+    collect_undecided: bool,
+    /// File for `--collect-undecided`.
+    #[arg(long, default_value = "undecided", requires = "collect_undecided")]
+    undecided_file: PathBuf,
+    /// Append one in this many machines to the file given by `--sample-file` together with the decision and, for halting machines, the step count, or else the reason. Machines are chosen by a hash so that every run and every resumed session chooses the same machines.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    sample: Option<u64>,
+    /// File for `--sample`.
+    #[arg(long, default_value = "sample", requires = "sample")]
+    sample_file: PathBuf,
+    /// Number of worker threads that take tasks. Defaults to all cores.
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Maximum number of machines enumerated per second. Worker threads sleep between machines to stay below it.
+    #[arg(long)]
+    max_rate: Option<u64>,
+    /// File with thread counts and maximum rates that change with the time of day, for example to share a workstation during the day and run at full speed at night. See `governor::DaySchedule` for the format.
+    #[arg(long, conflicts_with_all = ["threads", "max_rate"])]
+    governor_schedule: Option<PathBuf>,
+    /// Write a proof that can be spot-checked with the `proof` subcommand for every machine that halts after more than this many steps. Proofs are written to the directory `proofs`.
+    #[arg(long)]
+    proof_steps: Option<u64>,
+    /// Also send every log entry to this Unix socket, named pipe or file while running, so that other programs can process results live. Entries are sent in batches at least once per second. If the receiver goes away the run continues without streaming.
+    #[arg(long)]
+    stream: Option<PathBuf>,
+    /// If the log or the sample file has more entries than the resume file accounts for, remove the excess entries instead of refusing to start. This happens when the program is killed after writing the log but before writing the resume file. The removed machines are enumerated again because their tasks are still in the resume file.
+    #[arg(long)]
+    repair: bool,
+    /// Decide this fraction of the results again with a simple reference simulation in a background thread, for example 0.001, and stop the run at the first mismatch. Samples are skipped while the verifier is busy so verification does not slow down the run.
+    #[arg(long)]
+    verify: Option<f64>,
+    /// Enumerate this many million machines without reading or writing the resume file and the log file, then estimate the duration and output sizes of a complete run with the given settings.
+    #[arg(long, conflicts_with_all = ["collect_halting", "collect_undecided", "sample", "proof_steps", "stream", "repair", "check_against_published"])]
+    dry_run: Option<f64>,
+    /// Form in which machines are written to the log and the stream. `normalized` writes the form of `busy_beaver::normalize`, which does not depend on the order in which the enumeration defines transitions, so logs of different enumeration strategies can be compared directly. The form is saved in the resume file and resuming with a different form is refused.
+    #[arg(long, value_enum, default_value_t = LogForm::Enumerated)]
+    log_form: LogForm,
+    /// Split the log into files of this many entries, named `log.0000`, `log.0001` and so on. The file `log` becomes a manifest listing the files. An existing log becomes the first file. Given again on a rotated log, changes the size of the current and later files.
+    #[arg(long, conflicts_with = "dry_run", value_parser = clap::value_parser!(u64).range(1..))]
+    rotate_log: Option<u64>,
+    /// Plan written by the `plan-shards` subcommand. A new run enumerates only the shard given by `--shard` instead of the whole tree. Resuming continues the shard.
+    #[arg(long, requires = "shard", conflicts_with = "dry_run")]
+    shard_plan: Option<PathBuf>,
+    /// Index of the shard in `--shard-plan`, starting at 0.
+    #[arg(long, requires = "shard_plan")]
+    shard: Option<u32>,
+    /// Add the most recent undecided machine and the deepest task that was queued since the previous stats line to the stats lines, for a sense of where in the tree the run is.
+    #[arg(long)]
+    show_machines: bool,
+    /// Longest interval between stats lines in seconds. The stats are printed every second for the first minutes of a session, then the interval doubles up to this value so that long runs do not fill the terminal.
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    stats_interval: u64,
+    /// Nodes with up to this many halting transitions are enumerated by the thread that created them instead of going through the task queue. Defaults to 3.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(2..=MAX_LOCAL_HALTING_TRANSITIONS as i64))]
+    local_halting_transitions: Option<u8>,
+    /// Order in which worker threads take tasks from the global queue. `lifo` takes the newest task, which finishes subtrees before starting new ones and keeps the queue and the resume file small. `fifo` takes the oldest task, which covers the tree broadly early in the run. `random` takes any task, so that the results of an early stop are spread over the whole tree.
+    #[arg(long, value_enum, default_value_t = TaskOrder::Fifo)]
+    task_order: TaskOrder,
+    /// Before the run, enumerate a sample subtree with every value of `--local-halting-transitions` and use the fastest. The best value depends on the number of cores and the memory system. The sample's machines are not logged. Only the threshold is calibrated: `--task-order` is used as given. The sample runs with a step limit of at most 10,000, so its machines are far cheaper than the undecided machines of a run at the default limit and the fastest value for the sample is not always the fastest for the run.
+    #[arg(long, conflicts_with = "local_halting_transitions")]
+    calibrate: bool,
+    /// File for the report that is written when the run completes, with the stats, the limits, the duration and a hash of the log as JSON.
+    #[arg(long, default_value = "report.json")]
+    report: PathBuf,
+    /// When the run completes, compare the stats with the published stats of the original seed run and fail if they differ. Only meaningful with the default limits.
+    #[arg(long)]
+    check_against_published: bool,
+}
+
+impl RunArgs {
+    fn limits(&self) -> Limits {
+        let default = Limits::default();
+        Limits {
+            steps: self.limit_steps.unwrap_or(default.steps),
+            memory: self.limit_memory.unwrap_or(default.memory),
+            bb4_steps: self.bb4_steps.unwrap_or(default.bb4_steps),
+        }
+    }
+
+    fn schedule(&self, limits: &Limits) -> Result<Schedule> {
+        let schedule = match self.first_budget {
+            Some(first) => Schedule::exponential(first, self.budget_factor, limits),
+            None => Schedule::new(&self.budgets, limits),
+        }?;
+        Ok(schedule.with_deciders(&self.deciders))
+    }
+}
 
+#[derive(Subcommand)]
+enum Command {
+    /// Group the undecided machines of a log by how they behave in their first steps.
+    Cluster(cluster::Args),
+    /// Check that the undecided machines of a log are exactly the machines of a sorted seed database.
+    Compare(compare::Args),
+    /// Find the first entry where two logs differ.
+    Diverge(diverge::Args),
+    /// Execute the runs of an experiment config file, each in its own directory.
+    Experiment(experiment::Args),
+    /// Report the normal form, reachable states, transitions, decision and score of one machine.
+    Explain(explain::Args),
+    /// Print the machines that match a pattern of transitions.
+    Filter(filter::Args),
+    /// Export the transitions a machine uses in a bounded run as a DOT graph.
+    Graph(graph::Args),
+    /// Map between machines and their position in the enumeration order.
+    Ordinal(ordinal::Args),
+    /// Decide holdouts in passes with growing step limits and segment windows.
+    Passes(passes::Args),
+    /// Record and check proofs of long halting runs.
+    Proof(proof::Args),
+    /// Order undecided machines by an estimate of how hard they are to decide.
+    Rank(rank::Args),
+    /// Split the enumeration into shards of similar estimated work for `--shard-plan`.
+    PlanShards(shard::Args),
+    /// Run the deciders on Skelet's machines and report which are decided.
+    Skelet(skelet::Args),
+    /// Copy some machines of a seed database into a new database.
+    Slice(slice::Args),
+    /// Check that a seed database is sorted and deduplicated, and optionally repair it.
+    Sort(sort::Args),
+    /// Print the machines that are in exactly one of two machine lists.
+    Symdiff(symdiff::Args),
+    /// Export the first levels of the enumeration tree as DOT or JSON.
+    Tree(tree::Args),
+    /// Create, run, validate and merge self-contained work units.
+    Unit(work_unit::Args),
+}
+
+/// See `RunArgs::log_form`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, clap::ValueEnum)]
+enum LogForm {
+    /// As enumerated, which is tree normal form.
+    Enumerated,
+    Normalized,
+}
+
+impl LogForm {
+    /// The name on the command line.
+    fn name(self) -> &'static str {
+        match self {
+            LogForm::Enumerated => "enumerated",
+            LogForm::Normalized => "normalized",
+        }
+    }
+}
+
+/// Copy of the log entries sent to another process. See `RunArgs::stream`.
+struct Stream {
+    writer: BufWriter<Box<dyn Write>>,
+    last_flush: Instant,
+}
+
+impl Stream {
+    fn open(path: &std::path::Path) -> Result<Self> {
+        #[cfg(unix)]
+        let writer: Box<dyn Write> = {
+            use std::os::unix::fs::FileTypeExt;
+            let is_socket = std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket());
+            if is_socket {
+                Box::new(std::os::unix::net::UnixStream::connect(path).context("connect socket")?)
+            } else {
+                Box::new(
+                    std::fs::OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(path)?,
+                )
+            }
+        };
+        #[cfg(not(unix))]
+        let writer: Box<dyn Write> = Box::new(
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?,
+        );
+        Ok(Self {
+            writer: BufWriter::new(writer),
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Streaming is optional so an error ends streaming instead of the run.
+    fn write(stream: &mut Option<Self>, states: &States, code: char) {
+        let Some(stream_) = stream else {
+            return;
+        };
+        let mut write = || -> std::io::Result<()> {
+            writeln!(stream_.writer, "{states} {code}")?;
+            if stream_.last_flush.elapsed() >= Duration::from_secs(1) {
+                stream_.writer.flush()?;
+                stream_.last_flush = Instant::now();
+            }
+            Ok(())
+        };
+        if let Err(err) = write() {
+            println!("Stopped streaming: {err}");
+            *stream = None;
+        }
+    }
+}
+
+/// Check resumed tasks against the enumeration invariants and remove duplicates.
+///
+/// A corrupt resume file would otherwise feed invalid nodes into code that relies on the invariants for unchecked indexing. Invalid tasks cannot be repaired because we do not know which part of the tree they were supposed to cover. They are reported and the run is not started. Duplicate tasks would enumerate the same subtree twice. They are removed.
+fn validate_tasks(tasks: &mut Vec<Task>) -> Result<()> {
+    const MAX_REPORTED: usize = 10;
+    let invalid: Vec<String> = tasks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (node, branch))| {
+            let err = validate_node(node, *branch).err()?;
+            Some(format!(
+                "task {i}, machine {}, branch {branch:?}: {err}",
+                node.0
+            ))
+        })
+        .collect();
+    if !invalid.is_empty() {
+        let mut message = format!("{} of {} tasks are invalid.", invalid.len(), tasks.len());
+        for line in invalid.iter().take(MAX_REPORTED) {
+            message.push('\n');
+            message.push_str(line);
+        }
+        if invalid.len() > MAX_REPORTED {
+            message.push_str("\n...");
+        }
+        return Err(anyhow!(message));
+    }
+
+    let len = tasks.len();
+    let mut seen = HashSet::with_capacity(len);
+    tasks.retain(|task| seen.insert(*task));
+    let duplicates = len - tasks.len();
+    if duplicates != 0 {
+        println!("Removed {duplicates} duplicate tasks from the resume file.");
+    }
+    Ok(())
+}
+
+/// Whether `--sample one_in` chooses the machine. The hash is of the database encoding so that it does not depend on the build or the platform.
+fn is_sampled(states: &States, one_in: u64) -> bool {
+    sample_hash(&write_seed_database(states)).is_multiple_of(one_in)
+}
+
+/// Next index below `len` of the SplitMix64 sequence with `state`. The random task order only has to spread the tasks, so this small generator is enough.
+fn random_below(state: &mut u64, len: usize) -> usize {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    ((z as u128 * len as u128) >> 64) as usize
+}
+
+/// Hash of `bytes` that chooses the sampled machines. Every byte is mixed in with the SplitMix64 finalizer. The function must never change: resumed sessions and other runs compare their samples with files written by earlier versions, so a different hash would silently choose different machines halfway through a run.
+fn sample_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |hash, byte| {
+        let mut z = (hash ^ *byte as u64).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    })
+}
+
+/// The last column of a line of the sample file: the step count of a halting machine or the reason of any other decision.
+fn sample_detail(decision: Decision) -> String {
+    match decision {
+        Decision::Halt(_, steps) => steps.to_string(),
+        Decision::Loop(reason) => reason.name().to_string(),
+        Decision::Undecided(reason) => reason.name().to_string(),
+        Decision::Irrelevant => "irrelevant".to_string(),
+    }
+}
+
+/// Truncate the log to `len` bytes, which must be a multiple of the entry length, after checking that the entry before the cut is intact. The log file is left positioned at its new end.
+fn repair_log(log: &mut std::fs::File, len: u64) -> Result<()> {
+    if len != 0 {
+        let mut entry = [0u8; LOG_ENTRY_LEN];
+        log.seek(SeekFrom::Start(len - LOG_ENTRY_LEN as u64))
+            .context("seek log file")?;
+        std::io::Read::read_exact(log, &mut entry).context("read log file")?;
+        if len != LOG_ENTRY_LEN as u64 || read_log_header(&entry).is_none() {
+            busy_beaver::format::read_log_entry(&entry)
+                .context("the last entry accounted for by the resume file is corrupt")?;
+        }
+    }
+    log.set_len(len).context("truncate log file")?;
+    log.seek(SeekFrom::End(0)).context("seek log file to end")?;
+    Ok(())
+}
+
+fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    let result = match args.command {
+        None => return run(args.run),
+        Some(Command::Cluster(args)) => cluster::main(args),
+        Some(Command::Compare(args)) => compare::main(args),
+        Some(Command::Diverge(args)) => diverge::main(args),
+        Some(Command::Experiment(args)) => experiment::main(args),
+        Some(Command::Explain(args)) => explain::main(args),
+        Some(Command::Filter(args)) => filter::main(args),
+        Some(Command::Graph(args)) => graph::main(args),
+        Some(Command::Ordinal(args)) => ordinal::main(args),
+        Some(Command::Passes(args)) => passes::main(args),
+        Some(Command::Proof(args)) => proof::main(args),
+        Some(Command::Rank(args)) => rank::main(args),
+        Some(Command::PlanShards(args)) => shard::main(args),
+        Some(Command::Skelet(args)) => skelet::main(args),
+        Some(Command::Slice(args)) => slice::main(args),
+        Some(Command::Sort(args)) => sort::main(args),
+        Some(Command::Symdiff(args)) => symdiff::main(args),
+        Some(Command::Tree(args)) => tree::main(args),
+        Some(Command::Unit(args)) => work_unit::main(args),
+    };
+    result.map(|()| ExitCode::SUCCESS)
+}
+
+/// Exit code of a run that was stopped before it was complete. Its progress is saved and it can be resumed. A complete run exits with 0 and an error with 1.
+const EXIT_INTERRUPTED: u8 = 3;
+
+fn run(args: RunArgs) -> Result<ExitCode> {
+    let limits = args.limits();
+    limits.validate().context("limits")?;
+    let schedule = args.schedule(&limits).context("step budgets")?;
+    let dry_run = args.dry_run.map(|millions| (millions * 1e6) as u64);
+    let shard = match (&args.shard_plan, args.shard) {
+        (Some(path), Some(index)) => {
+            let plan = shard::ShardPlan::read(path)?;
+            if plan.limits != limits {
+                return Err(anyhow!(
+                    "the shard plan was made with {:?} but the run uses {limits:?}",
+                    plan.limits
+                ));
+            }
+            if plan.budgets != schedule.budgets() {
+                return Err(anyhow!(
+                    "the shard plan was made with step budgets {:?} but the run uses {:?}",
+                    plan.budgets,
+                    schedule.budgets()
+                ));
+            }
+            // Without budgets the deciders are never applied, so they only have to match with budgets.
+            if !plan.budgets.is_empty() && plan.deciders != schedule.deciders() {
+                return Err(anyhow!(
+                    "the shard plan was made with deciders {:?} but the run uses {:?}",
+                    plan.deciders,
+                    schedule.deciders()
+                ));
+            }
+            Some(plan.shard(index)?)
+        }
+        _ => None,
+    };
+    let (mut resume_file, log_file, mut resume): (_, Box<dyn Write>, _) = match dry_run {
+        Some(_) => {
+            println!("Dry run. The resume file and the log file are not used.");
+            (None, Box::new(std::io::sink()), Resume::default())
+        }
+        None => {
+            let (resume_file, log_file, resume) = open_run_files(&args)?;
+            (Some(resume_file), log_file, resume)
+        }
+    };
+
+    // Shutdown has three levels. The first lets worker threads complete their current tasks. The second makes them abandon their current tasks after the machine they are simulating and put the tasks back into the queue, so that the tasks are saved in the resume file and enumerated again when the run is resumed. The third exits immediately without saving progress.
     let keep_running = Arc::new(AtomicBool::new(true));
-    let message = "Received request to terminate. Waiting for worker threads to complete their current tasks. This can take a minute. Request termination again to terminate immediately without saving progress.";
+    let abort = Arc::new(AtomicBool::new(false));
     ctrlc::set_handler({
         let keep_running = keep_running.clone();
-        let mut first_invocation = true;
+        let abort = abort.clone();
+        let mut invocations = 0;
         move || {
-            if first_invocation {
-                println!("{}", message);
-                keep_running.store(false, Ordering::Relaxed);
-                first_invocation = false;
-            } else {
-                std::process::exit(1);
+            invocations += 1;
+            match invocations {
+                1 => {
+                    println!("Received request to terminate. Waiting for worker threads to complete their current tasks. This can take a minute. Request termination again to abort the current tasks.");
+                    keep_running.store(false, Ordering::Relaxed);
+                }
+                2 => {
+                    println!("Aborting the current tasks. They are saved in the resume file and run again when resuming. Request termination again to terminate immediately without saving progress.");
+                    abort.store(true, Ordering::Relaxed);
+                }
+                _ => std::process::exit(1),
             }
         }
     })
@@ -110,16 +721,61 @@ fn main() -> Result<()> {
     // Physical instead of logical core count because in my testing scaling with logical cores falls off.
     let thread_count = num_cpus::get();
     println!("Using {thread_count} threads.");
+    let day_schedule = match &args.governor_schedule {
+        Some(path) => DaySchedule::read(path).context("governor schedule")?,
+        None => DaySchedule::default(),
+    };
+    let governor = Arc::new(Governor::new(thread_count));
+    governor.set(day_schedule.now().unwrap_or(Setting {
+        threads: args.threads,
+        max_rate: args.max_rate,
+    }));
+    let unlimited = Setting {
+        threads: None,
+        max_rate: None,
+    };
+    if governor.setting() != unlimited {
+        println!("Throttling to {:?}.", governor.setting());
+    }
+    println!(
+        "Using {limits:?}, step budgets {:?} and deciders {:?}.",
+        schedule.budgets(),
+        schedule.deciders()
+    );
 
     // This is the number of active worker threads. A worker thread is either active or inactive. It is active while it handling a task or fetching the next task. It is possible that it turns out that there is no next task but this is still counted as active. A thread is inactive while waiting for a new task to appear.
     //
     // Some threads might temporarily be inactive and become active again when another thread adds more work to the queue. When all threads are inactive we know that the queue is empty and will stay empty.
     let active_threads = Arc::new(AtomicUsize::new(thread_count));
     // Remaining work tasks. Worker threads take tasks from here and put new tasks back.
-    let tasks = Arc::new(SegQueue::<Task>::new());
+    let tasks = Arc::new(TaskQueue::new(args.task_order));
     // Result of a task. Worker threads put items on it and the main thread takes items from it.
     let results = Arc::new(SegQueue::<TaskResult>::new());
-    if resume.tasks.is_empty() {
+    // Simulations that were paused when the previous session was aborted. Worker threads take them when they reach their machine again.
+    if !resume.paused.is_empty() {
+        println!("Continuing {} paused simulations.", resume.paused.len());
+    }
+    let resumed = Arc::new(Mutex::new(
+        resume.paused.drain(..).collect::<HashMap<_, _>>(),
+    ));
+    // Simulations that are paused when this session is aborted.
+    let paused = Arc::new(SegQueue::<(States, Paused)>::new());
+    if let Some(shard) = shard.filter(|_| resume.tasks.is_empty()) {
+        println!(
+            "Starting shard {} with {} tasks.",
+            args.shard.unwrap(),
+            shard.tasks.len()
+        );
+        // The machines the planner enumerated are decided here and logged before the results of the worker threads.
+        let mut runner = create_runner(&limits);
+        for (states, changed) in shard.machines {
+            let decision = decide(&mut runner, &states, changed, &limits, &schedule);
+            results.push((states, decision));
+        }
+        for task in shard.tasks {
+            tasks.push(task);
+        }
+    } else if resume.tasks.is_empty() {
         tasks.push((Node::root(), HaltingTransitionIndex::root()));
         // Replace previous line with the following to test the run quickly completing.
         /*
@@ -145,105 +801,692 @@ fn main() -> Result<()> {
         resume.tasks.shrink_to_fit();
     }
 
+    let local_halting_transitions = if args.calibrate {
+        calibrate(
+            thread_count,
+            &governor,
+            &keep_running,
+            args.task_order,
+            limits,
+            &schedule,
+        )?
+    } else {
+        args.local_halting_transitions
+            .unwrap_or(DEFAULT_LOCAL_HALTING_TRANSITIONS)
+    };
+
+    // The deepest task that worker threads queued since the previous stats line.
+    let deepest_queued = args
+        .show_machines
+        .then(|| Arc::new(Mutex::new(None::<Task>)));
     let start = Instant::now();
-    let threads: Vec<JoinHandle<()>> = (0..thread_count)
-        .map(|_| {
+    let threads: Vec<JoinHandle<Option<SpaceChampion>>> = (0..thread_count)
+        .map(|thread| {
             let keep_running = keep_running.clone();
+            let abort = abort.clone();
+            let governor = governor.clone();
             let tasks = tasks.clone();
             let results = results.clone();
+            let resumed = resumed.clone();
+            let paused = paused.clone();
             let active_threads = active_threads.clone();
-            std::thread::spawn(|| thread_(keep_running, active_threads, tasks, results))
+            let schedule = schedule.clone();
+            let deepest_queued = deepest_queued.clone();
+            std::thread::spawn(move || {
+                thread_(
+                    thread,
+                    keep_running,
+                    abort,
+                    governor,
+                    active_threads,
+                    tasks,
+                    results,
+                    resumed,
+                    paused,
+                    deepest_queued,
+                    local_halting_transitions,
+                    limits,
+                    schedule,
+                    args.track_space,
+                )
+            })
         })
         .collect();
 
     let mut log_file = BufWriter::new(log_file);
-    let mut handle_result = |stats: &mut Stats, result: TaskResult| match result.1 {
-        Decision::Halt(_) => {
-            stats.halt += 1;
-            writeln!(&mut log_file, "{} h", result.0).unwrap();
+    let mut halting_file = match args.collect_halting {
+        Some(_) => Some(BufWriter::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&args.halting_file)
+                .context("open halting file")?,
+        )),
+        None => None,
+    };
+    let mut undecided_file = match args.collect_undecided {
+        true => Some(BufWriter::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&args.undecided_file)
+                .context("open undecided file")?,
+        )),
+        false => None,
+    };
+    let mut sample_file = match args.sample {
+        Some(_) => Some(BufWriter::new(open_sample_file(
+            &args.sample_file,
+            resume.sample_len,
+            args.repair,
+        )?)),
+        None => None,
+    };
+    let collect_halting = args.collect_halting.unwrap_or(u64::MAX);
+    let proof_steps = args.proof_steps.unwrap_or(u64::MAX);
+    if args.proof_steps.is_some() {
+        std::fs::create_dir_all("proofs").context("create proofs directory")?;
+    }
+    let mut stream = match &args.stream {
+        Some(path) => Some(Stream::open(path).context("open stream")?),
+        None => None,
+    };
+    let mut verifier = args
+        .verify
+        .map(|fraction| Verifier::start(fraction, limits, keep_running.clone(), abort.clone()));
+    let mut handle_result = |resume: &mut Resume, result: TaskResult| {
+        resume.distributions.add(result.1);
+        resume.undecided_reasons.add(result.1);
+        let stats = &mut resume.stats;
+        match result.1 {
+            Decision::Halt(_, steps) => {
+                stats.halt += 1;
+                if steps > collect_halting {
+                    if let Some(halting_file) = &mut halting_file {
+                        writeln!(halting_file, "{} {steps}", result.0).unwrap();
+                    }
+                }
+                if steps > proof_steps {
+                    let path = PathBuf::from(format!("proofs/{}.proof", result.0));
+                    // This runs the machine again on the main thread. Only few machines halt late so this does not slow down the run.
+                    let written = proof::write(&result.0, proof::DEFAULT_INTERVAL, steps, &path);
+                    if let Err(err) = written {
+                        println!("Could not write proof for {}: {err:#}", result.0);
+                    }
+                }
+            }
+            Decision::Loop(_) => stats.loop_ += 1,
+            Decision::Undecided(reason) => {
+                stats.undecided += 1;
+                if let Some(undecided_file) = &mut undecided_file {
+                    writeln!(undecided_file, "{} {}", result.0, reason.name()).unwrap();
+                }
+            }
+            Decision::Irrelevant => stats.irrelevant += 1,
         }
-        Decision::Loop => {
-            stats.loop_ += 1;
-            writeln!(&mut log_file, "{} l", result.0).unwrap();
+        let code = result.1.log_decision().code() as char;
+        let mut logged = result.0;
+        if args.log_form == LogForm::Normalized {
+            normalize(&mut logged);
         }
-        Decision::Undecided => {
-            stats.undecided += 1;
-            writeln!(&mut log_file, "{} u", result.0).unwrap();
+        writeln!(&mut log_file, "{logged} {code}").unwrap();
+        if let Some(sample_file) = &mut sample_file {
+            if is_sampled(&result.0, args.sample.unwrap()) {
+                writeln!(sample_file, "{logged} {code} {}", sample_detail(result.1)).unwrap();
+            }
         }
-        Decision::Irrelevant => {
-            stats.irrelevant += 1;
-            writeln!(&mut log_file, "{} i", result.0).unwrap();
+        Stream::write(&mut stream, &logged, code);
+        if let Some(verifier) = &mut verifier {
+            verifier.sample(&result.0, result.1);
         }
     };
 
     let start_total = resume.stats.total();
-    let print_stats = |stats: &Stats, task_queue_len: usize| {
+    let print_stats = |stats: &Stats, task_queue_len: usize, histogram: &[(usize, usize)]| {
         let elapsed = start.elapsed();
         let seconds_elapsed = elapsed.as_secs_f64();
         let total_enumerated = stats.total();
         let enumerated_per_second_this_run =
             (total_enumerated - start_total) as f64 / elapsed.as_secs_f64();
-        println!("seconds elapsed {seconds_elapsed:.1e}, task queue len {task_queue_len:.1e}, total enumerated {total_enumerated:.1e}, enumerated per second this run {enumerated_per_second_this_run:.1e}, {stats:?}");
+        print!("seconds elapsed {seconds_elapsed:.1e}, task queue len {task_queue_len:.1e}, total enumerated {total_enumerated:.1e}, enumerated per second this run {enumerated_per_second_this_run:.1e}, {stats:?}");
+        if !histogram.is_empty() {
+            let histogram: Vec<String> = histogram
+                .iter()
+                .map(|(halting, count)| format!("{halting}:{count}"))
+                .collect();
+            print!(
+                ", queued tasks by halting transitions {}",
+                histogram.join(" ")
+            );
+        }
+    };
+    let mut latest_undecided: Option<States> = None;
+    let print_machines = |latest_undecided: Option<States>| {
+        if let Some(deepest_queued) = &deepest_queued {
+            let latest = latest_undecided.map_or("none".to_string(), |m| m.to_string());
+            let deepest = match deepest_queued.lock().unwrap().take() {
+                Some((node, branch)) => format!(
+                    "{} branch {} depth {}",
+                    node.0,
+                    shard::format_transition_index(branch),
+                    node.depth()
+                ),
+                None => "none".to_string(),
+            };
+            print!(", latest undecided {latest}, deepest queued since the last line {deepest}");
+        }
+        println!();
     };
 
     println!("Printing initial stats.");
-    print_stats(&resume.stats, tasks.len());
+    print_stats(&resume.stats, tasks.len(), &tasks.histogram());
+    println!();
+    let max_stats_interval = Duration::from_secs(args.stats_interval);
+    let mut stats_interval = Duration::from_secs(1);
+    let mut last_stats = Instant::now();
+    let mut complete = false;
     while keep_running.load(Ordering::Relaxed) {
         while let Some(result) = results.pop() {
-            handle_result(&mut resume.stats, result);
+            if let Decision::Undecided(_) = result.1 {
+                latest_undecided = Some(result.0);
+            }
+            handle_result(&mut resume, result);
         }
 
         // TODO: Double check Ordering. Here and in the thread for this variable. Might have to be SeqCst.
         // TODO: Can't the worker threads check this condition on their own?
         if active_threads.load(Ordering::Relaxed) == 0 {
             keep_running.store(false, Ordering::Relaxed);
+            complete = true;
             println!("The run is complete. All machines have been enumerated.");
             break;
         }
+        if dry_run.is_some_and(|machines| resume.stats.total() >= machines) {
+            keep_running.store(false, Ordering::Relaxed);
+            abort.store(true, Ordering::Relaxed);
+            println!("Enumerated the machines of the dry run.");
+            break;
+        }
 
-        print_stats(&resume.stats, tasks.len());
+        if last_stats.elapsed() + Duration::from_millis(100) >= stats_interval {
+            print_stats(&resume.stats, tasks.len(), &tasks.histogram());
+            print_machines(latest_undecided);
+            last_stats = Instant::now();
+            stats_interval =
+                next_stats_interval(stats_interval, start.elapsed(), max_stats_interval);
+        }
+
+        if let Some(setting) = day_schedule.now() {
+            if setting != governor.setting() {
+                governor.set(setting);
+                println!("Throttling to {:?}.", governor.setting());
+            }
+        }
 
         std::thread::sleep(Duration::from_secs(1));
     }
 
-    for thread in threads {
-        thread.join().unwrap();
-    }
+    let space_champion = threads
+        .into_iter()
+        .filter_map(|thread| thread.join().unwrap())
+        .max_by_key(|(space, states)| (*space, std::cmp::Reverse(*states)));
     println!("Worker threads have finished.");
+    if let Some((space, states)) = space_champion {
+        println!("Space champion of this session: {states} visits {space} cells.");
+    }
 
     println!("Writing remaining logs.");
-    let tasks = Arc::into_inner(tasks).unwrap();
+    let tasks = Arc::into_inner(tasks).unwrap().into_inner();
     let results = Arc::into_inner(results).unwrap();
     for result in results.into_iter() {
-        handle_result(&mut resume.stats, result);
+        handle_result(&mut resume, result);
     }
     println!("Printing final stats.");
-    print_stats(&resume.stats, tasks.len());
+    print_stats(&resume.stats, tasks.len(), &[]);
+    println!();
+    resume.distributions.print();
+    resume.undecided_reasons.print();
+    if let Some(verifier) = &verifier {
+        println!(
+            "Verified {} results. Skipped {} samples while the verifier was busy.",
+            verifier.verified(),
+            verifier.dropped
+        );
+    }
     log_file.flush().context("flush log file")?;
+    if let Some(halting_file) = &mut halting_file {
+        halting_file.flush().context("flush halting file")?;
+    }
+    if let Some(undecided_file) = &mut undecided_file {
+        undecided_file.flush().context("flush undecided file")?;
+    }
+    if let Some(sample_file) = &mut sample_file {
+        sample_file.flush().context("flush sample file")?;
+        let len = sample_file
+            .get_ref()
+            .metadata()
+            .context("read sample file metadata")?
+            .len();
+        resume.sample_len = Some(len);
+    }
+    if let Some(stream) = &mut stream {
+        if let Err(err) = stream.writer.flush() {
+            println!("Stopped streaming: {err}");
+        }
+    }
 
+    let Some(resume_file) = &mut resume_file else {
+        print_estimate(&resume.stats, start.elapsed(), &limits);
+        return Ok(ExitCode::SUCCESS);
+    };
     println!("Writing resume file.");
     assert!(resume.tasks.is_empty());
-    resume.tasks.extend(tasks.into_iter());
-    resume_file.set_len(0).unwrap();
-    resume_file.seek(SeekFrom::Start(0)).unwrap();
-    bincode_config
-        .serialize_into(&resume_file, &resume)
-        .context("write resume file")?;
-    resume_file.flush().context("flush resume file")?;
+    resume.tasks.extend(tasks);
+    // Paused simulations whose tasks were not reached in this session are kept.
+    assert!(resume.paused.is_empty());
+    resume
+        .paused
+        .extend(Arc::into_inner(resumed).unwrap().into_inner().unwrap());
+    resume.paused.extend(Arc::into_inner(paused).unwrap());
+    if !resume.paused.is_empty() {
+        println!(
+            "Saving {} paused simulations. They continue where they stopped when resuming.",
+            resume.paused.len()
+        );
+    }
+    resume::write(resume_file, &resume)?;
+    if complete {
+        println!("Writing report.");
+        let report = CompletionReport::new(&resume, limits, &schedule, start.elapsed())?;
+        let json = serde_json::to_string_pretty(&report).context("serialize report")?;
+        std::fs::write(&args.report, json + "\n").context("write report")?;
+    }
+
+    if let Some(mismatch) = verifier.as_ref().and_then(Verifier::mismatch) {
+        return Err(anyhow!(
+            "Verification failed: {mismatch}. The log is suspect. The current tasks were saved in the resume file."
+        ));
+    }
+
+    if args.check_against_published {
+        if !complete {
+            println!("The run is not complete. Skipping the comparison with the published stats.");
+        } else {
+            if limits != Limits::default() {
+                println!("Warning: the limits differ from the seed run's. The stats are not expected to match.");
+            }
+            let discrepancies = resume.stats.discrepancies(&PUBLISHED_STATS);
+            if !discrepancies.is_empty() {
+                return Err(anyhow!(
+                    "Stats differ from the published stats of the seed run:\n{}",
+                    discrepancies.join("\n")
+                ));
+            }
+            println!("Stats match the published stats of the seed run.");
+        }
+    }
 
     println!("done");
 
+    Ok(match complete {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::from(EXIT_INTERRUPTED),
+    })
+}
+
+/// Summary of a complete run for scripts around long runs. Written to `--report`.
+#[derive(Debug, Serialize)]
+struct CompletionReport {
+    version: &'static str,
+    /// In hex like in the header of the log. `None` for runs started by older versions.
+    run_id: Option<String>,
+    limits: Limits,
+    budgets: Vec<u64>,
+    deciders: Vec<ScheduleDecider>,
+    stats: Stats,
+    total: u64,
+    /// Undecided machines by the `UndecidedReason` names.
+    undecided_reasons: BTreeMap<&'static str, u64>,
+    /// Duration of the session that completed the run. Earlier sessions are not included.
+    session_seconds: f64,
+    log_entries: u64,
+    /// Hash of the log entries in hex. It is the wrapping sum of the FNV-1a hashes of the entries, so it does not depend on the order in which threads, shards or the task order wrote them. The header is left out so that logs of separate runs with the same results have the same hash.
+    log_hash: String,
+}
+
+impl CompletionReport {
+    fn new(
+        resume: &Resume,
+        limits: Limits,
+        schedule: &Schedule,
+        session: Duration,
+    ) -> Result<Self> {
+        let (log_entries, log_hash) = hash_log(std::path::Path::new("log")).context("hash log")?;
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION"),
+            run_id: resume.run_id.map(|id| format!("{id:016x}")),
+            limits,
+            budgets: schedule.budgets().to_vec(),
+            deciders: schedule.deciders().to_vec(),
+            stats: resume.stats,
+            total: resume.stats.total(),
+            undecided_reasons: UndecidedReason::ALL
+                .iter()
+                .zip(resume.undecided_reasons.0)
+                .map(|(reason, count)| (reason.name(), count))
+                .collect(),
+            session_seconds: session.as_secs_f64(),
+            log_entries,
+            log_hash: format!("{log_hash:016x}"),
+        })
+    }
+}
+
+/// Number of entries of the log and the sum of their FNV-1a hashes, which is the same for every order of the entries.
+fn hash_log(path: &std::path::Path) -> Result<(u64, u64)> {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    let mut log = LogReader::new(open_log(path)?);
+    let mut hash: u64 = 0;
+    while let Some(entry) = log.next_raw()? {
+        let entry = entry.iter().fold(FNV_OFFSET, |h, byte| {
+            (h ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        });
+        hash = hash.wrapping_add(entry);
+    }
+    Ok((log.line(), hash))
+}
+
+/// The interval after a stats line was printed `elapsed` into the session.
+fn next_stats_interval(interval: Duration, elapsed: Duration, max: Duration) -> Duration {
+    if elapsed < STATS_WARMUP {
+        Duration::from_secs(1).min(max)
+    } else {
+        (interval * 2).min(max)
+    }
+}
+
+/// Extrapolate a complete run from the first machines of a dry run.
+fn print_estimate(stats: &Stats, elapsed: Duration, limits: &Limits) {
+    if stats.total() == 0 {
+        println!("No machines were enumerated. Nothing to estimate.");
+        return;
+    }
+    let rate = stats.total() as f64 / elapsed.as_secs_f64();
+    let share = |count: u64| count as f64 / stats.total() as f64;
+    println!(
+        "Dry run: {} machines in {:.1} s, {rate:.2e} machines per second. Halt {:.2}%, loop {:.2}%, undecided {:.2}%, irrelevant {:.2}%.",
+        stats.total(),
+        elapsed.as_secs_f64(),
+        share(stats.halt) * 100.,
+        share(stats.loop_) * 100.,
+        share(stats.undecided) * 100.,
+        share(stats.irrelevant) * 100.
+    );
+    // The size of the tree depends on the limits only through the machines that halt late, so the seed run's total is a good guess for other limits too.
+    let total = PUBLISHED_STATS.total();
+    let undecided = (total as f64 * share(stats.undecided)) as u64;
+    let gigabytes = |bytes: u64| bytes as f64 / 1e9;
+    println!(
+        "Estimate for a complete run of about {total} machines: {:.1} hours, log {:.1} GB, {undecided} undecided machines, seed database {:.1} GB.",
+        total as f64 / rate / 3600.,
+        gigabytes(total * LOG_ENTRY_LEN as u64),
+        gigabytes(
+            SEED_DATABASE_HEADER_LEN as u64 + undecided * SEED_DATABASE_ENTRY_LEN as u64
+        ),
+    );
+    println!("The first machines of the enumeration halt earlier and are decided faster than the average machine, so the duration is underestimated and the share of undecided machines can be off.");
+    if *limits != Limits::default() {
+        println!("The limits differ from the seed run's. The number of machines is taken from the seed run and differs somewhat.");
+    }
+}
+
+/// Open the resume file and the log file and check that they agree. An empty resume file starts a new run. The log file is positioned at its end.
+fn open_run_files(args: &RunArgs) -> Result<(std::fs::File, Box<dyn Write>, Resume)> {
+    let mut resume_file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .read(true)
+        .open("resume")
+        .context("open resume file")?;
+    let resume_len = resume_file
+        .metadata()
+        .context("read resume file metadata")?
+        .len();
+    let is_new = resume_len == 0;
+    let mut resume: Resume = if is_new {
+        println!("Resume file has been newly created or was blank. Starting new run.");
+        Resume {
+            run_id: Some(new_run_id()),
+            log_form: Some(args.log_form),
+            limits: Some(args.limits()),
+            ..Resume::default()
+        }
+    } else {
+        println!("Resume file exists. Continuing previous run.\nReading resume file.");
+        let mut resume = resume::read(&mut resume_file)?;
+        check_log_form(&mut resume, args.log_form)?;
+        check_limits(&mut resume, args.limits())?;
+        resume
+    };
+    validate_tasks(&mut resume.tasks).context("validate resume file")?;
+
+    if (resume.stats.total() == 0) != (resume.tasks.is_empty()) {
+        return Err(anyhow!("Resume file stats disagrees with resume file task list about whether this is a fresh run. Try deleting the resume fiel and the log file."));
+    }
+    let log_path = std::path::Path::new("log");
+    // The header is written below for a new run.
+    let header_len = match is_new {
+        true => 0,
+        false => {
+            check_run_id(resume.run_id, log_path)?;
+            resume.run_id.map_or(0, |_| LOG_ENTRY_LEN as u64)
+        }
+    };
+    let expected_log_len = header_len + resume.stats.total() * LOG_ENTRY_LEN as u64;
+    let mut log_file: Box<dyn Write> = if rotate::is_rotated(log_path)? {
+        let mut log = RotatingLog::open(log_path).context("open rotated log")?;
+        check_log_len(log.len(), expected_log_len, args.repair, |len| {
+            log.truncate(len)
+        })?;
+        if let Some(entries) = args.rotate_log {
+            log.set_entries_per_file(entries)?;
+        }
+        Box::new(log)
+    } else {
+        let mut log_file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(log_path)
+            .context("open `log` file")?;
+        // Seek to the end because we want to append.
+        let log_len = log_file
+            .seek(SeekFrom::End(0))
+            .context("seek log file to end")?;
+        check_log_len(log_len, expected_log_len, args.repair, |len| {
+            repair_log(&mut log_file, len)
+        })?;
+        match args.rotate_log {
+            Some(entries) => {
+                drop(log_file);
+                println!("Splitting the log into files of {entries} entries.");
+                Box::new(RotatingLog::convert(log_path, entries).context("rotate log")?)
+            }
+            None => Box::new(log_file),
+        }
+    };
+    if is_new {
+        println!("Run id {:016x}.", resume.run_id.unwrap());
+        log_file
+            .write_all(&write_log_header(resume.run_id.unwrap()))
+            .context("write log header")?;
+    }
+    Ok((resume_file, log_file, resume))
+}
+
+/// Refuse to continue a log in another form than it was started with. Resume files of older versions get `form`.
+fn check_log_form(resume: &mut Resume, form: LogForm) -> Result<()> {
+    match resume.log_form {
+        Some(saved) if saved != form => Err(anyhow!(
+            "the log has machines in the {} form but `--log-form {}` was given. Resume with `--log-form {}`",
+            saved.name(),
+            form.name(),
+            saved.name()
+        )),
+        Some(_) => Ok(()),
+        None => {
+            resume.log_form = Some(form);
+            Ok(())
+        }
+    }
+}
+
+/// The limits cannot change during a run because the log would mix results of both. Runs saved by older versions take the given limits.
+fn check_limits(resume: &mut Resume, limits: Limits) -> Result<()> {
+    match resume.limits {
+        Some(saved) if saved != limits => Err(anyhow!(
+            "the run was started with {saved:?} but {limits:?} were given. Resume with `--limit-steps {}`, `--limit-memory {}` and `--bb4-steps {}`",
+            saved.steps,
+            saved.memory,
+            saved.bb4_steps
+        )),
+        Some(_) => Ok(()),
+        None => {
+            resume.limits = Some(limits);
+            Ok(())
+        }
+    }
+}
+
+/// A random id for a new run. It only has to differ between runs, so an FNV-1a hash of the time and the process id is enough.
+fn new_run_id() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    let bytes = nanos
+        .to_le_bytes()
+        .into_iter()
+        .chain(std::process::id().to_le_bytes());
+    let id = bytes.fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    });
+    id.max(1)
+}
+
+/// Check that the log at `log_path` belongs to the same run as the resume file. Otherwise continuing would append to the log of another run, which goes unnoticed if the entry counts happen to match.
+fn check_run_id(run_id: Option<u64>, log_path: &std::path::Path) -> Result<()> {
+    let log_run_id = match open_log(log_path) {
+        Ok(log) => {
+            let mut reader = LogReader::new(log);
+            // A log that is too short for a header is reported by the length check.
+            let _ = reader.next_raw();
+            reader.run_id()
+        }
+        Err(_) => None,
+    };
+    match (run_id, log_run_id) {
+        (Some(a), Some(b)) if a != b => Err(anyhow!("The log belongs to run {b:016x} but the resume file to run {a:016x}. The files are from different runs.")),
+        (Some(a), None) => Err(anyhow!("The resume file belongs to run {a:016x} but the log has no header. The log is from a different run or was started by an older version.")),
+        (None, Some(b)) => Err(anyhow!("The log belongs to run {b:016x} but the resume file has no run id. The resume file is from a different run.")),
+        _ => Ok(()),
+    }
+}
+
+/// Compare the length of the log with the length that the resume file accounts for. `repair` truncates the log to the expected length.
+fn check_log_len(
+    log_len: u64,
+    expected_log_len: u64,
+    repair: bool,
+    truncate: impl FnOnce(u64) -> Result<()>,
+) -> Result<()> {
+    if log_len > expected_log_len && repair {
+        truncate(expected_log_len).context("repair log file")?;
+        println!(
+            "Removed {} bytes from the end of the log file that are not accounted for by the resume file.",
+            log_len - expected_log_len
+        );
+    } else if log_len > expected_log_len {
+        return Err(anyhow!("The log file has more entries than the stats in the resume file account for. This happens when the program is killed after writing the log file but before writing the resume file. Run with `--repair` to remove the excess entries."));
+    } else if log_len < expected_log_len {
+        return Err(anyhow!("The expected number of entries in the log file based on the stats in the resume file do not match the actual number of of entries. Try deleting the resume file and the log file."));
+    }
     Ok(())
 }
 
+/// Open the sample file for appending. `expected_len` is its length when the resume file was written. Lines after it are from a session that stopped without saving its progress. Their machines are enumerated and sampled again, so `repair` removes them like the excess entries of the log.
+fn open_sample_file(
+    path: &std::path::Path,
+    expected_len: Option<u64>,
+    repair: bool,
+) -> Result<std::fs::File> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("open sample file")?;
+    let Some(expected_len) = expected_len else {
+        return Ok(file);
+    };
+    let len = file.metadata().context("read sample file metadata")?.len();
+    if len > expected_len && repair {
+        file.set_len(expected_len).context("repair sample file")?;
+        println!(
+            "Removed {} bytes from the end of the sample file that are not accounted for by the resume file.",
+            len - expected_len
+        );
+    } else if len > expected_len {
+        return Err(anyhow!("The sample file has more lines than the resume file accounts for. This happens when the program is killed after writing the sample file but before writing the resume file. Run with `--repair` to remove the excess lines."));
+    } else if len < expected_len {
+        println!("The sample file is shorter than when the resume file was written. New lines are appended to it.");
+    }
+    Ok(file)
+}
+
+/// Results and new tasks of a task are only published when the task is complete. This way an aborted task can be put back into the queue without its machines being logged twice. A machine whose simulation is aborted after running for long is paused and goes to `paused`. `resumed` has the pauses of the previous session, which continue when their machine comes up again.
+#[allow(clippy::too_many_arguments)]
 fn thread_(
+    thread: usize,
     keep_running: Arc<AtomicBool>,
+    abort: Arc<AtomicBool>,
+    governor: Arc<Governor>,
     active_threads: Arc<AtomicUsize>,
-    tasks: Arc<SegQueue<Task>>,
+    tasks: Arc<TaskQueue>,
     results: Arc<SegQueue<TaskResult>>,
-) {
-    let mut runner = create_runner();
+    resumed: Arc<Mutex<HashMap<States, Paused>>>,
+    paused: Arc<SegQueue<(States, Paused)>>,
+    deepest_queued: Option<Arc<Mutex<Option<Task>>>>,
+    local_halting_transitions: u8,
+    limits: Limits,
+    schedule: Schedule,
+    track_space: bool,
+) -> Option<SpaceChampion> {
+    let mut runner = create_runner(&limits);
+    // Only look up paused simulations while there are some left.
+    let mut any_resumed = !resumed.lock().unwrap().is_empty();
+    let mut space_champion: Option<SpaceChampion> = None;
+    let mut task_results = Vec::<TaskResult>::new();
+    let mut task_tasks = Vec::<Task>::new();
+    let mut pace = Pace::default();
     'keep_running: while keep_running.load(Ordering::Relaxed) {
+        if governor.is_parked(thread) {
+            cold();
+            active_threads.fetch_sub(1, Ordering::Relaxed);
+            while governor.is_parked(thread) {
+                std::thread::sleep(Duration::from_secs(1));
+                if !keep_running.load(Ordering::Relaxed) {
+                    break 'keep_running;
+                }
+            }
+            active_threads.fetch_add(1, Ordering::Relaxed);
+            pace = Pace::default();
+            continue;
+        }
         let Some((mut node, branch)) = tasks.pop() else {
             cold();
             active_threads.fetch_sub(1, Ordering::Relaxed);
@@ -256,6 +1499,7 @@ fn thread_(
             active_threads.fetch_add(1, Ordering::Relaxed);
             continue;
         };
+        let task = (node, branch);
 
         let mut stack = ArrayVec::<_, { MAX_LOCAL_HALTING_TRANSITIONS as usize }>::new();
         let element = (ChildNodes::new(&node, branch), branch);
@@ -267,25 +1511,181 @@ fn thread_(
                 debug_assert!(result.is_some());
                 continue;
             };
+            if abort.load(Ordering::Relaxed) {
+                cold();
+                task_results.clear();
+                task_tasks.clear();
+                tasks.push(task);
+                break 'keep_running;
+            }
             *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
-            let decision = decide(&mut runner, &node.0, *branch);
-            results.push((node.0, decision));
-            if let Decision::Halt(branch) = decision {
+            let from = if any_resumed {
+                cold();
+                let mut resumed = resumed.lock().unwrap();
+                any_resumed = !resumed.is_empty();
+                resumed.remove(&node.0)
+            } else {
+                None
+            };
+            let decision = match decide_pausable(
+                &mut runner,
+                &node.0,
+                *branch,
+                &limits,
+                &schedule,
+                from.as_ref(),
+                &abort,
+            ) {
+                Ok(decision) => decision,
+                Err(pause) => {
+                    cold();
+                    paused.push((node.0, pause));
+                    task_results.clear();
+                    task_tasks.clear();
+                    tasks.push(task);
+                    break 'keep_running;
+                }
+            };
+            task_results.push((node.0, decision));
+            governor.pace(&mut pace);
+            if let Decision::Halt(branch, _) = decision {
+                if track_space {
+                    let space = busy_beaver::score::space(&runner);
+                    if space_champion.is_none_or(|(best, _)| space > best) {
+                        space_champion = Some((space, node.0));
+                    }
+                }
                 match node.halting_transition_count() {
                     0 | 1 => (),
-                    2..=MAX_LOCAL_HALTING_TRANSITIONS => {
+                    count if count <= local_halting_transitions => {
                         let element = (ChildNodes::new(&node, branch), branch);
                         unsafe { stack.push_unchecked(element) };
                     }
                     _ => {
                         cold();
-                        tasks.push((node, branch));
+                        task_tasks.push((node, branch));
                     }
                 }
             }
         }
+        for result in task_results.drain(..) {
+            results.push(result);
+        }
+        if let Some(deepest_queued) = &deepest_queued {
+            // Deeper tasks have fewer halting transitions.
+            if let Some(deepest) = task_tasks
+                .iter()
+                .min_by_key(|(node, _)| node.halting_transition_count())
+            {
+                let mut queued = deepest_queued.lock().unwrap();
+                if queued.is_none_or(|(node, _)| {
+                    deepest.0.halting_transition_count() < node.halting_transition_count()
+                }) {
+                    *queued = Some(*deepest);
+                }
+            }
+        }
+        for task in task_tasks.drain(..) {
+            tasks.push(task);
+        }
     }
     cold();
+    space_champion
+}
+
+/// Enumerate the subtree of the first node with `CALIBRATION_HALTING_TRANSITIONS` halting transitions with a step limit of at most `CALIBRATION_STEPS` once for every value of `--local-halting-transitions` and return the one with the most machines per second. The worker threads are set up like in the run so that the measurement includes the synchronization through the task queue. Stops early and returns the default if the run is asked to terminate.
+fn calibrate(
+    thread_count: usize,
+    governor: &Arc<Governor>,
+    run_keep_running: &AtomicBool,
+    task_order: TaskOrder,
+    limits: Limits,
+    schedule: &Schedule,
+) -> Result<u8> {
+    let limits = Limits {
+        steps: limits.steps.min(CALIBRATION_STEPS),
+        ..limits
+    };
+    let mut runner = create_runner(&limits);
+    let (mut node, mut branch) = (Node::root(), HaltingTransitionIndex::root());
+    while node.halting_transition_count() > CALIBRATION_HALTING_TRANSITIONS {
+        let parent = node;
+        (node, branch) = ChildNodes::new(&parent, branch)
+            .find_map(|transition| {
+                let mut child = parent;
+                *child.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+                match decide(&mut runner, &child.0, branch, &limits, schedule) {
+                    Decision::Halt(next, _) if child.halting_transition_count() >= 2 => {
+                        Some((child, next))
+                    }
+                    _ => None,
+                }
+            })
+            .context("the limits leave no sample subtree for calibration")?;
+    }
+    println!("Calibrating with the subtree of {}.", node.0);
+
+    let mut best: Option<(u8, f64)> = None;
+    for local_halting_transitions in 2..=MAX_LOCAL_HALTING_TRANSITIONS {
+        let keep_running = Arc::new(AtomicBool::new(true));
+        let active_threads = Arc::new(AtomicUsize::new(thread_count));
+        let tasks = Arc::new(TaskQueue::new(task_order));
+        tasks.push((node, branch));
+        let results = Arc::new(SegQueue::<TaskResult>::new());
+        let start = Instant::now();
+        let threads: Vec<JoinHandle<_>> = (0..thread_count)
+            .map(|thread| {
+                let keep_running = keep_running.clone();
+                let governor = governor.clone();
+                let active_threads = active_threads.clone();
+                let tasks = tasks.clone();
+                let results = results.clone();
+                let schedule = schedule.clone();
+                std::thread::spawn(move || {
+                    thread_(
+                        thread,
+                        keep_running,
+                        Arc::default(),
+                        governor,
+                        active_threads,
+                        tasks,
+                        results,
+                        Arc::default(),
+                        Arc::default(),
+                        None,
+                        local_halting_transitions,
+                        limits,
+                        schedule,
+                        false,
+                    )
+                })
+            })
+            .collect();
+        while active_threads.load(Ordering::Relaxed) != 0
+            && run_keep_running.load(Ordering::Relaxed)
+        {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        keep_running.store(false, Ordering::Relaxed);
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        if !run_keep_running.load(Ordering::Relaxed) {
+            return Ok(DEFAULT_LOCAL_HALTING_TRANSITIONS);
+        }
+        let elapsed = start.elapsed();
+        let rate = results.len() as f64 / elapsed.as_secs_f64();
+        println!(
+            "{local_halting_transitions} local halting transitions: {} machines in {elapsed:.1?}, {rate:.0} per second.",
+            results.len()
+        );
+        if best.is_none_or(|(_, best)| rate > best) {
+            best = Some((local_halting_transitions, rate));
+        }
+    }
+    let (best, _) = best.unwrap();
+    println!("Using {best} local halting transitions.");
+    Ok(best)
 }
 
 /// Calling this function is a hint to the compiler that this code path is unlikely to be executed.
@@ -302,12 +1702,223 @@ fn cold() {}
 mod tests {
     use std::io::{BufReader, Read};
 
-    use rayon::{
-        prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator},
-        slice::ParallelSliceMut,
-    };
+    use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 
     use super::*;
+    use crate::mapped::MappedDatabase;
+
+    #[test]
+    fn validate_resumed_tasks() {
+        let task = (Node::root(), HaltingTransitionIndex::root());
+        let mut tasks = vec![task, task];
+        validate_tasks(&mut tasks).unwrap();
+        assert_eq!(tasks.len(), 1);
+
+        let mut invalid = task;
+        invalid.0 .0 .0[0][0] = Transition::Halt;
+        assert!(validate_tasks(&mut vec![task, invalid]).is_err());
+    }
+
+    #[test]
+    fn log_form_is_kept() {
+        let mut resume = Resume::default();
+        check_log_form(&mut resume, LogForm::Normalized).unwrap();
+        assert_eq!(resume.log_form, Some(LogForm::Normalized));
+        check_log_form(&mut resume, LogForm::Normalized).unwrap();
+        assert!(check_log_form(&mut resume, LogForm::Enumerated).is_err());
+        assert_eq!(resume.log_form, Some(LogForm::Normalized));
+    }
+
+    #[test]
+    fn limits_are_kept() {
+        let mut resume = Resume::default();
+        let limits = Limits::default();
+        check_limits(&mut resume, limits).unwrap();
+        assert_eq!(resume.limits, Some(limits));
+        check_limits(&mut resume, limits).unwrap();
+        let tighter = Limits {
+            steps: 1000,
+            ..limits
+        };
+        assert!(check_limits(&mut resume, tighter).is_err());
+        assert_eq!(resume.limits, Some(limits));
+    }
+
+    #[test]
+    fn repair_log_removes_excess_entries() {
+        let path = std::env::temp_dir().join(format!("seed-repair-log-{}", std::process::id()));
+        let entry = "1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA h\n";
+        std::fs::write(&path, format!("{entry}{entry}1RB")).unwrap();
+        let mut log = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        repair_log(&mut log, LOG_ENTRY_LEN as u64).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), entry);
+
+        std::fs::write(&path, format!("{}{entry}", "x".repeat(LOG_ENTRY_LEN))).unwrap();
+        assert!(repair_log(&mut log, LOG_ENTRY_LEN as u64).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn repair_sample_file_removes_excess_lines() {
+        let path = std::env::temp_dir().join(format!("seed-repair-sample-{}", std::process::id()));
+        std::fs::write(&path, "saved\nunsaved\n").unwrap();
+        open_sample_file(&path, None, false).unwrap();
+        open_sample_file(&path, Some(20), false).unwrap();
+        assert!(open_sample_file(&path, Some(6), false).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "saved\nunsaved\n");
+        let mut file = open_sample_file(&path, Some(6), true).unwrap();
+        writeln!(file, "again").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "saved\nagain\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sample_is_uniform() {
+        use busy_beaver::stress::{machine, Kind, Rng};
+        let mut rng = Rng::new(0);
+        let machines: Vec<States> = (0..10_000)
+            .map(|_| machine(&mut rng, Kind::Random))
+            .collect();
+        assert!(machines.iter().all(|states| is_sampled(states, 1)));
+        let sampled = machines
+            .iter()
+            .filter(|states| is_sampled(states, 10))
+            .count();
+        assert!((800..1200).contains(&sampled), "{sampled}");
+        // The hash is pinned because runs compare their samples with files of earlier versions.
+        let champion = write_seed_database(&busy_beaver::fixtures::BB5);
+        assert_eq!(sample_hash(&champion), 4066234405748656567);
+        assert_eq!(
+            sample_detail(Decision::Undecided(UndecidedReason::Steps)),
+            "steps"
+        );
+    }
+
+    #[test]
+    fn swapped_log_is_detected() {
+        let path = std::env::temp_dir().join(format!("seed-run-id-log-{}", std::process::id()));
+        let entry = "1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA h\n";
+        let mut log = write_log_header(7).to_vec();
+        log.extend_from_slice(entry.as_bytes());
+        std::fs::write(&path, &log).unwrap();
+        check_run_id(Some(7), &path).unwrap();
+        assert!(check_run_id(Some(8), &path).is_err());
+        assert!(check_run_id(None, &path).is_err());
+        std::fs::write(&path, entry).unwrap();
+        check_run_id(None, &path).unwrap();
+        assert!(check_run_id(Some(7), &path).is_err());
+
+        // The header alone passes the check of a repair.
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        std::fs::write(&path, &log).unwrap();
+        repair_log(&mut file, LOG_ENTRY_LEN as u64).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), write_log_header(7));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn log_hash_ignores_the_header_and_the_order() {
+        let path = std::env::temp_dir().join(format!("seed-hash-log-{}", std::process::id()));
+        let entry = "1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA h\n";
+        let other = "1RB1LC_1RC1RB_1RD0LE_1LA1LD_---1LA u\n";
+        std::fs::write(&path, [entry, entry, other].concat()).unwrap();
+        let (entries, hash) = hash_log(&path).unwrap();
+        assert_eq!(entries, 3);
+        let mut log = write_log_header(7).to_vec();
+        log.extend_from_slice([entry, other, entry].concat().as_bytes());
+        std::fs::write(&path, log).unwrap();
+        assert_eq!(hash_log(&path).unwrap(), (3, hash));
+        std::fs::write(&path, [entry, other].concat()).unwrap();
+        assert_ne!(hash_log(&path).unwrap().1, hash);
+        std::fs::write(&path, [entry, other, other].concat()).unwrap();
+        assert_ne!(hash_log(&path).unwrap().1, hash);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn task_queue_histogram() {
+        let queue = TaskQueue::new(TaskOrder::Fifo);
+        let root = (Node::root(), HaltingTransitionIndex::root());
+        queue.push(root);
+        queue.push(root);
+        let mut deeper = root;
+        deeper.0 .0 .0[1][0] = busy_beaver::fixtures::BB5.0[1][0];
+        queue.push(deeper);
+        assert_eq!(queue.histogram(), [(8, 1), (9, 2)]);
+        while queue.pop().is_some() {}
+        assert!(queue.histogram().is_empty());
+    }
+
+    #[test]
+    fn task_queue_order() {
+        let root = (Node::root(), HaltingTransitionIndex::root());
+        let tasks: Vec<Task> = (0..4)
+            .map(|i| {
+                let mut task = root;
+                task.1 .1 = crate::enumerate::Symbol::new(i % 2).unwrap();
+                task.1 .0 = crate::enumerate::State::new(i).unwrap();
+                task
+            })
+            .collect();
+        for order in [TaskOrder::Fifo, TaskOrder::Lifo, TaskOrder::Random] {
+            let queue = TaskQueue::new(order);
+            for task in &tasks {
+                queue.push(*task);
+            }
+            let first = queue.pop().unwrap();
+            match order {
+                TaskOrder::Fifo => assert_eq!(first, tasks[0]),
+                TaskOrder::Lifo => assert_eq!(first, tasks[3]),
+                TaskOrder::Random => (),
+            }
+            assert_eq!(queue.len(), 3);
+            let mut popped = queue.into_inner();
+            popped.push(first);
+            popped.sort_by_key(|(_, branch)| branch.0);
+            assert_eq!(popped, tasks);
+        }
+    }
+
+    #[test]
+    fn stats_interval_backs_off() {
+        let second = Duration::from_secs(1);
+        let max = Duration::from_secs(60);
+        assert_eq!(next_stats_interval(second, second, max), second);
+        let mut interval = second;
+        let mut intervals = Vec::new();
+        for _ in 0..8 {
+            interval = next_stats_interval(interval, STATS_WARMUP, max);
+            intervals.push(interval.as_secs());
+        }
+        assert_eq!(intervals, [2, 4, 8, 16, 32, 60, 60, 60]);
+        assert_eq!(next_stats_interval(second, STATS_WARMUP, second), second);
+    }
+
+    #[test]
+    fn stats_discrepancies() {
+        assert!(PUBLISHED_STATS.discrepancies(&PUBLISHED_STATS).is_empty());
+        assert_eq!(PUBLISHED_STATS.total(), 126424532);
+        let stats = Stats {
+            halt: PUBLISHED_STATS.halt + 1,
+            undecided: PUBLISHED_STATS.undecided - 1,
+            ..PUBLISHED_STATS
+        };
+        assert_eq!(
+            stats.discrepancies(&PUBLISHED_STATS),
+            [
+                "halt: 34104724 instead of 34104723 (+1)",
+                "undecided: 88664063 instead of 88664064 (-1)"
+            ]
+        );
+    }
 
     /// Optimized comparison of the logs produced by this binary with the seed database.
     ///
@@ -319,42 +1930,16 @@ mod tests {
     #[ignore]
     #[test]
     fn compare_log() {
-        // Get this file from http://docs.bbchallenge.org/all_5_states_undecided_machines_with_global_header.zip . Its `shasum` is `2576b647185063db2aa3dc2f5622908e99f3cd40`.
-        const SEED_DATABASE_PATH: &str = "all_5_states_undecided_machines_with_global_header.zip";
-        let database = std::fs::OpenOptions::new()
-            .read(true)
-            .open(SEED_DATABASE_PATH)
-            .unwrap();
+        // Get this file from http://docs.bbchallenge.org/all_5_states_undecided_machines_with_global_header.zip . Its `shasum` is `2576b647185063db2aa3dc2f5622908e99f3cd40`. Unzip it because the database is mapped.
+        const SEED_DATABASE_PATH: &str = "all_5_states_undecided_machines_with_global_header";
+        let database = MappedDatabase::open(std::path::Path::new(SEED_DATABASE_PATH)).unwrap();
         let log = std::fs::OpenOptions::new().read(true).open("log").unwrap();
-
-        println!("Reading seed database.");
-        let mut database = zip::ZipArchive::new(database).unwrap();
-        assert_eq!(database.len(), 1);
-        let mut database = database.by_index(0).unwrap();
-        const DB_ENTRY_LEN: usize = 30;
-        const DB_HEADER_LEN: usize = 30;
-        // Skip header.
-        database.read_exact(&mut [0u8; DB_HEADER_LEN]).unwrap();
-        let entries_bytes = database.size() - DB_HEADER_LEN as u64;
-        assert!(entries_bytes % DB_ENTRY_LEN as u64 == 0);
-        let entries_count = entries_bytes / DB_ENTRY_LEN as u64;
-        let mut database_ = Vec::<States>::with_capacity(entries_count as usize);
-        let mut buffer = [0u8; 30];
-        for _ in 0..entries_count {
-            database.read_exact(&mut buffer).unwrap();
-            let states = busy_beaver::format::read_seed_database(&buffer).unwrap();
-            database_.push(states);
-        }
-        let mut database = database_;
-        println!("Read {} machines.", database.len());
-
-        println!("Sorting machines.");
-        database.par_sort_unstable();
+        println!("Mapped {} machines.", database.len());
 
         println!("Comparing log.");
         let log_bytes = log.metadata().unwrap().len();
         let mut log = BufReader::new(log);
-        assert!(log_bytes % LOG_ENTRY_LEN as u64 == 0);
+        assert!(log_bytes.is_multiple_of(LOG_ENTRY_LEN as u64));
         let log_count = log_bytes / LOG_ENTRY_LEN as u64;
         const BUFFERED_LOGS_LEN: usize = 1_000_000;
         let mut buf: Vec<[u8; LOG_ENTRY_LEN]> = vec![[0u8; LOG_ENTRY_LEN]; BUFFERED_LOGS_LEN];
@@ -370,7 +1955,7 @@ mod tests {
                     b'h' | b'l' | b'i' => false,
                     other => panic!("line {line}, machine {states}, bad character {other}"),
                 };
-                let undecided_according_to_database = database.binary_search(&states).is_ok();
+                let undecided_according_to_database = database.find(&states).unwrap().is_some();
                 assert_eq!(
                     undecided, undecided_according_to_database,
                     "line {line}, machine {states}, {undecided} != {undecided_according_to_database}"
@@ -378,18 +1963,23 @@ mod tests {
             });
         };
         let mut lines_handled: u64 = 0;
-        for _ in 0..log_count {
+        let mut header_present = false;
+        for i in 0..log_count {
+            log.read_exact(&mut buf[buf_pos]).unwrap();
+            if i == 0 && read_log_header(&buf[0]).is_some() {
+                header_present = true;
+                continue;
+            }
+            buf_pos += 1;
             if buf_pos == buf.len() {
                 handle_logs(lines_handled, buf);
                 lines_handled += buf.len() as u64;
                 buf_pos = 0;
             }
-            log.read_exact(&mut buf[buf_pos]).unwrap();
-            buf_pos += 1;
         }
         handle_logs(lines_handled, &buf[..buf_pos]);
         lines_handled += buf_pos as u64;
-        assert_eq!(lines_handled, log_count);
+        assert_eq!(lines_handled, log_count - header_present as u64);
         println!("No errors in {log_count} logs.");
     }
 }
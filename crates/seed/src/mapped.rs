@@ -0,0 +1,182 @@
+// Random access to an uncompressed seed database without reading it into memory.
+//
+// The seed database has 88 million machines and is 2.6 GB uncompressed. Reading it into a vector takes a while and needs that much memory in every process that compares against it. Mapping the file lets the operating system page in only the parts that are used and share them between processes. Entries are accessed in place, and the sorted sections can be binary searched from many threads at once.
+
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::format::{
+    read_seed_database, read_seed_database_header, write_seed_database, SeedDatabaseHeader,
+    SEED_DATABASE_ENTRY_LEN, SEED_DATABASE_HEADER_LEN,
+};
+use memmap2::Mmap;
+
+use crate::{enumerate::States, sort::Entry};
+
+/// Read only memory mapped seed database. The file must not be modified or truncated while it is open.
+pub struct MappedDatabase {
+    map: Mmap,
+    header: SeedDatabaseHeader,
+}
+
+impl MappedDatabase {
+    /// Map the database at `path` and check that its length matches its header.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+        // SAFETY: The file must not be modified or truncated while it is mapped. Writes by another process would change the bytes behind the shared slices, which is undefined behavior, and reading a truncated part of the map raises SIGBUS. Nothing in this program writes to a database it compares against. Users must not replace the file in place while a tool runs.
+        let map = unsafe { Mmap::map(&file) }.with_context(|| format!("map {}", path.display()))?;
+        let header = map
+            .get(..SEED_DATABASE_HEADER_LEN)
+            .context("database is shorter than its header")?;
+        let header = read_seed_database_header(header).context("parse database header")?;
+        let expected = SEED_DATABASE_HEADER_LEN as u64
+            + header.total() as u64 * SEED_DATABASE_ENTRY_LEN as u64;
+        if map.len() as u64 != expected {
+            return Err(anyhow!(
+                "database has {} bytes but its header describes {expected} bytes",
+                map.len()
+            ));
+        }
+        Ok(Self { map, header })
+    }
+
+    pub fn header(&self) -> &SeedDatabaseHeader {
+        &self.header
+    }
+
+    pub fn len(&self) -> u32 {
+        self.header.total()
+    }
+
+    /// All entries in the order of the file.
+    pub fn entries(&self) -> &[Entry] {
+        let (entries, rest) = self.map[SEED_DATABASE_HEADER_LEN..].as_chunks();
+        debug_assert!(rest.is_empty());
+        entries
+    }
+
+    /// The machine at `index`. Panics if `index` is out of bounds.
+    pub fn get(&self, index: u32) -> Result<States> {
+        read_seed_database(&self.entries()[index as usize])
+            .with_context(|| format!("parse machine {index}"))
+    }
+
+    /// Index of `states` in the database. The time limit and the space limit section are searched separately because each is only sorted within itself. Entries are compared as bytes, which is the order of `States::seed_database_cmp` and not the derived `Ord` of `States`. Fails if the header does not mark the database as sorted.
+    pub fn find(&self, states: &States) -> Result<Option<u32>> {
+        if !self.header.sorted {
+            return Err(anyhow!(
+                "the database is not sorted. Sort it with `sort --repair`"
+            ));
+        }
+        let entry = write_seed_database(states);
+        let (time_limit, space_limit) = self.entries().split_at(self.header.time_limit as usize);
+        if let Ok(index) = time_limit.binary_search(&entry) {
+            return Ok(Some(index as u32));
+        }
+        Ok(space_limit
+            .binary_search(&entry)
+            .ok()
+            .map(|index| self.header.time_limit + index as u32))
+    }
+}
+
+/// Write a database with the given sections to `path`. The sections are written in the given order.
+#[cfg(test)]
+pub fn write_test_database(
+    path: &Path,
+    time_limit: &[States],
+    space_limit: &[States],
+    sorted: bool,
+) {
+    let mut database = busy_beaver::format::write_seed_database_header(&SeedDatabaseHeader {
+        time_limit: time_limit.len() as u32,
+        space_limit: space_limit.len() as u32,
+        sorted,
+    })
+    .to_vec();
+    for states in time_limit.iter().chain(space_limit) {
+        database.extend_from_slice(&write_seed_database(states));
+    }
+    std::fs::write(path, database).unwrap();
+}
+
+/// The first `count` machines of the enumeration with a small step limit, split into a sorted time limit and a sorted space limit section, and further machines that are not in either section.
+#[cfg(test)]
+pub fn test_sections(count: usize) -> (Vec<States>, Vec<States>, Vec<States>) {
+    use crate::enumerate::{
+        create_runner, enumerate_recursively, HaltingTransitionIndex, Limits, Node, Schedule,
+    };
+
+    let limits = Limits {
+        steps: 20,
+        ..Limits::default()
+    };
+    let mut machines = Vec::new();
+    enumerate_recursively(
+        Node::root(),
+        HaltingTransitionIndex::root(),
+        &mut create_runner(&limits),
+        &limits,
+        &Schedule::default(),
+        &mut |states, _| {
+            machines.push(*states);
+            machines.len() == 2 * count
+        },
+    );
+    assert_eq!(machines.len(), 2 * count);
+    let absent = machines.split_off(count);
+    let (mut time_limit, mut space_limit) = (Vec::new(), Vec::new());
+    for (i, states) in machines.into_iter().enumerate() {
+        match i.is_multiple_of(3) {
+            true => space_limit.push(states),
+            false => time_limit.push(states),
+        }
+    }
+    time_limit.sort_by(States::seed_database_cmp);
+    space_limit.sort_by(States::seed_database_cmp);
+    (time_limit, space_limit, absent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorted_database() {
+        let (time_limit, space_limit, absent) = test_sections(30);
+        let directory = std::env::temp_dir().join(format!("seed-mapped-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("database");
+        write_test_database(&path, &time_limit, &space_limit, true);
+        let database = MappedDatabase::open(&path).unwrap();
+        assert_eq!(database.len(), 30);
+        assert_eq!(database.header().time_limit, time_limit.len() as u32);
+        assert_eq!(database.entries().len(), 30);
+        // The sections overlap, so a single binary search over both would miss machines.
+        assert!(space_limit[0]
+            .seed_database_cmp(time_limit.last().unwrap())
+            .is_lt());
+        for (index, states) in time_limit.iter().chain(&space_limit).enumerate() {
+            assert_eq!(database.entries()[index], write_seed_database(states));
+            assert_eq!(&database.get(index as u32).unwrap(), states);
+            assert_eq!(database.find(states).unwrap(), Some(index as u32));
+        }
+        for states in &absent {
+            assert_eq!(database.find(states).unwrap(), None);
+        }
+
+        // Unsorted databases are not searched.
+        write_test_database(&path, &time_limit, &space_limit, false);
+        let database = MappedDatabase::open(&path).unwrap();
+        assert_eq!(database.get(0).unwrap(), time_limit[0]);
+        assert!(database.find(&time_limit[0]).is_err());
+        // The length must match the header.
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.pop();
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(MappedDatabase::open(&path).is_err());
+        std::fs::write(&path, &bytes[..SEED_DATABASE_HEADER_LEN - 1]).unwrap();
+        assert!(MappedDatabase::open(&path).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
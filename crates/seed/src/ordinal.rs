@@ -0,0 +1,187 @@
+// Machines by their position in the enumeration.
+//
+// The enumeration visits the tree in a fixed order: a child node, then the subtree below it, then the next child. The position of a machine in this order, its ordinal, is a single number that identifies it. Knowing the size of every subtree, the machine at an ordinal is found by skipping whole subtrees, and the ordinal of a machine by adding up the subtrees before it on its path from the root. This makes uniformly random samples of the enumerated machines possible and gives distributed runs a cheap way to describe progress.
+//
+// Subtree sizes are only known by enumerating the subtree. `SubtreeCounter` keeps the sizes below shallow nodes, so after the first lookup further lookups mostly count small subtrees. Still, this is practical only for small trees, like with a small step limit or below a deep node. Both directions depend on the limits because the limits decide which machines halt and have children.
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::{format::read_compact, stress::Rng};
+
+use crate::enumerate::{
+    count_subtree, ChildNodes, HaltingTransitionIndex, Limits, Node, States, SubtreeCounter,
+    Transition,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Print the ordinal of this machine in the compact format.
+    #[arg(conflicts_with_all = ["nth", "sample"])]
+    machine: Option<String>,
+    /// Print the machine at this ordinal.
+    #[arg(long, conflicts_with = "sample")]
+    nth: Option<u64>,
+    /// Print this many uniformly random machines of the enumeration.
+    #[arg(long)]
+    sample: Option<u64>,
+    /// Seed of the random samples.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Step limit of the enumeration. The seed run's value makes the tree too large to count.
+    #[arg(long, default_value_t = 100)]
+    limit_steps: u64,
+    /// Space limit of the enumeration in cells in either direction. Defaults to the seed run's value.
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    limit_memory: Option<usize>,
+    /// BB(4) step heuristic of the enumeration. Defaults to the step count of the BB(4) champion.
+    #[arg(long)]
+    bb4_steps: Option<u64>,
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let default = Limits::default();
+    let limits = Limits {
+        steps: args.limit_steps,
+        memory: args.limit_memory.unwrap_or(default.memory),
+        bb4_steps: args.bb4_steps.unwrap_or(default.bb4_steps),
+    };
+    let mut counter = SubtreeCounter::new(&limits);
+    let root = (Node::root(), HaltingTransitionIndex::root());
+    if let Some(machine) = &args.machine {
+        let states: States = read_compact(machine.as_bytes()).context("invalid machine")?;
+        let ordinal = ordinal(&states, &mut counter).context("machine is not enumerated")?;
+        println!("{ordinal}");
+    } else if let Some(n) = args.nth {
+        let states = nth(n, &mut counter).ok_or_else(|| {
+            anyhow!(
+                "the enumeration has only {} machines",
+                counter.count(&root.0, root.1)
+            )
+        })?;
+        println!("{states}");
+    } else if let Some(samples) = args.sample {
+        let total = counter.count(&root.0, root.1);
+        println!("{total} machines.");
+        let mut rng = Rng::new(args.seed);
+        for _ in 0..samples {
+            let n = rng.below(total);
+            println!("{n} {}", nth(n, &mut counter).unwrap());
+        }
+    } else {
+        let total = count_subtree(&root.0, root.1, &limits);
+        println!("{total} machines.");
+    }
+    Ok(())
+}
+
+/// The machine at ordinal `n`, counting from 0, or `None` if the enumeration has fewer machines.
+pub fn nth(mut n: u64, counter: &mut SubtreeCounter) -> Option<States> {
+    let mut node = Node::root();
+    let mut branch = HaltingTransitionIndex::root();
+    'level: loop {
+        for transition in ChildNodes::new(&node, branch) {
+            *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+            if n == 0 {
+                return Some(node.0);
+            }
+            n -= 1;
+            let Some(next) = counter.expand(&node, branch) else {
+                continue;
+            };
+            let size = counter.count(&node, next);
+            if n < size {
+                branch = next;
+                continue 'level;
+            }
+            n -= size;
+        }
+        return None;
+    }
+}
+
+/// The ordinal of `states`, or `None` if the enumeration does not visit it.
+pub fn ordinal(states: &States, counter: &mut SubtreeCounter) -> Option<u64> {
+    let mut node = Node::root();
+    let mut branch = HaltingTransitionIndex::root();
+    if node.0 .0[0][0] != states.0[0][0] {
+        return None;
+    }
+    let mut n = 0;
+    loop {
+        let Transition::Continue(target) = *states.get_transition(branch.0, branch.1) else {
+            return None;
+        };
+        let mut found = false;
+        for transition in ChildNodes::new(&node, branch) {
+            *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+            if transition == target {
+                found = true;
+                break;
+            }
+            n += 1;
+            if let Some(next) = counter.expand(&node, branch) {
+                n += counter.count(&node, next);
+            }
+        }
+        if !found {
+            return None;
+        }
+        if node.0 == *states {
+            return Some(n);
+        }
+        n += 1;
+        branch = counter.expand(&node, branch)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumerate::{create_runner, decide, Decision, Runner, Schedule};
+
+    #[test]
+    fn ordinals_follow_the_enumeration() {
+        let limits = Limits {
+            steps: 3,
+            ..Limits::default()
+        };
+        let mut enumerated = Vec::new();
+        let mut runner = create_runner(&limits);
+        collect(
+            &mut runner,
+            Node::root(),
+            HaltingTransitionIndex::root(),
+            &limits,
+            &mut enumerated,
+        );
+        let mut counter = SubtreeCounter::new(&limits);
+        let total = counter.count(&Node::root(), HaltingTransitionIndex::root());
+        assert_eq!(total, enumerated.len() as u64);
+        assert!(total > 100);
+        for (n, states) in enumerated.iter().enumerate() {
+            assert_eq!(nth(n as u64, &mut counter), Some(*states), "{n}");
+            assert_eq!(ordinal(states, &mut counter), Some(n as u64), "{n}");
+        }
+        assert_eq!(nth(total, &mut counter), None);
+        // The root is not enumerated itself.
+        assert_eq!(ordinal(&Node::root().0, &mut counter), None);
+    }
+
+    fn collect(
+        runner: &mut Runner,
+        mut node: Node,
+        branch: HaltingTransitionIndex,
+        limits: &Limits,
+        enumerated: &mut Vec<States>,
+    ) {
+        for transition in ChildNodes::new(&node, branch) {
+            *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+            enumerated.push(node.0);
+            let decision = decide(runner, &node.0, branch, limits, &Schedule::default());
+            if let Decision::Halt(branch, _) = decision {
+                if node.halting_transition_count() >= 2 {
+                    collect(runner, node, branch, limits, enumerated);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,441 @@
+// Deciding holdouts in passes with growing budgets.
+//
+// Most holdouts are decided by a small budget and only a few need a large one. Running every machine with the largest budget wastes most of the time on machines that a small budget would have decided. This tool runs the deciders in passes. Every pass uses larger budgets than the previous one and only sees the machines that are still undecided. The budgets are the step limit of the simulation and the window of `busy_beaver::segment`, and both grow geometrically.
+//
+// A change to a decider can silently stop it from deciding machines it used to decide, or worse, make it decide them differently. The verdict of every machine can be written to a results file and compared with the results file of a previous version on the same input. Machines that were decided before and are now undecided, and machines whose verdict switched between halting and running forever, fail the comparison.
+
+use std::{
+    collections::HashMap,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::{
+    format::{read_compact, LogDecision, MachineList},
+    segment::{Config, Outcome, Position, SegmentSimulator},
+};
+
+use crate::enumerate::{
+    create_runner, decide_standalone, Decision, Limits, Runner, Schedule, States,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Machines to decide in any format of `MachineList`. Only undecided machines of a log are decided.
+    #[arg(default_value = "log")]
+    input: PathBuf,
+    /// Index file selecting machines of the seed database given as input.
+    #[arg(long)]
+    index: Option<PathBuf>,
+    /// Number of passes.
+    #[arg(long, default_value_t = 4)]
+    passes: u32,
+    /// Step limit of the first pass.
+    #[arg(long, default_value_t = 1_000)]
+    first_steps: u64,
+    /// Factor between the step limits of consecutive passes.
+    #[arg(long, default_value_t = 10)]
+    step_factor: u64,
+    /// Step limit of the run that wrote the log given as input. Its undecided machines were already simulated this far, so passes up to this step limit only run the segment simulation. Defaults to the seed run's value. Not used for machine lists without decisions.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    log_steps: Option<u64>,
+    /// Segment window of the first pass in cells.
+    #[arg(long, default_value_t = 2)]
+    first_window: usize,
+    /// Factor between the segment windows of consecutive passes.
+    #[arg(long, default_value_t = 2)]
+    window_factor: usize,
+    /// Number of threads. Defaults to all cores.
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Write the machines that are undecided after the last pass to this file, one per line.
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Write the verdict of every machine and the pass that decided it to this file, for comparing with `--previous` later.
+    #[arg(long)]
+    results: Option<PathBuf>,
+    /// Results file of a previous version on the same input. Reports the machines whose verdict changed and fails if a machine is no longer decided or switched between halting and running forever.
+    #[arg(long)]
+    previous: Option<PathBuf>,
+}
+
+/// Budgets of one pass.
+#[derive(Debug, Clone, Copy)]
+struct Pass {
+    steps: u64,
+    window: usize,
+}
+
+/// How a pass decided a machine.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Verdict {
+    Halt,
+    Loop,
+    Segment,
+    Undecided,
+}
+
+impl Verdict {
+    const ALL: [Self; 4] = [Self::Halt, Self::Loop, Self::Segment, Self::Undecided];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Halt => "halt",
+            Self::Loop => "loop",
+            Self::Segment => "segment",
+            Self::Undecided => "undecided",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|verdict| verdict.name() == name)
+    }
+
+    /// Whether the machine halts. `None` if it is undecided.
+    fn halts(self) -> Option<bool> {
+        match self {
+            Self::Halt => Some(true),
+            Self::Loop | Self::Segment => Some(false),
+            Self::Undecided => None,
+        }
+    }
+}
+
+/// The verdict of a machine and the pass that decided it, counting from 1.
+type Results = HashMap<States, (Verdict, Option<u32>)>;
+
+/// First line of a results file.
+const RESULTS_HEADER: &str = "# passes results";
+
+pub fn main(args: Args) -> Result<()> {
+    if args.passes == 0 || args.first_steps == 0 || args.first_window == 0 {
+        return Err(anyhow!(
+            "passes, first step limit and first window must be positive"
+        ));
+    }
+    if args.step_factor == 0 || args.window_factor == 0 {
+        return Err(anyhow!("factors must be positive"));
+    }
+    let passes: Vec<Pass> = (0..args.passes)
+        .map(|i| -> Result<Pass> {
+            let steps = args
+                .step_factor
+                .checked_pow(i)
+                .and_then(|factor| factor.checked_mul(args.first_steps))
+                .context("step limit overflows")?;
+            let window = args
+                .window_factor
+                .checked_pow(i)
+                .and_then(|factor| factor.checked_mul(args.first_window))
+                .context("window overflows")?;
+            Ok(Pass { steps, window })
+        })
+        .collect::<Result<_>>()?;
+
+    let machines = match &args.index {
+        Some(index) => MachineList::open_indexed(&args.input, index)?,
+        None => MachineList::open(&args.input)?,
+    };
+    let mut holdouts = Vec::<States>::new();
+    let mut from_log = false;
+    for machine in machines {
+        let machine = machine.context("read machines")?;
+        from_log |= machine.decision.is_some();
+        if matches!(machine.decision, None | Some(LogDecision::Undecided)) {
+            holdouts.push(machine.states);
+        }
+    }
+    println!("{} undecided machines.", holdouts.len());
+    // The undecided machines of a log reached the step limit of its run. Simulating them again up to it cannot decide them.
+    let simulated = match from_log {
+        true => args.log_steps.unwrap_or(Limits::default().steps),
+        false => 0,
+    };
+    let input = holdouts.clone();
+    let mut decided = Results::new();
+
+    let thread_count = args.threads.unwrap_or_else(num_cpus::get).max(1);
+    for (i, pass) in passes.iter().enumerate() {
+        let start = Instant::now();
+        let input = holdouts.len();
+        let simulate = pass.steps > simulated;
+        let results = decide_all(&holdouts, *pass, simulate, thread_count);
+        let count = |result: Verdict| results.iter().filter(|r| **r == result).count();
+        for (states, verdict) in holdouts.iter().zip(&results) {
+            if *verdict != Verdict::Undecided {
+                decided.insert(*states, (*verdict, Some(i as u32 + 1)));
+            }
+        }
+        holdouts = holdouts
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| **result == Verdict::Undecided)
+            .map(|(states, _)| *states)
+            .collect();
+        let steps = match simulate {
+            true => pass.steps.to_string(),
+            false => "no".to_string(),
+        };
+        println!(
+            "Pass {}: {steps} steps, window {}: {input} machines, {} halt, {} loop, {} segment, {} undecided in {:.1} s.",
+            i + 1,
+            pass.window,
+            count(Verdict::Halt),
+            count(Verdict::Loop),
+            count(Verdict::Segment),
+            holdouts.len(),
+            start.elapsed().as_secs_f64()
+        );
+        if holdouts.is_empty() {
+            break;
+        }
+    }
+
+    if let Some(path) = &args.output {
+        let file = std::fs::File::create(path).context("create output file")?;
+        let mut file = BufWriter::new(file);
+        for states in &holdouts {
+            writeln!(file, "{states}").context("write output file")?;
+        }
+        file.flush().context("flush output file")?;
+    }
+    let verdict = |states: &States| {
+        decided
+            .get(states)
+            .copied()
+            .unwrap_or((Verdict::Undecided, None))
+    };
+    if let Some(path) = &args.results {
+        let file = std::fs::File::create(path).context("create results file")?;
+        let mut file = BufWriter::new(file);
+        let results: Vec<_> = input
+            .iter()
+            .map(|states| (*states, verdict(states)))
+            .collect();
+        write_results(&mut file, &results).context("write results file")?;
+        file.flush().context("flush results file")?;
+    }
+    if let Some(path) = &args.previous {
+        let previous = read_results(path)?;
+        let current: Results = input
+            .iter()
+            .map(|states| (*states, verdict(states)))
+            .collect();
+        compare(&previous, &current)?;
+    }
+    Ok(())
+}
+
+fn write_results(
+    output: &mut impl Write,
+    results: &[(States, (Verdict, Option<u32>))],
+) -> std::io::Result<()> {
+    writeln!(
+        output,
+        "{RESULTS_HEADER} of seed {}",
+        env!("CARGO_PKG_VERSION")
+    )?;
+    for (states, (verdict, pass)) in results {
+        match pass {
+            Some(pass) => writeln!(output, "{states} {} {pass}", verdict.name())?,
+            None => writeln!(output, "{states} {}", verdict.name())?,
+        }
+    }
+    Ok(())
+}
+
+/// Read a file of `write_results`.
+fn read_results(path: &Path) -> Result<Results> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("read results file {}", path.display()))?;
+    if !text.starts_with(RESULTS_HEADER) {
+        return Err(anyhow!("{} is not a results file", path.display()));
+    }
+    text.lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|line| -> Result<_> {
+            let mut fields = line.split_whitespace();
+            let mut field = || fields.next().context("missing field");
+            let states: States = read_compact(field()?.as_bytes())?;
+            let verdict = field()?;
+            let verdict = Verdict::from_name(verdict)
+                .with_context(|| format!("invalid verdict {verdict:?}"))?;
+            let pass = fields.next().map(str::parse).transpose()?;
+            Ok((states, (verdict, pass)))
+        })
+        .collect::<Result<_>>()
+        .with_context(|| format!("parse results file {}", path.display()))
+}
+
+/// Report the machines whose verdict differs between `previous` and `current`. Fails if a machine is no longer decided or its verdict switched between halting and running forever.
+fn compare(previous: &Results, current: &Results) -> Result<()> {
+    const MAX_REPORTED: usize = 20;
+    let mut regressions = Vec::new();
+    let mut contradictions = Vec::new();
+    let mut improvements = Vec::new();
+    let mut other = Vec::new();
+    for (states, (verdict, _)) in current {
+        let Some((before, _)) = previous.get(states) else {
+            continue;
+        };
+        if before == verdict {
+            continue;
+        }
+        let change = (*states, *before, *verdict);
+        match (before.halts(), verdict.halts()) {
+            (Some(_), None) => regressions.push(change),
+            (None, Some(_)) => improvements.push(change),
+            (Some(a), Some(b)) if a != b => contradictions.push(change),
+            _ => other.push(change),
+        }
+    }
+    let only_current = current.keys().filter(|s| !previous.contains_key(s)).count();
+    let only_previous = previous.keys().filter(|s| !current.contains_key(s)).count();
+    if only_current + only_previous != 0 {
+        println!("{only_current} machines are not in the previous results and {only_previous} previous machines are not in the input.");
+    }
+    for (name, changes) in [
+        ("no longer decided", &mut regressions),
+        (
+            "switched between halting and running forever",
+            &mut contradictions,
+        ),
+        ("newly decided", &mut improvements),
+        ("decided by another decider", &mut other),
+    ] {
+        if changes.is_empty() {
+            continue;
+        }
+        changes.sort_by_key(|(states, _, _)| *states);
+        println!("{} machines {name}:", changes.len());
+        for (states, before, after) in changes.iter().take(MAX_REPORTED) {
+            println!("  {states} {} -> {}", before.name(), after.name());
+        }
+        if changes.len() > MAX_REPORTED {
+            println!("  ...");
+        }
+    }
+    if regressions.is_empty() && contradictions.is_empty() {
+        println!("No regressions compared to the previous results.");
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} regressions and {} contradictions compared to the previous results",
+            regressions.len(),
+            contradictions.len()
+        ))
+    }
+}
+
+/// `simulate` is false if the machines were already simulated for the pass's step limit, in which case only the segment simulation runs.
+fn decide_all(
+    machines: &[States],
+    pass: Pass,
+    simulate: bool,
+    thread_count: usize,
+) -> Vec<Verdict> {
+    let limits = Limits {
+        steps: pass.steps,
+        ..Limits::default()
+    };
+    let config = Config {
+        window: pass.window,
+        position: Position::Every,
+    };
+    let chunk_len = machines.len().div_ceil(thread_count).max(1);
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = machines
+            .chunks(chunk_len)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut runner = create_runner(&limits);
+                    let mut simulator = SegmentSimulator::new(config);
+                    chunk
+                        .iter()
+                        .map(|states| {
+                            verdict(&mut runner, &mut simulator, states, &limits, simulate)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect()
+    })
+}
+
+fn verdict(
+    runner: &mut Runner,
+    simulator: &mut SegmentSimulator<5, 2>,
+    states: &States,
+    limits: &Limits,
+    simulate: bool,
+) -> Verdict {
+    if simulate {
+        match decide_standalone(runner, states, limits, &Schedule::default()) {
+            Decision::Halt(..) => return Verdict::Halt,
+            Decision::Loop(_) => return Verdict::Loop,
+            Decision::Undecided(_) | Decision::Irrelevant => (),
+        }
+    }
+    match simulator.run(states) {
+        Outcome::NeverHalts { .. } => Verdict::Segment,
+        Outcome::MayHalt => Verdict::Undecided,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use busy_beaver::fixtures::BB5;
+
+    #[test]
+    fn log_holdouts_skip_the_simulation() {
+        let pass = Pass {
+            steps: 100_000,
+            window: 2,
+        };
+        let halting: States = read_compact(b"1RB---_0LA---_------_------_------").unwrap();
+        let looping: States = read_compact(b"1RA---_------_------_------_------").unwrap();
+        let machines = [halting, looping];
+        assert_eq!(
+            decide_all(&machines, pass, true, 1),
+            [Verdict::Halt, Verdict::Loop]
+        );
+        // Without the simulation only the segment simulation runs. It cannot show that a machine halts, and the looping machine can read a 1 outside of the window.
+        assert_eq!(
+            decide_all(&machines, pass, false, 1),
+            [Verdict::Undecided, Verdict::Undecided]
+        );
+    }
+
+    #[test]
+    fn results_are_compared() {
+        let looping: States = read_compact(b"1RA---_------_------_------_------").unwrap();
+        let results = [
+            (BB5, (Verdict::Halt, Some(1))),
+            (looping, (Verdict::Loop, Some(2))),
+        ];
+        let path = std::env::temp_dir().join(format!("seed-passes-results-{}", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        write_results(&mut file, &results).unwrap();
+        drop(file);
+        let previous = read_results(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(previous, results.into_iter().collect());
+
+        let mut current = previous.clone();
+        assert!(compare(&previous, &current).is_ok());
+        current.insert(looping, (Verdict::Segment, Some(3)));
+        assert!(compare(&previous, &current).is_ok());
+        current.insert(looping, (Verdict::Undecided, None));
+        assert!(compare(&previous, &current).is_err());
+        assert!(compare(&current, &previous).is_ok());
+        current.insert(looping, (Verdict::Halt, Some(1)));
+        assert!(compare(&previous, &current).is_err());
+    }
+}
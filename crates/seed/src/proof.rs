@@ -0,0 +1,112 @@
+// Spot-checkable proofs of long halting runs.
+//
+// A proof records the configuration of a halting run at regular intervals. See `busy_beaver::proof`. The run writes proofs for machines that halt late when started with `--proof-steps`. This tool records proofs for single machines and checks random segments of a proof.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::{format::read_compact, proof::Proof, stress::Rng};
+
+use crate::enumerate::{Limits, States};
+
+/// Default number of steps between checkpoints. A BB(5) champion proof has 48 checkpoints.
+pub const DEFAULT_INTERVAL: u64 = 1_000_000;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: ProofCommand,
+}
+
+#[derive(clap::Subcommand)]
+enum ProofCommand {
+    /// Run a machine and write a proof of it halting.
+    Record {
+        /// The machine in the compact format.
+        machine: String,
+        /// File to write the proof to.
+        output: PathBuf,
+        /// Steps between checkpoints.
+        #[arg(long, default_value_t = DEFAULT_INTERVAL)]
+        interval: u64,
+        /// Fail if the machine does not halt within this many steps. Defaults to the seed run's step limit.
+        #[arg(long)]
+        limit: Option<u64>,
+    },
+    /// Check random segments of a proof.
+    Check {
+        proof: PathBuf,
+        /// Number of segments to check. All segments are checked if the proof has fewer.
+        #[arg(long, default_value_t = 10)]
+        segments: usize,
+        /// Seed for choosing the segments. Defaults to the current time.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// After checking, print a proof sketch in Markdown that can be posted as is.
+        #[arg(long)]
+        markdown: bool,
+    },
+}
+
+pub fn main(args: Args) -> Result<()> {
+    match args.command {
+        ProofCommand::Record {
+            machine,
+            output,
+            interval,
+            limit,
+        } => {
+            let states: States = read_compact(machine.as_bytes()).context("parse machine")?;
+            let limit = limit.unwrap_or(Limits::default().steps);
+            write(&states, interval, limit, &output)?;
+            println!("Wrote proof to {}.", output.display());
+            Ok(())
+        }
+        ProofCommand::Check {
+            proof,
+            segments,
+            seed,
+            markdown,
+        } => {
+            let proof = std::fs::read_to_string(&proof).context("read proof")?;
+            let proof = Proof::<5, 2>::read(&proof).context("parse proof")?;
+            if proof.segments() == 0 {
+                return Err(anyhow!("the proof has no segments"));
+            }
+            let seed = seed.unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as u64
+            });
+            let mut chosen: Vec<usize> = (0..proof.segments()).collect();
+            // Partial Fisher-Yates shuffle so that every segment is chosen at most once.
+            let mut rng = Rng::new(seed);
+            let count = segments.min(chosen.len());
+            for i in 0..count {
+                let j = i + rng.below((chosen.len() - i) as u64) as usize;
+                chosen.swap(i, j);
+            }
+            for segment in &chosen[..count] {
+                proof.check_segment(*segment)?;
+            }
+            println!(
+                "{} halts after {} steps: {count} of {} segments checked with seed {seed}.",
+                proof.states,
+                proof.steps(),
+                proof.segments()
+            );
+            if markdown {
+                println!();
+                print!("{}", proof.to_markdown());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Record a proof of `states` halting within `limit` steps and write it to `path`.
+pub fn write(states: &States, interval: u64, limit: u64, path: &Path) -> Result<()> {
+    let proof = Proof::record(states, interval, limit)?;
+    std::fs::write(path, proof.write()).context("write proof")
+}
@@ -0,0 +1,239 @@
+// Ordering undecided machines by how hard they look.
+//
+// Expensive deciders run for a long time on hard machines. A time limited run of a decider over a holdout set decides more machines when it sees the easy machines first. The difficulty of a machine is estimated from a short simulation: how far the sequence of transitions is from being periodic and how fast the used tape grows. Cyclers and translated cyclers have a periodic sequence and come first. Bouncers have a nearly periodic sequence. Counters and chaotic machines come last.
+
+use std::{cmp::Ordering, collections::BinaryHeap, io::Write, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::{
+    format::{LogDecision, MachineList},
+    run::StepResult,
+};
+
+use crate::enumerate::{Runner, States};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Machines to rank in any format of `MachineList`. Only undecided machines of a log are ranked.
+    #[arg(default_value = "log")]
+    input: PathBuf,
+    /// Index file selecting machines of the seed database given as input.
+    #[arg(long)]
+    index: Option<PathBuf>,
+    /// Number of steps every machine is simulated for.
+    #[arg(long, default_value_t = 10_000)]
+    steps: u32,
+    /// Stop after this many undecided machines have been read.
+    #[arg(long)]
+    limit: Option<u64>,
+    /// Only print this many of the easiest machines. Only these are kept in memory, so large holdout sets can be ranked with little memory. Without it every machine is kept until the input has been read.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    top: Option<u64>,
+}
+
+/// Longest period that is looked for in the sequence of transitions.
+const MAX_PERIOD: usize = 64;
+
+/// Machines read from the input per thread before they are ranked together.
+const BATCH_PER_THREAD: usize = 4096;
+
+/// Estimated difficulty of a machine. Machines are ordered by aperiodicity and then by growth. Lower is easier.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Difficulty {
+    /// Share of the steps in the second half of the run that do not repeat the transition `period` steps earlier, for the period with the smallest share. 0 for periodic machines.
+    aperiodicity: f64,
+    /// How fast the used tape grows, as the exponent `e` in `width ~ steps^e`. Cyclers and counters are close to 0, bouncers close to 0.5 and translated cyclers close to 1. 0 for machines that halted or ran out of tape.
+    growth: f64,
+}
+
+/// A ranked machine. Ordered by difficulty and then by the position in the input, so machines of equal difficulty keep the order of the input.
+struct Ranked {
+    difficulty: Difficulty,
+    position: u64,
+    states: States,
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b) = (self.difficulty, other.difficulty);
+        a.aperiodicity
+            .total_cmp(&b.aperiodicity)
+            .then(a.growth.total_cmp(&b.growth))
+            .then(self.position.cmp(&other.position))
+    }
+}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+pub fn main(args: Args) -> Result<()> {
+    if args.steps < 2 {
+        return Err(anyhow!("need at least 2 steps"));
+    }
+    let mut machines = match &args.index {
+        Some(index) => MachineList::open_indexed(&args.input, index)?,
+        None => MachineList::open(&args.input)?,
+    };
+    let limit = args.limit.unwrap_or(u64::MAX);
+    let top = args.top.unwrap_or(u64::MAX);
+    let thread_count = num_cpus::get();
+
+    // The input is read and ranked in batches. The heap keeps the `top` easiest machines with the hardest on top.
+    let mut easiest = BinaryHeap::<Ranked>::new();
+    let mut read = 0u64;
+    let mut batch = Vec::with_capacity(BATCH_PER_THREAD * thread_count);
+    loop {
+        batch.clear();
+        while batch.len() < batch.capacity() && read < limit {
+            let Some(machine) = machines.next() else {
+                break;
+            };
+            let machine = machine.context("read machines")?;
+            if matches!(machine.decision, None | Some(LogDecision::Undecided)) {
+                batch.push(machine.states);
+                read += 1;
+            }
+        }
+        if batch.is_empty() {
+            break;
+        }
+        let first = read - batch.len() as u64;
+        let chunk_len = batch.len().div_ceil(thread_count);
+        let ranked: Vec<Difficulty> = std::thread::scope(|scope| {
+            let threads: Vec<_> = batch
+                .chunks(chunk_len)
+                .map(|chunk| scope.spawn(|| rank_machines(chunk, args.steps)))
+                .collect();
+            threads
+                .into_iter()
+                .flat_map(|t| t.join().unwrap())
+                .collect()
+        });
+        for (i, (states, difficulty)) in batch.iter().zip(ranked).enumerate() {
+            let ranked = Ranked {
+                difficulty,
+                position: first + i as u64,
+                states: *states,
+            };
+            keep_easiest(&mut easiest, ranked, top);
+        }
+    }
+
+    let ranked = easiest.into_sorted_vec();
+    let mut stdout = std::io::stdout().lock();
+    for Ranked {
+        states, difficulty, ..
+    } in &ranked
+    {
+        writeln!(
+            stdout,
+            "{states} aperiodicity {:.3} growth {:.2}",
+            difficulty.aperiodicity, difficulty.growth
+        )?;
+    }
+    eprintln!(
+        "Ranked {read} undecided machines and printed the {} easiest.",
+        ranked.len()
+    );
+    Ok(())
+}
+
+/// Add `ranked` to `easiest` and drop the hardest machine if there are more than `top`.
+fn keep_easiest(easiest: &mut BinaryHeap<Ranked>, ranked: Ranked, top: u64) {
+    easiest.push(ranked);
+    if easiest.len() as u64 > top {
+        easiest.pop();
+    }
+}
+
+fn rank_machines(machines: &[States], steps: u32) -> Vec<Difficulty> {
+    // The tape is large enough that the head cannot leave it in `steps` steps.
+    let mut runner = Runner::vector_backed(2 * steps as usize + 3);
+    let mut trace = Vec::with_capacity(steps as usize);
+    machines
+        .iter()
+        .map(|states| difficulty(&mut runner, &mut trace, states, steps))
+        .collect()
+}
+
+fn difficulty(runner: &mut Runner, trace: &mut Vec<u8>, states: &States, steps: u32) -> Difficulty {
+    runner.set_states(states);
+    runner.reset();
+    trace.clear();
+    let (mut left, mut right) = (0isize, 0isize);
+    let mut half_width = 0;
+    for step in 0..steps {
+        if step == steps / 2 {
+            half_width = right - left + 1;
+        }
+        trace.push(runner.state().get() * 2 + runner.symbol().get());
+        match runner.step() {
+            StepResult::Ok => (),
+            StepResult::Halt | StepResult::TapeFullLeft | StepResult::TapeFullRight => {
+                return Difficulty {
+                    aperiodicity: 0.,
+                    growth: 0.,
+                }
+            }
+        }
+        let position = runner.position();
+        left = left.min(position);
+        right = right.max(position);
+    }
+    let width = right - left + 1;
+    let second_half = &trace[trace.len() / 2..];
+    let mismatches = (1..=MAX_PERIOD.min(second_half.len() - 1))
+        .map(|period| {
+            second_half
+                .iter()
+                .zip(&second_half[period..])
+                .filter(|(a, b)| a != b)
+                .count()
+        })
+        .min()
+        .unwrap_or(0);
+    Difficulty {
+        aperiodicity: mismatches as f64 / second_half.len() as f64,
+        growth: (width as f64 / half_width as f64).log2(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easiest_are_kept() {
+        let aperiodicities = [0.5, 0.1, 0.9, 0.1, 0.3, 0.];
+        let mut easiest = BinaryHeap::new();
+        for (position, aperiodicity) in aperiodicities.into_iter().enumerate() {
+            let ranked = Ranked {
+                difficulty: Difficulty {
+                    aperiodicity,
+                    growth: 0.,
+                },
+                position: position as u64,
+                states: States::default(),
+            };
+            keep_easiest(&mut easiest, ranked, 4);
+        }
+        let positions: Vec<u64> = easiest
+            .into_sorted_vec()
+            .iter()
+            .map(|ranked| ranked.position)
+            .collect();
+        // Equal difficulties keep the order of the input.
+        assert_eq!(positions, [5, 1, 3, 4]);
+    }
+}
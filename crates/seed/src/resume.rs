@@ -0,0 +1,500 @@
+// The resume file.
+//
+// The resume file holds what a run needs to continue after it was stopped: the stats, the tasks that are left, the distributions of the results and the paused simulations. It is written in an explicit record format so that a run can be continued on another machine or architecture and so that changes to the types in memory cannot silently change the meaning of an existing file. Every integer is little-endian with the stated width.
+//
+// ```text
+// magic             8 bytes "SEEDRSM\0"
+// version           u32, currently 5
+// stats             u64 halt, u64 loop, u64 undecided, u64 irrelevant
+// tasks             u64 count, then per task:
+//   machine         30 bytes in the seed database format
+//   branch          u8 state, u8 symbol
+// halt steps        u64 count, then u64 per bucket
+// loop reasons      u64 count, then u64 per reason in the order of `LoopReason::ALL`
+// paused            u64 count, then per paused simulation:
+//   machine         30 bytes in the seed database format
+//   step            u64
+//   state seen      u8 bit set of states
+//   state           u8
+//   position        i64 relative to the start cell
+//   left            i64 leftmost visited position
+//   cells           u64 count, then u8 per cell
+// undecided reasons u64 count, then u64 per reason in the order of `UndecidedReason::ALL`
+// run id            u64, the id in the header of the run's log, 0 if the log has no header
+// sample length     u64, bytes in the `--sample` file when the resume file was written, u64::MAX if unknown
+// log form          u8, 0 if unknown, 1 enumerated, 2 normalized
+// limits            u64 steps, u64 memory, u64 BB(4) steps, all 0 if unknown
+// ```
+//
+// Version 1 ends before the run id, version 2 before the sample length, version 3 before the log form and version 4 before the limits. A file that does not start with the magic bytes was written with bincode by an older version. These files are still read but written in the record format.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use anyhow::{anyhow, Context, Result};
+use bincode::Options;
+use busy_beaver::{
+    format::{read_seed_database, write_seed_database},
+    run::Snapshot,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enumerate::{HaltingTransitionIndex, Limits, LoopReason, Node, Paused, State, States, Symbol},
+    Distributions, LogForm, Stats, Task, UndecidedReason, UndecidedReasons,
+};
+
+const MAGIC: &[u8; 8] = b"SEEDRSM\0";
+const VERSION: u32 = 5;
+
+/// Resume data saved on disk.
+#[derive(Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Resume {
+    pub stats: Stats,
+    pub tasks: Vec<Task>,
+    pub distributions: Distributions,
+    /// Simulations of machines of the tasks that were paused when the run was aborted.
+    pub paused: Vec<(States, Paused)>,
+    /// Not part of `distributions` because older resume files end after `paused`.
+    pub undecided_reasons: UndecidedReasons,
+    /// Id of the run, which is also in the header of the log. `None` for runs started by older versions, whose logs have no header. Never 0.
+    #[serde(skip)]
+    pub run_id: Option<u64>,
+    /// Length of the `--sample` file when the resume file was written. Lines after it were written by a session that stopped without saving its progress and are written again when the run continues. `None` if the run had no sample file or was saved by an older version.
+    #[serde(skip)]
+    pub sample_len: Option<u64>,
+    /// Form of the machines in the log. A run cannot change it because the log would mix both forms. `None` for runs saved by older versions.
+    #[serde(skip)]
+    pub log_form: Option<LogForm>,
+    /// Limits of the run. Results of different limits cannot be mixed in one log. `None` for runs saved by older versions.
+    #[serde(skip)]
+    pub limits: Option<Limits>,
+}
+
+impl Resume {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        let Stats {
+            halt,
+            loop_,
+            undecided,
+            irrelevant,
+        } = self.stats;
+        for count in [halt, loop_, undecided, irrelevant] {
+            put_u64(&mut out, count);
+        }
+        put_u64(&mut out, self.tasks.len() as u64);
+        for (node, branch) in &self.tasks {
+            out.extend_from_slice(&write_seed_database(&node.0));
+            out.extend_from_slice(&[branch.0.get(), branch.1.get()]);
+        }
+        put_counts(&mut out, &self.distributions.halt_steps);
+        put_counts(&mut out, &self.distributions.loop_reasons);
+        put_u64(&mut out, self.paused.len() as u64);
+        for (states, paused) in &self.paused {
+            out.extend_from_slice(&write_seed_database(states));
+            put_u64(&mut out, paused.step);
+            out.extend_from_slice(&[paused.state_seen, paused.snapshot.state]);
+            out.extend_from_slice(&(paused.snapshot.position as i64).to_le_bytes());
+            out.extend_from_slice(&(paused.snapshot.left as i64).to_le_bytes());
+            put_u64(&mut out, paused.snapshot.cells.len() as u64);
+            out.extend_from_slice(&paused.snapshot.cells);
+        }
+        put_counts(&mut out, &self.undecided_reasons.0);
+        put_u64(&mut out, self.run_id.unwrap_or(0));
+        put_u64(&mut out, self.sample_len.unwrap_or(u64::MAX));
+        out.push(match self.log_form {
+            None => 0,
+            Some(LogForm::Enumerated) => 1,
+            Some(LogForm::Normalized) => 2,
+        });
+        let limits = self.limits.map_or([0; 3], |limits| {
+            [limits.steps, limits.memory as u64, limits.bb4_steps]
+        });
+        for value in limits {
+            put_u64(&mut out, value);
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut d = Decoder(bytes);
+        if d.take(MAGIC.len())? != MAGIC {
+            return Err(anyhow!("not a resume file in the record format"));
+        }
+        let version = u32::from_le_bytes(d.array()?);
+        if !(1..=VERSION).contains(&version) {
+            return Err(anyhow!(
+                "resume file version {version} is not supported, expected {VERSION}"
+            ));
+        }
+        let stats = Stats {
+            halt: d.u64()?,
+            loop_: d.u64()?,
+            undecided: d.u64()?,
+            irrelevant: d.u64()?,
+        };
+        let tasks = (0..d.count()?)
+            .map(|i| d.task().with_context(|| format!("task {i}")))
+            .collect::<Result<_>>()?;
+        let halt_steps = (0..d.count()?).map(|_| d.u64()).collect::<Result<_>>()?;
+        let loop_reasons = d.counts::<{ LoopReason::ALL.len() }>("loop reasons")?;
+        let paused = (0..d.count()?)
+            .map(|_| -> Result<(States, Paused)> {
+                let states = d.machine()?;
+                let step = d.u64()?;
+                let [state_seen, state] = d.array()?;
+                let position = d.i64()?;
+                let left = d.i64()?;
+                let len = d.count()?;
+                let cells = d.take(len)?.to_vec();
+                let snapshot = Snapshot {
+                    state,
+                    position,
+                    left,
+                    cells,
+                };
+                Ok((
+                    states,
+                    Paused {
+                        step,
+                        state_seen,
+                        snapshot,
+                    },
+                ))
+            })
+            .collect::<Result<_>>()
+            .context("paused simulations")?;
+        let undecided_reasons =
+            UndecidedReasons(d.counts::<{ UndecidedReason::ALL.len() }>("undecided reasons")?);
+        let run_id = match version {
+            1 => None,
+            _ => Some(d.u64()?).filter(|id| *id != 0),
+        };
+        let sample_len = match version {
+            1 | 2 => None,
+            _ => Some(d.u64()?).filter(|len| *len != u64::MAX),
+        };
+        let log_form = match version {
+            1..=3 => None,
+            _ => match d.array()? {
+                [0] => None,
+                [1] => Some(LogForm::Enumerated),
+                [2] => Some(LogForm::Normalized),
+                [form] => return Err(anyhow!("invalid log form {form}")),
+            },
+        };
+        let limits = match version {
+            1..=4 => None,
+            _ => match [d.u64()?, d.u64()?, d.u64()?] {
+                [0, 0, 0] => None,
+                [steps, memory, bb4_steps] => {
+                    let limits = Limits {
+                        steps,
+                        memory: memory.try_into().map_err(|_| {
+                            anyhow!("memory limit {memory} does not fit this platform")
+                        })?,
+                        bb4_steps,
+                    };
+                    limits.validate()?;
+                    Some(limits)
+                }
+            },
+        };
+        if !d.0.is_empty() {
+            return Err(anyhow!("{} unexpected bytes at the end", d.0.len()));
+        }
+        Ok(Self {
+            stats,
+            tasks,
+            distributions: Distributions {
+                halt_steps,
+                loop_reasons,
+            },
+            paused,
+            undecided_reasons,
+            run_id,
+            sample_len,
+            log_form,
+            limits,
+        })
+    }
+}
+
+pub fn read(file: &mut std::fs::File) -> Result<Resume> {
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).context("read resume file")?;
+    if bytes.starts_with(MAGIC) {
+        return Resume::decode(&bytes).context("decode resume file");
+    }
+    println!(
+        "The resume file was written by an older version. It will be saved in the current format."
+    );
+    read_bincode(&bytes)
+}
+
+/// Replace the content of the resume file.
+pub fn write(file: &mut std::fs::File, resume: &Resume) -> Result<()> {
+    file.set_len(0).context("truncate resume file")?;
+    file.seek(SeekFrom::Start(0)).context("seek resume file")?;
+    file.write_all(&resume.encode())
+        .context("write resume file")?;
+    file.flush().context("flush resume file")
+}
+
+fn read_bincode(bytes: &[u8]) -> Result<Resume> {
+    // Older versions added fields at the end of `Resume`: the distributions, then the paused simulations, then the undecided machines by reason. A file ends after any of them, so the fields are read in order until the file ends. Reading from the file did not check for trailing bytes, so bytes that do not form the next field also end the file.
+    let mut rest = bytes;
+    let stats = next_field(&mut rest).context("deserialize resume file")?;
+    let tasks = next_field(&mut rest).context("deserialize resume file")?;
+    let mut resume = Resume {
+        stats,
+        tasks,
+        ..Resume::default()
+    };
+    let Ok(distributions) = next_field(&mut rest) else {
+        println!("The resume file has no distributions of the results. They only cover machines enumerated from now on.");
+        return Ok(resume);
+    };
+    resume.distributions = distributions;
+    let reasons = next_field(&mut rest).and_then(|paused| {
+        resume.paused = paused;
+        next_field(&mut rest)
+    });
+    match reasons {
+        Ok(reasons) => resume.undecided_reasons = reasons,
+        Err(_) => println!("The resume file has no undecided machines by reason. They only cover machines enumerated from now on."),
+    }
+    Ok(resume)
+}
+
+/// Deserialize the next field of a bincode resume file and advance `rest` past it.
+fn next_field<T: serde::de::DeserializeOwned>(rest: &mut &[u8]) -> bincode::Result<T> {
+    bincode::options()
+        .allow_trailing_bytes()
+        .deserialize_from(rest)
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_counts(out: &mut Vec<u8>, counts: &[u64]) {
+    put_u64(out, counts.len() as u64);
+    for count in counts {
+        put_u64(out, *count);
+    }
+}
+
+/// The bytes that are left to decode.
+struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(anyhow!("unexpected end of the resume file"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn i64(&mut self) -> Result<isize> {
+        let value = i64::from_le_bytes(self.array()?);
+        value
+            .try_into()
+            .map_err(|_| anyhow!("position {value} does not fit this platform"))
+    }
+
+    /// A length that is checked against the remaining bytes so that a corrupt length fails before allocating.
+    fn count(&mut self) -> Result<usize> {
+        let count = self.u64()?;
+        if count > self.0.len() as u64 {
+            return Err(anyhow!("count {count} exceeds the resume file"));
+        }
+        Ok(count as usize)
+    }
+
+    /// Counts for every variant of an enum. Adding a variant needs a new version of the format.
+    fn counts<const N: usize>(&mut self, name: &str) -> Result<[u64; N]> {
+        let count = self.count()?;
+        if count != N {
+            return Err(anyhow!("{count} {name} instead of {N}"));
+        }
+        let mut counts = [0; N];
+        for c in &mut counts {
+            *c = self.u64()?;
+        }
+        Ok(counts)
+    }
+
+    fn task(&mut self) -> Result<Task> {
+        let node = Node(self.machine()?);
+        let [state, symbol] = self.array()?;
+        let branch = HaltingTransitionIndex(
+            State::new(state).context("invalid branch state")?,
+            Symbol::new(symbol).context("invalid branch symbol")?,
+        );
+        Ok((node, branch))
+    }
+
+    fn machine(&mut self) -> Result<States> {
+        read_seed_database(self.take(30)?).context("invalid machine")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Resume {
+        let champion = busy_beaver::fixtures::BB5;
+        let mut node = Node::root();
+        node.0 .0[1][0] = champion.0[1][0];
+        let branch = HaltingTransitionIndex(State::new(2).unwrap(), Symbol::new(0).unwrap());
+        Resume {
+            stats: Stats {
+                halt: 1,
+                loop_: 2,
+                undecided: 3,
+                irrelevant: 4,
+            },
+            tasks: vec![
+                (node, branch),
+                (Node::root(), HaltingTransitionIndex::root()),
+            ],
+            distributions: Distributions {
+                halt_steps: vec![5, 6, 7],
+                loop_reasons: [8; LoopReason::ALL.len()],
+            },
+            paused: vec![(
+                champion,
+                Paused {
+                    step: 1 << 40,
+                    state_seen: 0b10111,
+                    snapshot: Snapshot {
+                        state: 3,
+                        position: -2,
+                        left: -5,
+                        cells: vec![1, 0, 1, 1, 0, 1, 1],
+                    },
+                },
+            )],
+            undecided_reasons: UndecidedReasons([9, 10, 11]),
+            run_id: Some(0x0123_4567_89ab_cdef),
+            sample_len: Some(12),
+            log_form: Some(LogForm::Normalized),
+            limits: Some(Limits {
+                steps: 13,
+                memory: 14,
+                bb4_steps: 15,
+            }),
+        }
+    }
+
+    #[test]
+    fn record_format() {
+        let resume = example();
+        let bytes = resume.encode();
+        assert_eq!(Resume::decode(&bytes).unwrap(), resume);
+        // The layout is fixed: little-endian stats right after the header, then the tasks.
+        assert_eq!(&bytes[..8], MAGIC);
+        assert_eq!(bytes[8..12], [5, 0, 0, 0]);
+        assert_eq!(bytes[12..20], [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes[36..44], [4, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes[44..52], [2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes[52..82], write_seed_database(&resume.tasks[0].0 .0));
+        assert_eq!(bytes[82..84], [2, 0]);
+
+        for len in 0..bytes.len() {
+            assert!(Resume::decode(&bytes[..len]).is_err(), "{len}");
+        }
+        let mut newer = bytes.clone();
+        newer[8] = 6;
+        assert!(Resume::decode(&newer).is_err());
+        let mut invalid_form = bytes.clone();
+        invalid_form[bytes.len() - 25] = 3;
+        assert!(Resume::decode(&invalid_form).is_err());
+        let mut invalid_limits = bytes.clone();
+        invalid_limits[bytes.len() - 24] = 0;
+        assert!(Resume::decode(&invalid_limits).is_err());
+        let mut unknown_limits = bytes.clone();
+        unknown_limits[bytes.len() - 24..].fill(0);
+        assert_eq!(Resume::decode(&unknown_limits).unwrap().limits, None);
+        // Version 4 has no limits, version 3 no log form, version 2 no sample length and version 1 no run id either.
+        let mut version_4 = bytes[..bytes.len() - 24].to_vec();
+        version_4[8] = 4;
+        let decoded = Resume::decode(&version_4).unwrap();
+        assert_eq!(decoded.limits, None);
+        assert_eq!(decoded.log_form, resume.log_form);
+        let mut version_3 = bytes[..bytes.len() - 25].to_vec();
+        version_3[8] = 3;
+        let decoded = Resume::decode(&version_3).unwrap();
+        assert_eq!(decoded.log_form, None);
+        assert_eq!(decoded.sample_len, resume.sample_len);
+        let mut version_2 = bytes[..bytes.len() - 33].to_vec();
+        version_2[8] = 2;
+        let decoded = Resume::decode(&version_2).unwrap();
+        assert_eq!(decoded.sample_len, None);
+        assert_eq!(decoded.run_id, resume.run_id);
+        let mut version_1 = bytes[..bytes.len() - 41].to_vec();
+        version_1[8] = 1;
+        let decoded = Resume::decode(&version_1).unwrap();
+        assert_eq!(decoded.run_id, None);
+        assert_eq!(decoded.tasks, resume.tasks);
+        let mut longer = bytes;
+        longer.push(0);
+        assert!(Resume::decode(&longer).is_err());
+    }
+
+    #[test]
+    fn bincode_files_are_still_read() {
+        let resume = Resume {
+            run_id: None,
+            sample_len: None,
+            log_form: None,
+            limits: None,
+            ..example()
+        };
+        let bytes = bincode::options().serialize(&resume).unwrap();
+        assert_eq!(read_bincode(&bytes).unwrap(), resume);
+        let bytes = bincode::options()
+            .serialize(&(resume.stats, &resume.tasks))
+            .unwrap();
+        let legacy = read_bincode(&bytes).unwrap();
+        assert_eq!(legacy.tasks, resume.tasks);
+        assert!(legacy.paused.is_empty());
+        // Files that end after the distributions or the paused simulations.
+        let bytes = bincode::options()
+            .serialize(&(resume.stats, &resume.tasks, &resume.distributions))
+            .unwrap();
+        let unpaused = read_bincode(&bytes).unwrap();
+        assert_eq!(unpaused.distributions, resume.distributions);
+        assert!(unpaused.paused.is_empty());
+        let bytes = bincode::options()
+            .serialize(&(
+                resume.stats,
+                &resume.tasks,
+                &resume.distributions,
+                &resume.paused,
+            ))
+            .unwrap();
+        let reasonless = read_bincode(&bytes).unwrap();
+        assert_eq!(reasonless.paused, resume.paused);
+        assert_eq!(reasonless.undecided_reasons, UndecidedReasons::default());
+        // Bytes after the last field are ignored like before.
+        let mut bytes = bincode::options().serialize(&resume).unwrap();
+        bytes.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(read_bincode(&bytes).unwrap(), resume);
+        assert!(read_bincode(&[1, 2, 3]).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+// Splitting the log of a run into several files.
+//
+// The log of a complete run is several gigabytes. With `--rotate-log` the file `log` becomes a `LogManifest` and the entries go to the files `log.0000`, `log.0001` and so on, each holding a fixed number of entries. Finished files do not change anymore, so they can be compressed, copied or checked while the run continues. The tools that read logs understand the manifest.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::format::{LogManifest, LOG_ENTRY_LEN, LOG_MANIFEST_HEADER};
+
+/// Writer that appends to the last file of a manifest and starts a new file when the last file is full.
+pub struct RotatingLog {
+    path: PathBuf,
+    manifest: LogManifest,
+    /// Last file of the manifest, positioned at its end.
+    current: File,
+    /// Bytes in `current`.
+    current_len: u64,
+}
+
+/// Whether the file at `path` exists and is a `LogManifest`.
+pub fn is_rotated(path: &Path) -> Result<bool> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err).with_context(|| format!("open {}", path.display())),
+    };
+    let mut prefix = Vec::new();
+    file.take(LOG_MANIFEST_HEADER.len() as u64)
+        .read_to_end(&mut prefix)
+        .with_context(|| format!("read {}", path.display()))?;
+    Ok(LogManifest::is_manifest(&prefix))
+}
+
+impl RotatingLog {
+    /// Open the log whose manifest is at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let manifest = LogManifest::read(path)?;
+        // Checks the lengths of all files but the last.
+        manifest.open(path)?;
+        let last = manifest
+            .paths(path)
+            .pop()
+            .context("the manifest lists no files")?;
+        let mut current = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&last)
+            .with_context(|| format!("open {}", last.display()))?;
+        let current_len = current
+            .seek(SeekFrom::End(0))
+            .with_context(|| format!("seek {} to end", last.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+            manifest,
+            current,
+            current_len,
+        })
+    }
+
+    /// Turn the single file log at `path` into the first file of a new manifest at `path`. A missing log is created empty.
+    pub fn convert(path: &Path, entries_per_file: u64) -> Result<Self> {
+        let name = file_name(path, 0)?;
+        let first = path.with_file_name(&name);
+        if path.exists() {
+            // The entries are reachable under one of the names at every moment, so an interruption loses nothing.
+            std::fs::hard_link(path, &first)
+                .with_context(|| format!("link {} to {}", path.display(), first.display()))?;
+        } else {
+            File::create(&first).with_context(|| format!("create {}", first.display()))?;
+        }
+        let manifest = LogManifest {
+            entries_per_file,
+            files: vec![(0, name)],
+        };
+        write_manifest(path, &manifest)?;
+        Self::open(path)
+    }
+
+    /// Change the number of entries after which a new file is started. Applies from the current file on.
+    pub fn set_entries_per_file(&mut self, entries_per_file: u64) -> Result<()> {
+        self.manifest.entries_per_file = entries_per_file;
+        write_manifest(&self.path, &self.manifest)
+    }
+
+    /// Length of the whole log in bytes.
+    pub fn len(&self) -> u64 {
+        self.first_byte() + self.current_len
+    }
+
+    /// Truncate the whole log to `len` bytes like `repair_log`. Files that start at or after `len` are removed, except for the first file.
+    pub fn truncate(&mut self, len: u64) -> Result<()> {
+        let paths = self.manifest.paths(&self.path);
+        let keep = self
+            .manifest
+            .files
+            .iter()
+            .skip(1)
+            .take_while(|(first, _)| first * (LOG_ENTRY_LEN as u64) < len)
+            .count()
+            + 1;
+        if keep < paths.len() {
+            self.manifest.files.truncate(keep);
+            write_manifest(&self.path, &self.manifest)?;
+            for path in &paths[keep..] {
+                std::fs::remove_file(path).with_context(|| format!("remove {}", path.display()))?;
+            }
+            let last = &paths[keep - 1];
+            self.current = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(last)
+                .with_context(|| format!("open {}", last.display()))?;
+        }
+        let len = len - self.first_byte();
+        crate::repair_log(&mut self.current, len)?;
+        self.current_len = len;
+        Ok(())
+    }
+
+    /// Byte offset of the current file in the whole log.
+    fn first_byte(&self) -> u64 {
+        self.manifest.files.last().unwrap().0 * LOG_ENTRY_LEN as u64
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let name = file_name(&self.path, self.manifest.files.len())?;
+        let path = self.path.with_file_name(&name);
+        // The new file is created before it is listed so that the manifest never lists a missing file. A file left over from an interrupted rotation is not listed and is overwritten.
+        let current = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("create {}", path.display()))?;
+        let first = self.len() / LOG_ENTRY_LEN as u64;
+        self.manifest.files.push((first, name));
+        write_manifest(&self.path, &self.manifest)?;
+        self.current = current;
+        self.current_len = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // The limit is a multiple of the entry length so entries are never split between files.
+        let limit = self.manifest.entries_per_file * LOG_ENTRY_LEN as u64;
+        if self.current_len >= limit && !buf.is_empty() {
+            self.rotate().map_err(std::io::Error::other)?;
+        }
+        let available = (limit - self.current_len).min(buf.len() as u64) as usize;
+        let written = self.current.write(&buf[..available])?;
+        self.current_len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.current.flush()
+    }
+}
+
+/// Name of the file with the given index of the manifest at `path`.
+fn file_name(path: &Path, index: usize) -> Result<String> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))?;
+    Ok(format!("{name}.{index:04}"))
+}
+
+/// Replace the manifest at `path` so that it is never left partially written.
+fn write_manifest(path: &Path, manifest: &LogManifest) -> Result<()> {
+    let temporary = path.with_extension("manifest-tmp");
+    std::fs::write(&temporary, manifest.write())
+        .with_context(|| format!("write {}", temporary.display()))?;
+    std::fs::rename(&temporary, path).with_context(|| format!("replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use busy_beaver::format::open_log;
+
+    use super::*;
+
+    #[test]
+    fn rotate_and_truncate() {
+        let directory =
+            std::env::temp_dir().join(format!("seed-rotate-log-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("log");
+        let entry = "1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA h\n";
+        std::fs::write(&path, entry).unwrap();
+
+        let mut log = RotatingLog::convert(&path, 2).unwrap();
+        log.write_all(entry.repeat(4).as_bytes()).unwrap();
+        assert_eq!(log.len(), 5 * LOG_ENTRY_LEN as u64);
+        let manifest = LogManifest::read(&path).unwrap();
+        assert_eq!(
+            manifest.files,
+            [
+                (0, "log.0000".to_string()),
+                (2, "log.0001".to_string()),
+                (4, "log.0002".to_string())
+            ]
+        );
+        let mut content = String::new();
+        open_log(&path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, entry.repeat(5));
+
+        let mut log = RotatingLog::open(&path).unwrap();
+        log.truncate(2 * LOG_ENTRY_LEN as u64).unwrap();
+        assert_eq!(LogManifest::read(&path).unwrap().files.len(), 1);
+        assert!(!directory.join("log.0001").exists());
+        log.write_all(entry.as_bytes()).unwrap();
+        assert_eq!(LogManifest::read(&path).unwrap().files.len(), 2);
+        assert_eq!(log.len(), 3 * LOG_ENTRY_LEN as u64);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,355 @@
+// Splitting a run into shards of similar work.
+//
+// A run can be split by giving every shard some of the tasks, the subtrees below some nodes, that the enumeration would otherwise take from one queue. Splitting at a fixed depth gives shards that differ in work by orders of magnitude, because the size of a subtree and the time its machines take vary wildly: a few subtrees contain most of the late halting and undecided machines.
+//
+// The planner starts with the root task and repeatedly splits the task with the most estimated work into its children until there are enough tasks. The children that are created while splitting are decided right away and listed in the plan, so that exactly one shard logs them. The work of a task is estimated with random probes (Knuth's estimator): a probe walks from the task to a leaf along random children and multiplies the cost of every machine it decides by the number of siblings along the way. The average of many probes is an unbiased estimate of the steps the subtree takes. The tasks are then assigned greedily, largest first, to the shard with the least work so far.
+//
+// A shard is run with `--shard-plan` and `--shard`. It starts with the tasks of its shard instead of the root and logs the decided machines of the plan first. Once started, it resumes like any other run. The logs of all shards together contain every machine of the enumeration exactly once.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::{format::read_compact, stress::Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::enumerate::{
+    create_runner, decide, validate_node, ChildNodes, Decision, HaltingTransitionIndex, Limits,
+    LoopReason, Node, Runner, Schedule, ScheduleDecider, State, States, Symbol, Transition,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Number of shards.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    shards: u32,
+    /// Split tasks until every shard can get this many on average. More tasks balance better but the planner decides more machines itself.
+    #[arg(long, default_value_t = 16)]
+    tasks_per_shard: u32,
+    /// Number of random probes per task for estimating its work.
+    #[arg(long, default_value_t = 200)]
+    probes: u32,
+    /// Seed of the random probes.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Step limit of the shards. Defaults to the seed run's value.
+    #[arg(long)]
+    limit_steps: Option<u64>,
+    /// Space limit of the shards in cells in either direction. Defaults to the seed run's value.
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    limit_memory: Option<usize>,
+    /// BB(4) step heuristic of the shards. Defaults to the step count of the BB(4) champion.
+    #[arg(long)]
+    bb4_steps: Option<u64>,
+    /// Step budgets of the shards, like the run's `--budgets`.
+    #[arg(long, value_delimiter = ',', conflicts_with = "first_budget")]
+    budgets: Vec<u64>,
+    /// Exponentially growing step budgets of the shards, like the run's `--first-budget`.
+    #[arg(long)]
+    first_budget: Option<u64>,
+    /// Factor between consecutive budgets when using `--first-budget`.
+    #[arg(long, default_value_t = 10, requires = "first_budget")]
+    budget_factor: u64,
+    /// Deciders of the shards between the budgets, like the run's `--deciders`.
+    #[arg(long, value_delimiter = ',', default_values_t = ScheduleDecider::ALL)]
+    deciders: Vec<ScheduleDecider>,
+    /// File to write the plan to.
+    #[arg(long, default_value = "shards.json")]
+    output: PathBuf,
+}
+
+/// Assignment of the enumeration to shards. Written as JSON.
+#[derive(Serialize, Deserialize)]
+pub struct ShardPlan {
+    /// The limits the plan was made with. They decide which machines have children, so shards must use the same.
+    pub limits: Limits,
+    /// The step budgets the plan was made with. The checks between budgets can decide machines early, so shards must use the same. Empty in plans of older versions, which always used a single phase.
+    #[serde(default)]
+    pub budgets: Vec<u64>,
+    /// The deciders that are applied between the budgets. Empty in plans of older versions, which did not apply any.
+    #[serde(default)]
+    pub deciders: Vec<ScheduleDecider>,
+    pub shards: Vec<PlannedShard>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlannedShard {
+    /// Estimated steps of the shard's work.
+    pub estimated_steps: f64,
+    /// Machines the planner enumerated while splitting tasks. `changed` is the transition that was defined last, like `B0`.
+    pub machines: Vec<PlannedMachine>,
+    /// Tasks whose subtrees the shard enumerates. `branch` is the halting transition whose children are enumerated.
+    pub tasks: Vec<PlannedTask>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlannedMachine {
+    pub machine: String,
+    pub changed: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlannedTask {
+    pub machine: String,
+    pub branch: String,
+    pub estimated_steps: f64,
+}
+
+/// The part of a plan a shard runs.
+pub struct Shard {
+    pub machines: Vec<(States, HaltingTransitionIndex)>,
+    pub tasks: Vec<(Node, HaltingTransitionIndex)>,
+}
+
+impl ShardPlan {
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let plan: Self = serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("parse {}", path.display()))?;
+        plan.limits.validate().context("plan limits")?;
+        Ok(plan)
+    }
+
+    /// The machines and tasks of shard `index`, checked against the enumeration invariants.
+    pub fn shard(&self, index: u32) -> Result<Shard> {
+        let shard = self.shards.get(index as usize).ok_or_else(|| {
+            anyhow!(
+                "shard {index} does not exist, the plan has {} shards",
+                self.shards.len()
+            )
+        })?;
+        let machines = shard
+            .machines
+            .iter()
+            .map(|m| -> Result<_> {
+                let states: States = read_compact(m.machine.as_bytes())?;
+                Ok((states, parse_transition_index(&m.changed)?))
+            })
+            .collect::<Result<_>>()
+            .context("planned machines")?;
+        let tasks = shard
+            .tasks
+            .iter()
+            .map(|t| -> Result<_> {
+                let node = Node(read_compact(t.machine.as_bytes())?);
+                let branch = parse_transition_index(&t.branch)?;
+                validate_node(&node, branch).with_context(|| format!("task {}", t.machine))?;
+                Ok((node, branch))
+            })
+            .collect::<Result<_>>()
+            .context("planned tasks")?;
+        Ok(Shard { machines, tasks })
+    }
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let default = Limits::default();
+    let limits = Limits {
+        steps: args.limit_steps.unwrap_or(default.steps),
+        memory: args.limit_memory.unwrap_or(default.memory),
+        bb4_steps: args.bb4_steps.unwrap_or(default.bb4_steps),
+    };
+    limits.validate().context("limits")?;
+    let schedule = match args.first_budget {
+        Some(first) => Schedule::exponential(first, args.budget_factor, &limits),
+        None => Schedule::new(&args.budgets, &limits),
+    }
+    .context("step budgets")?
+    .with_deciders(&args.deciders);
+    let mut runner = create_runner(&limits);
+    let mut rng = Rng::new(args.seed);
+    let target = args.shards as usize * args.tasks_per_shard as usize;
+
+    // Split the task with the most estimated work until there are enough tasks.
+    let root = (Node::root(), HaltingTransitionIndex::root());
+    let mut tasks = vec![(
+        root,
+        estimate(&mut runner, &mut rng, root, &limits, &schedule, args.probes),
+    )];
+    let mut machines = Vec::<(States, HaltingTransitionIndex)>::new();
+    let mut planner_steps = 0u64;
+    while tasks.len() < target {
+        let Some((largest, _)) = tasks
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.1.total_cmp(&b.1))
+        else {
+            break;
+        };
+        let ((mut node, branch), _) = tasks.swap_remove(largest);
+        for transition in ChildNodes::new(&node, branch) {
+            *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+            let decision = decide(&mut runner, &node.0, branch, &limits, &schedule);
+            machines.push((node.0, branch));
+            planner_steps += cost(decision, &limits);
+            if let Decision::Halt(next, _) = decision {
+                if node.halting_transition_count() >= 2 {
+                    let task = (node, next);
+                    tasks.push((
+                        task,
+                        estimate(&mut runner, &mut rng, task, &limits, &schedule, args.probes),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Largest first, each to the shard with the least work so far. The planned machines go to the first shard.
+    tasks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut shards: Vec<PlannedShard> = (0..args.shards)
+        .map(|_| PlannedShard {
+            estimated_steps: 0.,
+            machines: Vec::new(),
+            tasks: Vec::new(),
+        })
+        .collect();
+    shards[0].estimated_steps = planner_steps as f64;
+    shards[0].machines = machines
+        .iter()
+        .map(|(states, changed)| PlannedMachine {
+            machine: states.to_string(),
+            changed: format_transition_index(*changed),
+        })
+        .collect();
+    for ((node, branch), estimated_steps) in tasks {
+        let shard = shards
+            .iter_mut()
+            .min_by(|a, b| a.estimated_steps.total_cmp(&b.estimated_steps))
+            .unwrap();
+        shard.estimated_steps += estimated_steps;
+        shard.tasks.push(PlannedTask {
+            machine: node.0.to_string(),
+            branch: format_transition_index(branch),
+            estimated_steps,
+        });
+    }
+
+    let total: f64 = shards.iter().map(|s| s.estimated_steps).sum();
+    let largest = shards.iter().map(|s| s.estimated_steps).fold(0., f64::max);
+    for (i, shard) in shards.iter().enumerate() {
+        println!(
+            "Shard {i}: {} tasks, {} machines, {:.2e} estimated steps.",
+            shard.tasks.len(),
+            shard.machines.len(),
+            shard.estimated_steps
+        );
+    }
+    println!(
+        "{:.2e} estimated steps in total. The largest shard has {:.2} times the average.",
+        total,
+        largest / (total / shards.len() as f64)
+    );
+    let plan = ShardPlan {
+        limits,
+        budgets: schedule.budgets().to_vec(),
+        deciders: schedule.deciders().to_vec(),
+        shards,
+    };
+    let file = std::fs::File::create(&args.output).context("create plan file")?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &plan).context("write plan")?;
+    Ok(())
+}
+
+/// Steps it takes to decide a machine. Every machine costs at least one step so that many cheap machines add up.
+fn cost(decision: Decision, limits: &Limits) -> u64 {
+    match decision {
+        Decision::Halt(_, steps) => steps,
+        Decision::Loop(LoopReason::UnvisitedState) => limits.bb4_steps + 1,
+        Decision::Loop(LoopReason::Check) | Decision::Undecided(_) => limits.steps,
+        Decision::Irrelevant => 1,
+    }
+}
+
+/// Average of `probes` random probes of the steps it takes to enumerate `task`.
+fn estimate(
+    runner: &mut Runner,
+    rng: &mut Rng,
+    task: (Node, HaltingTransitionIndex),
+    limits: &Limits,
+    schedule: &Schedule,
+    probes: u32,
+) -> f64 {
+    let mut sum = 0.;
+    for _ in 0..probes {
+        let (mut node, mut branch) = task;
+        let mut weight = 1.;
+        loop {
+            let children: Vec<_> = ChildNodes::new(&node, branch).collect();
+            let child = children[rng.below(children.len() as u64) as usize];
+            weight *= children.len() as f64;
+            *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(child);
+            let decision = decide(runner, &node.0, branch, limits, schedule);
+            sum += weight * cost(decision, limits) as f64;
+            match decision {
+                Decision::Halt(next, _) if node.halting_transition_count() >= 2 => branch = next,
+                _ => break,
+            }
+        }
+    }
+    sum / probes.max(1) as f64
+}
+
+/// Like `B0`.
+pub fn format_transition_index(index: HaltingTransitionIndex) -> String {
+    format!("{}{}", char::from(b'A' + index.0.get()), index.1.get())
+}
+
+pub fn parse_transition_index(s: &str) -> Result<HaltingTransitionIndex> {
+    let &[state, symbol] = s.as_bytes() else {
+        return Err(anyhow!("invalid transition {s:?}"));
+    };
+    let state = State::new(state.wrapping_sub(b'A')).context("invalid state")?;
+    let symbol = Symbol::new(symbol.wrapping_sub(b'0')).context("invalid symbol")?;
+    Ok(HaltingTransitionIndex(state, symbol))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumerate::count_subtree;
+
+    #[test]
+    fn shards_cover_the_enumeration() {
+        let limits = Limits {
+            steps: 5,
+            ..Limits::default()
+        };
+        let directory = std::env::temp_dir().join(format!("seed-shard-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let output = directory.join("plan.json");
+        main(Args {
+            shards: 3,
+            tasks_per_shard: 4,
+            probes: 20,
+            seed: 0,
+            limit_steps: Some(limits.steps),
+            limit_memory: None,
+            bb4_steps: None,
+            budgets: vec![2],
+            first_budget: None,
+            budget_factor: 10,
+            deciders: vec![ScheduleDecider::Cyclers],
+            output: output.clone(),
+        })
+        .unwrap();
+        let plan = ShardPlan::read(&output).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(plan.limits, limits);
+        assert_eq!(plan.budgets, [2]);
+        assert_eq!(plan.deciders, [ScheduleDecider::Cyclers]);
+        assert_eq!(plan.shards.len(), 3);
+        let mut total = 0;
+        for i in 0..3 {
+            let shard = plan.shard(i).unwrap();
+            assert!(!shard.tasks.is_empty());
+            total += shard.machines.len() as u64;
+            for (node, branch) in shard.tasks {
+                total += count_subtree(&node, branch, &limits);
+            }
+        }
+        assert_eq!(
+            total,
+            count_subtree(&Node::root(), HaltingTransitionIndex::root(), &limits)
+        );
+        assert!(plan.shard(3).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+// Progress on Skelet's machines.
+//
+// Skelet's 43 machines were the hardest holdouts of BB(5). How many of them the deciders resolve is a natural measure of progress of the deciders. Every machine is first simulated like in the seed run. Machines that are still undecided are given to the deciders of `busy_beaver` in turn.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use busy_beaver::{
+    counter::{self, CounterDecider},
+    decider::{self, cyclers::CyclersDecider, Decider},
+    fixtures::SKELET_MACHINES,
+    format::MachineList,
+    segment::{self, SegmentSimulator},
+};
+
+use crate::enumerate::{
+    create_runner, decide_standalone, Decision, Limits, Runner, Schedule, States,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Machines to check in any format of `MachineList`, for example a text file with one machine per line. A number in front of a machine on its line, like Skelet's numbering, is kept in the output. Other machines are numbered by their position in the list. Defaults to the machines in `busy_beaver::fixtures`, which are only some of Skelet's machines.
+    #[arg(long)]
+    list: Option<PathBuf>,
+    /// Index file selecting machines of the seed database given with `--list`, like Bbchallenge's index file of Skelet's machines. The output then also shows the index of each machine in the seed database.
+    #[arg(long, requires = "list")]
+    index: Option<PathBuf>,
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let builtin = args.list.is_none();
+    let machines: Vec<(String, States)> = match args.list {
+        Some(path) => {
+            let list = match &args.index {
+                Some(index) => MachineList::open_indexed(&path, index)?,
+                None => MachineList::open(&path)?,
+            };
+            let indexed = args.index.is_some();
+            list.enumerate()
+                .map(|(position, machine)| {
+                    machine.map(|m| {
+                        let number = m.number.unwrap_or(position as u64 + 1);
+                        match indexed {
+                            true => (format!("#{number} index {}", m.index), m.states),
+                            false => (format!("#{number}"), m.states),
+                        }
+                    })
+                })
+                .collect::<busy_beaver::Result<_>>()
+                .context("read list")?
+        }
+        None => busy_beaver::fixtures::skelet()
+            .map(|(number, fixture)| (format!("Skelet #{number}"), fixture.machine()))
+            .collect(),
+    };
+
+    let limits = Limits::default();
+    let mut runner = create_runner(&limits);
+    let mut deciders = deciders();
+    let mut decided = 0;
+    for (name, states) in &machines {
+        let (decision, decider) = decide(&mut runner, &mut deciders, states, &limits);
+        decided += (decision != decider::Decision::Undecided) as u32;
+        let by = decider.map(|decider| format!(" by {decider}"));
+        println!(
+            "{name:<16} {states} {}{}",
+            decision.name(),
+            by.unwrap_or_default()
+        );
+    }
+    println!("Decided {decided} of {} machines.", machines.len());
+    if builtin && machines.len() < SKELET_MACHINES as usize {
+        println!(
+            "The built-in list has {} of Skelet's {SKELET_MACHINES} machines. Pass the full list with `--list`.",
+            machines.len()
+        );
+    }
+    Ok(())
+}
+
+type NamedDecider = (&'static str, Box<dyn Decider>);
+
+/// The deciders in the order they are tried. Cheap deciders come first.
+fn deciders() -> Vec<NamedDecider> {
+    vec![
+        (
+            "cyclers",
+            Box::new(CyclersDecider::<5, 2>::new(Default::default())),
+        ),
+        (
+            "segment",
+            Box::new(SegmentSimulator::<5, 2>::new(segment::Config {
+                window: 8,
+                position: segment::Position::Every,
+            })),
+        ),
+        (
+            "counter",
+            Box::new(CounterDecider::<5, 2>::new(counter::Config::default())),
+        ),
+    ]
+}
+
+/// Decide `states` by simulation and then by the first of `deciders` that decides it. Returns what decided the machine, `simulation` or the name of the decider, unless it is undecided.
+fn decide(
+    runner: &mut Runner,
+    deciders: &mut [NamedDecider],
+    states: &States,
+    limits: &Limits,
+) -> (decider::Decision, Option<&'static str>) {
+    let decision = match decide_standalone(runner, states, limits, &Schedule::default()) {
+        Decision::Halt(..) => decider::Decision::Halt,
+        Decision::Loop(_) => decider::Decision::RunForever,
+        Decision::Irrelevant => decider::Decision::Irrelevant,
+        Decision::Undecided(_) => decider::Decision::Undecided,
+    };
+    if decision != decider::Decision::Undecided {
+        return (decision, Some("simulation"));
+    }
+    deciders
+        .iter_mut()
+        .find_map(|(name, decider)| {
+            let decision = decider.decide(states);
+            (decision != decider::Decision::Undecided).then_some((decision, Some(*name)))
+        })
+        .unwrap_or((decider::Decision::Undecided, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deciders_follow_the_simulation() {
+        let limits = Limits::default();
+        let mut runner = create_runner(&limits);
+        let mut deciders = deciders();
+        let mut decide = |machine: &str| {
+            let states = busy_beaver::format::read_compact(machine.as_bytes()).unwrap();
+            decide(&mut runner, &mut deciders, &states, &limits)
+        };
+        assert_eq!(
+            decide("1RB1LB_1LA---_------_------_------"),
+            (decider::Decision::Halt, Some("simulation"))
+        );
+        // Runs to the right through every state until it reaches the space limit, so the simulation cannot decide it.
+        assert_eq!(
+            decide("1RB1RB_1RC1RC_1RD1RD_1RE1RE_1RA1RA"),
+            (decider::Decision::RunForever, Some("segment"))
+        );
+    }
+}
@@ -0,0 +1,98 @@
+// Extracting a subset of a seed database into a new database.
+//
+// Researchers often want to share a handful of machines. A small database in the original format can be used with all existing tools.
+
+use std::{
+    io::{BufWriter, Write},
+    ops::Range,
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::format::{write_seed_database_header, SeedDatabaseHeader};
+
+use crate::mapped::MappedDatabase;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Uncompressed seed database to read from.
+    database: PathBuf,
+    /// Database to create.
+    output: PathBuf,
+    /// Half open range of indices like `100..200`.
+    #[arg(long, value_parser = parse_range, required_unless_present = "indices", conflicts_with = "indices")]
+    range: Option<Range<u32>>,
+    /// Comma separated list of indices.
+    #[arg(long, value_delimiter = ',')]
+    indices: Vec<u32>,
+}
+
+fn parse_range(s: &str) -> Result<Range<u32>> {
+    let (start, end) = s.split_once("..").context("expected `start..end`")?;
+    let start = start.parse().context("invalid start")?;
+    let end = end.parse().context("invalid end")?;
+    if start > end {
+        return Err(anyhow!("start is larger than end"));
+    }
+    Ok(start..end)
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let indices: Vec<u32> = match args.range {
+        Some(range) => range.collect(),
+        None => args.indices,
+    };
+
+    let database = MappedDatabase::open(&args.database)?;
+    let header = *database.header();
+    if let Some(index) = indices.iter().find(|i| **i >= header.total()) {
+        return Err(anyhow!(
+            "index {index} is out of bounds for a database with {} machines",
+            header.total()
+        ));
+    }
+
+    // Time limit machines come first. The new header needs to know how many of the selected machines are of either kind.
+    let time_limit = indices.iter().filter(|i| **i < header.time_limit).count() as u32;
+    // The ordering is only kept if the indices are in ascending order.
+    let ascending = indices.windows(2).all(|w| w[0] < w[1]);
+    let time_limit_first = indices
+        .iter()
+        .skip(time_limit as usize)
+        .all(|i| *i >= header.time_limit);
+    if !time_limit_first {
+        return Err(anyhow!(
+            "time limit machines (indices below {}) must come before space limit machines",
+            header.time_limit
+        ));
+    }
+    let new_header = SeedDatabaseHeader {
+        time_limit,
+        space_limit: indices.len() as u32 - time_limit,
+        sorted: header.sorted && ascending,
+    };
+
+    let output = std::fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&args.output)
+        .context("create output database")?;
+    let mut output = BufWriter::new(output);
+    output
+        .write_all(&write_seed_database_header(&new_header))
+        .context("write header")?;
+    for index in &indices {
+        database.get(*index)?;
+        output
+            .write_all(&database.entries()[*index as usize])
+            .context("write machine")?;
+    }
+    output.flush().context("flush output database")?;
+    println!(
+        "Wrote {} machines ({} time limit, {} space limit).",
+        indices.len(),
+        new_header.time_limit,
+        new_header.space_limit
+    );
+    Ok(())
+}
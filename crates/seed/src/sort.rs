@@ -0,0 +1,189 @@
+// Checking that a seed database is sorted and free of duplicates, and repairing it if it is not.
+//
+// Within the time limit and space limit sections of the database, machines are ordered by their bytes. A database assembled from several logs is usually neither sorted nor deduplicated. Such a database can be larger than memory so the repair uses the external merge sort of `external_sort`.
+
+use std::{
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::format::{
+    read_seed_database, read_seed_database_header, write_seed_database_header, SeedDatabaseHeader,
+    SEED_DATABASE_ENTRY_LEN, SEED_DATABASE_HEADER_LEN,
+};
+
+use crate::external_sort::Sorter;
+
+pub type Entry = [u8; SEED_DATABASE_ENTRY_LEN];
+
+/// Number of duplicate machines that are printed.
+const MAX_REPORTED: usize = 10;
+
+#[derive(Default)]
+pub struct Duplicates {
+    pub count: u64,
+    /// The first `MAX_REPORTED` duplicates.
+    examples: Vec<Entry>,
+}
+
+impl Duplicates {
+    fn add(&mut self, entry: Entry) {
+        self.count += 1;
+        if self.examples.len() < MAX_REPORTED {
+            self.examples.push(entry);
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Uncompressed seed database to check.
+    database: PathBuf,
+    /// Write a sorted and deduplicated copy of the database to this path.
+    #[arg(long)]
+    repair: Option<PathBuf>,
+    /// Memory used for sorting, in MiB.
+    #[arg(long, default_value_t = 1024)]
+    memory: usize,
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let database = std::fs::OpenOptions::new()
+        .read(true)
+        .open(&args.database)
+        .context("open database")?;
+    let mut database = BufReader::new(database);
+    let mut header = [0u8; SEED_DATABASE_HEADER_LEN];
+    database
+        .read_exact(&mut header)
+        .context("read database header")?;
+    let header = read_seed_database_header(&header).context("parse database header")?;
+
+    let mut is_canonical = true;
+    for (name, len) in sections(&header) {
+        let (unsorted, duplicates) =
+            check_section(&mut database, len).with_context(|| format!("check {name} section"))?;
+        println!("{name} section: {len} machines, {unsorted} out of order, {duplicates} adjacent duplicates.");
+        is_canonical &= (unsorted == 0) & (duplicates == 0);
+    }
+    if header.sorted != is_canonical {
+        println!(
+            "The header's sorted flag is {} but the database is {}sorted.",
+            header.sorted,
+            if is_canonical { "" } else { "not " }
+        );
+    }
+
+    let Some(output_path) = args.repair else {
+        return Ok(());
+    };
+    let memory = args
+        .memory
+        .checked_mul(1 << 20)
+        .context("memory too large")?;
+    let output = std::fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&output_path)
+        .context("create output database")?;
+    let mut output = BufWriter::new(output);
+    // The header is written last when the counts are known.
+    output
+        .write_all(&[0u8; SEED_DATABASE_HEADER_LEN])
+        .context("write header")?;
+    database
+        .seek(SeekFrom::Start(SEED_DATABASE_HEADER_LEN as u64))
+        .context("seek database")?;
+    let mut counts = [0u32; 2];
+    for ((name, len), count) in sections(&header).into_iter().zip(counts.iter_mut()) {
+        let temp_prefix = output_path.with_extension(format!("{}.run", name.replace(" ", "_")));
+        let mut duplicates = Duplicates::default();
+        let written = sort_section(
+            &mut database,
+            len,
+            memory,
+            &temp_prefix,
+            &mut output,
+            &mut duplicates,
+        )
+        .with_context(|| format!("sort {name} section"))?;
+        println!(
+            "{name} section: wrote {written} machines, removed {} duplicates.",
+            duplicates.count
+        );
+        for entry in &duplicates.examples {
+            println!("duplicate: {}", read_seed_database(entry)?);
+        }
+        *count = written;
+    }
+    let header = SeedDatabaseHeader {
+        time_limit: counts[0],
+        space_limit: counts[1],
+        sorted: true,
+    };
+    let mut output = output.into_inner().context("flush output database")?;
+    output
+        .seek(SeekFrom::Start(0))
+        .context("seek output database")?;
+    output
+        .write_all(&write_seed_database_header(&header))
+        .context("write header")?;
+    output.flush().context("flush output database")?;
+    Ok(())
+}
+
+fn sections(header: &SeedDatabaseHeader) -> [(&'static str, u32); 2] {
+    [
+        ("time limit", header.time_limit),
+        ("space limit", header.space_limit),
+    ]
+}
+
+/// Returns how many entries are smaller than their predecessor and how many are equal to it.
+fn check_section(database: &mut impl Read, len: u32) -> Result<(u64, u64)> {
+    let mut unsorted = 0;
+    let mut duplicates = 0;
+    let mut previous: Option<Entry> = None;
+    let mut entry: Entry = [0; SEED_DATABASE_ENTRY_LEN];
+    for _ in 0..len {
+        database.read_exact(&mut entry).context("read machine")?;
+        if let Some(previous) = previous {
+            unsorted += (entry < previous) as u64;
+            duplicates += (entry == previous) as u64;
+        }
+        previous = Some(entry);
+    }
+    Ok((unsorted, duplicates))
+}
+
+/// Sort `len` entries from `input` into `output`. See `sort_entries`.
+fn sort_section(
+    input: &mut impl Read,
+    len: u32,
+    memory: usize,
+    temp_prefix: &Path,
+    output: &mut impl Write,
+    duplicates: &mut Duplicates,
+) -> Result<u32> {
+    let entries = (0..len).map(|_| {
+        let mut entry: Entry = [0; SEED_DATABASE_ENTRY_LEN];
+        input.read_exact(&mut entry).context("read machine")?;
+        Ok(entry)
+    });
+    sort_entries(entries, memory, temp_prefix, output, duplicates)
+}
+
+/// Sort and deduplicate entries into `output` with an external merge sort using up to `memory` bytes. The runs are written to temporary files whose paths start with `temp_prefix`. Removed duplicates are recorded in `duplicates`. Returns the number of written entries.
+pub fn sort_entries(
+    entries: impl Iterator<Item = Result<Entry>>,
+    memory: usize,
+    temp_prefix: &Path,
+    output: &mut impl Write,
+    duplicates: &mut Duplicates,
+) -> Result<u32> {
+    let written = Sorter::new(memory, temp_prefix)
+        .dedup()
+        .sort(entries, output, |entry| duplicates.add(*entry))?;
+    u32::try_from(written).map_err(|_| anyhow!("too many machines"))
+}
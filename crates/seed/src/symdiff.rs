@@ -0,0 +1,236 @@
+// Comparing the machine sets of two lists.
+//
+// An independent seed run should end with the same undecided machines as the official database. The lists are in different formats and orders and too large for memory, so both are converted to seed database entries, sorted with the external merge sort of `sort` and then merged. Only machines that are in exactly one list are printed.
+
+use std::{
+    cmp::Ordering,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use busy_beaver::format::{
+    read_seed_database, write_seed_database, LogDecision, MachineList, SEED_DATABASE_ENTRY_LEN,
+};
+
+use crate::sort::{sort_entries, Duplicates, Entry};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// First list of machines in any format of `MachineList`. Machines only in this list are printed as `< machine`.
+    a: PathBuf,
+    /// Second list of machines in any format of `MachineList`. Machines only in this list are printed as `> machine`.
+    b: PathBuf,
+    /// Index file selecting machines of the seed database given as the first list.
+    #[arg(long)]
+    index_a: Option<PathBuf>,
+    /// Index file selecting machines of the seed database given as the second list.
+    #[arg(long)]
+    index_b: Option<PathBuf>,
+    /// Skip machines that a log marks as decided.
+    #[arg(long)]
+    undecided: bool,
+    /// Directory for the temporary sorted copies of the lists. They need as much space as two seed databases of the lists.
+    #[arg(long, default_value = ".")]
+    temp_dir: PathBuf,
+    /// Memory used for sorting, in MiB.
+    #[arg(long, default_value_t = 1024)]
+    memory: usize,
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let prefix = args
+        .temp_dir
+        .join(format!("symdiff-{}", std::process::id()));
+    let sorted_a = PathBuf::from(format!("{}-a", prefix.display()));
+    let sorted_b = PathBuf::from(format!("{}-b", prefix.display()));
+    let result = (|| {
+        for (input, index, sorted) in [
+            (&args.a, &args.index_a, &sorted_a),
+            (&args.b, &args.index_b, &sorted_b),
+        ] {
+            let (written, duplicates) = sort_list(input, index.as_deref(), sorted, &args)
+                .with_context(|| format!("sort {}", input.display()))?;
+            eprintln!(
+                "{}: {written} machines, {duplicates} duplicates.",
+                input.display()
+            );
+        }
+        merge(&sorted_a, &sorted_b, &mut std::io::stdout().lock())
+    })();
+    for path in [&sorted_a, &sorted_b] {
+        let _ = std::fs::remove_file(path);
+    }
+    let (only_a, only_b, both) = result?;
+    eprintln!(
+        "{only_a} machines only in the first list, {only_b} only in the second, {both} in both."
+    );
+    Ok(())
+}
+
+/// Write the machines of `input`, restricted by `index` if given, sorted and deduplicated to `output`. Returns the number of written machines and of removed duplicates.
+fn sort_list(input: &Path, index: Option<&Path>, output: &Path, args: &Args) -> Result<(u32, u64)> {
+    let machines = match index {
+        Some(index) => MachineList::open_indexed(input, index)?,
+        None => MachineList::open(input)?,
+    };
+    let entries = machines.filter_map(|machine| match machine.context("read machines") {
+        Ok(machine)
+            if args.undecided
+                && !matches!(machine.decision, None | Some(LogDecision::Undecided)) =>
+        {
+            None
+        }
+        Ok(machine) => Some(Ok(write_seed_database(&machine.states))),
+        Err(err) => Some(Err(err)),
+    });
+    let file = std::fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(output)
+        .with_context(|| format!("create {}", output.display()))?;
+    let mut file = BufWriter::new(file);
+    let mut duplicates = Duplicates::default();
+    let run_prefix = PathBuf::from(format!("{}-run", output.display()));
+    let written = sort_entries(
+        entries,
+        args.memory * 1024 * 1024,
+        &run_prefix,
+        &mut file,
+        &mut duplicates,
+    )?;
+    file.flush().context("flush sorted list")?;
+    Ok((written, duplicates.count))
+}
+
+/// Merge two sorted lists and write the machines that are in only one of them to `output`. Returns the number of machines only in `a`, only in `b` and in both.
+fn merge(a: &Path, b: &Path, output: &mut impl Write) -> Result<(u64, u64, u64)> {
+    let open = |path: &Path| -> Result<_> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .open(path)
+            .with_context(|| format!("open {}", path.display()))?;
+        Ok(BufReader::new(file))
+    };
+    let mut a = open(a)?;
+    let mut b = open(b)?;
+    let mut print = |marker: char, entry: &Entry| -> Result<()> {
+        let states = read_seed_database(entry).context("parse sorted machine")?;
+        writeln!(output, "{marker} {states}")?;
+        Ok(())
+    };
+    let (mut only_a, mut only_b, mut both) = (0u64, 0u64, 0u64);
+    let mut entry_a = next(&mut a)?;
+    let mut entry_b = next(&mut b)?;
+    loop {
+        // A list that has ended orders after every machine.
+        let order = match (&entry_a, &entry_b) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(x), Some(y)) => x.cmp(y),
+        };
+        match order {
+            Ordering::Equal => {
+                both += 1;
+                entry_a = next(&mut a)?;
+                entry_b = next(&mut b)?;
+            }
+            Ordering::Less => {
+                print('<', entry_a.as_ref().unwrap())?;
+                only_a += 1;
+                entry_a = next(&mut a)?;
+            }
+            Ordering::Greater => {
+                print('>', entry_b.as_ref().unwrap())?;
+                only_b += 1;
+                entry_b = next(&mut b)?;
+            }
+        }
+    }
+    Ok((only_a, only_b, both))
+}
+
+fn next(reader: &mut impl Read) -> Result<Option<Entry>> {
+    let mut entry: Entry = [0; SEED_DATABASE_ENTRY_LEN];
+    match reader.read_exact(&mut entry) {
+        Ok(()) => Ok(Some(entry)),
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err).context("read sorted list"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use busy_beaver::format::{read_compact, write_seed_database_header, SeedDatabaseHeader};
+
+    use super::*;
+    use crate::enumerate::States;
+
+    #[test]
+    fn lists_are_merged() {
+        let machines: Vec<States> = [
+            "1RB---_------_------_------_------",
+            "1RB---_1LA---_------_------_------",
+            "1RB---_1LB---_------_------_------",
+            "1RB---_1RA---_------_------_------",
+            "1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA",
+        ]
+        .iter()
+        .map(|m| read_compact(m.as_bytes()).unwrap())
+        .collect();
+        let directory = std::env::temp_dir().join(format!("seed-symdiff-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let args = Args {
+            a: directory.join("a"),
+            b: directory.join("b"),
+            index_a: None,
+            index_b: Some(directory.join("index")),
+            undecided: false,
+            temp_dir: directory.clone(),
+            memory: 1,
+        };
+        // The first list is text in a different order with a duplicate.
+        let text: String = [3, 0, 1, 3]
+            .iter()
+            .map(|&i| format!("{}\n", machines[i]))
+            .collect();
+        std::fs::write(&args.a, text).unwrap();
+        // The second list is a seed database restricted by an index file.
+        let mut database = write_seed_database_header(&SeedDatabaseHeader {
+            time_limit: 3,
+            space_limit: 2,
+            sorted: true,
+        })
+        .to_vec();
+        for states in &machines {
+            database.extend_from_slice(&write_seed_database(states));
+        }
+        std::fs::write(&args.b, database).unwrap();
+        let index: Vec<u8> = [4u32, 1, 2].iter().flat_map(|i| i.to_be_bytes()).collect();
+        std::fs::write(args.index_b.as_ref().unwrap(), index).unwrap();
+
+        let (sorted_a, sorted_b) = (directory.join("sorted-a"), directory.join("sorted-b"));
+        assert_eq!(sort_list(&args.a, None, &sorted_a, &args).unwrap(), (3, 1));
+        assert_eq!(
+            sort_list(&args.b, args.index_b.as_deref(), &sorted_b, &args).unwrap(),
+            (3, 0)
+        );
+        let mut output = Vec::new();
+        let counts = merge(&sorted_a, &sorted_b, &mut output).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(counts, (2, 2, 1));
+        let mut expected: Vec<(Entry, String)> = [('<', 0), ('<', 3), ('>', 2), ('>', 4)]
+            .iter()
+            .map(|&(marker, i)| {
+                (
+                    write_seed_database(&machines[i]),
+                    format!("{marker} {}", machines[i]),
+                )
+            })
+            .collect();
+        expected.sort();
+        let expected: String = expected.into_iter().map(|(_, line)| line + "\n").collect();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+}
@@ -0,0 +1,148 @@
+// Export of the top of the enumeration tree.
+//
+// The first levels of the tree show how tree normal form enumeration works. Comparing exports before and after a change to `ChildNodes` shows whether the order of the enumeration changed.
+
+use std::{
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::enumerate::{
+    create_runner, decide, ChildNodes, Decision, HaltingTransitionIndex, Limits, Node, Runner,
+    Schedule, Transition,
+};
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Format {
+    Dot,
+    Json,
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Number of levels below the root to export.
+    #[arg(long, default_value_t = 2)]
+    depth: u32,
+    #[arg(long, value_enum, default_value_t = Format::Dot)]
+    format: Format,
+    /// File to write to. Defaults to stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct TreeNode {
+    id: usize,
+    parent: Option<usize>,
+    depth: u32,
+    machine: String,
+    /// The transition that was defined to create this node from its parent, like `B0`.
+    changed: Option<String>,
+    decision: &'static str,
+}
+
+pub fn main(args: Args) -> Result<()> {
+    let mut nodes = vec![TreeNode {
+        id: 0,
+        parent: None,
+        depth: Node::root().depth(),
+        machine: Node::root().0.to_string(),
+        changed: None,
+        decision: "root",
+    }];
+    collect(
+        &mut nodes,
+        &mut create_runner(&Limits::default()),
+        Node::root(),
+        HaltingTransitionIndex::root(),
+        0,
+        args.depth,
+    );
+
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            std::fs::File::create(path).context("create output file")?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    match args.format {
+        Format::Dot => write_dot(&mut output, &nodes),
+        Format::Json => serde_json::to_writer_pretty(&mut output, &nodes).map_err(Into::into),
+    }
+    .context("write tree")?;
+    output.flush().context("flush output")?;
+    Ok(())
+}
+
+/// Like `enumerate_recursively` but stops at `max_depth` and records every node.
+fn collect(
+    nodes: &mut Vec<TreeNode>,
+    runner: &mut Runner,
+    mut node: Node,
+    branch: HaltingTransitionIndex,
+    parent: usize,
+    max_depth: u32,
+) {
+    if nodes[parent].depth >= max_depth {
+        return;
+    }
+    for transition in ChildNodes::new(&node, branch) {
+        *node.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+        let depth = node.depth();
+        let decision = decide(
+            runner,
+            &node.0,
+            branch,
+            &Limits::default(),
+            &Schedule::default(),
+        );
+        let id = nodes.len();
+        nodes.push(TreeNode {
+            id,
+            parent: Some(parent),
+            depth,
+            machine: node.0.to_string(),
+            changed: Some(format!(
+                "{}{}",
+                char::from(b'A' + branch.0.get()),
+                branch.1.get()
+            )),
+            decision: decision.log_decision().name(),
+        });
+        if let Decision::Halt(branch, _) = decision {
+            if node.halting_transition_count() >= 2 {
+                collect(nodes, runner, node, branch, id, max_depth);
+            }
+        }
+    }
+}
+
+fn write_dot(output: &mut impl Write, nodes: &[TreeNode]) -> Result<()> {
+    writeln!(output, "digraph enumeration {{")?;
+    writeln!(
+        output,
+        "  node [shape=box, style=filled, fontname=monospace];"
+    )?;
+    for node in nodes {
+        let color = match node.decision {
+            "halt" => "lightblue",
+            "loop" => "palegreen",
+            "undecided" => "orange",
+            "irrelevant" => "lightgray",
+            _ => "white",
+        };
+        writeln!(
+            output,
+            "  {} [label=\"{}\", fillcolor={color}];",
+            node.id, node.machine
+        )?;
+        if let (Some(parent), Some(changed)) = (node.parent, &node.changed) {
+            writeln!(output, "  {parent} -> {} [label=\"{changed}\"];", node.id)?;
+        }
+    }
+    writeln!(output, "}}")?;
+    Ok(())
+}
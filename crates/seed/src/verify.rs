@@ -0,0 +1,194 @@
+// Checking a sample of the results of a run while it is going.
+//
+// A bug in the optimized simulation would otherwise only be noticed after the run completes, if at all. The verifier thread takes a random fraction of the results and decides them again with a straightforward simulation that does not share code with `Runner`. The first mismatch stops the run. Samples are dropped instead of queued when the verifier falls behind so that verification never slows down the enumeration. With the default limits undecided machines take the verifier a fraction of a second each, so one verifier thread checks a few machines per second.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use busy_beaver::{
+    states::{Direction, Transition},
+    stress::Rng,
+};
+
+use crate::enumerate::{
+    Decision, HaltingTransitionIndex, Limits, LoopReason, State, States, Symbol, UndecidedReason,
+};
+
+/// Number of samples waiting for the verifier. More samples are dropped.
+const QUEUE_LEN: usize = 64;
+
+/// Shared state of the main thread, which samples results, and the verifier thread.
+pub struct Verifier {
+    fraction: f64,
+    rng: Rng,
+    sender: SyncSender<(States, Decision)>,
+    /// Machines that were sampled but not verified because the verifier was busy.
+    pub dropped: u64,
+    verified: Arc<AtomicU64>,
+    mismatch: Arc<Mutex<Option<String>>>,
+}
+
+impl Verifier {
+    /// Start the verifier thread. `fraction` of the results passed to `sample` are verified. On a mismatch `keep_running` is cleared and `abort` is set so that the run stops and the current tasks are saved.
+    pub fn start(
+        fraction: f64,
+        limits: Limits,
+        keep_running: Arc<AtomicBool>,
+        abort: Arc<AtomicBool>,
+    ) -> Self {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(QUEUE_LEN);
+        let verified = Arc::new(AtomicU64::new(0));
+        let mismatch = Arc::new(Mutex::new(None));
+        std::thread::spawn({
+            let verified = verified.clone();
+            let mismatch = mismatch.clone();
+            move || {
+                if let Some(message) = verify(receiver, &limits, &verified) {
+                    println!("VERIFICATION FAILED: {message}. Stopping the run.");
+                    *mismatch.lock().unwrap() = Some(message);
+                    keep_running.store(false, Ordering::Relaxed);
+                    abort.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        Self {
+            fraction,
+            rng: Rng::new(seed),
+            sender,
+            dropped: 0,
+            verified,
+            mismatch,
+        }
+    }
+
+    /// Called by the main thread for every result.
+    pub fn sample(&mut self, states: &States, decision: Decision) {
+        // Irrelevant machines are not simulated so there is nothing to verify.
+        if decision == Decision::Irrelevant
+            || (self.rng.next_u64() as f64) >= self.fraction * u64::MAX as f64
+        {
+            return;
+        }
+        match self.sender.try_send((*states, decision)) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => self.dropped += 1,
+            // The verifier has stopped after a mismatch.
+            Err(TrySendError::Disconnected(_)) => (),
+        }
+    }
+
+    pub fn verified(&self) -> u64 {
+        self.verified.load(Ordering::Relaxed)
+    }
+
+    /// Description of the first mismatch.
+    pub fn mismatch(&self) -> Option<String> {
+        self.mismatch.lock().unwrap().clone()
+    }
+}
+
+fn verify(
+    receiver: Receiver<(States, Decision)>,
+    limits: &Limits,
+    verified: &AtomicU64,
+) -> Option<String> {
+    for (states, decision) in receiver {
+        let expected = reference_decide(&states, limits);
+        // The checks of a step budget schedule decide machines that the reference leaves undecided.
+        let consistent = decision == expected
+            || (decision == Decision::Loop(LoopReason::Check)
+                && matches!(expected, Decision::Undecided(_)));
+        if !consistent {
+            return Some(format!(
+                "{states} was decided as {decision:?} but the reference simulation decides {expected:?}"
+            ));
+        }
+        verified.fetch_add(1, Ordering::Relaxed);
+    }
+    None
+}
+
+/// Decide a machine like `enumerate::decide_standalone` with the default schedule, using the simplest possible simulation.
+pub fn reference_decide(states: &States, limits: &Limits) -> Decision {
+    let mut tape = vec![0u8; limits.tape_size()];
+    let mut position = tape.len() / 2;
+    let mut state = 0usize;
+    let mut seen = [false; 5];
+    for step in 0u64.. {
+        seen[state] = true;
+        if step > limits.bb4_steps && !seen.iter().all(|seen| *seen) {
+            return Decision::Loop(LoopReason::UnvisitedState);
+        }
+        if step > limits.steps {
+            return Decision::Undecided(UndecidedReason::Steps);
+        }
+        let symbol = tape[position] as usize;
+        let Transition::Continue(transition) = states.0[state][symbol] else {
+            let index = HaltingTransitionIndex(
+                State::new(state as u8).unwrap(),
+                Symbol::new(symbol as u8).unwrap(),
+            );
+            return Decision::Halt(index, step + 1);
+        };
+        tape[position] = transition.write.get();
+        state = transition.state.get() as usize;
+        position = match transition.move_ {
+            Direction::Left if position == 0 => {
+                return Decision::Undecided(UndecidedReason::SpaceLeft)
+            }
+            Direction::Left => position - 1,
+            Direction::Right if position + 1 == tape.len() => {
+                return Decision::Undecided(UndecidedReason::SpaceRight)
+            }
+            Direction::Right => position + 1,
+        };
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use busy_beaver::stress::{machine, Kind};
+
+    use super::*;
+    use crate::enumerate::{create_runner, decide_standalone, Schedule};
+
+    #[test]
+    fn reference_decide_agrees() {
+        let limits = Limits {
+            steps: 2000,
+            memory: 20,
+            bb4_steps: 107,
+        };
+        let mut runner = create_runner(&limits);
+        let mut rng = Rng::new(0);
+        for _ in 0..2000 {
+            for kind in Kind::ALL {
+                let states = machine::<5, 2>(&mut rng, kind);
+                let expected =
+                    decide_standalone(&mut runner, &states, &limits, &Schedule::default());
+                assert_eq!(reference_decide(&states, &limits), expected, "{states}");
+            }
+        }
+        // The BB(4) champion with an unused fifth state.
+        let champion = busy_beaver::fixtures::champion(4, 2).unwrap();
+        let states: States =
+            busy_beaver::format::read_compact(format!("{}_------", champion.compact).as_bytes())
+                .unwrap();
+        let limits = Limits::default();
+        let mut runner = create_runner(&limits);
+        let expected = decide_standalone(&mut runner, &states, &limits, &Schedule::default());
+        assert!(matches!(expected, Decision::Halt(_, 107)));
+        assert_eq!(reference_decide(&states, &limits), expected);
+    }
+}
@@ -0,0 +1,590 @@
+// Work units for distributing a run by any transport.
+//
+// A shard of `shard::ShardPlan` is run with the normal run, which needs the plan file and keeps a resume file and a log. That suits computers under one's own control. Volunteers need something simpler: a small file that says exactly what to compute, and a file with the answer that can be checked and combined without trusting how it was made or how it got back. Both are plain text, so they can be sent by mail, copied with a USB stick or served by any program.
+//
+// A work unit holds the format version, the limits, the step budgets and deciders of the schedule, the machines the planner decided and the tasks of one shard. The budgets and deciders lines are empty for the default schedule:
+//
+// ```text
+// busy beaver work unit 2
+// limits 47176870 12289 107
+// budgets 1000 100000
+// deciders cyclers segment
+// machine 1RB---_0LA---_------_------_------ B0
+// task 1RB---_1LC---_------_------_------ C0
+// ```
+//
+// A result starts with the version and the id of its unit, which is a hash of the unit file, followed by a log entry for every machine in any order and a line with the number of halting, looping, undecided and irrelevant machines:
+//
+// ```text
+// busy beaver work result 2
+// unit 4c0a63b7d6a1fe2d
+// 1RB---_1LC---_0RA---_------_------ h
+// end 1 0 0 0
+// ```
+//
+// The last line shows that the result is complete. Merging checks every result and that no unit is included twice, and writes a single log.
+
+use std::{
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use anyhow::{anyhow, Context, Result};
+use busy_beaver::format::{read_compact, read_log_entry, LOG_ENTRY_LEN};
+
+use crate::{
+    enumerate::{
+        create_runner, decide, enumerate_recursively, validate_node, Decision,
+        HaltingTransitionIndex, Limits, Node, Schedule, ScheduleDecider, States,
+    },
+    shard::{format_transition_index, parse_transition_index, ShardPlan},
+};
+
+pub const WORK_UNIT_VERSION: u32 = 2;
+const UNIT_HEADER: &str = "busy beaver work unit";
+const RESULT_HEADER: &str = "busy beaver work result";
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: UnitCommand,
+}
+
+#[derive(clap::Subcommand)]
+enum UnitCommand {
+    /// Write a work unit for every shard of a plan written by `plan-shards`.
+    Create {
+        plan: PathBuf,
+        /// Directory for the units, named `unit.0000`, `unit.0001` and so on.
+        #[arg(long, default_value = "units")]
+        output: PathBuf,
+    },
+    /// Enumerate a work unit and write its result.
+    Run {
+        unit: PathBuf,
+        /// File to write the result to. Defaults to the unit's path with `.result` appended.
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Number of threads. Defaults to all cores.
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Check that a result is complete and consistent, and optionally that it belongs to a unit.
+    Validate {
+        result: PathBuf,
+        #[arg(long)]
+        unit: Option<PathBuf>,
+    },
+    /// Check results and combine them into a log.
+    Merge {
+        results: Vec<PathBuf>,
+        /// File to write the log to.
+        #[arg(long, default_value = "log")]
+        output: PathBuf,
+    },
+}
+
+/// Self-contained description of some work of a run.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WorkUnit {
+    pub limits: Limits,
+    /// Step budgets of the schedule, valid for `limits`.
+    pub budgets: Vec<u64>,
+    /// Deciders of the schedule.
+    pub deciders: Vec<ScheduleDecider>,
+    /// Machines to decide on their own, with the transition that was defined last.
+    pub machines: Vec<(States, HaltingTransitionIndex)>,
+    /// Subtrees to enumerate.
+    pub tasks: Vec<(Node, HaltingTransitionIndex)>,
+}
+
+/// What a valid result contains.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResultSummary {
+    pub unit: u64,
+    /// Entries by `LogDecision`, in the order halt, loop, undecided, irrelevant.
+    pub counts: [u64; 4],
+}
+
+impl WorkUnit {
+    /// One unit for every shard of `plan`.
+    pub fn create(plan: &ShardPlan) -> Result<Vec<Self>> {
+        (0..plan.shards.len() as u32)
+            .map(|i| {
+                let shard = plan.shard(i)?;
+                Ok(Self {
+                    limits: plan.limits,
+                    budgets: plan.budgets.clone(),
+                    deciders: plan.deciders.clone(),
+                    machines: shard.machines,
+                    tasks: shard.tasks,
+                })
+            })
+            .collect()
+    }
+
+    pub fn write(&self, mut w: impl Write) -> std::io::Result<()> {
+        let limits = &self.limits;
+        writeln!(w, "{UNIT_HEADER} {WORK_UNIT_VERSION}")?;
+        writeln!(
+            w,
+            "limits {} {} {}",
+            limits.steps, limits.memory, limits.bb4_steps
+        )?;
+        write!(w, "budgets")?;
+        for budget in &self.budgets {
+            write!(w, " {budget}")?;
+        }
+        write!(w, "\ndeciders")?;
+        for decider in &self.deciders {
+            write!(w, " {decider}")?;
+        }
+        writeln!(w)?;
+        for (states, changed) in &self.machines {
+            writeln!(w, "machine {states} {}", format_transition_index(*changed))?;
+        }
+        for (node, branch) in &self.tasks {
+            writeln!(w, "task {} {}", node.0, format_transition_index(*branch))?;
+        }
+        Ok(())
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix(UNIT_HEADER))
+            .context("missing header")?;
+        check_version(version)?;
+        let limits: Vec<&str> = lines
+            .next()
+            .and_then(|line| line.strip_prefix("limits "))
+            .context("missing limits")?
+            .split(' ')
+            .collect();
+        let &[steps, memory, bb4_steps] = limits.as_slice() else {
+            return Err(anyhow!("expected 3 limits"));
+        };
+        let mut unit = Self {
+            limits: Limits {
+                steps: steps.parse().context("invalid step limit")?,
+                memory: memory.parse().context("invalid memory limit")?,
+                bb4_steps: bb4_steps.parse().context("invalid bb4 steps")?,
+            },
+            budgets: lines
+                .next()
+                .and_then(|line| line.strip_prefix("budgets"))
+                .context("missing budgets")?
+                .split_whitespace()
+                .map(|budget| budget.parse().context("invalid budget"))
+                .collect::<Result<_>>()?,
+            deciders: lines
+                .next()
+                .and_then(|line| line.strip_prefix("deciders"))
+                .context("missing deciders")?
+                .split_whitespace()
+                .map(|name| {
+                    ScheduleDecider::from_name(name)
+                        .with_context(|| format!("unknown decider {name:?}"))
+                })
+                .collect::<Result<_>>()?,
+            machines: Vec::new(),
+            tasks: Vec::new(),
+        };
+        unit.limits.validate()?;
+        unit.schedule().context("invalid budgets")?;
+        for (i, line) in lines.enumerate() {
+            let mut parse = || -> Result<()> {
+                let fields: Vec<&str> = line.split(' ').collect();
+                let &[kind, machine, transition] = fields.as_slice() else {
+                    return Err(anyhow!("expected 3 fields"));
+                };
+                let states: States = read_compact(machine.as_bytes())?;
+                let transition = parse_transition_index(transition)?;
+                match kind {
+                    "machine" => unit.machines.push((states, transition)),
+                    "task" => {
+                        validate_node(&Node(states), transition)?;
+                        unit.tasks.push((Node(states), transition));
+                    }
+                    _ => return Err(anyhow!("unknown kind {kind:?}")),
+                }
+                Ok(())
+            };
+            parse().with_context(|| format!("line {}", i + 5))?;
+        }
+        Ok(unit)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let s =
+            std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        Self::parse(&s).with_context(|| format!("parse unit {}", path.display()))
+    }
+
+    pub fn schedule(&self) -> Result<Schedule> {
+        Ok(Schedule::new(&self.budgets, &self.limits)?.with_deciders(&self.deciders))
+    }
+
+    /// FNV-1a hash of the unit as written. Results name their unit by it.
+    pub fn id(&self) -> u64 {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes).unwrap();
+        bytes.iter().fold(0xcbf29ce484222325, |hash: u64, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// Decide the machines and enumerate the tasks with `thread_count` threads and write the result to `output`.
+    pub fn run(&self, thread_count: usize, output: impl Write + Send) -> Result<ResultSummary> {
+        let output = Mutex::new(BufWriter::new(output));
+        writeln!(
+            output.lock().unwrap(),
+            "{RESULT_HEADER} {WORK_UNIT_VERSION}"
+        )?;
+        writeln!(output.lock().unwrap(), "unit {:016x}", self.id())?;
+        let schedule = self.schedule()?;
+        let mut counts = [0u64; 4];
+        let mut runner = create_runner(&self.limits);
+        let mut entries = Vec::new();
+        for (states, changed) in &self.machines {
+            let decision = decide(&mut runner, states, *changed, &self.limits, &schedule);
+            push_entry(&mut entries, &mut counts, states, decision);
+        }
+        output.lock().unwrap().write_all(&entries)?;
+
+        let next_task = AtomicUsize::new(0);
+        let thread_counts = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..thread_count.max(1))
+                .map(|_| {
+                    scope.spawn(|| -> Result<[u64; 4]> {
+                        let mut runner = create_runner(&self.limits);
+                        let mut counts = [0u64; 4];
+                        let mut entries = Vec::new();
+                        while let Some((node, branch)) =
+                            self.tasks.get(next_task.fetch_add(1, Ordering::Relaxed))
+                        {
+                            entries.clear();
+                            enumerate_recursively(
+                                *node,
+                                *branch,
+                                &mut runner,
+                                &self.limits,
+                                &schedule,
+                                &mut |states, decision| {
+                                    push_entry(&mut entries, &mut counts, states, decision);
+                                    false
+                                },
+                            );
+                            output.lock().unwrap().write_all(&entries)?;
+                        }
+                        Ok(counts)
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect::<Result<Vec<_>>>()
+        })?;
+        for thread_counts in thread_counts {
+            for (count, thread_count) in counts.iter_mut().zip(thread_counts) {
+                *count += thread_count;
+            }
+        }
+        let mut output = output.into_inner().unwrap();
+        let [halt, loop_, undecided, irrelevant] = counts;
+        writeln!(output, "end {halt} {loop_} {undecided} {irrelevant}")?;
+        output.flush()?;
+        Ok(ResultSummary {
+            unit: self.id(),
+            counts,
+        })
+    }
+}
+
+fn check_version(version: &str) -> Result<()> {
+    let version: u32 = version.trim().parse().context("invalid version")?;
+    if version != WORK_UNIT_VERSION {
+        return Err(anyhow!(
+            "version {version} is not supported, this program reads version {WORK_UNIT_VERSION}"
+        ));
+    }
+    Ok(())
+}
+
+fn push_entry(entries: &mut Vec<u8>, counts: &mut [u64; 4], states: &States, decision: Decision) {
+    let decision = decision.log_decision();
+    counts[decision as usize] += 1;
+    writeln!(entries, "{states} {}", decision.code() as char).unwrap();
+}
+
+/// Check that a result is well formed and complete and pass its entries to `entry`.
+pub fn validate(
+    result: impl BufRead,
+    mut entry: impl FnMut(&[u8; LOG_ENTRY_LEN]) -> Result<()>,
+) -> Result<ResultSummary> {
+    let mut lines = result.split(b'\n');
+    let mut line = |what: &str| -> Result<Vec<u8>> {
+        lines
+            .next()
+            .with_context(|| format!("missing {what}"))?
+            .with_context(|| format!("read {what}"))
+    };
+    let header = String::from_utf8(line("header")?).context("invalid header")?;
+    check_version(
+        header
+            .strip_prefix(RESULT_HEADER)
+            .context("missing header")?,
+    )?;
+    let unit = String::from_utf8(line("unit")?).context("invalid unit")?;
+    let unit = unit
+        .strip_prefix("unit ")
+        .and_then(|id| u64::from_str_radix(id, 16).ok())
+        .context("invalid unit")?;
+    let mut counts = [0u64; 4];
+    let mut number = 2u64;
+    loop {
+        number += 1;
+        let mut line = line("end")?;
+        if let Some(end) = line.strip_prefix(b"end ") {
+            let end = std::str::from_utf8(end).context("invalid end")?;
+            let claimed = end
+                .split(' ')
+                .map(|count| count.parse::<u64>())
+                .collect::<Result<Vec<_>, _>>()
+                .context("invalid end")?;
+            if claimed != counts {
+                return Err(anyhow!(
+                    "the result claims {claimed:?} machines but has {counts:?}"
+                ));
+            }
+            break;
+        }
+        line.push(b'\n');
+        let line: &[u8; LOG_ENTRY_LEN] = line
+            .as_slice()
+            .try_into()
+            .with_context(|| format!("line {number} is not a log entry"))?;
+        let (_, decision) = read_log_entry(line).with_context(|| format!("line {number}"))?;
+        counts[decision as usize] += 1;
+        entry(line)?;
+    }
+    if lines.next().is_some() {
+        return Err(anyhow!("data after the end"));
+    }
+    Ok(ResultSummary { unit, counts })
+}
+
+/// Check `results` and write their entries to `output`. Fails if a unit is included twice. On failure `output` has the entries up to the failing result.
+pub fn merge(results: &[PathBuf], output: impl Write) -> Result<[u64; 4]> {
+    let mut output = BufWriter::new(output);
+    let mut units = std::collections::HashMap::<u64, &Path>::new();
+    let mut counts = [0u64; 4];
+    for path in results {
+        let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let summary = validate(BufReader::new(file), |entry| {
+            output.write_all(entry).context("write log")
+        })
+        .with_context(|| format!("result {}", path.display()))?;
+        if let Some(previous) = units.insert(summary.unit, path) {
+            return Err(anyhow!(
+                "{} and {} are results of the same unit",
+                previous.display(),
+                path.display()
+            ));
+        }
+        for (count, result_count) in counts.iter_mut().zip(summary.counts) {
+            *count += result_count;
+        }
+    }
+    output.flush().context("flush log")?;
+    Ok(counts)
+}
+
+pub fn main(args: Args) -> Result<()> {
+    match args.command {
+        UnitCommand::Create { plan, output } => {
+            let units = WorkUnit::create(&ShardPlan::read(&plan)?)?;
+            std::fs::create_dir_all(&output).context("create output directory")?;
+            for (i, unit) in units.iter().enumerate() {
+                let path = output.join(format!("unit.{i:04}"));
+                let file = std::fs::File::create(&path)
+                    .with_context(|| format!("create {}", path.display()))?;
+                let mut file = BufWriter::new(file);
+                unit.write(&mut file)
+                    .and_then(|_| file.flush())
+                    .with_context(|| format!("write {}", path.display()))?;
+                println!("{} {:016x}", path.display(), unit.id());
+            }
+            Ok(())
+        }
+        UnitCommand::Run {
+            unit,
+            output,
+            threads,
+        } => {
+            let work_unit = WorkUnit::read(&unit)?;
+            let output = output.unwrap_or_else(|| {
+                let mut path = unit.into_os_string();
+                path.push(".result");
+                path.into()
+            });
+            let file = std::fs::File::create(&output).context("create result file")?;
+            let summary = work_unit.run(threads.unwrap_or_else(num_cpus::get), file)?;
+            print_counts(&summary.counts);
+            println!("Wrote {}.", output.display());
+            Ok(())
+        }
+        UnitCommand::Validate { result, unit } => {
+            let file = std::fs::File::open(&result).context("open result")?;
+            let summary = validate(BufReader::new(file), |_| Ok(()))?;
+            if let Some(unit) = unit {
+                let id = WorkUnit::read(&unit)?.id();
+                if id != summary.unit {
+                    return Err(anyhow!(
+                        "the result belongs to unit {:016x} but {} is unit {id:016x}",
+                        summary.unit,
+                        unit.display()
+                    ));
+                }
+            }
+            print_counts(&summary.counts);
+            println!("The result of unit {:016x} is valid.", summary.unit);
+            Ok(())
+        }
+        UnitCommand::Merge { results, output } => {
+            let file = std::fs::File::create(&output).context("create log")?;
+            let counts = merge(&results, file)?;
+            print_counts(&counts);
+            println!(
+                "Merged {} results into {}.",
+                results.len(),
+                output.display()
+            );
+            Ok(())
+        }
+    }
+}
+
+fn print_counts(counts: &[u64; 4]) {
+    let [halt, loop_, undecided, irrelevant] = counts;
+    println!(
+        "{} machines: {halt} halt, {loop_} loop, {undecided} undecided, {irrelevant} irrelevant.",
+        counts.iter().sum::<u64>()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumerate::{count_subtree, ChildNodes, Transition};
+
+    #[test]
+    fn run_validate_and_merge() {
+        let limits = Limits {
+            steps: 4,
+            ..Limits::default()
+        };
+        let root = Node::root();
+        let branch = HaltingTransitionIndex::root();
+        // Split the root by hand: its children are decided on their own and the subtrees below them are tasks of two units.
+        let mut units = [
+            WorkUnit {
+                limits,
+                budgets: vec![2],
+                deciders: ScheduleDecider::ALL.to_vec(),
+                machines: Vec::new(),
+                tasks: Vec::new(),
+            },
+            WorkUnit {
+                limits,
+                budgets: vec![2],
+                deciders: ScheduleDecider::ALL.to_vec(),
+                machines: Vec::new(),
+                tasks: Vec::new(),
+            },
+        ];
+        let schedule = units[0].schedule().unwrap();
+        let mut runner = create_runner(&limits);
+        let mut child = root;
+        for (i, transition) in ChildNodes::new(&root, branch).enumerate() {
+            *child.0.get_transition_mut(branch.0, branch.1) = Transition::Continue(transition);
+            units[0].machines.push((child.0, branch));
+            let decision = decide(&mut runner, &child.0, branch, &limits, &schedule);
+            if let Decision::Halt(next, _) = decision {
+                if child.halting_transition_count() >= 2 {
+                    units[i % 2].tasks.push((child, next));
+                }
+            }
+        }
+        assert_ne!(units[0].id(), units[1].id());
+
+        let directory = std::env::temp_dir().join(format!("seed-work-unit-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut results = Vec::new();
+        for (i, unit) in units.iter().enumerate() {
+            let mut text = Vec::new();
+            unit.write(&mut text).unwrap();
+            assert_eq!(
+                &WorkUnit::parse(std::str::from_utf8(&text).unwrap()).unwrap(),
+                unit
+            );
+            let mut result = Vec::new();
+            let summary = unit.run(2, &mut result).unwrap();
+            assert_eq!(validate(&result[..], |_| Ok(())).unwrap(), summary);
+            let path = directory.join(format!("result.{i}"));
+            std::fs::write(&path, &result).unwrap();
+            results.push(path);
+        }
+
+        let mut log = Vec::new();
+        let counts = merge(&results, &mut log).unwrap();
+        let total = count_subtree(&root, branch, &limits);
+        assert_eq!(counts.iter().sum::<u64>(), total);
+        assert_eq!(log.len() as u64, total * LOG_ENTRY_LEN as u64);
+        // The deciders only turn machines that reach the step limit into loops, so the subtrees are the same as without them.
+        let without_deciders = WorkUnit {
+            budgets: Vec::new(),
+            deciders: Vec::new(),
+            ..units[0].clone()
+        };
+        let with_deciders = validate(&std::fs::read(&results[0]).unwrap()[..], |_| Ok(())).unwrap();
+        let without_deciders = without_deciders.run(1, Vec::new()).unwrap();
+        assert!(with_deciders.counts[2] < without_deciders.counts[2]);
+        assert_eq!(
+            with_deciders.counts.iter().sum::<u64>(),
+            without_deciders.counts.iter().sum::<u64>()
+        );
+        // Limits the runner does not support.
+        let mut text = Vec::new();
+        units[0].write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let zero_memory = text.replace(&format!(" {} ", limits.memory), " 0 ");
+        assert!(WorkUnit::parse(&zero_memory).is_err());
+        let zero_steps = text.replace(&format!("limits {} ", limits.steps), "limits 0 ");
+        assert!(WorkUnit::parse(&zero_steps).is_err());
+        // Budgets that the schedule does not accept and unknown deciders.
+        for (line, replacement) in [
+            ("budgets 2", "budgets 2 1"),
+            ("budgets 2", "budgets 4"),
+            ("budgets 2", "budgets two"),
+            ("deciders cyclers", "deciders cycler"),
+            ("deciders cyclers segment\n", ""),
+        ] {
+            assert!(text.contains(line));
+            assert!(WorkUnit::parse(&text.replace(line, replacement)).is_err());
+        }
+        // The same unit twice.
+        assert!(merge(&[results[0].clone(), results[0].clone()], &mut Vec::new()).is_err());
+        // A cut off result.
+        let mut result = std::fs::read(&results[1]).unwrap();
+        result.truncate(result.len() - 10);
+        assert!(validate(&result[..], |_| Ok(())).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
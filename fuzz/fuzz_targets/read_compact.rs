@@ -0,0 +1,17 @@
+#![no_main]
+
+use busy_beaver::format::read_compact;
+use libfuzzer_sys::fuzz_target;
+
+// Parsing never panics and a machine that parses is written back unchanged.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(states) = read_compact::<5, 2>(data) {
+        assert_eq!(states.to_string().as_bytes(), data);
+    }
+    if let Ok(states) = read_compact::<2, 4>(data) {
+        assert_eq!(states.to_string().as_bytes(), data);
+    }
+    if let Ok(states) = read_compact::<6, 2>(data) {
+        assert_eq!(states.to_string().as_bytes(), data);
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use busy_beaver::format::{
+    read_seed_database, read_seed_database_header, write_seed_database, write_seed_database_header,
+};
+use libfuzzer_sys::fuzz_target;
+
+// Parsing never panics and what parses is written back unchanged. The header ignores its reserved bytes.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(states) = read_seed_database(data) {
+        assert_eq!(write_seed_database(&states), data);
+    }
+    if let Ok(header) = read_seed_database_header(data) {
+        assert_eq!(write_seed_database_header(&header)[..13], data[..13]);
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use busy_beaver::format::{read_compact, round_trip_check};
+use libfuzzer_sys::fuzz_target;
+
+// Every machine survives the conversion to every format and back.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(states) = read_compact::<5, 2>(data) {
+        round_trip_check(&states).unwrap();
+    }
+    if let Ok(states) = read_compact::<2, 4>(data) {
+        round_trip_check(&states).unwrap();
+    }
+    if let Ok(states) = read_compact::<6, 2>(data) {
+        round_trip_check(&states).unwrap();
+    }
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use busy_beaver::{format::read_compact, stress::check_runner};
+use libfuzzer_sys::fuzz_target;
+
+// The first byte selects the tape length. The rest is a machine in the compact format. The runner, whose indexing is unchecked in release builds, has to agree with the reference simulation.
+fuzz_target!(|data: &[u8]| {
+    let Some((tape_len, compact)) = data.split_first() else {
+        return;
+    };
+    let tape_len = *tape_len as usize % 64 + 1;
+    if let Ok(states) = read_compact::<5, 2>(compact) {
+        check_runner(&states, tape_len, 1_000).unwrap();
+    }
+    if let Ok(states) = read_compact::<2, 4>(compact) {
+        check_runner(&states, tape_len, 1_000).unwrap();
+    }
+});
@@ -0,0 +1,64 @@
+// Write the initial corpus of the fuzz targets to `corpus/<target>`. The machines come from the generator of `busy_beaver::stress`, which favors machines that stress the tape boundaries, and from the fixtures. Run from the `fuzz` directory before fuzzing for the first time.
+
+use std::path::Path;
+
+use busy_beaver::{
+    fixtures::FIXTURES,
+    format::{write_seed_database, write_seed_database_header, SeedDatabaseHeader},
+    states::States,
+    stress::{machine, Kind, Rng},
+};
+
+const MACHINES_PER_KIND: usize = 100;
+
+fn main() -> std::io::Result<()> {
+    let mut rng = Rng::new(0);
+    let mut machines_5x2 = Vec::new();
+    let mut compact = Vec::new();
+    for kind in Kind::ALL {
+        for _ in 0..MACHINES_PER_KIND {
+            let states: States<5, 2> = machine(&mut rng, kind);
+            machines_5x2.push(states);
+            compact.push(states.to_string());
+            compact.push(machine::<2, 4>(&mut rng, kind).to_string());
+        }
+    }
+    compact.extend(FIXTURES.iter().map(|f| f.compact.to_string()));
+
+    let mut seed_database: Vec<Vec<u8>> = machines_5x2
+        .iter()
+        .map(|states| write_seed_database(states).to_vec())
+        .collect();
+    seed_database.push(
+        write_seed_database_header(&SeedDatabaseHeader {
+            time_limit: 14322029,
+            space_limit: 74342035,
+            sorted: true,
+        })
+        .to_vec(),
+    );
+
+    // The first byte of `simulate` input is the tape length.
+    let simulate: Vec<Vec<u8>> = compact
+        .iter()
+        .map(|c| {
+            let mut input = vec![rng.below(64) as u8];
+            input.extend_from_slice(c.as_bytes());
+            input
+        })
+        .collect();
+
+    let compact: Vec<Vec<u8>> = compact.into_iter().map(String::into_bytes).collect();
+    write("read_compact", &compact)?;
+    write("read_seed_database", &seed_database)?;
+    write("simulate", &simulate)
+}
+
+fn write(target: &str, inputs: &[Vec<u8>]) -> std::io::Result<()> {
+    let dir = Path::new("corpus").join(target);
+    std::fs::create_dir_all(&dir)?;
+    for (i, input) in inputs.iter().enumerate() {
+        std::fs::write(dir.join(format!("generated-{i}")), input)?;
+    }
+    Ok(())
+}